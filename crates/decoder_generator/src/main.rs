@@ -252,6 +252,22 @@ fn display_impl(encodings: &[Encoding<String>]) {
     println!("}}");
 }
 
+fn encoding_table(encodings: &[Encoding<String>]) {
+    println!(
+        "// The fixed bits of every encoding and their values, the other bits being operands."
+    );
+    println!("// Used to generate random instructions in tests.");
+    println!("#[cfg(test)]");
+    println!("pub const ENCODINGS: [(u32, u32); {}] = [", encodings.len());
+    for encoding in encodings {
+        println!(
+            "    ({:#010x}, {:#010x}), // {}",
+            encoding.mask, encoding.bits, encoding.payload
+        );
+    }
+    println!("];");
+}
+
 fn predicates<'a>(instructions: impl IntoIterator<Item = (&'a Yaml, &'a Yaml)>) {
     let mut predicate_opcodes: BTreeMap<String, Vec<String>> = BTreeMap::new();
     for (_, instruction) in instructions {
@@ -495,4 +511,6 @@ fn main() {
     predicates(raw_instructions);
     println!();
     definitions_and_uses(&yaml[0]["operands"], raw_instructions, &encodings);
+    println!();
+    encoding_table(&encodings);
 }
@@ -1,27 +1,108 @@
 use std::{
-    fmt::LowerHex,
-    io::Write,
-    ops::{Add, AddAssign, Sub, SubAssign},
+    cell::RefCell,
+    fmt::{Display, LowerHex},
+    ops::{Add, AddAssign, Range, Sub, SubAssign},
 };
 
+use log::{trace, warn};
+use mmap_rs::MmapMut;
+
 use crate::{bits::Bits, bytes::Bytes};
 
-use super::{dmac::Dmac, gif::Gif, gs::Gs, rdram::Rdram, timer::Timer};
+use super::{
+    dmac::Dmac,
+    fastmem::{Fastmem, FastmemRegions},
+    gif::Gif,
+    gs::Gs,
+    intc::Intc,
+    mmio::{self, Device, Kputchar, MmioDevice, MmioMap, Unimplemented, UnmappedPolicy},
+    rdram::Rdram,
+    timer::Timer,
+};
 
 pub const MAIN_MEMORY_SIZE: usize = 32 * 1024 * 1024;
 pub const BOOT_MEMORY_SIZE: usize = 4 * 1024 * 1024;
 pub const SCRATCHPAD_SIZE: usize = 16 * 1024;
+// The granularity at which writes to memory are tracked.
+pub const TRACKED_PAGE_SIZE: usize = 4 * 1024;
 
 pub struct Bus {
-    pub main_memory: Box<[u8]>,
-    pub boot_memory: Box<[u8]>,
-    pub scratchpad: Box<[u8]>,
+    pub main_memory: MmapMut,
+    pub boot_memory: MmapMut,
+    pub scratchpad: MmapMut,
+    pub fastmem: Fastmem,
     pub timer: Timer,
     pub gif: Gif,
     pub dmac: Dmac,
+    pub intc: Intc,
     pub gs: Gs,
     pub rdram: Rdram,
-    pub stdout: Vec<u8>,
+    pub mmio: MmioMap,
+    // The devices added with `add_device`, see `Device::Registered`.
+    devices: Vec<Box<dyn MmioDevice>>,
+    // Bumped by every write to a page of main memory or the scratchpad, so that code compiled from
+    // a page can tell whether it has changed since. Main memory pages come first.
+    page_generations: Box<[u32]>,
+    // Only used by the lockstep checker, which sets `lockstep` so that other runs don't have to
+    // look at the log on every device read.
+    pub lockstep: bool,
+    pub device_log: RefCell<DeviceLog>,
+}
+
+// Lets the lockstep checker run a block twice while the devices only see it once: the accesses
+// the compiled block makes to devices are recorded, and the interpreter is answered from the
+// recording.
+#[derive(Default)]
+pub enum DeviceLog {
+    #[default]
+    Off,
+    Recording(Vec<DeviceAccess>),
+    Replaying {
+        recorded: Vec<DeviceAccess>,
+        replayed: Vec<DeviceAccess>,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DeviceAccess {
+    pub address: PhysicalAddress,
+    pub size: usize,
+    pub value: u128,
+    pub write: bool,
+}
+
+impl DeviceAccess {
+    fn new<T: Bytes>(address: PhysicalAddress, value: T, write: bool) -> Self {
+        let bytes = value.to_bytes();
+        let bytes = bytes.as_ref();
+        let mut value = [0; 16];
+        value[..bytes.len()].copy_from_slice(bytes);
+        DeviceAccess {
+            address,
+            size: bytes.len(),
+            value: u128::from_le_bytes(value),
+            write,
+        }
+    }
+
+    fn value<T: Bytes>(&self) -> T {
+        T::from_bytes(&self.value.to_le_bytes()[..self.size])
+    }
+}
+
+impl Display for DeviceAccess {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let operator = if self.write { ":=" } else { "==" };
+        write!(
+            f,
+            "{}-bit {} at 0x{:08x}{}0x{:x}",
+            self.size * 8,
+            if self.write { "write" } else { "read" },
+            self.address.0,
+            operator,
+            self.value
+        )
+    }
 }
 
 #[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
@@ -32,6 +113,25 @@ pub enum PhysicalAddressView {
     Scratchpad(u32),
 }
 
+// The C field of a TLB entry, or the fixed mode of kseg0 and kseg1.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CacheMode {
+    Cached,
+    Uncached,
+    UncachedAccelerated,
+}
+
+impl CacheMode {
+    pub fn from_bits(bits: u8) -> CacheMode {
+        match bits {
+            0b010 => CacheMode::Uncached,
+            0b111 => CacheMode::UncachedAccelerated,
+            // 0b011 is cached write-back, the remaining encodings are reserved.
+            _ => CacheMode::Cached,
+        }
+    }
+}
+
 impl PhysicalAddress {
     pub fn memory(address: u32) -> Self {
         PhysicalAddress(address)
@@ -90,20 +190,211 @@ impl PhysicalAddress {
 
 impl Bus {
     pub fn new() -> Bus {
-        Bus {
-            main_memory: vec![0; MAIN_MEMORY_SIZE].into_boxed_slice(),
-            boot_memory: vec![0; BOOT_MEMORY_SIZE].into_boxed_slice(),
-            scratchpad: vec![0; SCRATCHPAD_SIZE].into_boxed_slice(),
+        let FastmemRegions {
+            fastmem,
+            main_memory,
+            boot_memory,
+            scratchpad,
+        } = Fastmem::reserve();
+        let mut bus = Bus {
+            main_memory,
+            boot_memory,
+            scratchpad,
+            fastmem,
             timer: Timer::new(),
             gif: Gif::new(),
             dmac: Dmac::default(),
+            intc: Intc::default(),
             gs: Gs::new(),
             rdram: Rdram::default(),
-            stdout: Vec::new(),
+            mmio: MmioMap::new(),
+            devices: Vec::new(),
+            page_generations: vec![0; (MAIN_MEMORY_SIZE + SCRATCHPAD_SIZE) / TRACKED_PAGE_SIZE]
+                .into_boxed_slice(),
+            lockstep: false,
+            device_log: RefCell::new(DeviceLog::Off),
+        };
+        bus.mmio.map(0x1000_0000..0x1000_2000, Device::Timer);
+        bus.mmio.map(0x1000_3000..0x1000_3800, Device::Gif);
+        bus.mmio.map(0x1000_8000..0x1000_F000, Device::Dmac);
+        bus.mmio.map(0x1000_F520..0x1000_F600, Device::Dmac);
+        bus.mmio.map(0x1000_F000..0x1000_F004, Device::Intc);
+        bus.mmio.map(0x1000_F010..0x1000_F014, Device::Intc);
+        bus.mmio.map(0x1000_F430..0x1000_F434, Device::Rdram);
+        bus.mmio.map(0x1000_F440..0x1000_F444, Device::Rdram);
+        bus.mmio.map(0x1200_0000..0x1201_0000, Device::Gs);
+        let kputchar = bus.add_device(Box::new(Kputchar::default()));
+        bus.mmio.map(0x1000_F180..0x1000_F181, kputchar);
+        let unimplemented = bus.add_device(Box::new(Unimplemented));
+        for range in [
+            0x1000_F100..0x1000_F104,
+            0x1000_F120..0x1000_F124,
+            0x1000_F130..0x1000_F134,
+            0x1000_F140..0x1000_F144,
+            0x1000_F150..0x1000_F154,
+            0x1000_F400..0x1000_F404,
+            0x1000_F410..0x1000_F414,
+            0x1000_F420..0x1000_F424,
+            0x1000_F450..0x1000_F454,
+            0x1000_F460..0x1000_F464,
+            0x1000_F480..0x1000_F484,
+            0x1000_F490..0x1000_F494,
+            0x1000_F500..0x1000_F504,
+            0x1F80_1470..0x1F80_1474,
+            // PS2 tool model number
+            // https://www.obscuregamers.com/threads/running-ps1-game-on-dtl-t10000-tool.1949
+            0x1F80_3204..0x1F80_3208,
+        ] {
+            bus.mmio.map(range, unimplemented);
+        }
+        bus
+    }
+
+    // Adds a device the bus doesn't otherwise know about, to be mapped with `MmioMap::map`.
+    pub fn add_device(&mut self, device: Box<dyn MmioDevice>) -> Device {
+        self.devices.push(device);
+        Device::Registered(self.devices.len() - 1)
+    }
+
+    fn device(&self, device: Device) -> &dyn MmioDevice {
+        match device {
+            Device::Timer => &self.timer,
+            Device::Gif => &self.gif,
+            Device::Dmac => &self.dmac,
+            Device::Intc => &self.intc,
+            Device::Rdram => &self.rdram,
+            Device::Gs => &self.gs,
+            Device::Registered(index) => &*self.devices[index],
+        }
+    }
+
+    fn device_mut(&mut self, device: Device) -> &mut dyn MmioDevice {
+        match device {
+            Device::Timer => &mut self.timer,
+            Device::Gif => &mut self.gif,
+            Device::Dmac => &mut self.dmac,
+            Device::Intc => &mut self.intc,
+            Device::Rdram => &mut self.rdram,
+            Device::Gs => &mut self.gs,
+            Device::Registered(index) => &mut *self.devices[index],
+        }
+    }
+
+    // Whether an address is backed by memory rather than a device.
+    fn is_memory(address: PhysicalAddress) -> bool {
+        match address.view() {
+            PhysicalAddressView::Memory(address) => {
+                matches!(address, 0x0000_0000..0x1000_0000 | 0x1FC0_0000..0x2000_0000)
+            }
+            PhysicalAddressView::Scratchpad(_) => true,
+        }
+    }
+
+    // The page generation covering a physical address, if the memory there is writable.
+    pub fn page_generation_index(address: PhysicalAddress) -> Option<usize> {
+        match address.view() {
+            PhysicalAddressView::Memory(address @ 0x0000_0000..0x1000_0000) => {
+                Some((address as usize & (MAIN_MEMORY_SIZE - 1)) / TRACKED_PAGE_SIZE)
+            }
+            PhysicalAddressView::Memory(_) => None,
+            PhysicalAddressView::Scratchpad(address) => Some(
+                (MAIN_MEMORY_SIZE + (address as usize & (SCRATCHPAD_SIZE - 1))) / TRACKED_PAGE_SIZE,
+            ),
+        }
+    }
+
+    // The tracked pages overlapping a range.
+    pub fn tracked_pages(range: Range<PhysicalAddress>) -> Vec<usize> {
+        let first_page = range.start.0 as usize / TRACKED_PAGE_SIZE;
+        let last_page = (range.end.0 as usize - 1) / TRACKED_PAGE_SIZE;
+        (first_page..=last_page)
+            .filter_map(|page| {
+                Self::page_generation_index(PhysicalAddress((page * TRACKED_PAGE_SIZE) as u32))
+            })
+            .collect()
+    }
+
+    pub fn page_generation(&self, index: usize) -> u32 {
+        self.page_generations[index]
+    }
+
+    fn bump_page_generation(&mut self, address: PhysicalAddress) {
+        if let Some(index) = Self::page_generation_index(address) {
+            self.page_generations[index] = self.page_generations[index].wrapping_add(1);
+        }
+    }
+
+    pub fn page_generations_address(&self) -> *const u32 {
+        self.page_generations.as_ptr()
+    }
+
+    pub fn tracked_page_count(&self) -> usize {
+        self.page_generations.len()
+    }
+
+    // The physical address of a tracked page.
+    pub fn tracked_page_address(index: usize) -> PhysicalAddress {
+        let offset = index * TRACKED_PAGE_SIZE;
+        if offset < MAIN_MEMORY_SIZE {
+            PhysicalAddress::memory(offset as u32)
+        } else {
+            PhysicalAddress::scratchpad((offset - MAIN_MEMORY_SIZE) as u32)
+        }
+    }
+
+    pub fn tracked_page(&self, index: usize) -> &[u8] {
+        let offset = index * TRACKED_PAGE_SIZE;
+        if offset < MAIN_MEMORY_SIZE {
+            &self.main_memory[offset..offset + TRACKED_PAGE_SIZE]
+        } else {
+            let offset = offset - MAIN_MEMORY_SIZE;
+            &self.scratchpad[offset..offset + TRACKED_PAGE_SIZE]
+        }
+    }
+
+    pub fn tracked_page_mut(&mut self, index: usize) -> &mut [u8] {
+        let offset = index * TRACKED_PAGE_SIZE;
+        if offset < MAIN_MEMORY_SIZE {
+            &mut self.main_memory[offset..offset + TRACKED_PAGE_SIZE]
+        } else {
+            let offset = offset - MAIN_MEMORY_SIZE;
+            &mut self.scratchpad[offset..offset + TRACKED_PAGE_SIZE]
         }
     }
 
     pub fn read<T: Bytes + LowerHex + Default>(&self, address: PhysicalAddress) -> T {
+        if !self.lockstep
+            || Self::is_memory(address)
+            || matches!(*self.device_log.borrow(), DeviceLog::Off)
+        {
+            return self.read_unlogged(address);
+        }
+        let mut device_log = self.device_log.borrow_mut();
+        match &mut *device_log {
+            DeviceLog::Off => unreachable!(),
+            DeviceLog::Recording(accesses) => {
+                let access = DeviceAccess::new(address, self.read_unlogged::<T>(address), false);
+                accesses.push(access);
+                access.value()
+            }
+            DeviceLog::Replaying { recorded, replayed } => {
+                let access = DeviceAccess::new(address, T::default(), false);
+                // Reads the compiled block didn't make in the same order get nothing.
+                let value = recorded
+                    .get(replayed.len())
+                    .filter(|recorded| {
+                        (recorded.address, recorded.size, recorded.write)
+                            == (access.address, access.size, access.write)
+                    })
+                    .map_or_else(T::default, DeviceAccess::value);
+                let access = DeviceAccess::new(address, value, false);
+                replayed.push(access);
+                access.value()
+            }
+        }
+    }
+
+    fn read_unlogged<T: Bytes + LowerHex + Default>(&self, address: PhysicalAddress) -> T {
         match address.view() {
             PhysicalAddressView::Memory(address) => {
                 assert!(address & (std::mem::size_of::<T>() - 1) as u32 == 0);
@@ -114,49 +405,34 @@ impl Bus {
                             &self.main_memory[address..address + std::mem::size_of::<T>()],
                         )
                     }
-                    0x1000_0000..0x1000_2000 => {
-                        let result = self.timer.read(address);
-                        println!("Read from TIMER: 0x{:08x}==0x{:08x}", address, result);
-                        result
-                    }
-                    0x1000_3000..0x1000_3800 => {
-                        let result = self.gif.read(address);
-                        println!("Read from GIF: 0x{:08x}==0x{:08x}", address, result);
-                        result
-                    }
-                    0x1000_8000..0x1000_F000 | 0x1000_F520..0x1000_F600 => {
-                        let result = self.dmac.read(address);
-                        // println!("Read from DMAC: 0x{:08x}==0x{:08x}", address, result);
-                        result
-                    }
-                    0x1000_F130 | 0x1000_F400 | 0x1000_F410 => {
-                        println!("Unhandled read at: 0x{:08x}", address);
-                        T::default()
-                    }
-                    0x1000_F430 | 0x1000_F440 => {
-                        let result = self.rdram.read(address);
-                        // println!("Read from RDRAM: 0x{:08x}==0x{:08x}", address, result);
-                        result
-                    }
-                    0x1200_0000..0x1201_0000 => {
-                        let result = self.gs.read_privileged(address);
-                        // println!("Read from GS: 0x{:08x}==0x{:08x}", address, result);
-                        result
-                    }
-                    // PS2 tool model number
-                    // https://www.obscuregamers.com/threads/running-ps1-game-on-dtl-t10000-tool.1949
-                    0x1F80_3204 => T::default(),
                     0x1FC0_0000..0x2000_0000 => {
                         let address = address as usize & (BOOT_MEMORY_SIZE - 1);
                         let result = T::from_bytes(
                             &self.boot_memory[address..address + std::mem::size_of::<T>()],
                         );
-                        println!("Read from Boot memory: 0x{:08x}==0x{:08x}", address, result);
+                        trace!(
+                            target: "bus.mmio",
+                            "Read from Boot memory: 0x{:08x}==0x{:08x}",
+                            address,
+                            result
+                        );
                         result
                     }
-                    _ => {
-                        panic!("Invalid read at address: 0x{:08x}", address);
-                    }
+                    _ => match self.mmio.device(address) {
+                        Some(device) => {
+                            let device = self.device(device);
+                            let result = mmio::read(device, address);
+                            trace!(
+                                target: "bus.mmio",
+                                "Read from {}: 0x{:08x}==0x{:08x}",
+                                device.name(),
+                                address,
+                                result
+                            );
+                            result
+                        }
+                        None => self.read_unmapped(address),
+                    },
                 }
             }
             PhysicalAddressView::Scratchpad(address) => {
@@ -168,7 +444,73 @@ impl Bus {
         }
     }
 
+    fn read_unmapped<T: Bytes + Default>(&self, address: u32) -> T {
+        match self.mmio.unmapped_policy {
+            UnmappedPolicy::Panic => panic!("Invalid read at address: 0x{:08x}", address),
+            UnmappedPolicy::LogAndIgnore => {
+                warn!(target: "bus.mmio", "Read from unmapped address: 0x{:08x}", address);
+                T::default()
+            }
+            UnmappedPolicy::OpenBus => T::from_bytes(&[0xFF; 16][..std::mem::size_of::<T>()]),
+        }
+    }
+
+    fn write_unmapped<T: LowerHex>(&self, address: u32, value: T) {
+        match self.mmio.unmapped_policy {
+            UnmappedPolicy::Panic => panic!("Invalid write 0x{:08x}=0x{:08x}", address, value),
+            UnmappedPolicy::LogAndIgnore => {
+                warn!(
+                    target: "bus.mmio",
+                    "Write to unmapped address: 0x{:08x}:=0x{:08x}",
+                    address,
+                    value
+                )
+            }
+            UnmappedPolicy::OpenBus => {}
+        }
+    }
+
+    // CPU accesses carry the cache mode of their page. The caches aren't emulated, and uncached
+    // accelerated writes go straight to the bus until the UCAB is.
+    pub fn read_with_cache_mode<T: Bytes + LowerHex + Default>(
+        &self,
+        address: PhysicalAddress,
+        cache_mode: CacheMode,
+    ) -> T {
+        match cache_mode {
+            CacheMode::Cached | CacheMode::Uncached | CacheMode::UncachedAccelerated => {
+                self.read(address)
+            }
+        }
+    }
+
+    pub fn write_with_cache_mode<T: Bytes + LowerHex>(
+        &mut self,
+        address: PhysicalAddress,
+        value: T,
+        cache_mode: CacheMode,
+    ) {
+        match cache_mode {
+            CacheMode::Cached | CacheMode::Uncached | CacheMode::UncachedAccelerated => {
+                self.write(address, value)
+            }
+        }
+    }
+
     pub fn write<T: Bytes + LowerHex>(&mut self, address: PhysicalAddress, value: T) {
+        let value = match self.device_log.get_mut() {
+            DeviceLog::Recording(accesses) if !Self::is_memory(address) => {
+                let access = DeviceAccess::new(address, value, true);
+                accesses.push(access);
+                access.value()
+            }
+            DeviceLog::Replaying { replayed, .. } if !Self::is_memory(address) => {
+                replayed.push(DeviceAccess::new(address, value, true));
+                return;
+            }
+            _ => value,
+        };
+        self.bump_page_generation(address);
         match address.view() {
             PhysicalAddressView::Memory(address) => {
                 assert!(address & (std::mem::size_of::<T>() - 1) as u32 == 0);
@@ -179,52 +521,33 @@ impl Bus {
                         self.main_memory[address..address + std::mem::size_of::<T>()]
                             .copy_from_slice(value.to_bytes().as_ref());
                     }
-                    0x1000_0000..0x1000_2000 => {
-                        println!("Write to TIMER: 0x{:08x}:=0x{:08x}", address, value);
-                        self.timer.write(address, value)
-                    }
-                    0x1000_3000..0x1000_3800 => {
-                        println!("Write to GIF: 0x{:08x}:=0x{:08x}", address, value);
-                        self.gif.write(address, value)
-                    }
-                    0x1000_8000..0x1000_F000 | 0x1000_F520..0x1000_F600 => {
-                        // println!("Write to DMAC: 0x{:08x}:=0x{:08x}", address, value);
-                        self.dmac.write(address, value)
-                    }
-                    0x1000_F100 | 0x1000_F120 | 0x1000_F140 | 0x1000_F150 | 0x1000_F400
-                    | 0x1000_F410 | 0x1000_F420 | 0x1000_F450 | 0x1000_F460 | 0x1000_F480
-                    | 0x1000_F490 | 0x1000_F500 | 0x1F80_1470 | 0x1F80_1472 => {
-                        println!("Unhandled write: 0x{:08x}:=0x{:08x}", address, value);
-                    }
-                    // kputchar
-                    0x1000_F180 => {
-                        let byte = value.to_bytes().as_ref()[0];
-                        if byte == b'\n' {
-                            print!("STDOUT:");
-                            std::io::stdout().write_all(&self.stdout).unwrap();
-                            println!();
-                            self.stdout.clear();
-                        } else {
-                            self.stdout.push(byte);
-                        }
-                    }
-                    0x1000_F430 | 0x1000_F440 => {
-                        // println!("Write to RDRAM: 0x{:08x}:=0x{:08x}", address, value);
-                        self.rdram.write(address, value);
-                    }
-                    0x1200_0000..0x1201_0000 => {
-                        println!("Write to GS: 0x{:08x}:=0x{:08x}", address, value);
-                        self.gs.write_privileged(address, value)
-                    }
                     0x1FC0_0000..0x2000_0000 => {
                         let address = address as usize & (BOOT_MEMORY_SIZE - 1);
-                        println!("Write to boot memory: 0x{:08x}:=0x{:08x}", address, value);
+                        trace!(
+                            target: "bus.mmio",
+                            "Write to boot memory: 0x{:08x}:=0x{:08x}",
+                            address,
+                            value
+                        );
+                        // The write lands in main memory.
+                        self.bump_page_generation(PhysicalAddress(address as u32));
                         self.main_memory[address..address + std::mem::size_of::<T>()]
                             .copy_from_slice(value.to_bytes().as_ref());
                     }
-                    _ => {
-                        panic!("Invalid write 0x{:08x}=0x{:08x}", address, value);
-                    }
+                    _ => match self.mmio.device(address) {
+                        Some(device) => {
+                            let device = self.device_mut(device);
+                            trace!(
+                                target: "bus.mmio",
+                                "Write to {}: 0x{:08x}:=0x{:08x}",
+                                device.name(),
+                                address,
+                                value
+                            );
+                            mmio::write(device, address, value)
+                        }
+                        None => self.write_unmapped(address, value),
+                    },
                 }
             }
             PhysicalAddressView::Scratchpad(address) => {
@@ -1,49 +1,62 @@
+use std::ops::RangeInclusive;
+
 use derive_more::Display;
 use enum_map::{enum_map, Enum, EnumMap};
+use log::debug;
 use num_derive::FromPrimitive;
 use num_traits::FromPrimitive;
 
 use crate::bits::Bits;
 
 // Coprocessor 0
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct Control {
     registers: EnumMap<Register, u32>,
+    // Set when Count or Compare changes so the scheduler can move the compare event.
+    compare_changed: bool,
 }
 
 impl Control {
     pub fn new() -> Control {
         Control {
             registers: enum_map! {
+                Register::Random => Self::TLB_ENTRIES - 1,
                 Register::PrId => 0x2E20,
                 Register::Config => 0x440,
                 _ => 0,
             },
+            compare_changed: true,
         }
     }
 
     pub fn step(&mut self, cycles: u64) {
         self.registers[Register::Count] =
             self.registers[Register::Count].wrapping_add(cycles as u32);
+        // Random counts down once per cycle from the last TLB entry to Wired.
+        let wired = self.registers[Register::Wired].min(Self::TLB_ENTRIES - 1);
+        let range = (Self::TLB_ENTRIES - wired) as u64;
+        let random = self.registers[Register::Random].saturating_sub(wired) as u64;
+        self.registers[Register::Random] =
+            wired + ((random + range - cycles % range) % range) as u32;
     }
 
     pub fn get_register(&self, register: Register) -> u32 {
         match register {
             Register::Index => self.registers[register],
-            Register::Random => todo!(),
+            Register::Random => self.registers[register],
             Register::EntryLo0 => self.registers[register],
             Register::EntryLo1 => self.registers[register],
-            Register::Context => todo!(),
+            Register::Context => self.registers[register],
             Register::PageMask => self.registers[register],
-            Register::Wired => todo!(),
+            Register::Wired => self.registers[register],
             Register::Undefined7 => todo!(),
-            Register::BadVAddr => todo!(),
+            Register::BadVAddr => self.registers[register],
             Register::Count => self.registers[register],
             Register::EntryHi => self.registers[register],
-            Register::Compare => todo!(),
-            Register::Status => todo!(),
-            Register::Cause => todo!(),
-            Register::Epc => todo!(),
+            Register::Compare => self.registers[register],
+            Register::Status => self.registers[register],
+            Register::Cause => self.registers[register],
+            Register::Epc => self.registers[register],
             Register::PrId => self.registers[register],
             Register::Config => todo!(),
             Register::Undefined17 => todo!(),
@@ -59,20 +72,21 @@ impl Control {
             Register::Undefined27 => todo!(),
             Register::TagLo => todo!(),
             Register::TagHi => todo!(),
-            Register::ErrorEpc => todo!(),
+            Register::ErrorEpc => self.registers[register],
             Register::Undefined31 => todo!(),
         }
     }
 
     pub fn set_register(&mut self, register: Register, value: u32) {
-        println!("Setting control register {:?} to {:#010x}", register, value);
+        debug!(target: "ee.core", "Setting control register {:?} to {:#010x}", register, value);
         let register_value = &mut self.registers[register];
         match register {
             Register::Index => {
                 *register_value = value;
                 register_value.set_bits(6..=30, 0u32);
             }
-            Register::Random => todo!(),
+            // Random is read-only.
+            Register::Random => {}
             Register::EntryLo0 => {
                 *register_value = value;
                 register_value.set_bits(26..=30, 0u32);
@@ -81,25 +95,38 @@ impl Control {
                 *register_value = value;
                 register_value.set_bits(26..=31, 0u32);
             }
-            Register::Context => todo!(),
+            // Only PTEBase is writable, BadVPN2 is set by TLB exceptions.
+            Register::Context => register_value.set_bits(23..=31, value.bits(23..=31)),
             Register::PageMask => register_value.set_bits(13..=24, value.bits(13..=24)),
-            Register::Wired => register_value.set_bits(0..=5, value.bits(0..=5)),
+            Register::Wired => {
+                register_value.set_bits(0..=5, value.bits(0..=5));
+                self.registers[Register::Random] = Self::TLB_ENTRIES - 1;
+            }
             Register::Undefined7 => todo!(),
             Register::BadVAddr => todo!(),
-            Register::Count => *register_value = value,
+            Register::Count => {
+                *register_value = value;
+                self.compare_changed = true;
+            }
             Register::EntryHi => {
                 *register_value = value;
                 register_value.set_bits(8..=12, 0u32);
             }
-            Register::Compare => *register_value = value,
+            // Writing Compare acknowledges the timer interrupt.
+            Register::Compare => {
+                *register_value = value;
+                self.set_interrupt_pending(Interrupt::Timer, false);
+                self.compare_changed = true;
+            }
             Register::Status => {
                 *register_value = value;
                 register_value.set_bits(5..10, 0u32);
                 register_value.set_bits(19..22, 0u32);
                 register_value.set_bits(24..28, 0u32);
             }
-            Register::Cause => todo!(),
-            Register::Epc => todo!(),
+            // Everything in Cause is set by hardware on the EE.
+            Register::Cause => {}
+            Register::Epc => *register_value = value,
             Register::PrId => todo!(),
             Register::Config => {
                 *register_value = value;
@@ -121,10 +148,138 @@ impl Control {
             Register::Undefined27 => todo!(),
             Register::TagLo => todo!(),
             Register::TagHi => todo!(),
-            Register::ErrorEpc => todo!(),
+            Register::ErrorEpc => *register_value = value,
             Register::Undefined31 => todo!(),
         }
     }
+
+    // Status.IE
+    pub fn interrupt_enable(&self) -> bool {
+        self.registers[Register::Status].bit(Self::STATUS_INTERRUPT_ENABLE)
+    }
+
+    // Status.EXL
+    pub fn exception_level(&self) -> bool {
+        self.registers[Register::Status].bit(Self::STATUS_EXCEPTION_LEVEL)
+    }
+
+    pub fn set_exception_level(&mut self, value: bool) {
+        self.registers[Register::Status].set_bit(Self::STATUS_EXCEPTION_LEVEL, value);
+    }
+
+    // Status.ERL
+    pub fn error_level(&self) -> bool {
+        self.registers[Register::Status].bit(Self::STATUS_ERROR_LEVEL)
+    }
+
+    pub fn set_error_level(&mut self, value: bool) {
+        self.registers[Register::Status].set_bit(Self::STATUS_ERROR_LEVEL, value);
+    }
+
+    // Status.KSU
+    pub fn kernel_supervisor_user_mode(&self) -> u32 {
+        self.registers[Register::Status].bits(Self::STATUS_KERNEL_SUPERVISOR_USER_MODE)
+    }
+
+    // Status.IM2, Status.IM3, Status.IM7
+    pub fn interrupt_mask(&self) -> u32 {
+        self.registers[Register::Status].bits(Self::INTERRUPTS)
+    }
+
+    // Status.EIE
+    pub fn enable_interrupt_enable(&self) -> bool {
+        self.registers[Register::Status].bit(Self::STATUS_ENABLE_INTERRUPT_ENABLE)
+    }
+
+    pub fn set_enable_interrupt_enable(&mut self, value: bool) {
+        self.registers[Register::Status].set_bit(Self::STATUS_ENABLE_INTERRUPT_ENABLE, value);
+    }
+
+    // Status.EDI
+    pub fn enable_ei_di(&self) -> bool {
+        self.registers[Register::Status].bit(Self::STATUS_ENABLE_EI_DI)
+    }
+
+    // Status.BEV
+    pub fn bootstrap_exception_vectors(&self) -> bool {
+        self.registers[Register::Status].bit(Self::STATUS_BOOTSTRAP_EXCEPTION_VECTORS)
+    }
+
+    // Cause.IP2, Cause.IP3, Cause.IP7
+    pub fn interrupt_pending(&self) -> u32 {
+        self.registers[Register::Cause].bits(Self::INTERRUPTS)
+    }
+
+    pub fn set_interrupt_pending(&mut self, interrupt: Interrupt, value: bool) {
+        self.registers[Register::Cause].set_bit(interrupt as u32, value);
+    }
+
+    // Count reached Compare, signalled by the scheduler.
+    pub fn compare_interrupt(&mut self) {
+        self.set_interrupt_pending(Interrupt::Timer, true);
+        self.compare_changed = true;
+    }
+
+    // The cycles until Count next equals Compare, if they changed since the last call.
+    pub fn take_compare_event(&mut self) -> Option<u64> {
+        if !std::mem::take(&mut self.compare_changed) {
+            return None;
+        }
+        let cycles =
+            self.registers[Register::Compare].wrapping_sub(self.registers[Register::Count]);
+        Some(if cycles == 0 { 1 << 32 } else { cycles as u64 })
+    }
+
+    pub fn interrupts_enabled(&self) -> bool {
+        self.interrupt_enable()
+            && self.enable_interrupt_enable()
+            && !self.exception_level()
+            && !self.error_level()
+    }
+
+    pub fn set_exception_code(&mut self, code: u32) {
+        self.registers[Register::Cause].set_bits(Self::CAUSE_EXCEPTION_CODE, code);
+    }
+
+    // Cause.BD
+    pub fn set_branch_delay(&mut self, value: bool) {
+        self.registers[Register::Cause].set_bit(Self::CAUSE_BRANCH_DELAY, value);
+    }
+
+    pub fn set_exception_program_counter(&mut self, value: u32) {
+        self.registers[Register::Epc] = value;
+    }
+
+    pub fn set_bad_virtual_address(&mut self, value: u32) {
+        self.registers[Register::BadVAddr] = value;
+    }
+
+    // EntryHi.VPN2 and Context.BadVPN2 on a TLB exception.
+    pub fn set_tlb_fault_address(&mut self, address: u32) {
+        self.registers[Register::EntryHi].set_bits(13..=31, address.bits(13..=31));
+        self.registers[Register::Context].set_bits(4..=22, address.bits(13..=31));
+    }
+
+    const STATUS_INTERRUPT_ENABLE: u32 = 0;
+    const STATUS_EXCEPTION_LEVEL: u32 = 1;
+    const STATUS_ERROR_LEVEL: u32 = 2;
+    const STATUS_KERNEL_SUPERVISOR_USER_MODE: RangeInclusive<u32> = 3..=4;
+    const STATUS_ENABLE_INTERRUPT_ENABLE: u32 = 16;
+    const STATUS_ENABLE_EI_DI: u32 = 17;
+    const STATUS_BOOTSTRAP_EXCEPTION_VECTORS: u32 = 22;
+    const TLB_ENTRIES: u32 = 48;
+    const CAUSE_EXCEPTION_CODE: RangeInclusive<u32> = 2..=6;
+    const CAUSE_BRANCH_DELAY: u32 = 31;
+    // IP2, IP3 and IP7 in Cause line up with IM2, IM3 and IM7 in Status.
+    const INTERRUPTS: RangeInclusive<u32> = 10..=15;
+}
+
+// The interrupt lines that are connected to the EE core.
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub enum Interrupt {
+    Int0 = 10,  // INTC
+    Int1 = 11,  // DMAC
+    Timer = 15, // Count/Compare
 }
 
 #[derive(Debug, PartialEq, Eq, Copy, Clone, Enum, Display, FromPrimitive)]
@@ -0,0 +1,155 @@
+use log::debug;
+
+use crate::emotion_engine::bus::Bus;
+
+use super::{control, Core, Mode};
+
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub enum Exception {
+    Interrupt,
+    TlbModified(u32),
+    TlbRefillLoad(u32),
+    TlbRefillStore(u32),
+    TlbInvalidLoad(u32),
+    TlbInvalidStore(u32),
+    AddressErrorLoad(u32),
+    AddressErrorStore(u32),
+    Syscall,
+    Break,
+    ReservedInstruction,
+    Overflow,
+    Trap,
+}
+
+impl Exception {
+    // Cause.ExcCode
+    pub fn code(self) -> u32 {
+        match self {
+            Exception::Interrupt => 0,
+            Exception::TlbModified(_) => 1,
+            Exception::TlbRefillLoad(_) | Exception::TlbInvalidLoad(_) => 2,
+            Exception::TlbRefillStore(_) | Exception::TlbInvalidStore(_) => 3,
+            Exception::AddressErrorLoad(_) => 4,
+            Exception::AddressErrorStore(_) => 5,
+            Exception::Syscall => 8,
+            Exception::Break => 9,
+            Exception::ReservedInstruction => 10,
+            Exception::Overflow => 12,
+            Exception::Trap => 13,
+        }
+    }
+
+    pub fn tlb_fault_address(self) -> Option<u32> {
+        match self {
+            Exception::TlbModified(address)
+            | Exception::TlbRefillLoad(address)
+            | Exception::TlbRefillStore(address)
+            | Exception::TlbInvalidLoad(address)
+            | Exception::TlbInvalidStore(address) => Some(address),
+            _ => None,
+        }
+    }
+
+    pub fn bad_virtual_address(self) -> Option<u32> {
+        match self {
+            Exception::TlbModified(address)
+            | Exception::TlbRefillLoad(address)
+            | Exception::TlbRefillStore(address)
+            | Exception::TlbInvalidLoad(address)
+            | Exception::TlbInvalidStore(address)
+            | Exception::AddressErrorLoad(address)
+            | Exception::AddressErrorStore(address) => Some(address),
+            _ => None,
+        }
+    }
+
+    fn vector_offset(self, exception_level: bool) -> u32 {
+        match self {
+            Exception::TlbRefillLoad(_) | Exception::TlbRefillStore(_) if !exception_level => 0x000,
+            Exception::Interrupt => 0x200,
+            _ => 0x180,
+        }
+    }
+}
+
+impl Core {
+    // Enter a level 1 exception. The program counter must point at the instruction that caused
+    // the exception.
+    pub fn raise_exception(&mut self, bus: &mut Bus, exception: Exception, in_delay_slot: bool) {
+        debug!(
+            target: "ee.core",
+            "Exception {:?} at {:#010x}{}",
+            exception,
+            self.state.program_counter,
+            if in_delay_slot { " (delay slot)" } else { "" }
+        );
+        if let Some(mut kernel) = self.kernel.take() {
+            kernel.handle_exception(self, bus, exception, in_delay_slot);
+            self.kernel = Some(kernel);
+            return;
+        }
+        self.enter_exception(exception, in_delay_slot);
+    }
+
+    // Jump to the exception vector of the guest, bypassing the HLE kernel.
+    pub fn enter_exception(&mut self, exception: Exception, in_delay_slot: bool) {
+        let control = &mut self.state.control;
+        let exception_level = control.exception_level();
+        if !exception_level {
+            if in_delay_slot {
+                control.set_exception_program_counter(self.state.program_counter.wrapping_sub(4));
+            } else {
+                control.set_exception_program_counter(self.state.program_counter);
+            }
+            control.set_branch_delay(in_delay_slot);
+        }
+        control.set_exception_code(exception.code());
+        if let Some(address) = exception.bad_virtual_address() {
+            control.set_bad_virtual_address(address);
+        }
+        if let Some(address) = exception.tlb_fault_address() {
+            control.set_tlb_fault_address(address);
+        }
+        control.set_exception_level(true);
+        let vector_base = if control.bootstrap_exception_vectors() {
+            0xBFC0_0200
+        } else {
+            0x8000_0000
+        };
+        self.state.program_counter = vector_base + exception.vector_offset(exception_level);
+        self.state.delayed_branch_target = None;
+        self.update_mode();
+    }
+
+    // ERET
+    pub fn return_from_exception(&mut self) -> u32 {
+        let control = &mut self.state.control;
+        let program_counter = if control.error_level() {
+            control.set_error_level(false);
+            control.get_register(control::Register::ErrorEpc)
+        } else {
+            control.set_exception_level(false);
+            control.get_register(control::Register::Epc)
+        };
+        self.update_mode();
+        program_counter
+    }
+
+    pub fn interrupt_pending(&self) -> bool {
+        let control = &self.state.control;
+        control.interrupts_enabled() && control.interrupt_pending() & control.interrupt_mask() != 0
+    }
+
+    pub fn update_mode(&mut self) {
+        let control = &self.state.control;
+        self.mode = if control.exception_level() || control.error_level() {
+            Mode::Kernel
+        } else {
+            match control.kernel_supervisor_user_mode() {
+                0b00 => Mode::Kernel,
+                0b01 => Mode::Supervisor,
+                _ => Mode::User,
+            }
+        };
+    }
+}
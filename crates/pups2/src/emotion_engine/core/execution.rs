@@ -1,47 +1,125 @@
+use std::time::Instant;
+
+use log::{log_enabled, trace, Level};
+
 use crate::emotion_engine::bus::Bus;
 
-use super::{instruction_gen::Instruction, jit::Code, Core};
+use super::{
+    control::Interrupt, exception::Exception, instruction_gen::Instruction, jit::Code,
+    timing::NULLIFIED_DELAY_SLOT_CYCLES, Core,
+};
 
 impl Core {
-    pub fn step(&mut self, mut cycles: u64, bus: &mut Bus) {
+    // Counts `cycles` as passed, e.g. while the scheduler skips an idle loop.
+    pub fn advance_time(&mut self, cycles: u64, bus: &mut Bus) {
         self.state.control.step(cycles);
+        if let Some(kernel) = &mut self.kernel {
+            kernel.step(cycles, bus);
+        }
+    }
+
+    pub fn step(&mut self, mut cycles: u64, bus: &mut Bus) {
+        self.advance_time(cycles, bus);
         while cycles > 0 {
-            if self.state.delayed_branch_target.is_some() {
+            let control = &mut self.state.control;
+            control.set_interrupt_pending(Interrupt::Int0, bus.intc.interrupt_pending());
+            control.set_interrupt_pending(Interrupt::Int1, bus.dmac.interrupt_pending());
+            let in_delay_slot = self.state.delayed_branch_target.is_some();
+            if !in_delay_slot && self.interrupt_pending() {
+                self.raise_exception(bus, Exception::Interrupt, false);
+            }
+            let Some(physical_program_counter) = self
+                .mmu
+                .virtual_to_physical(self.state.program_counter, self.mode)
+            else {
                 cycles -= 1;
-                let instruction =
-                    Instruction::decode(self.read_virtual(bus, self.state.program_counter));
-                println!("I {:08x}: {}", self.state.program_counter, instruction);
-                self.interpret_instruction(instruction, bus);
+                let exception = self.mmu.fault(self.state.program_counter, self.mode, false);
+                self.raise_exception(bus, exception, in_delay_slot);
+                continue;
+            };
+            if in_delay_slot {
+                let instruction = Instruction::decode(bus.read(physical_program_counter));
+                trace!(target: "ee.core", "I {:08x}: {}", self.state.program_counter, instruction);
+                cycles = cycles.saturating_sub(self.interpret_timed(instruction, bus));
             } else {
-                let cache_entry = self.jit.cache_entry(&self.state, &self.mmu, bus, self.mode);
-                // TODO: Better cycle counting
+                self.update_fastmem(bus);
+                let core = self as *mut Core;
+                let cache_entry = self.jit.cache_entry(physical_program_counter, core, bus);
                 match &cache_entry.code {
                     Code::Jitted(function) => {
+                        let function = *function;
+                        let address_range = cache_entry.address_range.clone();
                         let bytes = cache_entry.address_range.end - cache_entry.address_range.start;
-                        let mut prefix = "S";
-                        for pc in (self.state.program_counter..self.state.program_counter + bytes)
-                            .step_by(4)
-                        {
-                            let physical_address = self.mmu.virtual_to_physical(pc, self.mode);
-                            let instruction = Instruction::decode(bus.read(physical_address));
-                            println!("{prefix} {:08x}: {}", pc, instruction);
-                            prefix = "J";
+                        if log_enabled!(target: "ee.core", Level::Trace) {
+                            let mut prefix = "S";
+                            for pc in (self.state.program_counter
+                                ..self.state.program_counter + bytes)
+                                .step_by(4)
+                            {
+                                let physical_address =
+                                    self.mmu.virtual_to_physical(pc, self.mode).unwrap();
+                                let instruction = Instruction::decode(bus.read(physical_address));
+                                trace!(target: "ee.core", "{prefix} {:08x}: {}", pc, instruction);
+                                prefix = "J";
+                            }
                         }
                         assert!(self.mmu.physically_consecutive(
                             self.state.program_counter..self.state.program_counter + bytes,
                             self.mode
                         ));
-                        cycles = cycles.saturating_sub(bytes as u64 / 4);
-                        function(self.mode);
+                        let started = self.jit.profiler.times_blocks().then(Instant::now);
+                        let completed = if self.lockstep.is_some() {
+                            self.run_lockstep(function, bus, &mut cycles)
+                        } else {
+                            self.jit.run(
+                                function,
+                                self.mode,
+                                self.state.registers.as_slice().as_ptr(),
+                                &mut cycles,
+                            )
+                        };
+                        if let Some(started) = started {
+                            self.jit.profiler.ran(address_range, started.elapsed());
+                        }
+                        if !completed {
+                            // The block bailed out before an instruction that raises an exception
+                            // (or before the branch owning its delay slot). Interpret it so the
+                            // exception is raised precisely.
+                            let physical_program_counter = self
+                                .mmu
+                                .virtual_to_physical(self.state.program_counter, self.mode)
+                                .unwrap();
+                            let instruction =
+                                Instruction::decode(bus.read(physical_program_counter));
+                            trace!(
+                                target: "ee.core",
+                                "I {:08x}: {}",
+                                self.state.program_counter,
+                                instruction
+                            );
+                            cycles = cycles.saturating_sub(self.interpret_timed(instruction, bus));
+                        }
                     }
                     Code::Interpreted(instruction) => {
-                        cycles -= 1;
                         let instruction = *instruction;
-                        println!("I {:08x}: {}", self.state.program_counter, instruction);
-                        self.interpret_instruction(instruction, bus);
+                        trace!(
+                            target: "ee.core",
+                            "I {:08x}: {}",
+                            self.state.program_counter,
+                            instruction
+                        );
+                        cycles = cycles.saturating_sub(self.interpret_timed(instruction, bus));
                     }
                 }
             }
         }
     }
+
+    // Interprets an instruction, returning the cycles it took.
+    pub(super) fn interpret_timed(&mut self, instruction: Instruction, bus: &mut Bus) -> u64 {
+        self.interpret_instruction(instruction, bus);
+        let nullified =
+            instruction.is_branch_likely() && self.state.delayed_branch_target.is_none();
+        instruction.cycles() + nullified as u64 * NULLIFIED_DELAY_SLOT_CYCLES
+    }
 }
@@ -1,20 +1,249 @@
 use std::fmt::Display;
 
+use crate::bits::Bits;
+
 use super::register::{GetRegister, SetRegister};
 
 // Coprocessor 1
-#[derive(Debug)]
+//
+// The EE FPU is not IEEE 754 compliant: there are no denormals, infinities or NaNs, results are
+// rounded toward zero and overflows clamp to the largest representable magnitude.
+#[derive(Debug, Clone)]
 pub struct Fpu {
     registers: [f32; 32],
+    accumulator: f32, // ACC
+    control: u32,     // FCR31
 }
 
+// FCR31
+pub const UNDERFLOW_STICKY: usize = 3;
+pub const OVERFLOW_STICKY: usize = 4;
+pub const DIVIDE_STICKY: usize = 5;
+pub const INVALID_STICKY: usize = 6;
+pub const UNDERFLOW: usize = 14;
+pub const OVERFLOW: usize = 15;
+pub const DIVIDE: usize = 16;
+pub const INVALID: usize = 17;
+pub const CONDITION: usize = 23;
+const CONTROL_WRITE_MASK: u32 = 0x0083_C078;
+const CONTROL_FIXED_BITS: u32 = 0x0100_0001;
+
+// 2^128, the smallest magnitude that doesn't truncate to f32::MAX.
+const OVERFLOW_THRESHOLD: f64 = 340282366920938463463374607431768211456.0;
+
+// FCR0
+const IMPLEMENTATION_REVISION: u32 = 0x2E00;
+
 impl Fpu {
     pub fn new() -> Fpu {
         Fpu {
             registers: [0.0; 32],
+            accumulator: 0.0,
+            control: CONTROL_FIXED_BITS,
+        }
+    }
+
+    // CFC1
+    pub fn get_control(&self, register: Register) -> u32 {
+        match register.index {
+            0 => IMPLEMENTATION_REVISION,
+            31 => self.control,
+            index => panic!("Invalid FPU control register read: {}", index),
         }
     }
 
+    // CTC1
+    pub fn set_control(&mut self, register: Register, value: u32) {
+        match register.index {
+            0 => {}
+            31 => self.control = value & CONTROL_WRITE_MASK | CONTROL_FIXED_BITS,
+            index => panic!("Invalid FPU control register write: {}", index),
+        }
+    }
+
+    pub fn condition(&self) -> bool {
+        self.control.bit(CONDITION)
+    }
+
+    // FCR31, for the JIT to test the condition in place.
+    pub fn control_address(&self) -> *const u32 {
+        &self.control
+    }
+
+    pub fn register_address(&self, register: Register) -> *const f32 {
+        &self.registers[register.index as usize]
+    }
+
+    pub fn accumulator_address(&self) -> *const f32 {
+        &self.accumulator
+    }
+
+    pub fn accumulator(&self) -> f32 {
+        self.accumulator
+    }
+
+    pub fn set_accumulator(&mut self, value: f32) {
+        self.accumulator = value;
+    }
+
+    pub fn add(&mut self, a: f32, b: f32) -> f32 {
+        self.clear_exponent_flags();
+        self.round(operand(a) + operand(b))
+    }
+
+    pub fn sub(&mut self, a: f32, b: f32) -> f32 {
+        self.clear_exponent_flags();
+        self.round(operand(a) - operand(b))
+    }
+
+    pub fn mul(&mut self, a: f32, b: f32) -> f32 {
+        self.clear_exponent_flags();
+        self.round(operand(a) * operand(b))
+    }
+
+    // ACC + a * b, with the product rounded before the addition like the hardware does.
+    pub fn multiply_add(&mut self, a: f32, b: f32) -> f32 {
+        let product = self.mul(a, b);
+        let flags = self.control.bits(UNDERFLOW..=OVERFLOW);
+        let value = self.add(self.accumulator, product);
+        self.control |= flags << UNDERFLOW;
+        value
+    }
+
+    // ACC - a * b
+    pub fn multiply_subtract(&mut self, a: f32, b: f32) -> f32 {
+        let product = self.mul(a, b);
+        let flags = self.control.bits(UNDERFLOW..=OVERFLOW);
+        let value = self.sub(self.accumulator, product);
+        self.control |= flags << UNDERFLOW;
+        value
+    }
+
+    pub fn div(&mut self, a: f32, b: f32) -> f32 {
+        self.clear_division_flags();
+        let (a, b) = (operand(a), operand(b));
+        if b == 0.0 {
+            if a == 0.0 {
+                self.raise(INVALID, INVALID_STICKY);
+            } else {
+                self.raise(DIVIDE, DIVIDE_STICKY);
+            }
+            return clamped(a.is_sign_negative() != b.is_sign_negative());
+        }
+        self.round(a / b)
+    }
+
+    pub fn sqrt(&mut self, a: f32) -> f32 {
+        self.clear_division_flags();
+        let a = operand(a);
+        if a < 0.0 {
+            self.raise(INVALID, INVALID_STICKY);
+        }
+        // The square root of -0 is -0, negative inputs otherwise use their magnitude.
+        if a == 0.0 {
+            return a as f32;
+        }
+        self.round(a.abs().sqrt())
+    }
+
+    // a / sqrt(b)
+    pub fn rsqrt(&mut self, a: f32, b: f32) -> f32 {
+        self.clear_division_flags();
+        let (a, b) = (operand(a), operand(b));
+        if b == 0.0 {
+            self.raise(DIVIDE, DIVIDE_STICKY);
+            return clamped(a.is_sign_negative() != b.is_sign_negative());
+        }
+        if b < 0.0 {
+            self.raise(INVALID, INVALID_STICKY);
+        }
+        self.round(a / b.abs().sqrt())
+    }
+
+    pub fn abs(&mut self, a: f32) -> f32 {
+        self.clear_exponent_flags();
+        f32::from_bits(a.to_bits() & !(1 << 31))
+    }
+
+    pub fn neg(&mut self, a: f32) -> f32 {
+        self.clear_exponent_flags();
+        f32::from_bits(a.to_bits() ^ (1 << 31))
+    }
+
+    pub fn max(&mut self, a: f32, b: f32) -> f32 {
+        self.clear_exponent_flags();
+        if operand(a) >= operand(b) {
+            a
+        } else {
+            b
+        }
+    }
+
+    pub fn min(&mut self, a: f32, b: f32) -> f32 {
+        self.clear_exponent_flags();
+        if operand(a) <= operand(b) {
+            a
+        } else {
+            b
+        }
+    }
+
+    pub fn compare(&mut self, condition: bool) {
+        self.control.set_bit(CONDITION, condition);
+    }
+
+    pub fn compare_equal(&mut self, a: f32, b: f32) {
+        self.compare(operand(a) == operand(b));
+    }
+
+    pub fn compare_less_than(&mut self, a: f32, b: f32) {
+        self.compare(operand(a) < operand(b));
+    }
+
+    pub fn compare_less_equal(&mut self, a: f32, b: f32) {
+        self.compare(operand(a) <= operand(b));
+    }
+
+    // CVT.W.S, truncating and saturating out of range values.
+    pub fn to_word(&self, a: f32) -> u32 {
+        operand(a) as i32 as u32
+    }
+
+    // CVT.S.W
+    pub fn to_single(&self, a: u32) -> f32 {
+        truncate(a as i32 as f64)
+    }
+
+    fn clear_exponent_flags(&mut self) {
+        self.control.set_bit(OVERFLOW, false);
+        self.control.set_bit(UNDERFLOW, false);
+    }
+
+    fn clear_division_flags(&mut self) {
+        self.control.set_bit(INVALID, false);
+        self.control.set_bit(DIVIDE, false);
+    }
+
+    fn raise(&mut self, flag: usize, sticky: usize) {
+        self.control.set_bit(flag, true);
+        self.control.set_bit(sticky, true);
+    }
+
+    // Round toward zero, clamping overflows and flushing underflows to zero. Only values that
+    // don't truncate to f32::MAX overflow.
+    fn round(&mut self, value: f64) -> f32 {
+        if value.abs() >= OVERFLOW_THRESHOLD {
+            self.raise(OVERFLOW, OVERFLOW_STICKY);
+            return clamped(value.is_sign_negative());
+        }
+        let result = truncate(value);
+        if value != 0.0 && !result.is_normal() {
+            self.raise(UNDERFLOW, UNDERFLOW_STICKY);
+            return if value.is_sign_negative() { -0.0 } else { 0.0 };
+        }
+        result
+    }
+
     pub fn get_register<T>(&self, register: Register) -> T
     where
         f32: GetRegister<T>,
@@ -72,3 +301,151 @@ impl SetRegister<u32> for f32 {
         *self = f32::from_bits(value);
     }
 }
+
+// The value of a register as an operand. Denormals read as zero and the exponent 255 encodings,
+// which are ordinary numbers on the EE, are read as the largest representable magnitude.
+fn operand(value: f32) -> f64 {
+    if value.is_subnormal() {
+        if value.is_sign_negative() {
+            -0.0
+        } else {
+            0.0
+        }
+    } else if !value.is_finite() {
+        clamped(value.is_sign_negative()) as f64
+    } else {
+        value as f64
+    }
+}
+
+fn clamped(negative: bool) -> f32 {
+    if negative {
+        -f32::MAX
+    } else {
+        f32::MAX
+    }
+}
+
+fn truncate(value: f64) -> f32 {
+    let result = value as f32;
+    if (result as f64).abs() > value.abs() {
+        f32::from_bits(result.to_bits() - 1)
+    } else {
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn flags(fpu: &Fpu) -> u32 {
+        fpu.get_control(Register::from(31))
+    }
+
+    #[test]
+    fn round_toward_zero() {
+        let mut fpu = Fpu::new();
+        // 1/3 is 0x3EAAAAAB when rounded to nearest.
+        assert_eq!(fpu.div(1.0, 3.0).to_bits(), 0x3EAA_AAAA);
+        assert_eq!(fpu.div(-1.0, 3.0).to_bits(), 0xBEAA_AAAA);
+        // 2^31 - 1 is 2^31 when rounded to nearest.
+        assert_eq!(fpu.to_single(0x7FFF_FFFF), 2147483520.0);
+        assert_eq!(flags(&fpu), CONTROL_FIXED_BITS);
+    }
+
+    #[test]
+    fn overflow_at_max() {
+        let mut fpu = Fpu::new();
+        // Within an ulp of f32::MAX still truncates to it.
+        assert_eq!(fpu.add(f32::MAX, f32::MAX / (1 << 24) as f32), f32::MAX);
+        assert!(!flags(&fpu).bit(OVERFLOW));
+        assert_eq!(fpu.add(f32::MAX, f32::MAX), f32::MAX);
+        assert!(flags(&fpu).bit(OVERFLOW));
+        assert!(flags(&fpu).bit(OVERFLOW_STICKY));
+        assert_eq!(fpu.mul(-f32::MAX, 2.0), -f32::MAX);
+        assert!(flags(&fpu).bit(OVERFLOW));
+        // The flag is cleared by the next operation, the sticky one isn't.
+        assert_eq!(fpu.add(1.0, 1.0), 2.0);
+        assert!(!flags(&fpu).bit(OVERFLOW));
+        assert!(flags(&fpu).bit(OVERFLOW_STICKY));
+        // Exponent 255 reads as f32::MAX rather than infinity or NaN.
+        assert_eq!(fpu.sub(f32::INFINITY, 0.0), f32::MAX);
+        assert_eq!(fpu.add(f32::from_bits(0xFFFF_FFFF), 0.0), -f32::MAX);
+    }
+
+    #[test]
+    fn underflow_to_signed_zero() {
+        let mut fpu = Fpu::new();
+        let result = fpu.mul(f32::MIN_POSITIVE, 0.5);
+        assert_eq!(result.to_bits(), 0);
+        assert!(flags(&fpu).bit(UNDERFLOW));
+        assert!(flags(&fpu).bit(UNDERFLOW_STICKY));
+        let result = fpu.mul(-f32::MIN_POSITIVE, 0.5);
+        assert_eq!(result.to_bits(), 0x8000_0000);
+        assert!(flags(&fpu).bit(UNDERFLOW));
+        // Denormal operands read as zero without underflowing.
+        assert_eq!(fpu.add(f32::from_bits(1), 0.0), 0.0);
+        assert!(!flags(&fpu).bit(UNDERFLOW));
+        assert_eq!(fpu.mul(f32::MIN_POSITIVE, 1.0), f32::MIN_POSITIVE);
+        assert!(!flags(&fpu).bit(UNDERFLOW));
+    }
+
+    #[test]
+    fn divide_by_zero() {
+        let mut fpu = Fpu::new();
+        assert_eq!(fpu.div(1.0, 0.0), f32::MAX);
+        assert!(flags(&fpu).bit(DIVIDE));
+        assert!(flags(&fpu).bit(DIVIDE_STICKY));
+        assert!(!flags(&fpu).bit(INVALID));
+        assert_eq!(fpu.div(-1.0, 0.0), -f32::MAX);
+        assert_eq!(fpu.div(1.0, -0.0), -f32::MAX);
+        assert_eq!(fpu.div(-1.0, -0.0), f32::MAX);
+        // Denormal divisors are zero too.
+        assert_eq!(fpu.div(1.0, f32::from_bits(0x8000_0001)), -f32::MAX);
+        assert_eq!(fpu.div(0.0, 0.0), f32::MAX);
+        assert!(flags(&fpu).bit(INVALID));
+        assert!(flags(&fpu).bit(INVALID_STICKY));
+        assert!(!flags(&fpu).bit(DIVIDE));
+        assert_eq!(fpu.div(1.0, 2.0), 0.5);
+        assert!(!flags(&fpu).bit(INVALID));
+        assert!(flags(&fpu).bit(INVALID_STICKY));
+    }
+
+    #[test]
+    fn rsqrt_by_zero() {
+        let mut fpu = Fpu::new();
+        assert_eq!(fpu.rsqrt(1.0, 0.0), f32::MAX);
+        assert!(flags(&fpu).bit(DIVIDE));
+        assert!(flags(&fpu).bit(DIVIDE_STICKY));
+        assert_eq!(fpu.rsqrt(-1.0, 0.0), -f32::MAX);
+        assert_eq!(fpu.rsqrt(1.0, -0.0), -f32::MAX);
+        assert_eq!(fpu.rsqrt(4.0, 4.0), 2.0);
+        assert!(!flags(&fpu).bit(DIVIDE));
+        // Negative inputs use their magnitude.
+        assert_eq!(fpu.rsqrt(4.0, -4.0), 2.0);
+        assert!(flags(&fpu).bit(INVALID));
+    }
+
+    #[test]
+    fn multiply_add_keeps_product_flags() {
+        let mut fpu = Fpu::new();
+        fpu.set_accumulator(-f32::MAX);
+        // The product clamps to f32::MAX, and the sum doesn't overflow.
+        assert_eq!(fpu.multiply_add(f32::MAX, 2.0), 0.0);
+        assert!(flags(&fpu).bit(OVERFLOW));
+        assert!(!flags(&fpu).bit(UNDERFLOW));
+        fpu.set_accumulator(1.0);
+        assert_eq!(fpu.multiply_add(f32::MIN_POSITIVE, 0.5), 1.0);
+        assert!(flags(&fpu).bit(UNDERFLOW));
+        assert!(!flags(&fpu).bit(OVERFLOW));
+        assert_eq!(fpu.multiply_subtract(f32::MIN_POSITIVE, 0.5), 1.0);
+        assert!(flags(&fpu).bit(UNDERFLOW));
+        // The sum's own flags are raised too.
+        fpu.set_accumulator(f32::MAX);
+        assert_eq!(fpu.multiply_add(f32::MAX, 1.0), f32::MAX);
+        assert!(flags(&fpu).bit(OVERFLOW));
+        assert_eq!(fpu.multiply_add(1.0, 1.0), f32::MAX);
+        assert!(!flags(&fpu).bit(OVERFLOW));
+    }
+}
@@ -53,25 +53,104 @@ impl Instruction {
         }
     }
 
-    pub fn branch_target(&self, address: u32) -> Option<u32> {
+    // The offset of PC-relative branches, in instructions from the delay slot.
+    pub fn branch_offset(&self) -> Option<u16> {
         match self {
             Instruction::Bltz(_, offset)
             | Instruction::Bgez(_, offset)
+            | Instruction::Bltzl(_, offset)
+            | Instruction::Bgezl(_, offset)
+            | Instruction::Bltzal(_, offset)
+            | Instruction::Bgezal(_, offset)
+            | Instruction::Bltzall(_, offset)
+            | Instruction::Bgezall(_, offset)
             | Instruction::Beq(_, _, offset)
             | Instruction::Bne(_, _, offset)
             | Instruction::Blez(_, offset)
+            | Instruction::Bgtz(_, offset)
             | Instruction::Beql(_, _, offset)
-            | Instruction::Bnel(_, _, offset) => Some({
-                let offset: u32 = offset.sign_extend();
-                address.wrapping_add(4).wrapping_add(offset << 2)
-            }),
+            | Instruction::Bnel(_, _, offset)
+            | Instruction::Blezl(_, offset)
+            | Instruction::Bgtzl(_, offset)
+            | Instruction::Bc1f(offset)
+            | Instruction::Bc1t(offset)
+            | Instruction::Bc1fl(offset)
+            | Instruction::Bc1tl(offset) => Some(*offset),
+            _ => None,
+        }
+    }
+
+    pub fn branch_target(&self, address: u32) -> Option<u32> {
+        match self {
             Instruction::J(target) | Instruction::Jal(target) => {
                 Some((address.wrapping_add(4) & 0xF000_0000).wrapping_add(target << 2))
             }
-            _ => None,
+            _ => self.branch_offset().map(|offset| {
+                let offset: u32 = offset.sign_extend();
+                address.wrapping_add(4).wrapping_add(offset << 2)
+            }),
         }
     }
 
+    // Loads, register arithmetic and conditional branches, which a loop can repeat without
+    // changing anything but the registers they write. That holds for loads from memory only: a
+    // load from MMIO (0x1000_0000-0x2000_0000) can have side effects, which the JIT checks for as
+    // the loop runs since the address isn't known here.
+    pub fn is_side_effect_free(&self) -> bool {
+        self.is_nop()
+            || matches!(
+                self,
+                Instruction::Sll(..)
+                    | Instruction::Srl(..)
+                    | Instruction::Sra(..)
+                    | Instruction::Sllv(..)
+                    | Instruction::Srlv(..)
+                    | Instruction::Srav(..)
+                    | Instruction::Movz(..)
+                    | Instruction::Movn(..)
+                    | Instruction::Addu(..)
+                    | Instruction::Subu(..)
+                    | Instruction::And(..)
+                    | Instruction::Or(..)
+                    | Instruction::Xor(..)
+                    | Instruction::Nor(..)
+                    | Instruction::Slt(..)
+                    | Instruction::Sltu(..)
+                    | Instruction::Daddu(..)
+                    | Instruction::Dsll(..)
+                    | Instruction::Dsrl(..)
+                    | Instruction::Dsll32(..)
+                    | Instruction::Dsrl32(..)
+                    | Instruction::Addiu(..)
+                    | Instruction::Daddiu(..)
+                    | Instruction::Slti(..)
+                    | Instruction::Sltiu(..)
+                    | Instruction::Andi(..)
+                    | Instruction::Ori(..)
+                    | Instruction::Xori(..)
+                    | Instruction::Lui(..)
+                    | Instruction::Lb(..)
+                    | Instruction::Lbu(..)
+                    | Instruction::Lh(..)
+                    | Instruction::Lhu(..)
+                    | Instruction::Lw(..)
+                    | Instruction::Ld(..)
+                    | Instruction::Lq(..)
+                    | Instruction::Beq(..)
+                    | Instruction::Bne(..)
+                    | Instruction::Blez(..)
+                    | Instruction::Bgtz(..)
+                    | Instruction::Bltz(..)
+                    | Instruction::Bgez(..)
+                    | Instruction::Beql(..)
+                    | Instruction::Bnel(..)
+                    | Instruction::Blezl(..)
+                    | Instruction::Bgtzl(..)
+                    | Instruction::Bltzl(..)
+                    | Instruction::Bgezl(..)
+            )
+    }
+
     pub fn definitions(&self) -> impl Iterator<Item = Occurrence> {
         self.raw_definitions()
             .into_iter()
@@ -63,6 +63,12 @@ pub enum Instruction {
     Dsra32(Register, Register, u8),
     Bltz(Register, u16),
     Bgez(Register, u16),
+    Bltzl(Register, u16),
+    Bgezl(Register, u16),
+    Bltzal(Register, u16),
+    Bgezal(Register, u16),
+    Bltzall(Register, u16),
+    Bgezall(Register, u16),
     J(u32),
     Jal(u32),
     Beq(Register, Register, u16),
@@ -83,16 +89,46 @@ pub enum Instruction {
     Tlbwi,
     Tlbwr,
     Tlbp,
+    Eret,
     Ei,
+    Di,
     Mfc1(Register, fpu::Register),
+    Cfc1(Register, fpu::Register),
     Mtc1(fpu::Register, Register),
+    Ctc1(Register, fpu::Register),
+    Bc1f(u16),
+    Bc1t(u16),
+    Bc1fl(u16),
+    Bc1tl(u16),
+    Adds(fpu::Register, fpu::Register, fpu::Register),
+    Subs(fpu::Register, fpu::Register, fpu::Register),
     Muls(fpu::Register, fpu::Register, fpu::Register),
     Divs(fpu::Register, fpu::Register, fpu::Register),
+    Sqrts(fpu::Register, fpu::Register),
+    Abss(fpu::Register, fpu::Register),
     Movs(fpu::Register, fpu::Register),
+    Negs(fpu::Register, fpu::Register),
+    Rsqrts(fpu::Register, fpu::Register, fpu::Register),
+    Addas(fpu::Register, fpu::Register),
+    Subas(fpu::Register, fpu::Register),
+    Mulas(fpu::Register, fpu::Register),
+    Madds(fpu::Register, fpu::Register, fpu::Register),
+    Msubs(fpu::Register, fpu::Register, fpu::Register),
+    Maddas(fpu::Register, fpu::Register),
+    Msubas(fpu::Register, fpu::Register),
     Cvtws(fpu::Register, fpu::Register),
+    Maxs(fpu::Register, fpu::Register, fpu::Register),
+    Mins(fpu::Register, fpu::Register, fpu::Register),
+    Cfs(fpu::Register, fpu::Register),
+    Ceqs(fpu::Register, fpu::Register),
+    Clts(fpu::Register, fpu::Register),
+    Cles(fpu::Register, fpu::Register),
     Cvtsw(fpu::Register, fpu::Register),
     Beql(Register, Register, u16),
     Bnel(Register, Register, u16),
+    Blezl(Register, u16),
+    Bgtzl(Register, u16),
+    Daddi(Register, Register, u16),
     Daddiu(Register, Register, u16),
     Mfhi1(Register),
     Mthi1(Register),
@@ -105,6 +141,100 @@ pub enum Instruction {
     Por(Register, Register, Register),
     Pxor(Register, Register, Register),
     Pnor(Register, Register, Register),
+    Madd(Register, Register, Register),
+    Maddu(Register, Register, Register),
+    Plzcw(Register, Register),
+    Multu1(Register, Register, Register),
+    Madd1(Register, Register, Register),
+    Maddu1(Register, Register, Register),
+    Pmfhllw(Register),
+    Pmfhluw(Register),
+    Pmfhlslw(Register),
+    Pmfhllh(Register),
+    Pmfhlsh(Register),
+    Pmthllw(Register),
+    Psllh(Register, Register, u8),
+    Psrlh(Register, Register, u8),
+    Psrah(Register, Register, u8),
+    Psllw(Register, Register, u8),
+    Psrlw(Register, Register, u8),
+    Psraw(Register, Register, u8),
+    Paddw(Register, Register, Register),
+    Psubw(Register, Register, Register),
+    Pcgtw(Register, Register, Register),
+    Pmaxw(Register, Register, Register),
+    Paddh(Register, Register, Register),
+    Psubh(Register, Register, Register),
+    Pcgth(Register, Register, Register),
+    Pmaxh(Register, Register, Register),
+    Paddb(Register, Register, Register),
+    Psubb(Register, Register, Register),
+    Pcgtb(Register, Register, Register),
+    Paddsw(Register, Register, Register),
+    Psubsw(Register, Register, Register),
+    Pextlw(Register, Register, Register),
+    Ppacw(Register, Register, Register),
+    Paddsh(Register, Register, Register),
+    Psubsh(Register, Register, Register),
+    Pextlh(Register, Register, Register),
+    Ppach(Register, Register, Register),
+    Paddsb(Register, Register, Register),
+    Psubsb(Register, Register, Register),
+    Pextlb(Register, Register, Register),
+    Ppacb(Register, Register, Register),
+    Pext5(Register, Register),
+    Ppac5(Register, Register),
+    Pabsw(Register, Register),
+    Pceqw(Register, Register, Register),
+    Pminw(Register, Register, Register),
+    Padsbh(Register, Register, Register),
+    Pabsh(Register, Register),
+    Pceqh(Register, Register, Register),
+    Pminh(Register, Register, Register),
+    Pceqb(Register, Register, Register),
+    Padduw(Register, Register, Register),
+    Psubuw(Register, Register, Register),
+    Pextuw(Register, Register, Register),
+    Padduh(Register, Register, Register),
+    Psubuh(Register, Register, Register),
+    Pextuh(Register, Register, Register),
+    Paddub(Register, Register, Register),
+    Psubub(Register, Register, Register),
+    Pextub(Register, Register, Register),
+    Qfsrv(Register, Register, Register),
+    Pmaddw(Register, Register, Register),
+    Psllvw(Register, Register, Register),
+    Psrlvw(Register, Register, Register),
+    Pmsubw(Register, Register, Register),
+    Pmfhi(Register),
+    Pmflo(Register),
+    Pinth(Register, Register, Register),
+    Pmultw(Register, Register, Register),
+    Pdivw(Register, Register),
+    Pcpyld(Register, Register, Register),
+    Pmaddh(Register, Register, Register),
+    Phmadh(Register, Register, Register),
+    Pmsubh(Register, Register, Register),
+    Phmsbh(Register, Register, Register),
+    Pexeh(Register, Register),
+    Prevh(Register, Register),
+    Pmulth(Register, Register, Register),
+    Pdivbw(Register, Register),
+    Pexew(Register, Register),
+    Prot3w(Register, Register),
+    Pmadduw(Register, Register, Register),
+    Psravw(Register, Register, Register),
+    Pmthi(Register),
+    Pmtlo(Register),
+    Pinteh(Register, Register, Register),
+    Pmultuw(Register, Register, Register),
+    Pdivuw(Register, Register),
+    Pcpyud(Register, Register, Register),
+    Pexch(Register, Register),
+    Pcpyh(Register, Register),
+    Pexcw(Register, Register),
+    Ldl(Register, u16, Register),
+    Ldr(Register, u16, Register),
     Lq(Register, u16, Register),
     Sq(Register, u16, Register),
     Lb(Register, u16, Register),
@@ -112,10 +242,15 @@ pub enum Instruction {
     Lw(Register, u16, Register),
     Lbu(Register, u16, Register),
     Lhu(Register, u16, Register),
+    Lwl(Register, u16, Register),
     Lwr(Register, u16, Register),
     Sb(Register, u16, Register),
     Sh(Register, u16, Register),
+    Swl(Register, u16, Register),
     Sw(Register, u16, Register),
+    Sdl(Register, u16, Register),
+    Sdr(Register, u16, Register),
+    Swr(Register, u16, Register),
     Cache(CacheOperation, u16, Register),
     Lwc1(fpu::Register, u16, Register),
     Ld(Register, u16, Register),
@@ -351,6 +486,12 @@ impl Instruction {
             0b000001 => match data.bits(16..21) {
                 0b00000 => Instruction::Bltz(rs(), imm16()),
                 0b00001 => Instruction::Bgez(rs(), imm16()),
+                0b00010 => Instruction::Bltzl(rs(), imm16()),
+                0b00011 => Instruction::Bgezl(rs(), imm16()),
+                0b10000 => Instruction::Bltzal(rs(), imm16()),
+                0b10001 => Instruction::Bgezal(rs(), imm16()),
+                0b10010 => Instruction::Bltzall(rs(), imm16()),
+                0b10011 => Instruction::Bgezall(rs(), imm16()),
                 _ => panic!("Unhandled instruction: {:#034b}", data),
             }
             0b000010 => Instruction::J(imm26()),
@@ -398,89 +539,410 @@ impl Instruction {
                     0b100000000000000 => Instruction::Tlbp,
                     _ => panic!("Unhandled instruction: {:#034b}", data),
                 }
+                0b00000011000 => match data.bits(11..26) {
+                    0b100000000000000 => Instruction::Eret,
+                    _ => panic!("Unhandled instruction: {:#034b}", data),
+                }
                 0b00000111000 => match data.bits(11..26) {
                     0b100000000000000 => Instruction::Ei,
                     _ => panic!("Unhandled instruction: {:#034b}", data),
                 }
+                0b00000111001 => match data.bits(11..26) {
+                    0b100000000000000 => Instruction::Di,
+                    _ => panic!("Unhandled instruction: {:#034b}", data),
+                }
                 _ => panic!("Unhandled instruction: {:#034b}", data),
             }
-            0b010001 => match data.bits(0..6) {
-                0b000000 => match data.bits(6..11) {
-                    0b00000 => match data.bits(21..26) {
-                        0b00000 => Instruction::Mfc1(rt(), fs()),
-                        0b00100 => Instruction::Mtc1(fs(), rt()),
-                        _ => panic!("Unhandled instruction: {:#034b}", data),
-                    }
+            0b010001 => match data.bits(21..26) {
+                0b00000 => match data.bits(0..11) {
+                    0b00000000000 => Instruction::Mfc1(rt(), fs()),
                     _ => panic!("Unhandled instruction: {:#034b}", data),
                 }
-                0b000010 => match data.bits(21..26) {
-                    0b10000 => Instruction::Muls(fd(), fs(), ft()),
+                0b00010 => match data.bits(0..11) {
+                    0b00000000000 => Instruction::Cfc1(rt(), fs()),
                     _ => panic!("Unhandled instruction: {:#034b}", data),
                 }
-                0b000011 => match data.bits(21..26) {
-                    0b10000 => Instruction::Divs(fd(), fs(), ft()),
+                0b00100 => match data.bits(0..11) {
+                    0b00000000000 => Instruction::Mtc1(fs(), rt()),
                     _ => panic!("Unhandled instruction: {:#034b}", data),
                 }
-                0b000110 => match data.bits(16..26) {
-                    0b1000000000 => Instruction::Movs(fd(), fs()),
+                0b00110 => match data.bits(0..11) {
+                    0b00000000000 => Instruction::Ctc1(rt(), fs()),
                     _ => panic!("Unhandled instruction: {:#034b}", data),
                 }
-                0b100000 => match data.bits(16..26) {
-                    0b1010000000 => Instruction::Cvtsw(fd(), fs()),
+                0b01000 => match data.bits(16..21) {
+                    0b00000 => Instruction::Bc1f(imm16()),
+                    0b00001 => Instruction::Bc1t(imm16()),
+                    0b00010 => Instruction::Bc1fl(imm16()),
+                    0b00011 => Instruction::Bc1tl(imm16()),
                     _ => panic!("Unhandled instruction: {:#034b}", data),
                 }
-                0b100100 => match data.bits(16..26) {
-                    0b1000000000 => Instruction::Cvtws(fd(), fs()),
+                0b10000 => match data.bits(0..6) {
+                    0b000000 => Instruction::Adds(fd(), fs(), ft()),
+                    0b000001 => Instruction::Subs(fd(), fs(), ft()),
+                    0b000010 => Instruction::Muls(fd(), fs(), ft()),
+                    0b000011 => Instruction::Divs(fd(), fs(), ft()),
+                    0b000100 => match data.bits(11..16) {
+                        0b00000 => Instruction::Sqrts(fd(), ft()),
+                        _ => panic!("Unhandled instruction: {:#034b}", data),
+                    }
+                    0b000101 => match data.bits(16..21) {
+                        0b00000 => Instruction::Abss(fd(), fs()),
+                        _ => panic!("Unhandled instruction: {:#034b}", data),
+                    }
+                    0b000110 => match data.bits(16..21) {
+                        0b00000 => Instruction::Movs(fd(), fs()),
+                        _ => panic!("Unhandled instruction: {:#034b}", data),
+                    }
+                    0b000111 => match data.bits(16..21) {
+                        0b00000 => Instruction::Negs(fd(), fs()),
+                        _ => panic!("Unhandled instruction: {:#034b}", data),
+                    }
+                    0b010110 => Instruction::Rsqrts(fd(), fs(), ft()),
+                    0b011000 => match data.bits(6..11) {
+                        0b00000 => Instruction::Addas(fs(), ft()),
+                        _ => panic!("Unhandled instruction: {:#034b}", data),
+                    }
+                    0b011001 => match data.bits(6..11) {
+                        0b00000 => Instruction::Subas(fs(), ft()),
+                        _ => panic!("Unhandled instruction: {:#034b}", data),
+                    }
+                    0b011010 => match data.bits(6..11) {
+                        0b00000 => Instruction::Mulas(fs(), ft()),
+                        _ => panic!("Unhandled instruction: {:#034b}", data),
+                    }
+                    0b011100 => Instruction::Madds(fd(), fs(), ft()),
+                    0b011101 => Instruction::Msubs(fd(), fs(), ft()),
+                    0b011110 => match data.bits(6..11) {
+                        0b00000 => Instruction::Maddas(fs(), ft()),
+                        _ => panic!("Unhandled instruction: {:#034b}", data),
+                    }
+                    0b011111 => match data.bits(6..11) {
+                        0b00000 => Instruction::Msubas(fs(), ft()),
+                        _ => panic!("Unhandled instruction: {:#034b}", data),
+                    }
+                    0b100100 => match data.bits(16..21) {
+                        0b00000 => Instruction::Cvtws(fd(), fs()),
+                        _ => panic!("Unhandled instruction: {:#034b}", data),
+                    }
+                    0b101000 => Instruction::Maxs(fd(), fs(), ft()),
+                    0b101001 => Instruction::Mins(fd(), fs(), ft()),
+                    0b110000 => match data.bits(6..11) {
+                        0b00000 => Instruction::Cfs(fs(), ft()),
+                        _ => panic!("Unhandled instruction: {:#034b}", data),
+                    }
+                    0b110010 => match data.bits(6..11) {
+                        0b00000 => Instruction::Ceqs(fs(), ft()),
+                        _ => panic!("Unhandled instruction: {:#034b}", data),
+                    }
+                    0b110100 => match data.bits(6..11) {
+                        0b00000 => Instruction::Clts(fs(), ft()),
+                        _ => panic!("Unhandled instruction: {:#034b}", data),
+                    }
+                    0b110110 => match data.bits(6..11) {
+                        0b00000 => Instruction::Cles(fs(), ft()),
+                        _ => panic!("Unhandled instruction: {:#034b}", data),
+                    }
+                    _ => panic!("Unhandled instruction: {:#034b}", data),
+                }
+                0b10100 => match data.bits(0..6) {
+                    0b100000 => match data.bits(16..21) {
+                        0b00000 => Instruction::Cvtsw(fd(), fs()),
+                        _ => panic!("Unhandled instruction: {:#034b}", data),
+                    }
                     _ => panic!("Unhandled instruction: {:#034b}", data),
                 }
                 _ => panic!("Unhandled instruction: {:#034b}", data),
             }
             0b010100 => Instruction::Beql(rs(), rt(), imm16()),
             0b010101 => Instruction::Bnel(rs(), rt(), imm16()),
+            0b010110 => match data.bits(16..21) {
+                0b00000 => Instruction::Blezl(rs(), imm16()),
+                _ => panic!("Unhandled instruction: {:#034b}", data),
+            }
+            0b010111 => match data.bits(16..21) {
+                0b00000 => Instruction::Bgtzl(rs(), imm16()),
+                _ => panic!("Unhandled instruction: {:#034b}", data),
+            }
+            0b011000 => Instruction::Daddi(rt(), rs(), imm16()),
             0b011001 => Instruction::Daddiu(rt(), rs(), imm16()),
-            0b011100 => match data.bits(0..11) {
-                0b00000010000 => match data.bits(16..26) {
-                    0b0000000000 => Instruction::Mfhi1(rd()),
+            0b011010 => Instruction::Ldl(rt(), imm16(), rs()),
+            0b011011 => Instruction::Ldr(rt(), imm16(), rs()),
+            0b011100 => match data.bits(0..6) {
+                0b000000 => match data.bits(6..11) {
+                    0b00000 => Instruction::Madd(rd(), rs(), rt()),
                     _ => panic!("Unhandled instruction: {:#034b}", data),
                 }
-                0b00000010001 => match data.bits(11..21) {
-                    0b0000000000 => Instruction::Mthi1(rs()),
+                0b000001 => match data.bits(6..11) {
+                    0b00000 => Instruction::Maddu(rd(), rs(), rt()),
                     _ => panic!("Unhandled instruction: {:#034b}", data),
                 }
-                0b00000010010 => match data.bits(16..26) {
-                    0b0000000000 => Instruction::Mflo1(rd()),
+                0b000100 => match data.bits(6..11) {
+                    0b00000 => match data.bits(16..21) {
+                        0b00000 => Instruction::Plzcw(rd(), rs()),
+                        _ => panic!("Unhandled instruction: {:#034b}", data),
+                    }
                     _ => panic!("Unhandled instruction: {:#034b}", data),
                 }
-                0b00000010011 => match data.bits(11..21) {
-                    0b0000000000 => Instruction::Mtlo1(rs()),
+                0b001000 => match data.bits(6..11) {
+                    0b00000 => Instruction::Paddw(rd(), rs(), rt()),
+                    0b00001 => Instruction::Psubw(rd(), rs(), rt()),
+                    0b00010 => Instruction::Pcgtw(rd(), rs(), rt()),
+                    0b00011 => Instruction::Pmaxw(rd(), rs(), rt()),
+                    0b00100 => Instruction::Paddh(rd(), rs(), rt()),
+                    0b00101 => Instruction::Psubh(rd(), rs(), rt()),
+                    0b00110 => Instruction::Pcgth(rd(), rs(), rt()),
+                    0b00111 => Instruction::Pmaxh(rd(), rs(), rt()),
+                    0b01000 => Instruction::Paddb(rd(), rs(), rt()),
+                    0b01001 => Instruction::Psubb(rd(), rs(), rt()),
+                    0b01010 => Instruction::Pcgtb(rd(), rs(), rt()),
+                    0b10000 => Instruction::Paddsw(rd(), rs(), rt()),
+                    0b10001 => Instruction::Psubsw(rd(), rs(), rt()),
+                    0b10010 => Instruction::Pextlw(rd(), rs(), rt()),
+                    0b10011 => Instruction::Ppacw(rd(), rs(), rt()),
+                    0b10100 => Instruction::Paddsh(rd(), rs(), rt()),
+                    0b10101 => Instruction::Psubsh(rd(), rs(), rt()),
+                    0b10110 => Instruction::Pextlh(rd(), rs(), rt()),
+                    0b10111 => Instruction::Ppach(rd(), rs(), rt()),
+                    0b11000 => Instruction::Paddsb(rd(), rs(), rt()),
+                    0b11001 => Instruction::Psubsb(rd(), rs(), rt()),
+                    0b11010 => Instruction::Pextlb(rd(), rs(), rt()),
+                    0b11011 => Instruction::Ppacb(rd(), rs(), rt()),
+                    0b11110 => match data.bits(21..26) {
+                        0b00000 => Instruction::Pext5(rd(), rt()),
+                        _ => panic!("Unhandled instruction: {:#034b}", data),
+                    }
+                    0b11111 => match data.bits(21..26) {
+                        0b00000 => Instruction::Ppac5(rd(), rt()),
+                        _ => panic!("Unhandled instruction: {:#034b}", data),
+                    }
                     _ => panic!("Unhandled instruction: {:#034b}", data),
                 }
-                0b00000011000 => Instruction::Mult1(rd(), rs(), rt()),
-                0b00000011010 => match data.bits(11..16) {
-                    0b00000 => Instruction::Div1(rs(), rt()),
+                0b001001 => match data.bits(6..11) {
+                    0b00000 => Instruction::Pmaddw(rd(), rs(), rt()),
+                    0b00010 => Instruction::Psllvw(rd(), rt(), rs()),
+                    0b00011 => Instruction::Psrlvw(rd(), rt(), rs()),
+                    0b00100 => Instruction::Pmsubw(rd(), rs(), rt()),
+                    0b01000 => match data.bits(16..26) {
+                        0b0000000000 => Instruction::Pmfhi(rd()),
+                        _ => panic!("Unhandled instruction: {:#034b}", data),
+                    }
+                    0b01001 => match data.bits(16..26) {
+                        0b0000000000 => Instruction::Pmflo(rd()),
+                        _ => panic!("Unhandled instruction: {:#034b}", data),
+                    }
+                    0b01010 => Instruction::Pinth(rd(), rs(), rt()),
+                    0b01100 => Instruction::Pmultw(rd(), rs(), rt()),
+                    0b01101 => match data.bits(11..16) {
+                        0b00000 => Instruction::Pdivw(rs(), rt()),
+                        _ => panic!("Unhandled instruction: {:#034b}", data),
+                    }
+                    0b01110 => Instruction::Pcpyld(rd(), rs(), rt()),
+                    0b10000 => Instruction::Pmaddh(rd(), rs(), rt()),
+                    0b10001 => Instruction::Phmadh(rd(), rs(), rt()),
+                    0b10010 => Instruction::Pand(rd(), rs(), rt()),
+                    0b10011 => Instruction::Pxor(rd(), rs(), rt()),
+                    0b10100 => Instruction::Pmsubh(rd(), rs(), rt()),
+                    0b10101 => Instruction::Phmsbh(rd(), rs(), rt()),
+                    0b11010 => match data.bits(21..26) {
+                        0b00000 => Instruction::Pexeh(rd(), rt()),
+                        _ => panic!("Unhandled instruction: {:#034b}", data),
+                    }
+                    0b11011 => match data.bits(21..26) {
+                        0b00000 => Instruction::Prevh(rd(), rt()),
+                        _ => panic!("Unhandled instruction: {:#034b}", data),
+                    }
+                    0b11100 => Instruction::Pmulth(rd(), rs(), rt()),
+                    0b11101 => match data.bits(11..16) {
+                        0b00000 => Instruction::Pdivbw(rs(), rt()),
+                        _ => panic!("Unhandled instruction: {:#034b}", data),
+                    }
+                    0b11110 => match data.bits(21..26) {
+                        0b00000 => Instruction::Pexew(rd(), rt()),
+                        _ => panic!("Unhandled instruction: {:#034b}", data),
+                    }
+                    0b11111 => match data.bits(21..26) {
+                        0b00000 => Instruction::Prot3w(rd(), rt()),
+                        _ => panic!("Unhandled instruction: {:#034b}", data),
+                    }
                     _ => panic!("Unhandled instruction: {:#034b}", data),
                 }
-                0b00000011011 => match data.bits(11..16) {
-                    0b00000 => Instruction::Divu1(rs(), rt()),
+                0b010000 => match data.bits(6..11) {
+                    0b00000 => match data.bits(16..26) {
+                        0b0000000000 => Instruction::Mfhi1(rd()),
+                        _ => panic!("Unhandled instruction: {:#034b}", data),
+                    }
+                    _ => panic!("Unhandled instruction: {:#034b}", data),
+                }
+                0b010001 => match data.bits(6..21) {
+                    0b000000000000000 => Instruction::Mthi1(rs()),
+                    _ => panic!("Unhandled instruction: {:#034b}", data),
+                }
+                0b010010 => match data.bits(6..11) {
+                    0b00000 => match data.bits(16..26) {
+                        0b0000000000 => Instruction::Mflo1(rd()),
+                        _ => panic!("Unhandled instruction: {:#034b}", data),
+                    }
+                    _ => panic!("Unhandled instruction: {:#034b}", data),
+                }
+                0b010011 => match data.bits(6..21) {
+                    0b000000000000000 => Instruction::Mtlo1(rs()),
+                    _ => panic!("Unhandled instruction: {:#034b}", data),
+                }
+                0b011000 => match data.bits(6..11) {
+                    0b00000 => Instruction::Mult1(rd(), rs(), rt()),
+                    _ => panic!("Unhandled instruction: {:#034b}", data),
+                }
+                0b011001 => match data.bits(6..11) {
+                    0b00000 => Instruction::Multu1(rd(), rs(), rt()),
+                    _ => panic!("Unhandled instruction: {:#034b}", data),
+                }
+                0b011010 => match data.bits(6..16) {
+                    0b0000000000 => Instruction::Div1(rs(), rt()),
+                    _ => panic!("Unhandled instruction: {:#034b}", data),
+                }
+                0b011011 => match data.bits(6..16) {
+                    0b0000000000 => Instruction::Divu1(rs(), rt()),
+                    _ => panic!("Unhandled instruction: {:#034b}", data),
+                }
+                0b100000 => match data.bits(6..11) {
+                    0b00000 => Instruction::Madd1(rd(), rs(), rt()),
+                    _ => panic!("Unhandled instruction: {:#034b}", data),
+                }
+                0b100001 => match data.bits(6..11) {
+                    0b00000 => Instruction::Maddu1(rd(), rs(), rt()),
+                    _ => panic!("Unhandled instruction: {:#034b}", data),
+                }
+                0b101000 => match data.bits(6..11) {
+                    0b00001 => match data.bits(21..26) {
+                        0b00000 => Instruction::Pabsw(rd(), rt()),
+                        _ => panic!("Unhandled instruction: {:#034b}", data),
+                    }
+                    0b00010 => Instruction::Pceqw(rd(), rs(), rt()),
+                    0b00011 => Instruction::Pminw(rd(), rs(), rt()),
+                    0b00100 => Instruction::Padsbh(rd(), rs(), rt()),
+                    0b00101 => match data.bits(21..26) {
+                        0b00000 => Instruction::Pabsh(rd(), rt()),
+                        _ => panic!("Unhandled instruction: {:#034b}", data),
+                    }
+                    0b00110 => Instruction::Pceqh(rd(), rs(), rt()),
+                    0b00111 => Instruction::Pminh(rd(), rs(), rt()),
+                    0b01010 => Instruction::Pceqb(rd(), rs(), rt()),
+                    0b10000 => Instruction::Padduw(rd(), rs(), rt()),
+                    0b10001 => Instruction::Psubuw(rd(), rs(), rt()),
+                    0b10010 => Instruction::Pextuw(rd(), rs(), rt()),
+                    0b10100 => Instruction::Padduh(rd(), rs(), rt()),
+                    0b10101 => Instruction::Psubuh(rd(), rs(), rt()),
+                    0b10110 => Instruction::Pextuh(rd(), rs(), rt()),
+                    0b11000 => Instruction::Paddub(rd(), rs(), rt()),
+                    0b11001 => Instruction::Psubub(rd(), rs(), rt()),
+                    0b11010 => Instruction::Pextub(rd(), rs(), rt()),
+                    0b11011 => Instruction::Qfsrv(rd(), rs(), rt()),
+                    _ => panic!("Unhandled instruction: {:#034b}", data),
+                }
+                0b101001 => match data.bits(6..11) {
+                    0b00000 => Instruction::Pmadduw(rd(), rs(), rt()),
+                    0b00011 => Instruction::Psravw(rd(), rt(), rs()),
+                    0b01000 => match data.bits(11..21) {
+                        0b0000000000 => Instruction::Pmthi(rs()),
+                        _ => panic!("Unhandled instruction: {:#034b}", data),
+                    }
+                    0b01001 => match data.bits(11..21) {
+                        0b0000000000 => Instruction::Pmtlo(rs()),
+                        _ => panic!("Unhandled instruction: {:#034b}", data),
+                    }
+                    0b01010 => Instruction::Pinteh(rd(), rs(), rt()),
+                    0b01100 => Instruction::Pmultuw(rd(), rs(), rt()),
+                    0b01101 => match data.bits(11..16) {
+                        0b00000 => Instruction::Pdivuw(rs(), rt()),
+                        _ => panic!("Unhandled instruction: {:#034b}", data),
+                    }
+                    0b01110 => Instruction::Pcpyud(rd(), rs(), rt()),
+                    0b10010 => Instruction::Por(rd(), rs(), rt()),
+                    0b10011 => Instruction::Pnor(rd(), rs(), rt()),
+                    0b11010 => match data.bits(21..26) {
+                        0b00000 => Instruction::Pexch(rd(), rt()),
+                        _ => panic!("Unhandled instruction: {:#034b}", data),
+                    }
+                    0b11011 => match data.bits(21..26) {
+                        0b00000 => Instruction::Pcpyh(rd(), rt()),
+                        _ => panic!("Unhandled instruction: {:#034b}", data),
+                    }
+                    0b11110 => match data.bits(21..26) {
+                        0b00000 => Instruction::Pexcw(rd(), rt()),
+                        _ => panic!("Unhandled instruction: {:#034b}", data),
+                    }
+                    _ => panic!("Unhandled instruction: {:#034b}", data),
+                }
+                0b110000 => match data.bits(6..11) {
+                    0b00000 => match data.bits(16..26) {
+                        0b0000000000 => Instruction::Pmfhllw(rd()),
+                        _ => panic!("Unhandled instruction: {:#034b}", data),
+                    }
+                    0b00001 => match data.bits(16..26) {
+                        0b0000000000 => Instruction::Pmfhluw(rd()),
+                        _ => panic!("Unhandled instruction: {:#034b}", data),
+                    }
+                    0b00010 => match data.bits(16..26) {
+                        0b0000000000 => Instruction::Pmfhlslw(rd()),
+                        _ => panic!("Unhandled instruction: {:#034b}", data),
+                    }
+                    0b00011 => match data.bits(16..26) {
+                        0b0000000000 => Instruction::Pmfhllh(rd()),
+                        _ => panic!("Unhandled instruction: {:#034b}", data),
+                    }
+                    0b00100 => match data.bits(16..26) {
+                        0b0000000000 => Instruction::Pmfhlsh(rd()),
+                        _ => panic!("Unhandled instruction: {:#034b}", data),
+                    }
+                    _ => panic!("Unhandled instruction: {:#034b}", data),
+                }
+                0b110001 => match data.bits(6..21) {
+                    0b000000000000000 => Instruction::Pmthllw(rs()),
+                    _ => panic!("Unhandled instruction: {:#034b}", data),
+                }
+                0b110100 => match data.bits(21..26) {
+                    0b00000 => Instruction::Psllh(rd(), rt(), sa()),
+                    _ => panic!("Unhandled instruction: {:#034b}", data),
+                }
+                0b110110 => match data.bits(21..26) {
+                    0b00000 => Instruction::Psrlh(rd(), rt(), sa()),
+                    _ => panic!("Unhandled instruction: {:#034b}", data),
+                }
+                0b110111 => match data.bits(21..26) {
+                    0b00000 => Instruction::Psrah(rd(), rt(), sa()),
+                    _ => panic!("Unhandled instruction: {:#034b}", data),
+                }
+                0b111100 => match data.bits(21..26) {
+                    0b00000 => Instruction::Psllw(rd(), rt(), sa()),
+                    _ => panic!("Unhandled instruction: {:#034b}", data),
+                }
+                0b111110 => match data.bits(21..26) {
+                    0b00000 => Instruction::Psrlw(rd(), rt(), sa()),
+                    _ => panic!("Unhandled instruction: {:#034b}", data),
+                }
+                0b111111 => match data.bits(21..26) {
+                    0b00000 => Instruction::Psraw(rd(), rt(), sa()),
                     _ => panic!("Unhandled instruction: {:#034b}", data),
                 }
-                0b10010001001 => Instruction::Pand(rd(), rs(), rt()),
-                0b10010101001 => Instruction::Por(rd(), rs(), rt()),
-                0b10011001001 => Instruction::Pxor(rd(), rs(), rt()),
-                0b10011101001 => Instruction::Pnor(rd(), rs(), rt()),
                 _ => panic!("Unhandled instruction: {:#034b}", data),
             }
             0b011110 => Instruction::Lq(rt(), imm16(), rs()),
             0b011111 => Instruction::Sq(rt(), imm16(), rs()),
             0b100000 => Instruction::Lb(rt(), imm16(), rs()),
             0b100001 => Instruction::Lh(rt(), imm16(), rs()),
+            0b100010 => Instruction::Lwl(rt(), imm16(), rs()),
             0b100011 => Instruction::Lw(rt(), imm16(), rs()),
             0b100100 => Instruction::Lbu(rt(), imm16(), rs()),
             0b100101 => Instruction::Lhu(rt(), imm16(), rs()),
             0b100110 => Instruction::Lwr(rt(), imm16(), rs()),
             0b101000 => Instruction::Sb(rt(), imm16(), rs()),
             0b101001 => Instruction::Sh(rt(), imm16(), rs()),
+            0b101010 => Instruction::Swl(rt(), imm16(), rs()),
             0b101011 => Instruction::Sw(rt(), imm16(), rs()),
+            0b101100 => Instruction::Sdl(rt(), imm16(), rs()),
+            0b101101 => Instruction::Sdr(rt(), imm16(), rs()),
+            0b101110 => Instruction::Swr(rt(), imm16(), rs()),
             0b101111 => Instruction::Cache(cache_op(), imm16(), rs()),
             0b110001 => Instruction::Lwc1(ft(), imm16(), rs()),
             0b110111 => Instruction::Ld(rt(), imm16(), rs()),
@@ -549,6 +1011,12 @@ impl Display for Instruction {
             Instruction::Dsra32(rd, rt, sa) => write!(f, "{rd} = dsra32 {rt}, {sa}"),
             Instruction::Bltz(rs, imm16) => write!(f, "bltz {rs}, {imm16:#x}"),
             Instruction::Bgez(rs, imm16) => write!(f, "bgez {rs}, {imm16:#x}"),
+            Instruction::Bltzl(rs, imm16) => write!(f, "bltzl {rs}, {imm16:#x}"),
+            Instruction::Bgezl(rs, imm16) => write!(f, "bgezl {rs}, {imm16:#x}"),
+            Instruction::Bltzal(rs, imm16) => write!(f, "bltzal {rs}, {imm16:#x}"),
+            Instruction::Bgezal(rs, imm16) => write!(f, "bgezal {rs}, {imm16:#x}"),
+            Instruction::Bltzall(rs, imm16) => write!(f, "bltzall {rs}, {imm16:#x}"),
+            Instruction::Bgezall(rs, imm16) => write!(f, "bgezall {rs}, {imm16:#x}"),
             Instruction::J(imm26) => write!(f, "j {imm26:#x}"),
             Instruction::Jal(imm26) => write!(f, "jal {imm26:#x}"),
             Instruction::Beq(rs, rt, imm16) => write!(f, "beq {rs}, {rt}, {imm16:#x}"),
@@ -569,16 +1037,46 @@ impl Display for Instruction {
             Instruction::Tlbwi => write!(f, "tlbwi"),
             Instruction::Tlbwr => write!(f, "tlbwr"),
             Instruction::Tlbp => write!(f, "tlbp"),
+            Instruction::Eret => write!(f, "eret"),
             Instruction::Ei => write!(f, "ei"),
+            Instruction::Di => write!(f, "di"),
             Instruction::Mfc1(rt, fs) => write!(f, "{rt} = mfc1 {fs}"),
+            Instruction::Cfc1(rt, fs) => write!(f, "{rt} = cfc1 {fs}"),
             Instruction::Mtc1(fs, rt) => write!(f, "{fs} = mtc1 {rt}"),
+            Instruction::Ctc1(rt, fs) => write!(f, "ctc1 {rt}, {fs}"),
+            Instruction::Bc1f(imm16) => write!(f, "bc1f {imm16:#x}"),
+            Instruction::Bc1t(imm16) => write!(f, "bc1t {imm16:#x}"),
+            Instruction::Bc1fl(imm16) => write!(f, "bc1fl {imm16:#x}"),
+            Instruction::Bc1tl(imm16) => write!(f, "bc1tl {imm16:#x}"),
+            Instruction::Adds(fd, fs, ft) => write!(f, "{fd} = add.s {fs}, {ft}"),
+            Instruction::Subs(fd, fs, ft) => write!(f, "{fd} = sub.s {fs}, {ft}"),
             Instruction::Muls(fd, fs, ft) => write!(f, "{fd} = mul.s {fs}, {ft}"),
             Instruction::Divs(fd, fs, ft) => write!(f, "{fd} = div.s {fs}, {ft}"),
+            Instruction::Sqrts(fd, ft) => write!(f, "{fd} = sqrt.s {ft}"),
+            Instruction::Abss(fd, fs) => write!(f, "{fd} = abs.s {fs}"),
             Instruction::Movs(fd, fs) => write!(f, "{fd} = mov.s {fs}"),
+            Instruction::Negs(fd, fs) => write!(f, "{fd} = neg.s {fs}"),
+            Instruction::Rsqrts(fd, fs, ft) => write!(f, "{fd} = rsqrt.s {fs}, {ft}"),
+            Instruction::Addas(fs, ft) => write!(f, "adda.s {fs}, {ft}"),
+            Instruction::Subas(fs, ft) => write!(f, "suba.s {fs}, {ft}"),
+            Instruction::Mulas(fs, ft) => write!(f, "mula.s {fs}, {ft}"),
+            Instruction::Madds(fd, fs, ft) => write!(f, "{fd} = madd.s {fs}, {ft}"),
+            Instruction::Msubs(fd, fs, ft) => write!(f, "{fd} = msub.s {fs}, {ft}"),
+            Instruction::Maddas(fs, ft) => write!(f, "madda.s {fs}, {ft}"),
+            Instruction::Msubas(fs, ft) => write!(f, "msuba.s {fs}, {ft}"),
             Instruction::Cvtws(fd, fs) => write!(f, "{fd} = cvt.w.s {fs}"),
+            Instruction::Maxs(fd, fs, ft) => write!(f, "{fd} = max.s {fs}, {ft}"),
+            Instruction::Mins(fd, fs, ft) => write!(f, "{fd} = min.s {fs}, {ft}"),
+            Instruction::Cfs(fs, ft) => write!(f, "c.f.s {fs}, {ft}"),
+            Instruction::Ceqs(fs, ft) => write!(f, "c.eq.s {fs}, {ft}"),
+            Instruction::Clts(fs, ft) => write!(f, "c.lt.s {fs}, {ft}"),
+            Instruction::Cles(fs, ft) => write!(f, "c.le.s {fs}, {ft}"),
             Instruction::Cvtsw(fd, fs) => write!(f, "{fd} = cvt.s.w {fs}"),
             Instruction::Beql(rs, rt, imm16) => write!(f, "beql {rs}, {rt}, {imm16:#x}"),
             Instruction::Bnel(rs, rt, imm16) => write!(f, "bnel {rs}, {rt}, {imm16:#x}"),
+            Instruction::Blezl(rs, imm16) => write!(f, "blezl {rs}, {imm16:#x}"),
+            Instruction::Bgtzl(rs, imm16) => write!(f, "bgtzl {rs}, {imm16:#x}"),
+            Instruction::Daddi(rt, rs, imm16) => write!(f, "{rt} = daddi {rs}, {imm16}"),
             Instruction::Daddiu(rt, rs, imm16) => write!(f, "{rt} = daddiu {rs}, {imm16}"),
             Instruction::Mfhi1(rd) => write!(f, "{rd} = mfhi1"),
             Instruction::Mthi1(rs) => write!(f, "mthi1 {rs}"),
@@ -591,6 +1089,100 @@ impl Display for Instruction {
             Instruction::Por(rd, rs, rt) => write!(f, "{rd} = por {rs}, {rt}"),
             Instruction::Pxor(rd, rs, rt) => write!(f, "{rd} = pxor {rs}, {rt}"),
             Instruction::Pnor(rd, rs, rt) => write!(f, "{rd} = pnor {rs}, {rt}"),
+            Instruction::Madd(rd, rs, rt) => write!(f, "{rd} = madd {rs}, {rt}"),
+            Instruction::Maddu(rd, rs, rt) => write!(f, "{rd} = maddu {rs}, {rt}"),
+            Instruction::Plzcw(rd, rs) => write!(f, "{rd} = plzcw {rs}"),
+            Instruction::Multu1(rd, rs, rt) => write!(f, "{rd} = multu1 {rs}, {rt}"),
+            Instruction::Madd1(rd, rs, rt) => write!(f, "{rd} = madd1 {rs}, {rt}"),
+            Instruction::Maddu1(rd, rs, rt) => write!(f, "{rd} = maddu1 {rs}, {rt}"),
+            Instruction::Pmfhllw(rd) => write!(f, "{rd} = pmfhl.lw"),
+            Instruction::Pmfhluw(rd) => write!(f, "{rd} = pmfhl.uw"),
+            Instruction::Pmfhlslw(rd) => write!(f, "{rd} = pmfhl.slw"),
+            Instruction::Pmfhllh(rd) => write!(f, "{rd} = pmfhl.lh"),
+            Instruction::Pmfhlsh(rd) => write!(f, "{rd} = pmfhl.sh"),
+            Instruction::Pmthllw(rs) => write!(f, "pmthl.lw {rs}"),
+            Instruction::Psllh(rd, rt, sa) => write!(f, "{rd} = psllh {rt}, {sa}"),
+            Instruction::Psrlh(rd, rt, sa) => write!(f, "{rd} = psrlh {rt}, {sa}"),
+            Instruction::Psrah(rd, rt, sa) => write!(f, "{rd} = psrah {rt}, {sa}"),
+            Instruction::Psllw(rd, rt, sa) => write!(f, "{rd} = psllw {rt}, {sa}"),
+            Instruction::Psrlw(rd, rt, sa) => write!(f, "{rd} = psrlw {rt}, {sa}"),
+            Instruction::Psraw(rd, rt, sa) => write!(f, "{rd} = psraw {rt}, {sa}"),
+            Instruction::Paddw(rd, rs, rt) => write!(f, "{rd} = paddw {rs}, {rt}"),
+            Instruction::Psubw(rd, rs, rt) => write!(f, "{rd} = psubw {rs}, {rt}"),
+            Instruction::Pcgtw(rd, rs, rt) => write!(f, "{rd} = pcgtw {rs}, {rt}"),
+            Instruction::Pmaxw(rd, rs, rt) => write!(f, "{rd} = pmaxw {rs}, {rt}"),
+            Instruction::Paddh(rd, rs, rt) => write!(f, "{rd} = paddh {rs}, {rt}"),
+            Instruction::Psubh(rd, rs, rt) => write!(f, "{rd} = psubh {rs}, {rt}"),
+            Instruction::Pcgth(rd, rs, rt) => write!(f, "{rd} = pcgth {rs}, {rt}"),
+            Instruction::Pmaxh(rd, rs, rt) => write!(f, "{rd} = pmaxh {rs}, {rt}"),
+            Instruction::Paddb(rd, rs, rt) => write!(f, "{rd} = paddb {rs}, {rt}"),
+            Instruction::Psubb(rd, rs, rt) => write!(f, "{rd} = psubb {rs}, {rt}"),
+            Instruction::Pcgtb(rd, rs, rt) => write!(f, "{rd} = pcgtb {rs}, {rt}"),
+            Instruction::Paddsw(rd, rs, rt) => write!(f, "{rd} = paddsw {rs}, {rt}"),
+            Instruction::Psubsw(rd, rs, rt) => write!(f, "{rd} = psubsw {rs}, {rt}"),
+            Instruction::Pextlw(rd, rs, rt) => write!(f, "{rd} = pextlw {rs}, {rt}"),
+            Instruction::Ppacw(rd, rs, rt) => write!(f, "{rd} = ppacw {rs}, {rt}"),
+            Instruction::Paddsh(rd, rs, rt) => write!(f, "{rd} = paddsh {rs}, {rt}"),
+            Instruction::Psubsh(rd, rs, rt) => write!(f, "{rd} = psubsh {rs}, {rt}"),
+            Instruction::Pextlh(rd, rs, rt) => write!(f, "{rd} = pextlh {rs}, {rt}"),
+            Instruction::Ppach(rd, rs, rt) => write!(f, "{rd} = ppach {rs}, {rt}"),
+            Instruction::Paddsb(rd, rs, rt) => write!(f, "{rd} = paddsb {rs}, {rt}"),
+            Instruction::Psubsb(rd, rs, rt) => write!(f, "{rd} = psubsb {rs}, {rt}"),
+            Instruction::Pextlb(rd, rs, rt) => write!(f, "{rd} = pextlb {rs}, {rt}"),
+            Instruction::Ppacb(rd, rs, rt) => write!(f, "{rd} = ppacb {rs}, {rt}"),
+            Instruction::Pext5(rd, rt) => write!(f, "{rd} = pext5 {rt}"),
+            Instruction::Ppac5(rd, rt) => write!(f, "{rd} = ppac5 {rt}"),
+            Instruction::Pabsw(rd, rt) => write!(f, "{rd} = pabsw {rt}"),
+            Instruction::Pceqw(rd, rs, rt) => write!(f, "{rd} = pceqw {rs}, {rt}"),
+            Instruction::Pminw(rd, rs, rt) => write!(f, "{rd} = pminw {rs}, {rt}"),
+            Instruction::Padsbh(rd, rs, rt) => write!(f, "{rd} = padsbh {rs}, {rt}"),
+            Instruction::Pabsh(rd, rt) => write!(f, "{rd} = pabsh {rt}"),
+            Instruction::Pceqh(rd, rs, rt) => write!(f, "{rd} = pceqh {rs}, {rt}"),
+            Instruction::Pminh(rd, rs, rt) => write!(f, "{rd} = pminh {rs}, {rt}"),
+            Instruction::Pceqb(rd, rs, rt) => write!(f, "{rd} = pceqb {rs}, {rt}"),
+            Instruction::Padduw(rd, rs, rt) => write!(f, "{rd} = padduw {rs}, {rt}"),
+            Instruction::Psubuw(rd, rs, rt) => write!(f, "{rd} = psubuw {rs}, {rt}"),
+            Instruction::Pextuw(rd, rs, rt) => write!(f, "{rd} = pextuw {rs}, {rt}"),
+            Instruction::Padduh(rd, rs, rt) => write!(f, "{rd} = padduh {rs}, {rt}"),
+            Instruction::Psubuh(rd, rs, rt) => write!(f, "{rd} = psubuh {rs}, {rt}"),
+            Instruction::Pextuh(rd, rs, rt) => write!(f, "{rd} = pextuh {rs}, {rt}"),
+            Instruction::Paddub(rd, rs, rt) => write!(f, "{rd} = paddub {rs}, {rt}"),
+            Instruction::Psubub(rd, rs, rt) => write!(f, "{rd} = psubub {rs}, {rt}"),
+            Instruction::Pextub(rd, rs, rt) => write!(f, "{rd} = pextub {rs}, {rt}"),
+            Instruction::Qfsrv(rd, rs, rt) => write!(f, "{rd} = qfsrv {rs}, {rt}"),
+            Instruction::Pmaddw(rd, rs, rt) => write!(f, "{rd} = pmaddw {rs}, {rt}"),
+            Instruction::Psllvw(rd, rt, rs) => write!(f, "{rd} = psllvw {rt}, {rs}"),
+            Instruction::Psrlvw(rd, rt, rs) => write!(f, "{rd} = psrlvw {rt}, {rs}"),
+            Instruction::Pmsubw(rd, rs, rt) => write!(f, "{rd} = pmsubw {rs}, {rt}"),
+            Instruction::Pmfhi(rd) => write!(f, "{rd} = pmfhi"),
+            Instruction::Pmflo(rd) => write!(f, "{rd} = pmflo"),
+            Instruction::Pinth(rd, rs, rt) => write!(f, "{rd} = pinth {rs}, {rt}"),
+            Instruction::Pmultw(rd, rs, rt) => write!(f, "{rd} = pmultw {rs}, {rt}"),
+            Instruction::Pdivw(rs, rt) => write!(f, "pdivw {rs}, {rt}"),
+            Instruction::Pcpyld(rd, rs, rt) => write!(f, "{rd} = pcpyld {rs}, {rt}"),
+            Instruction::Pmaddh(rd, rs, rt) => write!(f, "{rd} = pmaddh {rs}, {rt}"),
+            Instruction::Phmadh(rd, rs, rt) => write!(f, "{rd} = phmadh {rs}, {rt}"),
+            Instruction::Pmsubh(rd, rs, rt) => write!(f, "{rd} = pmsubh {rs}, {rt}"),
+            Instruction::Phmsbh(rd, rs, rt) => write!(f, "{rd} = phmsbh {rs}, {rt}"),
+            Instruction::Pexeh(rd, rt) => write!(f, "{rd} = pexeh {rt}"),
+            Instruction::Prevh(rd, rt) => write!(f, "{rd} = prevh {rt}"),
+            Instruction::Pmulth(rd, rs, rt) => write!(f, "{rd} = pmulth {rs}, {rt}"),
+            Instruction::Pdivbw(rs, rt) => write!(f, "pdivbw {rs}, {rt}"),
+            Instruction::Pexew(rd, rt) => write!(f, "{rd} = pexew {rt}"),
+            Instruction::Prot3w(rd, rt) => write!(f, "{rd} = prot3w {rt}"),
+            Instruction::Pmadduw(rd, rs, rt) => write!(f, "{rd} = pmadduw {rs}, {rt}"),
+            Instruction::Psravw(rd, rt, rs) => write!(f, "{rd} = psravw {rt}, {rs}"),
+            Instruction::Pmthi(rs) => write!(f, "pmthi {rs}"),
+            Instruction::Pmtlo(rs) => write!(f, "pmtlo {rs}"),
+            Instruction::Pinteh(rd, rs, rt) => write!(f, "{rd} = pinteh {rs}, {rt}"),
+            Instruction::Pmultuw(rd, rs, rt) => write!(f, "{rd} = pmultuw {rs}, {rt}"),
+            Instruction::Pdivuw(rs, rt) => write!(f, "pdivuw {rs}, {rt}"),
+            Instruction::Pcpyud(rd, rs, rt) => write!(f, "{rd} = pcpyud {rs}, {rt}"),
+            Instruction::Pexch(rd, rt) => write!(f, "{rd} = pexch {rt}"),
+            Instruction::Pcpyh(rd, rt) => write!(f, "{rd} = pcpyh {rt}"),
+            Instruction::Pexcw(rd, rt) => write!(f, "{rd} = pexcw {rt}"),
+            Instruction::Ldl(rt, imm16, rs) => write!(f, "{rt} = ldl {imm16:#x}({rs})"),
+            Instruction::Ldr(rt, imm16, rs) => write!(f, "{rt} = ldr {imm16:#x}({rs})"),
             Instruction::Lq(rt, imm16, rs) => write!(f, "{rt} = lq {imm16:#x}({rs})"),
             Instruction::Sq(rt, imm16, rs) => write!(f, "sq {rt}, {imm16:#x}({rs})"),
             Instruction::Lb(rt, imm16, rs) => write!(f, "{rt} = lb {imm16:#x}({rs})"),
@@ -598,10 +1190,15 @@ impl Display for Instruction {
             Instruction::Lw(rt, imm16, rs) => write!(f, "{rt} = lw {imm16:#x}({rs})"),
             Instruction::Lbu(rt, imm16, rs) => write!(f, "{rt} = lbu {imm16:#x}({rs})"),
             Instruction::Lhu(rt, imm16, rs) => write!(f, "{rt} = lhu {imm16:#x}({rs})"),
+            Instruction::Lwl(rt, imm16, rs) => write!(f, "{rt} = lwl {imm16:#x}({rs})"),
             Instruction::Lwr(rt, imm16, rs) => write!(f, "{rt} = lwr {imm16:#x}({rs})"),
             Instruction::Sb(rt, imm16, rs) => write!(f, "sb {rt}, {imm16:#x}({rs})"),
             Instruction::Sh(rt, imm16, rs) => write!(f, "sh {rt}, {imm16:#x}({rs})"),
+            Instruction::Swl(rt, imm16, rs) => write!(f, "swl {rt}, {imm16:#x}({rs})"),
             Instruction::Sw(rt, imm16, rs) => write!(f, "sw {rt}, {imm16:#x}({rs})"),
+            Instruction::Sdl(rt, imm16, rs) => write!(f, "sdl {rt}, {imm16:#x}({rs})"),
+            Instruction::Sdr(rt, imm16, rs) => write!(f, "sdr {rt}, {imm16:#x}({rs})"),
+            Instruction::Swr(rt, imm16, rs) => write!(f, "swr {rt}, {imm16:#x}({rs})"),
             Instruction::Cache(cache_op, imm16, rs) => write!(f, "cache {cache_op}, {imm16:#x}({rs})"),
             Instruction::Lwc1(ft, imm16, rs) => write!(f, "{ft} = lwc1 {imm16:#x}({rs})"),
             Instruction::Ld(rt, imm16, rs) => write!(f, "{rt} = ld {imm16:#x}({rs})"),
@@ -613,11 +1210,11 @@ impl Display for Instruction {
 
 impl Instruction {
     pub fn is_branch(self) -> bool {
-        matches!(self, Instruction::Jr(..) | Instruction::Jalr(..) | Instruction::Bltz(..) | Instruction::Bgez(..) | Instruction::J(..) | Instruction::Jal(..) | Instruction::Beq(..) | Instruction::Bne(..) | Instruction::Blez(..) | Instruction::Bgtz(..) | Instruction::Beql(..) | Instruction::Bnel(..))
+        matches!(self, Instruction::Jr(..) | Instruction::Jalr(..) | Instruction::Bltz(..) | Instruction::Bgez(..) | Instruction::Bltzl(..) | Instruction::Bgezl(..) | Instruction::Bltzal(..) | Instruction::Bgezal(..) | Instruction::Bltzall(..) | Instruction::Bgezall(..) | Instruction::J(..) | Instruction::Jal(..) | Instruction::Beq(..) | Instruction::Bne(..) | Instruction::Blez(..) | Instruction::Bgtz(..) | Instruction::Bc1f(..) | Instruction::Bc1t(..) | Instruction::Bc1fl(..) | Instruction::Bc1tl(..) | Instruction::Beql(..) | Instruction::Bnel(..) | Instruction::Blezl(..) | Instruction::Bgtzl(..))
     }
 
     pub fn is_branch_likely(self) -> bool {
-        matches!(self, Instruction::Beql(..) | Instruction::Bnel(..))
+        matches!(self, Instruction::Bltzl(..) | Instruction::Bgezl(..) | Instruction::Bltzall(..) | Instruction::Bgezall(..) | Instruction::Bc1fl(..) | Instruction::Bc1tl(..) | Instruction::Beql(..) | Instruction::Bnel(..) | Instruction::Blezl(..) | Instruction::Bgtzl(..))
     }
 }
 
@@ -632,7 +1229,7 @@ impl Instruction {
             Instruction::Srlv(rd, _, _) => [Some(Occurrence::from(rd)), None, None],
             Instruction::Srav(rd, _, _) => [Some(Occurrence::from(rd)), None, None],
             Instruction::Jr(_) => [None, None, None],
-            Instruction::Jalr(_, _) => [None, None, None],
+            Instruction::Jalr(rd, _) => [Some(Occurrence::from(rd)), None, None],
             Instruction::Movz(rd, _, _) => [Some(Occurrence::from(rd)), None, None],
             Instruction::Movn(rd, _, _) => [Some(Occurrence::from(rd)), None, None],
             Instruction::Syscall => [None, None, None],
@@ -679,8 +1276,14 @@ impl Instruction {
             Instruction::Dsra32(rd, _, _) => [Some(Occurrence::from(rd)), None, None],
             Instruction::Bltz(_, _) => [None, None, None],
             Instruction::Bgez(_, _) => [None, None, None],
+            Instruction::Bltzl(_, _) => [None, None, None],
+            Instruction::Bgezl(_, _) => [None, None, None],
+            Instruction::Bltzal(_, _) => [Some(Occurrence::from(Register::Ra)), None, None],
+            Instruction::Bgezal(_, _) => [Some(Occurrence::from(Register::Ra)), None, None],
+            Instruction::Bltzall(_, _) => [Some(Occurrence::from(Register::Ra)), None, None],
+            Instruction::Bgezall(_, _) => [Some(Occurrence::from(Register::Ra)), None, None],
             Instruction::J(_) => [None, None, None],
-            Instruction::Jal(_) => [None, None, None],
+            Instruction::Jal(_) => [Some(Occurrence::from(Register::Ra)), None, None],
             Instruction::Beq(_, _, _) => [None, None, None],
             Instruction::Bne(_, _, _) => [None, None, None],
             Instruction::Blez(_, _) => [None, None, None],
@@ -699,16 +1302,46 @@ impl Instruction {
             Instruction::Tlbwi => [None, None, None],
             Instruction::Tlbwr => [None, None, None],
             Instruction::Tlbp => [None, None, None],
+            Instruction::Eret => [None, None, None],
             Instruction::Ei => [None, None, None],
+            Instruction::Di => [None, None, None],
             Instruction::Mfc1(rt, _) => [Some(Occurrence::from(rt)), None, None],
+            Instruction::Cfc1(rt, _) => [Some(Occurrence::from(rt)), None, None],
             Instruction::Mtc1(fs, _) => [Some(Occurrence::from(fs)), None, None],
+            Instruction::Ctc1(_, _) => [None, None, None],
+            Instruction::Bc1f(_) => [None, None, None],
+            Instruction::Bc1t(_) => [None, None, None],
+            Instruction::Bc1fl(_) => [None, None, None],
+            Instruction::Bc1tl(_) => [None, None, None],
+            Instruction::Adds(fd, _, _) => [Some(Occurrence::from(fd)), None, None],
+            Instruction::Subs(fd, _, _) => [Some(Occurrence::from(fd)), None, None],
             Instruction::Muls(fd, _, _) => [Some(Occurrence::from(fd)), None, None],
             Instruction::Divs(fd, _, _) => [Some(Occurrence::from(fd)), None, None],
+            Instruction::Sqrts(fd, _) => [Some(Occurrence::from(fd)), None, None],
+            Instruction::Abss(fd, _) => [Some(Occurrence::from(fd)), None, None],
             Instruction::Movs(fd, _) => [Some(Occurrence::from(fd)), None, None],
+            Instruction::Negs(fd, _) => [Some(Occurrence::from(fd)), None, None],
+            Instruction::Rsqrts(fd, _, _) => [Some(Occurrence::from(fd)), None, None],
+            Instruction::Addas(_, _) => [None, None, None],
+            Instruction::Subas(_, _) => [None, None, None],
+            Instruction::Mulas(_, _) => [None, None, None],
+            Instruction::Madds(fd, _, _) => [Some(Occurrence::from(fd)), None, None],
+            Instruction::Msubs(fd, _, _) => [Some(Occurrence::from(fd)), None, None],
+            Instruction::Maddas(_, _) => [None, None, None],
+            Instruction::Msubas(_, _) => [None, None, None],
             Instruction::Cvtws(fd, _) => [Some(Occurrence::from(fd)), None, None],
+            Instruction::Maxs(fd, _, _) => [Some(Occurrence::from(fd)), None, None],
+            Instruction::Mins(fd, _, _) => [Some(Occurrence::from(fd)), None, None],
+            Instruction::Cfs(_, _) => [None, None, None],
+            Instruction::Ceqs(_, _) => [None, None, None],
+            Instruction::Clts(_, _) => [None, None, None],
+            Instruction::Cles(_, _) => [None, None, None],
             Instruction::Cvtsw(fd, _) => [Some(Occurrence::from(fd)), None, None],
             Instruction::Beql(_, _, _) => [None, None, None],
             Instruction::Bnel(_, _, _) => [None, None, None],
+            Instruction::Blezl(_, _) => [None, None, None],
+            Instruction::Bgtzl(_, _) => [None, None, None],
+            Instruction::Daddi(rt, _, _) => [Some(Occurrence::from(rt)), None, None],
             Instruction::Daddiu(rt, _, _) => [Some(Occurrence::from(rt)), None, None],
             Instruction::Mfhi1(rd) => [Some(Occurrence::from(rd)), None, None],
             Instruction::Mthi1(_) => [Some(Occurrence::from(Register::Hi)), None, None],
@@ -721,6 +1354,100 @@ impl Instruction {
             Instruction::Por(rd, _, _) => [Some(Occurrence::from(rd)), None, None],
             Instruction::Pxor(rd, _, _) => [Some(Occurrence::from(rd)), None, None],
             Instruction::Pnor(rd, _, _) => [Some(Occurrence::from(rd)), None, None],
+            Instruction::Madd(rd, _, _) => [Some(Occurrence::from(rd)), Some(Occurrence::from(Register::Lo)), Some(Occurrence::from(Register::Hi))],
+            Instruction::Maddu(rd, _, _) => [Some(Occurrence::from(rd)), Some(Occurrence::from(Register::Lo)), Some(Occurrence::from(Register::Hi))],
+            Instruction::Plzcw(rd, _) => [Some(Occurrence::from(rd)), None, None],
+            Instruction::Multu1(rd, _, _) => [Some(Occurrence::from(rd)), Some(Occurrence::from(Register::Lo)), Some(Occurrence::from(Register::Hi))],
+            Instruction::Madd1(rd, _, _) => [Some(Occurrence::from(rd)), Some(Occurrence::from(Register::Lo)), Some(Occurrence::from(Register::Hi))],
+            Instruction::Maddu1(rd, _, _) => [Some(Occurrence::from(rd)), Some(Occurrence::from(Register::Lo)), Some(Occurrence::from(Register::Hi))],
+            Instruction::Pmfhllw(rd) => [Some(Occurrence::from(rd)), None, None],
+            Instruction::Pmfhluw(rd) => [Some(Occurrence::from(rd)), None, None],
+            Instruction::Pmfhlslw(rd) => [Some(Occurrence::from(rd)), None, None],
+            Instruction::Pmfhllh(rd) => [Some(Occurrence::from(rd)), None, None],
+            Instruction::Pmfhlsh(rd) => [Some(Occurrence::from(rd)), None, None],
+            Instruction::Pmthllw(_) => [Some(Occurrence::from(Register::Lo)), Some(Occurrence::from(Register::Hi)), None],
+            Instruction::Psllh(rd, _, _) => [Some(Occurrence::from(rd)), None, None],
+            Instruction::Psrlh(rd, _, _) => [Some(Occurrence::from(rd)), None, None],
+            Instruction::Psrah(rd, _, _) => [Some(Occurrence::from(rd)), None, None],
+            Instruction::Psllw(rd, _, _) => [Some(Occurrence::from(rd)), None, None],
+            Instruction::Psrlw(rd, _, _) => [Some(Occurrence::from(rd)), None, None],
+            Instruction::Psraw(rd, _, _) => [Some(Occurrence::from(rd)), None, None],
+            Instruction::Paddw(rd, _, _) => [Some(Occurrence::from(rd)), None, None],
+            Instruction::Psubw(rd, _, _) => [Some(Occurrence::from(rd)), None, None],
+            Instruction::Pcgtw(rd, _, _) => [Some(Occurrence::from(rd)), None, None],
+            Instruction::Pmaxw(rd, _, _) => [Some(Occurrence::from(rd)), None, None],
+            Instruction::Paddh(rd, _, _) => [Some(Occurrence::from(rd)), None, None],
+            Instruction::Psubh(rd, _, _) => [Some(Occurrence::from(rd)), None, None],
+            Instruction::Pcgth(rd, _, _) => [Some(Occurrence::from(rd)), None, None],
+            Instruction::Pmaxh(rd, _, _) => [Some(Occurrence::from(rd)), None, None],
+            Instruction::Paddb(rd, _, _) => [Some(Occurrence::from(rd)), None, None],
+            Instruction::Psubb(rd, _, _) => [Some(Occurrence::from(rd)), None, None],
+            Instruction::Pcgtb(rd, _, _) => [Some(Occurrence::from(rd)), None, None],
+            Instruction::Paddsw(rd, _, _) => [Some(Occurrence::from(rd)), None, None],
+            Instruction::Psubsw(rd, _, _) => [Some(Occurrence::from(rd)), None, None],
+            Instruction::Pextlw(rd, _, _) => [Some(Occurrence::from(rd)), None, None],
+            Instruction::Ppacw(rd, _, _) => [Some(Occurrence::from(rd)), None, None],
+            Instruction::Paddsh(rd, _, _) => [Some(Occurrence::from(rd)), None, None],
+            Instruction::Psubsh(rd, _, _) => [Some(Occurrence::from(rd)), None, None],
+            Instruction::Pextlh(rd, _, _) => [Some(Occurrence::from(rd)), None, None],
+            Instruction::Ppach(rd, _, _) => [Some(Occurrence::from(rd)), None, None],
+            Instruction::Paddsb(rd, _, _) => [Some(Occurrence::from(rd)), None, None],
+            Instruction::Psubsb(rd, _, _) => [Some(Occurrence::from(rd)), None, None],
+            Instruction::Pextlb(rd, _, _) => [Some(Occurrence::from(rd)), None, None],
+            Instruction::Ppacb(rd, _, _) => [Some(Occurrence::from(rd)), None, None],
+            Instruction::Pext5(rd, _) => [Some(Occurrence::from(rd)), None, None],
+            Instruction::Ppac5(rd, _) => [Some(Occurrence::from(rd)), None, None],
+            Instruction::Pabsw(rd, _) => [Some(Occurrence::from(rd)), None, None],
+            Instruction::Pceqw(rd, _, _) => [Some(Occurrence::from(rd)), None, None],
+            Instruction::Pminw(rd, _, _) => [Some(Occurrence::from(rd)), None, None],
+            Instruction::Padsbh(rd, _, _) => [Some(Occurrence::from(rd)), None, None],
+            Instruction::Pabsh(rd, _) => [Some(Occurrence::from(rd)), None, None],
+            Instruction::Pceqh(rd, _, _) => [Some(Occurrence::from(rd)), None, None],
+            Instruction::Pminh(rd, _, _) => [Some(Occurrence::from(rd)), None, None],
+            Instruction::Pceqb(rd, _, _) => [Some(Occurrence::from(rd)), None, None],
+            Instruction::Padduw(rd, _, _) => [Some(Occurrence::from(rd)), None, None],
+            Instruction::Psubuw(rd, _, _) => [Some(Occurrence::from(rd)), None, None],
+            Instruction::Pextuw(rd, _, _) => [Some(Occurrence::from(rd)), None, None],
+            Instruction::Padduh(rd, _, _) => [Some(Occurrence::from(rd)), None, None],
+            Instruction::Psubuh(rd, _, _) => [Some(Occurrence::from(rd)), None, None],
+            Instruction::Pextuh(rd, _, _) => [Some(Occurrence::from(rd)), None, None],
+            Instruction::Paddub(rd, _, _) => [Some(Occurrence::from(rd)), None, None],
+            Instruction::Psubub(rd, _, _) => [Some(Occurrence::from(rd)), None, None],
+            Instruction::Pextub(rd, _, _) => [Some(Occurrence::from(rd)), None, None],
+            Instruction::Qfsrv(rd, _, _) => [Some(Occurrence::from(rd)), None, None],
+            Instruction::Pmaddw(rd, _, _) => [Some(Occurrence::from(rd)), Some(Occurrence::from(Register::Lo)), Some(Occurrence::from(Register::Hi))],
+            Instruction::Psllvw(rd, _, _) => [Some(Occurrence::from(rd)), None, None],
+            Instruction::Psrlvw(rd, _, _) => [Some(Occurrence::from(rd)), None, None],
+            Instruction::Pmsubw(rd, _, _) => [Some(Occurrence::from(rd)), Some(Occurrence::from(Register::Lo)), Some(Occurrence::from(Register::Hi))],
+            Instruction::Pmfhi(rd) => [Some(Occurrence::from(rd)), None, None],
+            Instruction::Pmflo(rd) => [Some(Occurrence::from(rd)), None, None],
+            Instruction::Pinth(rd, _, _) => [Some(Occurrence::from(rd)), None, None],
+            Instruction::Pmultw(rd, _, _) => [Some(Occurrence::from(rd)), Some(Occurrence::from(Register::Lo)), Some(Occurrence::from(Register::Hi))],
+            Instruction::Pdivw(_, _) => [Some(Occurrence::from(Register::Lo)), Some(Occurrence::from(Register::Hi)), None],
+            Instruction::Pcpyld(rd, _, _) => [Some(Occurrence::from(rd)), None, None],
+            Instruction::Pmaddh(rd, _, _) => [Some(Occurrence::from(rd)), Some(Occurrence::from(Register::Lo)), Some(Occurrence::from(Register::Hi))],
+            Instruction::Phmadh(rd, _, _) => [Some(Occurrence::from(rd)), Some(Occurrence::from(Register::Lo)), Some(Occurrence::from(Register::Hi))],
+            Instruction::Pmsubh(rd, _, _) => [Some(Occurrence::from(rd)), Some(Occurrence::from(Register::Lo)), Some(Occurrence::from(Register::Hi))],
+            Instruction::Phmsbh(rd, _, _) => [Some(Occurrence::from(rd)), Some(Occurrence::from(Register::Lo)), Some(Occurrence::from(Register::Hi))],
+            Instruction::Pexeh(rd, _) => [Some(Occurrence::from(rd)), None, None],
+            Instruction::Prevh(rd, _) => [Some(Occurrence::from(rd)), None, None],
+            Instruction::Pmulth(rd, _, _) => [Some(Occurrence::from(rd)), Some(Occurrence::from(Register::Lo)), Some(Occurrence::from(Register::Hi))],
+            Instruction::Pdivbw(_, _) => [Some(Occurrence::from(Register::Lo)), Some(Occurrence::from(Register::Hi)), None],
+            Instruction::Pexew(rd, _) => [Some(Occurrence::from(rd)), None, None],
+            Instruction::Prot3w(rd, _) => [Some(Occurrence::from(rd)), None, None],
+            Instruction::Pmadduw(rd, _, _) => [Some(Occurrence::from(rd)), Some(Occurrence::from(Register::Lo)), Some(Occurrence::from(Register::Hi))],
+            Instruction::Psravw(rd, _, _) => [Some(Occurrence::from(rd)), None, None],
+            Instruction::Pmthi(_) => [Some(Occurrence::from(Register::Hi)), None, None],
+            Instruction::Pmtlo(_) => [Some(Occurrence::from(Register::Lo)), None, None],
+            Instruction::Pinteh(rd, _, _) => [Some(Occurrence::from(rd)), None, None],
+            Instruction::Pmultuw(rd, _, _) => [Some(Occurrence::from(rd)), Some(Occurrence::from(Register::Lo)), Some(Occurrence::from(Register::Hi))],
+            Instruction::Pdivuw(_, _) => [Some(Occurrence::from(Register::Lo)), Some(Occurrence::from(Register::Hi)), None],
+            Instruction::Pcpyud(rd, _, _) => [Some(Occurrence::from(rd)), None, None],
+            Instruction::Pexch(rd, _) => [Some(Occurrence::from(rd)), None, None],
+            Instruction::Pcpyh(rd, _) => [Some(Occurrence::from(rd)), None, None],
+            Instruction::Pexcw(rd, _) => [Some(Occurrence::from(rd)), None, None],
+            Instruction::Ldl(rt, _, _) => [Some(Occurrence::from(rt)), None, None],
+            Instruction::Ldr(rt, _, _) => [Some(Occurrence::from(rt)), None, None],
             Instruction::Lq(rt, _, _) => [Some(Occurrence::from(rt)), None, None],
             Instruction::Sq(_, _, _) => [None, None, None],
             Instruction::Lb(rt, _, _) => [Some(Occurrence::from(rt)), None, None],
@@ -728,10 +1455,15 @@ impl Instruction {
             Instruction::Lw(rt, _, _) => [Some(Occurrence::from(rt)), None, None],
             Instruction::Lbu(rt, _, _) => [Some(Occurrence::from(rt)), None, None],
             Instruction::Lhu(rt, _, _) => [Some(Occurrence::from(rt)), None, None],
+            Instruction::Lwl(rt, _, _) => [Some(Occurrence::from(rt)), None, None],
             Instruction::Lwr(rt, _, _) => [Some(Occurrence::from(rt)), None, None],
             Instruction::Sb(_, _, _) => [None, None, None],
             Instruction::Sh(_, _, _) => [None, None, None],
+            Instruction::Swl(_, _, _) => [None, None, None],
             Instruction::Sw(_, _, _) => [None, None, None],
+            Instruction::Sdl(_, _, _) => [None, None, None],
+            Instruction::Sdr(_, _, _) => [None, None, None],
+            Instruction::Swr(_, _, _) => [None, None, None],
             Instruction::Cache(_, _, _) => [None, None, None],
             Instruction::Lwc1(ft, _, _) => [Some(Occurrence::from(ft)), None, None],
             Instruction::Ld(rt, _, _) => [Some(Occurrence::from(rt)), None, None],
@@ -740,121 +1472,521 @@ impl Instruction {
         }
     }
 
-    pub fn raw_uses(self) -> [Option<Occurrence>; 2] {
+    pub fn raw_uses(self) -> [Option<Occurrence>; 4] {
         match self {
-            Instruction::Sll(_, rt, _) => [Some(Occurrence::from(rt)), None],
-            Instruction::Unknown => [None, None],
-            Instruction::Srl(_, rt, _) => [Some(Occurrence::from(rt)), None],
-            Instruction::Sra(_, rt, _) => [Some(Occurrence::from(rt)), None],
-            Instruction::Sllv(_, rt, rs) => [Some(Occurrence::from(rt)), Some(Occurrence::from(rs))],
-            Instruction::Srlv(_, rt, rs) => [Some(Occurrence::from(rt)), Some(Occurrence::from(rs))],
-            Instruction::Srav(_, rt, rs) => [Some(Occurrence::from(rt)), Some(Occurrence::from(rs))],
-            Instruction::Jr(rs) => [Some(Occurrence::from(rs)), None],
-            Instruction::Jalr(rd, rs) => [Some(Occurrence::from(rd)), Some(Occurrence::from(rs))],
-            Instruction::Movz(_, rs, rt) => [Some(Occurrence::from(rs)), Some(Occurrence::from(rt))],
-            Instruction::Movn(_, rs, rt) => [Some(Occurrence::from(rs)), Some(Occurrence::from(rt))],
-            Instruction::Syscall => [None, None],
-            Instruction::Break => [None, None],
-            Instruction::Sync => [None, None],
-            Instruction::Mfhi(_) => [Some(Occurrence::from(Register::Hi)), None],
-            Instruction::Mthi(rs) => [Some(Occurrence::from(rs)), None],
-            Instruction::Mflo(_) => [Some(Occurrence::from(Register::Lo)), None],
-            Instruction::Mtlo(rs) => [Some(Occurrence::from(rs)), None],
-            Instruction::Dsllv(_, rt, rs) => [Some(Occurrence::from(rt)), Some(Occurrence::from(rs))],
-            Instruction::Dsrlv(_, rt, rs) => [Some(Occurrence::from(rt)), Some(Occurrence::from(rs))],
-            Instruction::Dsrav(_, rt, rs) => [Some(Occurrence::from(rt)), Some(Occurrence::from(rs))],
-            Instruction::Mult(_, rs, rt) => [Some(Occurrence::from(rs)), Some(Occurrence::from(rt))],
-            Instruction::Multu(_, rs, rt) => [Some(Occurrence::from(rs)), Some(Occurrence::from(rt))],
-            Instruction::Div(rs, rt) => [Some(Occurrence::from(rs)), Some(Occurrence::from(rt))],
-            Instruction::Divu(rs, rt) => [Some(Occurrence::from(rs)), Some(Occurrence::from(rt))],
-            Instruction::Add(_, rs, rt) => [Some(Occurrence::from(rs)), Some(Occurrence::from(rt))],
-            Instruction::Addu(_, rs, rt) => [Some(Occurrence::from(rs)), Some(Occurrence::from(rt))],
-            Instruction::Sub(_, rs, rt) => [Some(Occurrence::from(rs)), Some(Occurrence::from(rt))],
-            Instruction::Subu(_, rs, rt) => [Some(Occurrence::from(rs)), Some(Occurrence::from(rt))],
-            Instruction::And(_, rs, rt) => [Some(Occurrence::from(rs)), Some(Occurrence::from(rt))],
-            Instruction::Or(_, rs, rt) => [Some(Occurrence::from(rs)), Some(Occurrence::from(rt))],
-            Instruction::Xor(_, rs, rt) => [Some(Occurrence::from(rs)), Some(Occurrence::from(rt))],
-            Instruction::Nor(_, rs, rt) => [Some(Occurrence::from(rs)), Some(Occurrence::from(rt))],
-            Instruction::Mfsa(_) => [None, None],
-            Instruction::Mtsa(rs) => [Some(Occurrence::from(rs)), None],
-            Instruction::Slt(_, rs, rt) => [Some(Occurrence::from(rs)), Some(Occurrence::from(rt))],
-            Instruction::Sltu(_, rs, rt) => [Some(Occurrence::from(rs)), Some(Occurrence::from(rt))],
-            Instruction::Dadd(_, rs, rt) => [Some(Occurrence::from(rs)), Some(Occurrence::from(rt))],
-            Instruction::Daddu(_, rs, rt) => [Some(Occurrence::from(rs)), Some(Occurrence::from(rt))],
-            Instruction::Dsub(_, rs, rt) => [Some(Occurrence::from(rs)), Some(Occurrence::from(rt))],
-            Instruction::Dsubu(_, rs, rt) => [Some(Occurrence::from(rs)), Some(Occurrence::from(rt))],
-            Instruction::Tge(rs, rt) => [Some(Occurrence::from(rs)), Some(Occurrence::from(rt))],
-            Instruction::Tgeu(rs, rt) => [Some(Occurrence::from(rs)), Some(Occurrence::from(rt))],
-            Instruction::Tlt(rs, rt) => [Some(Occurrence::from(rs)), Some(Occurrence::from(rt))],
-            Instruction::Tltu(rs, rt) => [Some(Occurrence::from(rs)), Some(Occurrence::from(rt))],
-            Instruction::Teq(rs, rt) => [Some(Occurrence::from(rs)), Some(Occurrence::from(rt))],
-            Instruction::Tne(rs, rt) => [Some(Occurrence::from(rs)), Some(Occurrence::from(rt))],
-            Instruction::Dsll(_, rt, _) => [Some(Occurrence::from(rt)), None],
-            Instruction::Dsrl(_, rt, _) => [Some(Occurrence::from(rt)), None],
-            Instruction::Dsra(_, rt, _) => [Some(Occurrence::from(rt)), None],
-            Instruction::Dsll32(_, rt, _) => [Some(Occurrence::from(rt)), None],
-            Instruction::Dsrl32(_, rt, _) => [Some(Occurrence::from(rt)), None],
-            Instruction::Dsra32(_, rt, _) => [Some(Occurrence::from(rt)), None],
-            Instruction::Bltz(rs, _) => [Some(Occurrence::from(rs)), None],
-            Instruction::Bgez(rs, _) => [Some(Occurrence::from(rs)), None],
-            Instruction::J(_) => [None, None],
-            Instruction::Jal(_) => [None, None],
-            Instruction::Beq(rs, rt, _) => [Some(Occurrence::from(rs)), Some(Occurrence::from(rt))],
-            Instruction::Bne(rs, rt, _) => [Some(Occurrence::from(rs)), Some(Occurrence::from(rt))],
-            Instruction::Blez(rs, _) => [Some(Occurrence::from(rs)), None],
-            Instruction::Bgtz(rs, _) => [Some(Occurrence::from(rs)), None],
-            Instruction::Addi(_, rs, _) => [Some(Occurrence::from(rs)), None],
-            Instruction::Addiu(_, rs, _) => [Some(Occurrence::from(rs)), None],
-            Instruction::Slti(_, rs, _) => [Some(Occurrence::from(rs)), None],
-            Instruction::Sltiu(_, rs, _) => [Some(Occurrence::from(rs)), None],
-            Instruction::Andi(_, rs, _) => [Some(Occurrence::from(rs)), None],
-            Instruction::Ori(_, rs, _) => [Some(Occurrence::from(rs)), None],
-            Instruction::Xori(_, rs, _) => [Some(Occurrence::from(rs)), None],
-            Instruction::Lui(_, _) => [None, None],
-            Instruction::Mfc0(_, cd) => [Some(Occurrence::from(cd)), None],
-            Instruction::Mtc0(_, rt) => [Some(Occurrence::from(rt)), None],
-            Instruction::Tlbr => [None, None],
-            Instruction::Tlbwi => [None, None],
-            Instruction::Tlbwr => [None, None],
-            Instruction::Tlbp => [None, None],
-            Instruction::Ei => [None, None],
-            Instruction::Mfc1(_, fs) => [Some(Occurrence::from(fs)), None],
-            Instruction::Mtc1(_, rt) => [Some(Occurrence::from(rt)), None],
-            Instruction::Muls(_, fs, ft) => [Some(Occurrence::from(fs)), Some(Occurrence::from(ft))],
-            Instruction::Divs(_, fs, ft) => [Some(Occurrence::from(fs)), Some(Occurrence::from(ft))],
-            Instruction::Movs(_, fs) => [Some(Occurrence::from(fs)), None],
-            Instruction::Cvtws(_, fs) => [Some(Occurrence::from(fs)), None],
-            Instruction::Cvtsw(_, fs) => [Some(Occurrence::from(fs)), None],
-            Instruction::Beql(rs, rt, _) => [Some(Occurrence::from(rs)), Some(Occurrence::from(rt))],
-            Instruction::Bnel(rs, rt, _) => [Some(Occurrence::from(rs)), Some(Occurrence::from(rt))],
-            Instruction::Daddiu(_, rs, _) => [Some(Occurrence::from(rs)), None],
-            Instruction::Mfhi1(_) => [Some(Occurrence::from(Register::Hi)), None],
-            Instruction::Mthi1(rs) => [Some(Occurrence::from(rs)), None],
-            Instruction::Mflo1(_) => [Some(Occurrence::from(Register::Lo)), None],
-            Instruction::Mtlo1(rs) => [Some(Occurrence::from(rs)), None],
-            Instruction::Mult1(_, rs, rt) => [Some(Occurrence::from(rs)), Some(Occurrence::from(rt))],
-            Instruction::Div1(rs, rt) => [Some(Occurrence::from(rs)), Some(Occurrence::from(rt))],
-            Instruction::Divu1(rs, rt) => [Some(Occurrence::from(rs)), Some(Occurrence::from(rt))],
-            Instruction::Pand(_, rs, rt) => [Some(Occurrence::from(rs)), Some(Occurrence::from(rt))],
-            Instruction::Por(_, rs, rt) => [Some(Occurrence::from(rs)), Some(Occurrence::from(rt))],
-            Instruction::Pxor(_, rs, rt) => [Some(Occurrence::from(rs)), Some(Occurrence::from(rt))],
-            Instruction::Pnor(_, rs, rt) => [Some(Occurrence::from(rs)), Some(Occurrence::from(rt))],
-            Instruction::Lq(_, _, rs) => [Some(Occurrence::from(rs)), None],
-            Instruction::Sq(rt, _, rs) => [Some(Occurrence::from(rt)), Some(Occurrence::from(rs))],
-            Instruction::Lb(_, _, rs) => [Some(Occurrence::from(rs)), None],
-            Instruction::Lh(_, _, rs) => [Some(Occurrence::from(rs)), None],
-            Instruction::Lw(_, _, rs) => [Some(Occurrence::from(rs)), None],
-            Instruction::Lbu(_, _, rs) => [Some(Occurrence::from(rs)), None],
-            Instruction::Lhu(_, _, rs) => [Some(Occurrence::from(rs)), None],
-            Instruction::Lwr(rt, _, rs) => [Some(Occurrence::from(rs)), Some(Occurrence::from(rt))],
-            Instruction::Sb(rt, _, rs) => [Some(Occurrence::from(rt)), Some(Occurrence::from(rs))],
-            Instruction::Sh(rt, _, rs) => [Some(Occurrence::from(rt)), Some(Occurrence::from(rs))],
-            Instruction::Sw(rt, _, rs) => [Some(Occurrence::from(rt)), Some(Occurrence::from(rs))],
-            Instruction::Cache(_, _, rs) => [Some(Occurrence::from(rs)), None],
-            Instruction::Lwc1(_, _, rs) => [Some(Occurrence::from(rs)), None],
-            Instruction::Ld(_, _, rs) => [Some(Occurrence::from(rs)), None],
-            Instruction::Swc1(ft, _, rs) => [Some(Occurrence::from(ft)), Some(Occurrence::from(rs))],
-            Instruction::Sd(rt, _, rs) => [Some(Occurrence::from(rt)), Some(Occurrence::from(rs))],
+            Instruction::Sll(_, rt, _) => [Some(Occurrence::from(rt)), None, None, None],
+            Instruction::Unknown => [None, None, None, None],
+            Instruction::Srl(_, rt, _) => [Some(Occurrence::from(rt)), None, None, None],
+            Instruction::Sra(_, rt, _) => [Some(Occurrence::from(rt)), None, None, None],
+            Instruction::Sllv(_, rt, rs) => [Some(Occurrence::from(rt)), Some(Occurrence::from(rs)), None, None],
+            Instruction::Srlv(_, rt, rs) => [Some(Occurrence::from(rt)), Some(Occurrence::from(rs)), None, None],
+            Instruction::Srav(_, rt, rs) => [Some(Occurrence::from(rt)), Some(Occurrence::from(rs)), None, None],
+            Instruction::Jr(rs) => [Some(Occurrence::from(rs)), None, None, None],
+            Instruction::Jalr(rd, rs) => [Some(Occurrence::from(rd)), Some(Occurrence::from(rs)), None, None],
+            Instruction::Movz(_, rs, rt) => [Some(Occurrence::from(rs)), Some(Occurrence::from(rt)), None, None],
+            Instruction::Movn(_, rs, rt) => [Some(Occurrence::from(rs)), Some(Occurrence::from(rt)), None, None],
+            Instruction::Syscall => [None, None, None, None],
+            Instruction::Break => [None, None, None, None],
+            Instruction::Sync => [None, None, None, None],
+            Instruction::Mfhi(_) => [Some(Occurrence::from(Register::Hi)), None, None, None],
+            Instruction::Mthi(rs) => [Some(Occurrence::from(rs)), None, None, None],
+            Instruction::Mflo(_) => [Some(Occurrence::from(Register::Lo)), None, None, None],
+            Instruction::Mtlo(rs) => [Some(Occurrence::from(rs)), None, None, None],
+            Instruction::Dsllv(_, rt, rs) => [Some(Occurrence::from(rt)), Some(Occurrence::from(rs)), None, None],
+            Instruction::Dsrlv(_, rt, rs) => [Some(Occurrence::from(rt)), Some(Occurrence::from(rs)), None, None],
+            Instruction::Dsrav(_, rt, rs) => [Some(Occurrence::from(rt)), Some(Occurrence::from(rs)), None, None],
+            Instruction::Mult(_, rs, rt) => [Some(Occurrence::from(rs)), Some(Occurrence::from(rt)), None, None],
+            Instruction::Multu(_, rs, rt) => [Some(Occurrence::from(rs)), Some(Occurrence::from(rt)), None, None],
+            Instruction::Div(rs, rt) => [Some(Occurrence::from(rs)), Some(Occurrence::from(rt)), None, None],
+            Instruction::Divu(rs, rt) => [Some(Occurrence::from(rs)), Some(Occurrence::from(rt)), None, None],
+            Instruction::Add(_, rs, rt) => [Some(Occurrence::from(rs)), Some(Occurrence::from(rt)), None, None],
+            Instruction::Addu(_, rs, rt) => [Some(Occurrence::from(rs)), Some(Occurrence::from(rt)), None, None],
+            Instruction::Sub(_, rs, rt) => [Some(Occurrence::from(rs)), Some(Occurrence::from(rt)), None, None],
+            Instruction::Subu(_, rs, rt) => [Some(Occurrence::from(rs)), Some(Occurrence::from(rt)), None, None],
+            Instruction::And(_, rs, rt) => [Some(Occurrence::from(rs)), Some(Occurrence::from(rt)), None, None],
+            Instruction::Or(_, rs, rt) => [Some(Occurrence::from(rs)), Some(Occurrence::from(rt)), None, None],
+            Instruction::Xor(_, rs, rt) => [Some(Occurrence::from(rs)), Some(Occurrence::from(rt)), None, None],
+            Instruction::Nor(_, rs, rt) => [Some(Occurrence::from(rs)), Some(Occurrence::from(rt)), None, None],
+            Instruction::Mfsa(_) => [None, None, None, None],
+            Instruction::Mtsa(rs) => [Some(Occurrence::from(rs)), None, None, None],
+            Instruction::Slt(_, rs, rt) => [Some(Occurrence::from(rs)), Some(Occurrence::from(rt)), None, None],
+            Instruction::Sltu(_, rs, rt) => [Some(Occurrence::from(rs)), Some(Occurrence::from(rt)), None, None],
+            Instruction::Dadd(_, rs, rt) => [Some(Occurrence::from(rs)), Some(Occurrence::from(rt)), None, None],
+            Instruction::Daddu(_, rs, rt) => [Some(Occurrence::from(rs)), Some(Occurrence::from(rt)), None, None],
+            Instruction::Dsub(_, rs, rt) => [Some(Occurrence::from(rs)), Some(Occurrence::from(rt)), None, None],
+            Instruction::Dsubu(_, rs, rt) => [Some(Occurrence::from(rs)), Some(Occurrence::from(rt)), None, None],
+            Instruction::Tge(rs, rt) => [Some(Occurrence::from(rs)), Some(Occurrence::from(rt)), None, None],
+            Instruction::Tgeu(rs, rt) => [Some(Occurrence::from(rs)), Some(Occurrence::from(rt)), None, None],
+            Instruction::Tlt(rs, rt) => [Some(Occurrence::from(rs)), Some(Occurrence::from(rt)), None, None],
+            Instruction::Tltu(rs, rt) => [Some(Occurrence::from(rs)), Some(Occurrence::from(rt)), None, None],
+            Instruction::Teq(rs, rt) => [Some(Occurrence::from(rs)), Some(Occurrence::from(rt)), None, None],
+            Instruction::Tne(rs, rt) => [Some(Occurrence::from(rs)), Some(Occurrence::from(rt)), None, None],
+            Instruction::Dsll(_, rt, _) => [Some(Occurrence::from(rt)), None, None, None],
+            Instruction::Dsrl(_, rt, _) => [Some(Occurrence::from(rt)), None, None, None],
+            Instruction::Dsra(_, rt, _) => [Some(Occurrence::from(rt)), None, None, None],
+            Instruction::Dsll32(_, rt, _) => [Some(Occurrence::from(rt)), None, None, None],
+            Instruction::Dsrl32(_, rt, _) => [Some(Occurrence::from(rt)), None, None, None],
+            Instruction::Dsra32(_, rt, _) => [Some(Occurrence::from(rt)), None, None, None],
+            Instruction::Bltz(rs, _) => [Some(Occurrence::from(rs)), None, None, None],
+            Instruction::Bgez(rs, _) => [Some(Occurrence::from(rs)), None, None, None],
+            Instruction::Bltzl(rs, _) => [Some(Occurrence::from(rs)), None, None, None],
+            Instruction::Bgezl(rs, _) => [Some(Occurrence::from(rs)), None, None, None],
+            Instruction::Bltzal(rs, _) => [Some(Occurrence::from(rs)), None, None, None],
+            Instruction::Bgezal(rs, _) => [Some(Occurrence::from(rs)), None, None, None],
+            Instruction::Bltzall(rs, _) => [Some(Occurrence::from(rs)), None, None, None],
+            Instruction::Bgezall(rs, _) => [Some(Occurrence::from(rs)), None, None, None],
+            Instruction::J(_) => [None, None, None, None],
+            Instruction::Jal(_) => [None, None, None, None],
+            Instruction::Beq(rs, rt, _) => [Some(Occurrence::from(rs)), Some(Occurrence::from(rt)), None, None],
+            Instruction::Bne(rs, rt, _) => [Some(Occurrence::from(rs)), Some(Occurrence::from(rt)), None, None],
+            Instruction::Blez(rs, _) => [Some(Occurrence::from(rs)), None, None, None],
+            Instruction::Bgtz(rs, _) => [Some(Occurrence::from(rs)), None, None, None],
+            Instruction::Addi(_, rs, _) => [Some(Occurrence::from(rs)), None, None, None],
+            Instruction::Addiu(_, rs, _) => [Some(Occurrence::from(rs)), None, None, None],
+            Instruction::Slti(_, rs, _) => [Some(Occurrence::from(rs)), None, None, None],
+            Instruction::Sltiu(_, rs, _) => [Some(Occurrence::from(rs)), None, None, None],
+            Instruction::Andi(_, rs, _) => [Some(Occurrence::from(rs)), None, None, None],
+            Instruction::Ori(_, rs, _) => [Some(Occurrence::from(rs)), None, None, None],
+            Instruction::Xori(_, rs, _) => [Some(Occurrence::from(rs)), None, None, None],
+            Instruction::Lui(_, _) => [None, None, None, None],
+            Instruction::Mfc0(_, cd) => [Some(Occurrence::from(cd)), None, None, None],
+            Instruction::Mtc0(_, rt) => [Some(Occurrence::from(rt)), None, None, None],
+            Instruction::Tlbr => [None, None, None, None],
+            Instruction::Tlbwi => [None, None, None, None],
+            Instruction::Tlbwr => [None, None, None, None],
+            Instruction::Tlbp => [None, None, None, None],
+            Instruction::Eret => [None, None, None, None],
+            Instruction::Ei => [None, None, None, None],
+            Instruction::Di => [None, None, None, None],
+            Instruction::Mfc1(_, fs) => [Some(Occurrence::from(fs)), None, None, None],
+            Instruction::Cfc1(_, fs) => [Some(Occurrence::from(fs)), None, None, None],
+            Instruction::Mtc1(_, rt) => [Some(Occurrence::from(rt)), None, None, None],
+            Instruction::Ctc1(rt, fs) => [Some(Occurrence::from(rt)), Some(Occurrence::from(fs)), None, None],
+            Instruction::Bc1f(_) => [None, None, None, None],
+            Instruction::Bc1t(_) => [None, None, None, None],
+            Instruction::Bc1fl(_) => [None, None, None, None],
+            Instruction::Bc1tl(_) => [None, None, None, None],
+            Instruction::Adds(_, fs, ft) => [Some(Occurrence::from(fs)), Some(Occurrence::from(ft)), None, None],
+            Instruction::Subs(_, fs, ft) => [Some(Occurrence::from(fs)), Some(Occurrence::from(ft)), None, None],
+            Instruction::Muls(_, fs, ft) => [Some(Occurrence::from(fs)), Some(Occurrence::from(ft)), None, None],
+            Instruction::Divs(_, fs, ft) => [Some(Occurrence::from(fs)), Some(Occurrence::from(ft)), None, None],
+            Instruction::Sqrts(_, ft) => [Some(Occurrence::from(ft)), None, None, None],
+            Instruction::Abss(_, fs) => [Some(Occurrence::from(fs)), None, None, None],
+            Instruction::Movs(_, fs) => [Some(Occurrence::from(fs)), None, None, None],
+            Instruction::Negs(_, fs) => [Some(Occurrence::from(fs)), None, None, None],
+            Instruction::Rsqrts(_, fs, ft) => [Some(Occurrence::from(fs)), Some(Occurrence::from(ft)), None, None],
+            Instruction::Addas(fs, ft) => [Some(Occurrence::from(fs)), Some(Occurrence::from(ft)), None, None],
+            Instruction::Subas(fs, ft) => [Some(Occurrence::from(fs)), Some(Occurrence::from(ft)), None, None],
+            Instruction::Mulas(fs, ft) => [Some(Occurrence::from(fs)), Some(Occurrence::from(ft)), None, None],
+            Instruction::Madds(_, fs, ft) => [Some(Occurrence::from(fs)), Some(Occurrence::from(ft)), None, None],
+            Instruction::Msubs(_, fs, ft) => [Some(Occurrence::from(fs)), Some(Occurrence::from(ft)), None, None],
+            Instruction::Maddas(fs, ft) => [Some(Occurrence::from(fs)), Some(Occurrence::from(ft)), None, None],
+            Instruction::Msubas(fs, ft) => [Some(Occurrence::from(fs)), Some(Occurrence::from(ft)), None, None],
+            Instruction::Cvtws(_, fs) => [Some(Occurrence::from(fs)), None, None, None],
+            Instruction::Maxs(_, fs, ft) => [Some(Occurrence::from(fs)), Some(Occurrence::from(ft)), None, None],
+            Instruction::Mins(_, fs, ft) => [Some(Occurrence::from(fs)), Some(Occurrence::from(ft)), None, None],
+            Instruction::Cfs(fs, ft) => [Some(Occurrence::from(fs)), Some(Occurrence::from(ft)), None, None],
+            Instruction::Ceqs(fs, ft) => [Some(Occurrence::from(fs)), Some(Occurrence::from(ft)), None, None],
+            Instruction::Clts(fs, ft) => [Some(Occurrence::from(fs)), Some(Occurrence::from(ft)), None, None],
+            Instruction::Cles(fs, ft) => [Some(Occurrence::from(fs)), Some(Occurrence::from(ft)), None, None],
+            Instruction::Cvtsw(_, fs) => [Some(Occurrence::from(fs)), None, None, None],
+            Instruction::Beql(rs, rt, _) => [Some(Occurrence::from(rs)), Some(Occurrence::from(rt)), None, None],
+            Instruction::Bnel(rs, rt, _) => [Some(Occurrence::from(rs)), Some(Occurrence::from(rt)), None, None],
+            Instruction::Blezl(rs, _) => [Some(Occurrence::from(rs)), None, None, None],
+            Instruction::Bgtzl(rs, _) => [Some(Occurrence::from(rs)), None, None, None],
+            Instruction::Daddi(_, rs, _) => [Some(Occurrence::from(rs)), None, None, None],
+            Instruction::Daddiu(_, rs, _) => [Some(Occurrence::from(rs)), None, None, None],
+            Instruction::Mfhi1(_) => [Some(Occurrence::from(Register::Hi)), None, None, None],
+            Instruction::Mthi1(rs) => [Some(Occurrence::from(rs)), None, None, None],
+            Instruction::Mflo1(_) => [Some(Occurrence::from(Register::Lo)), None, None, None],
+            Instruction::Mtlo1(rs) => [Some(Occurrence::from(rs)), None, None, None],
+            Instruction::Mult1(_, rs, rt) => [Some(Occurrence::from(rs)), Some(Occurrence::from(rt)), None, None],
+            Instruction::Div1(rs, rt) => [Some(Occurrence::from(rs)), Some(Occurrence::from(rt)), None, None],
+            Instruction::Divu1(rs, rt) => [Some(Occurrence::from(rs)), Some(Occurrence::from(rt)), None, None],
+            Instruction::Pand(_, rs, rt) => [Some(Occurrence::from(rs)), Some(Occurrence::from(rt)), None, None],
+            Instruction::Por(_, rs, rt) => [Some(Occurrence::from(rs)), Some(Occurrence::from(rt)), None, None],
+            Instruction::Pxor(_, rs, rt) => [Some(Occurrence::from(rs)), Some(Occurrence::from(rt)), None, None],
+            Instruction::Pnor(_, rs, rt) => [Some(Occurrence::from(rs)), Some(Occurrence::from(rt)), None, None],
+            Instruction::Madd(_, rs, rt) => [Some(Occurrence::from(rs)), Some(Occurrence::from(rt)), Some(Occurrence::from(Register::Lo)), Some(Occurrence::from(Register::Hi))],
+            Instruction::Maddu(_, rs, rt) => [Some(Occurrence::from(rs)), Some(Occurrence::from(rt)), Some(Occurrence::from(Register::Lo)), Some(Occurrence::from(Register::Hi))],
+            Instruction::Plzcw(_, rs) => [Some(Occurrence::from(rs)), None, None, None],
+            Instruction::Multu1(_, rs, rt) => [Some(Occurrence::from(rs)), Some(Occurrence::from(rt)), None, None],
+            Instruction::Madd1(_, rs, rt) => [Some(Occurrence::from(rs)), Some(Occurrence::from(rt)), Some(Occurrence::from(Register::Lo)), Some(Occurrence::from(Register::Hi))],
+            Instruction::Maddu1(_, rs, rt) => [Some(Occurrence::from(rs)), Some(Occurrence::from(rt)), Some(Occurrence::from(Register::Lo)), Some(Occurrence::from(Register::Hi))],
+            Instruction::Pmfhllw(_) => [Some(Occurrence::from(Register::Lo)), Some(Occurrence::from(Register::Hi)), None, None],
+            Instruction::Pmfhluw(_) => [Some(Occurrence::from(Register::Lo)), Some(Occurrence::from(Register::Hi)), None, None],
+            Instruction::Pmfhlslw(_) => [Some(Occurrence::from(Register::Lo)), Some(Occurrence::from(Register::Hi)), None, None],
+            Instruction::Pmfhllh(_) => [Some(Occurrence::from(Register::Lo)), Some(Occurrence::from(Register::Hi)), None, None],
+            Instruction::Pmfhlsh(_) => [Some(Occurrence::from(Register::Lo)), Some(Occurrence::from(Register::Hi)), None, None],
+            Instruction::Pmthllw(rs) => [Some(Occurrence::from(rs)), Some(Occurrence::from(Register::Lo)), Some(Occurrence::from(Register::Hi)), None],
+            Instruction::Psllh(_, rt, _) => [Some(Occurrence::from(rt)), None, None, None],
+            Instruction::Psrlh(_, rt, _) => [Some(Occurrence::from(rt)), None, None, None],
+            Instruction::Psrah(_, rt, _) => [Some(Occurrence::from(rt)), None, None, None],
+            Instruction::Psllw(_, rt, _) => [Some(Occurrence::from(rt)), None, None, None],
+            Instruction::Psrlw(_, rt, _) => [Some(Occurrence::from(rt)), None, None, None],
+            Instruction::Psraw(_, rt, _) => [Some(Occurrence::from(rt)), None, None, None],
+            Instruction::Paddw(_, rs, rt) => [Some(Occurrence::from(rs)), Some(Occurrence::from(rt)), None, None],
+            Instruction::Psubw(_, rs, rt) => [Some(Occurrence::from(rs)), Some(Occurrence::from(rt)), None, None],
+            Instruction::Pcgtw(_, rs, rt) => [Some(Occurrence::from(rs)), Some(Occurrence::from(rt)), None, None],
+            Instruction::Pmaxw(_, rs, rt) => [Some(Occurrence::from(rs)), Some(Occurrence::from(rt)), None, None],
+            Instruction::Paddh(_, rs, rt) => [Some(Occurrence::from(rs)), Some(Occurrence::from(rt)), None, None],
+            Instruction::Psubh(_, rs, rt) => [Some(Occurrence::from(rs)), Some(Occurrence::from(rt)), None, None],
+            Instruction::Pcgth(_, rs, rt) => [Some(Occurrence::from(rs)), Some(Occurrence::from(rt)), None, None],
+            Instruction::Pmaxh(_, rs, rt) => [Some(Occurrence::from(rs)), Some(Occurrence::from(rt)), None, None],
+            Instruction::Paddb(_, rs, rt) => [Some(Occurrence::from(rs)), Some(Occurrence::from(rt)), None, None],
+            Instruction::Psubb(_, rs, rt) => [Some(Occurrence::from(rs)), Some(Occurrence::from(rt)), None, None],
+            Instruction::Pcgtb(_, rs, rt) => [Some(Occurrence::from(rs)), Some(Occurrence::from(rt)), None, None],
+            Instruction::Paddsw(_, rs, rt) => [Some(Occurrence::from(rs)), Some(Occurrence::from(rt)), None, None],
+            Instruction::Psubsw(_, rs, rt) => [Some(Occurrence::from(rs)), Some(Occurrence::from(rt)), None, None],
+            Instruction::Pextlw(_, rs, rt) => [Some(Occurrence::from(rs)), Some(Occurrence::from(rt)), None, None],
+            Instruction::Ppacw(_, rs, rt) => [Some(Occurrence::from(rs)), Some(Occurrence::from(rt)), None, None],
+            Instruction::Paddsh(_, rs, rt) => [Some(Occurrence::from(rs)), Some(Occurrence::from(rt)), None, None],
+            Instruction::Psubsh(_, rs, rt) => [Some(Occurrence::from(rs)), Some(Occurrence::from(rt)), None, None],
+            Instruction::Pextlh(_, rs, rt) => [Some(Occurrence::from(rs)), Some(Occurrence::from(rt)), None, None],
+            Instruction::Ppach(_, rs, rt) => [Some(Occurrence::from(rs)), Some(Occurrence::from(rt)), None, None],
+            Instruction::Paddsb(_, rs, rt) => [Some(Occurrence::from(rs)), Some(Occurrence::from(rt)), None, None],
+            Instruction::Psubsb(_, rs, rt) => [Some(Occurrence::from(rs)), Some(Occurrence::from(rt)), None, None],
+            Instruction::Pextlb(_, rs, rt) => [Some(Occurrence::from(rs)), Some(Occurrence::from(rt)), None, None],
+            Instruction::Ppacb(_, rs, rt) => [Some(Occurrence::from(rs)), Some(Occurrence::from(rt)), None, None],
+            Instruction::Pext5(_, rt) => [Some(Occurrence::from(rt)), None, None, None],
+            Instruction::Ppac5(_, rt) => [Some(Occurrence::from(rt)), None, None, None],
+            Instruction::Pabsw(_, rt) => [Some(Occurrence::from(rt)), None, None, None],
+            Instruction::Pceqw(_, rs, rt) => [Some(Occurrence::from(rs)), Some(Occurrence::from(rt)), None, None],
+            Instruction::Pminw(_, rs, rt) => [Some(Occurrence::from(rs)), Some(Occurrence::from(rt)), None, None],
+            Instruction::Padsbh(_, rs, rt) => [Some(Occurrence::from(rs)), Some(Occurrence::from(rt)), None, None],
+            Instruction::Pabsh(_, rt) => [Some(Occurrence::from(rt)), None, None, None],
+            Instruction::Pceqh(_, rs, rt) => [Some(Occurrence::from(rs)), Some(Occurrence::from(rt)), None, None],
+            Instruction::Pminh(_, rs, rt) => [Some(Occurrence::from(rs)), Some(Occurrence::from(rt)), None, None],
+            Instruction::Pceqb(_, rs, rt) => [Some(Occurrence::from(rs)), Some(Occurrence::from(rt)), None, None],
+            Instruction::Padduw(_, rs, rt) => [Some(Occurrence::from(rs)), Some(Occurrence::from(rt)), None, None],
+            Instruction::Psubuw(_, rs, rt) => [Some(Occurrence::from(rs)), Some(Occurrence::from(rt)), None, None],
+            Instruction::Pextuw(_, rs, rt) => [Some(Occurrence::from(rs)), Some(Occurrence::from(rt)), None, None],
+            Instruction::Padduh(_, rs, rt) => [Some(Occurrence::from(rs)), Some(Occurrence::from(rt)), None, None],
+            Instruction::Psubuh(_, rs, rt) => [Some(Occurrence::from(rs)), Some(Occurrence::from(rt)), None, None],
+            Instruction::Pextuh(_, rs, rt) => [Some(Occurrence::from(rs)), Some(Occurrence::from(rt)), None, None],
+            Instruction::Paddub(_, rs, rt) => [Some(Occurrence::from(rs)), Some(Occurrence::from(rt)), None, None],
+            Instruction::Psubub(_, rs, rt) => [Some(Occurrence::from(rs)), Some(Occurrence::from(rt)), None, None],
+            Instruction::Pextub(_, rs, rt) => [Some(Occurrence::from(rs)), Some(Occurrence::from(rt)), None, None],
+            Instruction::Qfsrv(_, rs, rt) => [Some(Occurrence::from(rs)), Some(Occurrence::from(rt)), None, None],
+            Instruction::Pmaddw(_, rs, rt) => [Some(Occurrence::from(rs)), Some(Occurrence::from(rt)), Some(Occurrence::from(Register::Lo)), Some(Occurrence::from(Register::Hi))],
+            Instruction::Psllvw(_, rt, rs) => [Some(Occurrence::from(rt)), Some(Occurrence::from(rs)), None, None],
+            Instruction::Psrlvw(_, rt, rs) => [Some(Occurrence::from(rt)), Some(Occurrence::from(rs)), None, None],
+            Instruction::Pmsubw(_, rs, rt) => [Some(Occurrence::from(rs)), Some(Occurrence::from(rt)), Some(Occurrence::from(Register::Lo)), Some(Occurrence::from(Register::Hi))],
+            Instruction::Pmfhi(_) => [Some(Occurrence::from(Register::Hi)), None, None, None],
+            Instruction::Pmflo(_) => [Some(Occurrence::from(Register::Lo)), None, None, None],
+            Instruction::Pinth(_, rs, rt) => [Some(Occurrence::from(rs)), Some(Occurrence::from(rt)), None, None],
+            Instruction::Pmultw(_, rs, rt) => [Some(Occurrence::from(rs)), Some(Occurrence::from(rt)), None, None],
+            Instruction::Pdivw(rs, rt) => [Some(Occurrence::from(rs)), Some(Occurrence::from(rt)), None, None],
+            Instruction::Pcpyld(_, rs, rt) => [Some(Occurrence::from(rs)), Some(Occurrence::from(rt)), None, None],
+            Instruction::Pmaddh(_, rs, rt) => [Some(Occurrence::from(rs)), Some(Occurrence::from(rt)), Some(Occurrence::from(Register::Lo)), Some(Occurrence::from(Register::Hi))],
+            Instruction::Phmadh(_, rs, rt) => [Some(Occurrence::from(rs)), Some(Occurrence::from(rt)), None, None],
+            Instruction::Pmsubh(_, rs, rt) => [Some(Occurrence::from(rs)), Some(Occurrence::from(rt)), Some(Occurrence::from(Register::Lo)), Some(Occurrence::from(Register::Hi))],
+            Instruction::Phmsbh(_, rs, rt) => [Some(Occurrence::from(rs)), Some(Occurrence::from(rt)), None, None],
+            Instruction::Pexeh(_, rt) => [Some(Occurrence::from(rt)), None, None, None],
+            Instruction::Prevh(_, rt) => [Some(Occurrence::from(rt)), None, None, None],
+            Instruction::Pmulth(_, rs, rt) => [Some(Occurrence::from(rs)), Some(Occurrence::from(rt)), None, None],
+            Instruction::Pdivbw(rs, rt) => [Some(Occurrence::from(rs)), Some(Occurrence::from(rt)), None, None],
+            Instruction::Pexew(_, rt) => [Some(Occurrence::from(rt)), None, None, None],
+            Instruction::Prot3w(_, rt) => [Some(Occurrence::from(rt)), None, None, None],
+            Instruction::Pmadduw(_, rs, rt) => [Some(Occurrence::from(rs)), Some(Occurrence::from(rt)), Some(Occurrence::from(Register::Lo)), Some(Occurrence::from(Register::Hi))],
+            Instruction::Psravw(_, rt, rs) => [Some(Occurrence::from(rt)), Some(Occurrence::from(rs)), None, None],
+            Instruction::Pmthi(rs) => [Some(Occurrence::from(rs)), None, None, None],
+            Instruction::Pmtlo(rs) => [Some(Occurrence::from(rs)), None, None, None],
+            Instruction::Pinteh(_, rs, rt) => [Some(Occurrence::from(rs)), Some(Occurrence::from(rt)), None, None],
+            Instruction::Pmultuw(_, rs, rt) => [Some(Occurrence::from(rs)), Some(Occurrence::from(rt)), None, None],
+            Instruction::Pdivuw(rs, rt) => [Some(Occurrence::from(rs)), Some(Occurrence::from(rt)), None, None],
+            Instruction::Pcpyud(_, rs, rt) => [Some(Occurrence::from(rs)), Some(Occurrence::from(rt)), None, None],
+            Instruction::Pexch(_, rt) => [Some(Occurrence::from(rt)), None, None, None],
+            Instruction::Pcpyh(_, rt) => [Some(Occurrence::from(rt)), None, None, None],
+            Instruction::Pexcw(_, rt) => [Some(Occurrence::from(rt)), None, None, None],
+            Instruction::Ldl(rt, _, rs) => [Some(Occurrence::from(rs)), Some(Occurrence::from(rt)), None, None],
+            Instruction::Ldr(rt, _, rs) => [Some(Occurrence::from(rs)), Some(Occurrence::from(rt)), None, None],
+            Instruction::Lq(_, _, rs) => [Some(Occurrence::from(rs)), None, None, None],
+            Instruction::Sq(rt, _, rs) => [Some(Occurrence::from(rt)), Some(Occurrence::from(rs)), None, None],
+            Instruction::Lb(_, _, rs) => [Some(Occurrence::from(rs)), None, None, None],
+            Instruction::Lh(_, _, rs) => [Some(Occurrence::from(rs)), None, None, None],
+            Instruction::Lw(_, _, rs) => [Some(Occurrence::from(rs)), None, None, None],
+            Instruction::Lbu(_, _, rs) => [Some(Occurrence::from(rs)), None, None, None],
+            Instruction::Lhu(_, _, rs) => [Some(Occurrence::from(rs)), None, None, None],
+            Instruction::Lwl(rt, _, rs) => [Some(Occurrence::from(rs)), Some(Occurrence::from(rt)), None, None],
+            Instruction::Lwr(rt, _, rs) => [Some(Occurrence::from(rs)), Some(Occurrence::from(rt)), None, None],
+            Instruction::Sb(rt, _, rs) => [Some(Occurrence::from(rt)), Some(Occurrence::from(rs)), None, None],
+            Instruction::Sh(rt, _, rs) => [Some(Occurrence::from(rt)), Some(Occurrence::from(rs)), None, None],
+            Instruction::Swl(rt, _, rs) => [Some(Occurrence::from(rt)), Some(Occurrence::from(rs)), None, None],
+            Instruction::Sw(rt, _, rs) => [Some(Occurrence::from(rt)), Some(Occurrence::from(rs)), None, None],
+            Instruction::Sdl(rt, _, rs) => [Some(Occurrence::from(rt)), Some(Occurrence::from(rs)), None, None],
+            Instruction::Sdr(rt, _, rs) => [Some(Occurrence::from(rt)), Some(Occurrence::from(rs)), None, None],
+            Instruction::Swr(rt, _, rs) => [Some(Occurrence::from(rt)), Some(Occurrence::from(rs)), None, None],
+            Instruction::Cache(_, _, rs) => [Some(Occurrence::from(rs)), None, None, None],
+            Instruction::Lwc1(_, _, rs) => [Some(Occurrence::from(rs)), None, None, None],
+            Instruction::Ld(_, _, rs) => [Some(Occurrence::from(rs)), None, None, None],
+            Instruction::Swc1(ft, _, rs) => [Some(Occurrence::from(ft)), Some(Occurrence::from(rs)), None, None],
+            Instruction::Sd(rt, _, rs) => [Some(Occurrence::from(rt)), Some(Occurrence::from(rs)), None, None],
         }
     }
 }
+
+// The fixed bits of every encoding and their values, the other bits being operands.
+// Used to generate random instructions in tests.
+#[cfg(test)]
+pub const ENCODINGS: [(u32, u32); 259] = [
+    (0xffe0003f, 0x00000000), // {rd} = sll {rt}, {sa}
+    (0xfc00003f, 0x00000001), // unknown
+    (0xffe0003f, 0x00000002), // {rd} = srl {rt}, {sa}
+    (0xffe0003f, 0x00000003), // {rd} = sra {rt}, {sa}
+    (0xfc0007ff, 0x00000004), // {rd} = sllv {rt}, {rs}
+    (0xfc00003f, 0x00000005), // unknown
+    (0xfc0007ff, 0x00000006), // {rd} = srlv {rt}, {rs}
+    (0xfc0007ff, 0x00000007), // {rd} = srav {rt}, {rs}
+    (0xfc1fffff, 0x00000008), // jr {rs}
+    (0xfc1f07ff, 0x00000009), // jalr {rd}, {rs}
+    (0xfc0007ff, 0x0000000a), // {rd} = movz {rs}, {rt}
+    (0xfc0007ff, 0x0000000b), // {rd} = movn {rs}, {rt}
+    (0xfc00003f, 0x0000000c), // syscall
+    (0xfc00003f, 0x0000000d), // break
+    (0xfc00003f, 0x0000000e), // unknown
+    (0xfffff83f, 0x0000000f), // sync
+    (0xffff07ff, 0x00000010), // {rd} = mfhi
+    (0xfc1fffff, 0x00000011), // mthi {rs}
+    (0xffff07ff, 0x00000012), // {rd} = mflo
+    (0xfc1fffff, 0x00000013), // mtlo {rs}
+    (0xfc0007ff, 0x00000014), // {rd} = dsllv {rt}, {rs}
+    (0xfc00003f, 0x00000015), // unknown
+    (0xfc0007ff, 0x00000016), // {rd} = dsrlv {rt}, {rs}
+    (0xfc0007ff, 0x00000017), // {rd} = dsrav {rt}, {rs}
+    (0xfc0007ff, 0x00000018), // {rd} = mult {rs}, {rt}
+    (0xfc0007ff, 0x00000019), // {rd} = multu {rs}, {rt}
+    (0xfc00ffff, 0x0000001a), // div {rs}, {rt}
+    (0xfc00ffff, 0x0000001b), // divu {rs}, {rt}
+    (0xfc00003f, 0x0000001c), // unknown
+    (0xfc00003f, 0x0000001d), // unknown
+    (0xfc00003f, 0x0000001e), // unknown
+    (0xfc00003f, 0x0000001f), // unknown
+    (0xfc0007ff, 0x00000020), // {rd} = add {rs}, {rt}
+    (0xfc0007ff, 0x00000021), // {rd} = addu {rs}, {rt}
+    (0xfc0007ff, 0x00000022), // {rd} = sub {rs}, {rt}
+    (0xfc0007ff, 0x00000023), // {rd} = subu {rs}, {rt}
+    (0xfc0007ff, 0x00000024), // {rd} = and {rs}, {rt}
+    (0xfc0007ff, 0x00000025), // {rd} = or {rs}, {rt}
+    (0xfc0007ff, 0x00000026), // {rd} = xor {rs}, {rt}
+    (0xfc0007ff, 0x00000027), // {rd} = nor {rs}, {rt}
+    (0xffff07ff, 0x00000028), // {rd} = mfsa
+    (0xfc1fffff, 0x00000029), // mtsa {rs}
+    (0xfc0007ff, 0x0000002a), // {rd} = slt {rs}, {rt}
+    (0xfc0007ff, 0x0000002b), // {rd} = sltu {rs}, {rt}
+    (0xfc0007ff, 0x0000002c), // {rd} = dadd {rs}, {rt}
+    (0xfc0007ff, 0x0000002d), // {rd} = daddu {rs}, {rt}
+    (0xfc0007ff, 0x0000002e), // {rd} = dsub {rs}, {rt}
+    (0xfc0007ff, 0x0000002f), // {rd} = dsubu {rs}, {rt}
+    (0xfc00003f, 0x00000030), // tge {rs}, {rt}
+    (0xfc00003f, 0x00000031), // tgeu {rs}, {rt}
+    (0xfc00003f, 0x00000032), // tlt {rs}, {rt}
+    (0xfc00003f, 0x00000033), // tltu {rs}, {rt}
+    (0xfc00003f, 0x00000034), // teq {rs}, {rt}
+    (0xfc00003f, 0x00000035), // unknown
+    (0xfc00003f, 0x00000036), // tne {rs}, {rt}
+    (0xfc00003f, 0x00000037), // unknown
+    (0xffe0003f, 0x00000038), // {rd} = dsll {rt}, {sa}
+    (0xfc00003f, 0x00000039), // unknown
+    (0xffe0003f, 0x0000003a), // {rd} = dsrl {rt}, {sa}
+    (0xffe0003f, 0x0000003b), // {rd} = dsra {rt}, {sa}
+    (0xffe0003f, 0x0000003c), // {rd} = dsll32 {rt}, {sa}
+    (0xfc00003f, 0x0000003d), // unknown
+    (0xffe0003f, 0x0000003e), // {rd} = dsrl32 {rt}, {sa}
+    (0xffe0003f, 0x0000003f), // {rd} = dsra32 {rt}, {sa}
+    (0xfc1f0000, 0x04000000), // bltz {rs}, {imm16:#x}
+    (0xfc1f0000, 0x04010000), // bgez {rs}, {imm16:#x}
+    (0xfc1f0000, 0x04020000), // bltzl {rs}, {imm16:#x}
+    (0xfc1f0000, 0x04030000), // bgezl {rs}, {imm16:#x}
+    (0xfc1f0000, 0x04100000), // bltzal {rs}, {imm16:#x}
+    (0xfc1f0000, 0x04110000), // bgezal {rs}, {imm16:#x}
+    (0xfc1f0000, 0x04120000), // bltzall {rs}, {imm16:#x}
+    (0xfc1f0000, 0x04130000), // bgezall {rs}, {imm16:#x}
+    (0xfc000000, 0x08000000), // j {imm26:#x}
+    (0xfc000000, 0x0c000000), // jal {imm26:#x}
+    (0xfc000000, 0x10000000), // beq {rs}, {rt}, {imm16:#x}
+    (0xfc000000, 0x14000000), // bne {rs}, {rt}, {imm16:#x}
+    (0xfc1f0000, 0x18000000), // blez {rs}, {imm16:#x}
+    (0xfc1f0000, 0x1c000000), // bgtz {rs}, {imm16:#x}
+    (0xfc000000, 0x20000000), // {rt} = addi {rs}, {imm16}
+    (0xfc000000, 0x24000000), // {rt} = addiu {rs}, {imm16}
+    (0xfc000000, 0x28000000), // {rt} = slti {rs}, {imm16}
+    (0xfc000000, 0x2c000000), // {rt} = sltiu {rs}, {imm16}
+    (0xfc000000, 0x30000000), // {rt} = andi {rs}, {imm16}
+    (0xfc000000, 0x34000000), // {rt} = ori {rs}, {imm16}
+    (0xfc000000, 0x38000000), // {rt} = xori {rs}, {imm16}
+    (0xffe00000, 0x3c000000), // {rt} = lui {imm16:#x}
+    (0xffe007ff, 0x40000000), // {rt} = mfc0 {cd}
+    (0xffe007ff, 0x40800000), // {cd} = mtc0 {rt}
+    (0xffffffff, 0x42000001), // tlbr
+    (0xffffffff, 0x42000002), // tlbwi
+    (0xffffffff, 0x42000006), // tlbwr
+    (0xffffffff, 0x42000008), // tlbp
+    (0xffffffff, 0x42000018), // eret
+    (0xffffffff, 0x42000038), // ei
+    (0xffffffff, 0x42000039), // di
+    (0xffe007ff, 0x44000000), // {rt} = mfc1 {fs}
+    (0xffe007ff, 0x44400000), // {rt} = cfc1 {fs}
+    (0xffe007ff, 0x44800000), // {fs} = mtc1 {rt}
+    (0xffe007ff, 0x44c00000), // ctc1 {rt}, {fs}
+    (0xffff0000, 0x45000000), // bc1f {imm16:#x}
+    (0xffff0000, 0x45010000), // bc1t {imm16:#x}
+    (0xffff0000, 0x45020000), // bc1fl {imm16:#x}
+    (0xffff0000, 0x45030000), // bc1tl {imm16:#x}
+    (0xffe0003f, 0x46000000), // {fd} = add.s {fs}, {ft}
+    (0xffe0003f, 0x46000001), // {fd} = sub.s {fs}, {ft}
+    (0xffe0003f, 0x46000002), // {fd} = mul.s {fs}, {ft}
+    (0xffe0003f, 0x46000003), // {fd} = div.s {fs}, {ft}
+    (0xffe0f83f, 0x46000004), // {fd} = sqrt.s {ft}
+    (0xffff003f, 0x46000005), // {fd} = abs.s {fs}
+    (0xffff003f, 0x46000006), // {fd} = mov.s {fs}
+    (0xffff003f, 0x46000007), // {fd} = neg.s {fs}
+    (0xffe0003f, 0x46000016), // {fd} = rsqrt.s {fs}, {ft}
+    (0xffe007ff, 0x46000018), // adda.s {fs}, {ft}
+    (0xffe007ff, 0x46000019), // suba.s {fs}, {ft}
+    (0xffe007ff, 0x4600001a), // mula.s {fs}, {ft}
+    (0xffe0003f, 0x4600001c), // {fd} = madd.s {fs}, {ft}
+    (0xffe0003f, 0x4600001d), // {fd} = msub.s {fs}, {ft}
+    (0xffe007ff, 0x4600001e), // madda.s {fs}, {ft}
+    (0xffe007ff, 0x4600001f), // msuba.s {fs}, {ft}
+    (0xffff003f, 0x46000024), // {fd} = cvt.w.s {fs}
+    (0xffe0003f, 0x46000028), // {fd} = max.s {fs}, {ft}
+    (0xffe0003f, 0x46000029), // {fd} = min.s {fs}, {ft}
+    (0xffe007ff, 0x46000030), // c.f.s {fs}, {ft}
+    (0xffe007ff, 0x46000032), // c.eq.s {fs}, {ft}
+    (0xffe007ff, 0x46000034), // c.lt.s {fs}, {ft}
+    (0xffe007ff, 0x46000036), // c.le.s {fs}, {ft}
+    (0xffff003f, 0x46800020), // {fd} = cvt.s.w {fs}
+    (0xfc000000, 0x50000000), // beql {rs}, {rt}, {imm16:#x}
+    (0xfc000000, 0x54000000), // bnel {rs}, {rt}, {imm16:#x}
+    (0xfc1f0000, 0x58000000), // blezl {rs}, {imm16:#x}
+    (0xfc1f0000, 0x5c000000), // bgtzl {rs}, {imm16:#x}
+    (0xfc000000, 0x60000000), // {rt} = daddi {rs}, {imm16}
+    (0xfc000000, 0x64000000), // {rt} = daddiu {rs}, {imm16}
+    (0xffff07ff, 0x70000010), // {rd} = mfhi1
+    (0xfc1fffff, 0x70000011), // mthi1 {rs}
+    (0xffff07ff, 0x70000012), // {rd} = mflo1
+    (0xfc1fffff, 0x70000013), // mtlo1 {rs}
+    (0xfc0007ff, 0x70000018), // {rd} = mult1 {rs}, {rt}
+    (0xfc00ffff, 0x7000001a), // div1 {rs}, {rt}
+    (0xfc00ffff, 0x7000001b), // divu1 {rs}, {rt}
+    (0xfc0007ff, 0x70000489), // {rd} = pand {rs}, {rt}
+    (0xfc0007ff, 0x700004a9), // {rd} = por {rs}, {rt}
+    (0xfc0007ff, 0x700004c9), // {rd} = pxor {rs}, {rt}
+    (0xfc0007ff, 0x700004e9), // {rd} = pnor {rs}, {rt}
+    (0xfc0007ff, 0x70000000), // {rd} = madd {rs}, {rt}
+    (0xfc0007ff, 0x70000001), // {rd} = maddu {rs}, {rt}
+    (0xfc1f07ff, 0x70000004), // {rd} = plzcw {rs}
+    (0xfc0007ff, 0x70000019), // {rd} = multu1 {rs}, {rt}
+    (0xfc0007ff, 0x70000020), // {rd} = madd1 {rs}, {rt}
+    (0xfc0007ff, 0x70000021), // {rd} = maddu1 {rs}, {rt}
+    (0xffff07ff, 0x70000030), // {rd} = pmfhl.lw
+    (0xffff07ff, 0x70000070), // {rd} = pmfhl.uw
+    (0xffff07ff, 0x700000b0), // {rd} = pmfhl.slw
+    (0xffff07ff, 0x700000f0), // {rd} = pmfhl.lh
+    (0xffff07ff, 0x70000130), // {rd} = pmfhl.sh
+    (0xfc1fffff, 0x70000031), // pmthl.lw {rs}
+    (0xffe0003f, 0x70000034), // {rd} = psllh {rt}, {sa}
+    (0xffe0003f, 0x70000036), // {rd} = psrlh {rt}, {sa}
+    (0xffe0003f, 0x70000037), // {rd} = psrah {rt}, {sa}
+    (0xffe0003f, 0x7000003c), // {rd} = psllw {rt}, {sa}
+    (0xffe0003f, 0x7000003e), // {rd} = psrlw {rt}, {sa}
+    (0xffe0003f, 0x7000003f), // {rd} = psraw {rt}, {sa}
+    (0xfc0007ff, 0x70000008), // {rd} = paddw {rs}, {rt}
+    (0xfc0007ff, 0x70000048), // {rd} = psubw {rs}, {rt}
+    (0xfc0007ff, 0x70000088), // {rd} = pcgtw {rs}, {rt}
+    (0xfc0007ff, 0x700000c8), // {rd} = pmaxw {rs}, {rt}
+    (0xfc0007ff, 0x70000108), // {rd} = paddh {rs}, {rt}
+    (0xfc0007ff, 0x70000148), // {rd} = psubh {rs}, {rt}
+    (0xfc0007ff, 0x70000188), // {rd} = pcgth {rs}, {rt}
+    (0xfc0007ff, 0x700001c8), // {rd} = pmaxh {rs}, {rt}
+    (0xfc0007ff, 0x70000208), // {rd} = paddb {rs}, {rt}
+    (0xfc0007ff, 0x70000248), // {rd} = psubb {rs}, {rt}
+    (0xfc0007ff, 0x70000288), // {rd} = pcgtb {rs}, {rt}
+    (0xfc0007ff, 0x70000408), // {rd} = paddsw {rs}, {rt}
+    (0xfc0007ff, 0x70000448), // {rd} = psubsw {rs}, {rt}
+    (0xfc0007ff, 0x70000488), // {rd} = pextlw {rs}, {rt}
+    (0xfc0007ff, 0x700004c8), // {rd} = ppacw {rs}, {rt}
+    (0xfc0007ff, 0x70000508), // {rd} = paddsh {rs}, {rt}
+    (0xfc0007ff, 0x70000548), // {rd} = psubsh {rs}, {rt}
+    (0xfc0007ff, 0x70000588), // {rd} = pextlh {rs}, {rt}
+    (0xfc0007ff, 0x700005c8), // {rd} = ppach {rs}, {rt}
+    (0xfc0007ff, 0x70000608), // {rd} = paddsb {rs}, {rt}
+    (0xfc0007ff, 0x70000648), // {rd} = psubsb {rs}, {rt}
+    (0xfc0007ff, 0x70000688), // {rd} = pextlb {rs}, {rt}
+    (0xfc0007ff, 0x700006c8), // {rd} = ppacb {rs}, {rt}
+    (0xffe007ff, 0x70000788), // {rd} = pext5 {rt}
+    (0xffe007ff, 0x700007c8), // {rd} = ppac5 {rt}
+    (0xffe007ff, 0x70000068), // {rd} = pabsw {rt}
+    (0xfc0007ff, 0x700000a8), // {rd} = pceqw {rs}, {rt}
+    (0xfc0007ff, 0x700000e8), // {rd} = pminw {rs}, {rt}
+    (0xfc0007ff, 0x70000128), // {rd} = padsbh {rs}, {rt}
+    (0xffe007ff, 0x70000168), // {rd} = pabsh {rt}
+    (0xfc0007ff, 0x700001a8), // {rd} = pceqh {rs}, {rt}
+    (0xfc0007ff, 0x700001e8), // {rd} = pminh {rs}, {rt}
+    (0xfc0007ff, 0x700002a8), // {rd} = pceqb {rs}, {rt}
+    (0xfc0007ff, 0x70000428), // {rd} = padduw {rs}, {rt}
+    (0xfc0007ff, 0x70000468), // {rd} = psubuw {rs}, {rt}
+    (0xfc0007ff, 0x700004a8), // {rd} = pextuw {rs}, {rt}
+    (0xfc0007ff, 0x70000528), // {rd} = padduh {rs}, {rt}
+    (0xfc0007ff, 0x70000568), // {rd} = psubuh {rs}, {rt}
+    (0xfc0007ff, 0x700005a8), // {rd} = pextuh {rs}, {rt}
+    (0xfc0007ff, 0x70000628), // {rd} = paddub {rs}, {rt}
+    (0xfc0007ff, 0x70000668), // {rd} = psubub {rs}, {rt}
+    (0xfc0007ff, 0x700006a8), // {rd} = pextub {rs}, {rt}
+    (0xfc0007ff, 0x700006e8), // {rd} = qfsrv {rs}, {rt}
+    (0xfc0007ff, 0x70000009), // {rd} = pmaddw {rs}, {rt}
+    (0xfc0007ff, 0x70000089), // {rd} = psllvw {rt}, {rs}
+    (0xfc0007ff, 0x700000c9), // {rd} = psrlvw {rt}, {rs}
+    (0xfc0007ff, 0x70000109), // {rd} = pmsubw {rs}, {rt}
+    (0xffff07ff, 0x70000209), // {rd} = pmfhi
+    (0xffff07ff, 0x70000249), // {rd} = pmflo
+    (0xfc0007ff, 0x70000289), // {rd} = pinth {rs}, {rt}
+    (0xfc0007ff, 0x70000309), // {rd} = pmultw {rs}, {rt}
+    (0xfc00ffff, 0x70000349), // pdivw {rs}, {rt}
+    (0xfc0007ff, 0x70000389), // {rd} = pcpyld {rs}, {rt}
+    (0xfc0007ff, 0x70000409), // {rd} = pmaddh {rs}, {rt}
+    (0xfc0007ff, 0x70000449), // {rd} = phmadh {rs}, {rt}
+    (0xfc0007ff, 0x70000509), // {rd} = pmsubh {rs}, {rt}
+    (0xfc0007ff, 0x70000549), // {rd} = phmsbh {rs}, {rt}
+    (0xffe007ff, 0x70000689), // {rd} = pexeh {rt}
+    (0xffe007ff, 0x700006c9), // {rd} = prevh {rt}
+    (0xfc0007ff, 0x70000709), // {rd} = pmulth {rs}, {rt}
+    (0xfc00ffff, 0x70000749), // pdivbw {rs}, {rt}
+    (0xffe007ff, 0x70000789), // {rd} = pexew {rt}
+    (0xffe007ff, 0x700007c9), // {rd} = prot3w {rt}
+    (0xfc0007ff, 0x70000029), // {rd} = pmadduw {rs}, {rt}
+    (0xfc0007ff, 0x700000e9), // {rd} = psravw {rt}, {rs}
+    (0xfc1fffff, 0x70000229), // pmthi {rs}
+    (0xfc1fffff, 0x70000269), // pmtlo {rs}
+    (0xfc0007ff, 0x700002a9), // {rd} = pinteh {rs}, {rt}
+    (0xfc0007ff, 0x70000329), // {rd} = pmultuw {rs}, {rt}
+    (0xfc00ffff, 0x70000369), // pdivuw {rs}, {rt}
+    (0xfc0007ff, 0x700003a9), // {rd} = pcpyud {rs}, {rt}
+    (0xffe007ff, 0x700006a9), // {rd} = pexch {rt}
+    (0xffe007ff, 0x700006e9), // {rd} = pcpyh {rt}
+    (0xffe007ff, 0x700007a9), // {rd} = pexcw {rt}
+    (0xfc000000, 0x68000000), // {rt} = ldl {imm16:#x}({rs})
+    (0xfc000000, 0x6c000000), // {rt} = ldr {imm16:#x}({rs})
+    (0xfc000000, 0x78000000), // {rt} = lq {imm16:#x}({rs})
+    (0xfc000000, 0x7c000000), // sq {rt}, {imm16:#x}({rs})
+    (0xfc000000, 0x80000000), // {rt} = lb {imm16:#x}({rs})
+    (0xfc000000, 0x84000000), // {rt} = lh {imm16:#x}({rs})
+    (0xfc000000, 0x8c000000), // {rt} = lw {imm16:#x}({rs})
+    (0xfc000000, 0x90000000), // {rt} = lbu {imm16:#x}({rs})
+    (0xfc000000, 0x94000000), // {rt} = lhu {imm16:#x}({rs})
+    (0xfc000000, 0x88000000), // {rt} = lwl {imm16:#x}({rs})
+    (0xfc000000, 0x98000000), // {rt} = lwr {imm16:#x}({rs})
+    (0xfc000000, 0xa0000000), // sb {rt}, {imm16:#x}({rs})
+    (0xfc000000, 0xa4000000), // sh {rt}, {imm16:#x}({rs})
+    (0xfc000000, 0xa8000000), // swl {rt}, {imm16:#x}({rs})
+    (0xfc000000, 0xac000000), // sw {rt}, {imm16:#x}({rs})
+    (0xfc000000, 0xb0000000), // sdl {rt}, {imm16:#x}({rs})
+    (0xfc000000, 0xb4000000), // sdr {rt}, {imm16:#x}({rs})
+    (0xfc000000, 0xb8000000), // swr {rt}, {imm16:#x}({rs})
+    (0xfc000000, 0xbc000000), // cache {cache_op}, {imm16:#x}({rs})
+    (0xfc000000, 0xc4000000), // {ft} = lwc1 {imm16:#x}({rs})
+    (0xfc000000, 0xdc000000), // {rt} = ld {imm16:#x}({rs})
+    (0xfc000000, 0xe4000000), // swc1 {ft}, {imm16:#x}({rs})
+    (0xfc000000, 0xfc000000), // sd {rt}, {imm16:#x}({rs})
+];
@@ -1,3 +1,5 @@
+use log::{log_enabled, trace, warn, Level};
+
 use crate::{
     bits::{Bits, SignExtend},
     emotion_engine::{
@@ -7,7 +9,15 @@ use crate::{
 };
 
 use super::{
-    control, instruction::CacheOperation, instruction_gen::Instruction, mmu::TlbEntry, Core, State,
+    control,
+    exception::Exception,
+    instruction::CacheOperation,
+    instruction_gen::Instruction,
+    mmi::{
+        bytes, doublewords, from_bytes, from_doublewords, from_halfwords, from_words, halfwords,
+        map_bytes, map_halfwords, map_words, mask, saturate_to_i16, saturate_to_i32, words,
+    },
+    Core, Mode, State,
 };
 
 impl State {
@@ -21,16 +31,30 @@ impl State {
 
 impl Core {
     pub fn interpret_instruction(&mut self, instruction: Instruction, bus: &mut Bus) {
-        for reg in instruction.uses() {
-            match reg {
-                Occurrence::Core(reg) => {
-                    let value = self.get_register::<u64>(reg);
-                    println!("{}={:#x}", reg, value);
-                }
-                Occurrence::Control(_) => {}
-                Occurrence::Fpu(reg) => {
-                    let value = self.state.fpu.get_register::<u32>(reg);
-                    println!("{}={:#x}", reg, value);
+        let in_delay_slot = self.state.delayed_branch_target.is_some();
+        match self.execute_instruction(instruction, bus) {
+            Ok(next_program_counter) => self.state.program_counter = next_program_counter,
+            Err(exception) => self.raise_exception(bus, exception, in_delay_slot),
+        }
+    }
+
+    pub(super) fn execute_instruction(
+        &mut self,
+        instruction: Instruction,
+        bus: &mut Bus,
+    ) -> Result<u32, Exception> {
+        if log_enabled!(target: "ee.core", Level::Trace) {
+            for reg in instruction.uses() {
+                match reg {
+                    Occurrence::Core(reg) => {
+                        let value = self.get_register::<u64>(reg);
+                        trace!(target: "ee.core", "{}={:#x}", reg, value);
+                    }
+                    Occurrence::Control(_) => {}
+                    Occurrence::Fpu(reg) => {
+                        let value = self.state.fpu.get_register::<u32>(reg);
+                        trace!(target: "ee.core", "{}={:#x}", reg, value);
+                    }
                 }
             }
         }
@@ -39,13 +63,14 @@ impl Core {
             .delayed_branch_target
             .take()
             .unwrap_or(self.state.program_counter + 4);
-        // println!("pc={:#010}: {instruction}", self.program_counter);
         match instruction {
             Instruction::Unknown => {
-                println!(
+                warn!(
+                    target: "ee.core",
                     "Unknown instruction at {:#010x}",
                     self.state.program_counter
-                )
+                );
+                return Err(Exception::ReservedInstruction);
             }
             Instruction::Sll(rd, rt, shamt) => {
                 let value = self.get_register::<u32>(rt) << shamt;
@@ -93,59 +118,8 @@ impl Core {
                     self.set_register(rd, value);
                 }
             }
-            Instruction::Syscall => {
-                println!(
-                    "v1 register state: {:x}",
-                    self.get_register::<u64>(Register::V1)
-                );
-                println!(
-                    "a1 register state: {:x}",
-                    self.get_register::<u64>(Register::A1)
-                );
-                println!("Syscall");
-                let syscall_number = self.get_register::<u32>(Register::V1);
-                match syscall_number {
-                    // SetGsCrt
-                    0x02 => {
-                        // TODO
-                    }
-                    // RFU060/initialize main thread
-                    0x3c => {
-                        let base = self.get_register::<u32>(Register::A1);
-                        let size = self.get_register::<u32>(Register::A2);
-                        println!("Argument base={:#010x}, size={:#010x}", base, size);
-                        let base = if base == 0xFFFF_FFFF {
-                            0x0200_0000 - size
-                        } else {
-                            base
-                        };
-                        println!("Stack base={:#010x}, size={:#010x}", base, size);
-                        let stack_pointer = base + size;
-                        self.main_thread_stack_pointer = stack_pointer;
-                        self.set_register::<u64>(Register::V0, stack_pointer.sign_extend());
-                    }
-                    // RFU061/initialize heap
-                    0x3d => {
-                        let base = self.get_register::<u32>(Register::A0);
-                        let size = self.get_register::<u32>(Register::A1);
-                        println!("Argument base={:#010x}, size={:#010x}", base, size);
-                        let base = if base == 0xFFFF_FFFF {
-                            self.main_thread_stack_pointer
-                        } else {
-                            base
-                        };
-                        let end = base + size;
-                        println!("Heap base={:#010x}, size={:#010x}", base, size);
-                        self.set_register::<u64>(Register::V0, end.sign_extend());
-                    }
-                    // Flush cache
-                    0x64 => {}
-                    // GsPutIMR
-                    0x71 => {}
-                    _ => todo!("Syscall number: {syscall_number}"),
-                }
-            }
-            Instruction::Break => todo!(),
+            Instruction::Syscall => return Err(Exception::Syscall),
+            Instruction::Break => return Err(Exception::Break),
             Instruction::Sync => {
                 // TODO: maybe do something here
             }
@@ -211,11 +185,10 @@ impl Core {
                 self.set_register::<u64>(Register::Hi, remainder.sign_extend());
             }
             Instruction::Add(rd, rs, rt) => {
-                // TODO: Exception on overflow
-                let value = self
-                    .get_register::<u32>(rs)
-                    .wrapping_add(self.get_register::<u32>(rt));
-                self.set_register::<u64>(rd, value.sign_extend());
+                let value = (self.get_register::<u32>(rs) as i32)
+                    .checked_add(self.get_register::<u32>(rt) as i32)
+                    .ok_or(Exception::Overflow)?;
+                self.set_register::<u64>(rd, (value as u32).sign_extend());
             }
             Instruction::Addu(rd, rs, rt) => {
                 let value = self
@@ -224,11 +197,10 @@ impl Core {
                 self.set_register::<u64>(rd, value.sign_extend());
             }
             Instruction::Sub(rd, rs, rt) => {
-                // TODO: Exception on overflow
-                let value = self
-                    .get_register::<u32>(rs)
-                    .wrapping_sub(self.get_register::<u32>(rt));
-                self.set_register::<u64>(rd, value.sign_extend());
+                let value = (self.get_register::<u32>(rs) as i32)
+                    .checked_sub(self.get_register::<u32>(rt) as i32)
+                    .ok_or(Exception::Overflow)?;
+                self.set_register::<u64>(rd, (value as u32).sign_extend());
             }
             Instruction::Subu(rd, rs, rt) => {
                 self.set_register::<u64>(
@@ -250,10 +222,20 @@ impl Core {
                     self.get_register::<u64>(rs) | self.get_register::<u64>(rt),
                 );
             }
-            Instruction::Xor(_, _, _) => todo!(),
-            Instruction::Nor(_, _, _) => todo!(),
-            Instruction::Mfsa(_) => todo!(),
-            Instruction::Mtsa(_) => todo!(),
+            Instruction::Xor(rd, rs, rt) => {
+                self.set_register(
+                    rd,
+                    self.get_register::<u64>(rs) ^ self.get_register::<u64>(rt),
+                );
+            }
+            Instruction::Nor(rd, rs, rt) => {
+                self.set_register(
+                    rd,
+                    !(self.get_register::<u64>(rs) | self.get_register::<u64>(rt)),
+                );
+            }
+            Instruction::Mfsa(rd) => self.set_register(rd, self.state.shift_amount as u64),
+            Instruction::Mtsa(rs) => self.state.shift_amount = self.get_register(rs),
             Instruction::Slt(rd, rs, rt) => {
                 let value = if (self.get_register::<u64>(rs) as i64)
                     < (self.get_register::<u64>(rt) as i64)
@@ -272,28 +254,69 @@ impl Core {
                 };
                 self.set_register::<u64>(rd, value);
             }
-            Instruction::Dadd(_, _, _) => todo!(),
+            Instruction::Dadd(rd, rs, rt) => {
+                let value = (self.get_register::<u64>(rs) as i64)
+                    .checked_add(self.get_register::<u64>(rt) as i64)
+                    .ok_or(Exception::Overflow)?;
+                self.set_register(rd, value as u64);
+            }
             Instruction::Daddu(rd, rs, rt) => {
                 let value = self
                     .get_register::<u64>(rs)
                     .wrapping_add(self.get_register::<u64>(rt));
                 self.set_register(rd, value);
             }
-            Instruction::Dsub(_, _, _) => todo!(),
-            Instruction::Dsubu(_, _, _) => todo!(),
-            Instruction::Tge(_, _) => todo!(),
-            Instruction::Tgeu(_, _) => todo!(),
-            Instruction::Tlt(_, _) => todo!(),
-            Instruction::Tltu(_, _) => todo!(),
-            Instruction::Teq(_, _) => todo!(),
-            Instruction::Tne(_, _) => todo!(),
+            Instruction::Dsub(rd, rs, rt) => {
+                let value = (self.get_register::<u64>(rs) as i64)
+                    .checked_sub(self.get_register::<u64>(rt) as i64)
+                    .ok_or(Exception::Overflow)?;
+                self.set_register(rd, value as u64);
+            }
+            Instruction::Dsubu(rd, rs, rt) => {
+                let value = self
+                    .get_register::<u64>(rs)
+                    .wrapping_sub(self.get_register::<u64>(rt));
+                self.set_register(rd, value);
+            }
+            Instruction::Tge(rs, rt) => {
+                if (self.get_register::<u64>(rs) as i64) >= (self.get_register::<u64>(rt) as i64) {
+                    return Err(Exception::Trap);
+                }
+            }
+            Instruction::Tgeu(rs, rt) => {
+                if self.get_register::<u64>(rs) >= self.get_register::<u64>(rt) {
+                    return Err(Exception::Trap);
+                }
+            }
+            Instruction::Tlt(rs, rt) => {
+                if (self.get_register::<u64>(rs) as i64) < (self.get_register::<u64>(rt) as i64) {
+                    return Err(Exception::Trap);
+                }
+            }
+            Instruction::Tltu(rs, rt) => {
+                if self.get_register::<u64>(rs) < self.get_register::<u64>(rt) {
+                    return Err(Exception::Trap);
+                }
+            }
+            Instruction::Teq(rs, rt) => {
+                if self.get_register::<u64>(rs) == self.get_register::<u64>(rt) {
+                    return Err(Exception::Trap);
+                }
+            }
+            Instruction::Tne(rs, rt) => {
+                if self.get_register::<u64>(rs) != self.get_register::<u64>(rt) {
+                    return Err(Exception::Trap);
+                }
+            }
             Instruction::Dsll(rd, rt, shamt) => {
                 self.set_register(rd, self.get_register::<u64>(rt) << shamt);
             }
             Instruction::Dsrl(rd, rt, shamt) => {
                 self.set_register(rd, self.get_register::<u64>(rt) >> shamt);
             }
-            Instruction::Dsra(_, _, _) => todo!(),
+            Instruction::Dsra(rd, rt, shamt) => {
+                self.set_register(rd, ((self.get_register::<u64>(rt) as i64) >> shamt) as u64);
+            }
             Instruction::Dsll32(rd, rt, shamt) => {
                 self.set_register(rd, self.get_register::<u64>(rt) << (shamt + 32));
             }
@@ -320,6 +343,64 @@ impl Core {
                         .set_delayed_branch_target(next_program_counter.wrapping_add(offset << 2));
                 }
             }
+            Instruction::Bltzl(rs, offset) => {
+                if (self.get_register::<u64>(rs) as i64) < 0 {
+                    let offset: u32 = offset.sign_extend();
+                    self.state
+                        .set_delayed_branch_target(next_program_counter.wrapping_add(offset << 2));
+                } else {
+                    next_program_counter += 4;
+                }
+            }
+            Instruction::Bgezl(rs, offset) => {
+                if self.get_register::<u64>(rs) as i64 >= 0 {
+                    let offset: u32 = offset.sign_extend();
+                    self.state
+                        .set_delayed_branch_target(next_program_counter.wrapping_add(offset << 2));
+                } else {
+                    next_program_counter += 4;
+                }
+            }
+            Instruction::Bltzal(rs, offset) => {
+                let taken = (self.get_register::<u64>(rs) as i64) < 0;
+                self.set_register(Register::Ra, (next_program_counter + 4) as u64);
+                if taken {
+                    let offset: u32 = offset.sign_extend();
+                    self.state
+                        .set_delayed_branch_target(next_program_counter.wrapping_add(offset << 2));
+                }
+            }
+            Instruction::Bgezal(rs, offset) => {
+                let taken = self.get_register::<u64>(rs) as i64 >= 0;
+                self.set_register(Register::Ra, (next_program_counter + 4) as u64);
+                if taken {
+                    let offset: u32 = offset.sign_extend();
+                    self.state
+                        .set_delayed_branch_target(next_program_counter.wrapping_add(offset << 2));
+                }
+            }
+            Instruction::Bltzall(rs, offset) => {
+                let taken = (self.get_register::<u64>(rs) as i64) < 0;
+                self.set_register(Register::Ra, (next_program_counter + 4) as u64);
+                if taken {
+                    let offset: u32 = offset.sign_extend();
+                    self.state
+                        .set_delayed_branch_target(next_program_counter.wrapping_add(offset << 2));
+                } else {
+                    next_program_counter += 4;
+                }
+            }
+            Instruction::Bgezall(rs, offset) => {
+                let taken = self.get_register::<u64>(rs) as i64 >= 0;
+                self.set_register(Register::Ra, (next_program_counter + 4) as u64);
+                if taken {
+                    let offset: u32 = offset.sign_extend();
+                    self.state
+                        .set_delayed_branch_target(next_program_counter.wrapping_add(offset << 2));
+                } else {
+                    next_program_counter += 4;
+                }
+            }
             Instruction::J(target) => self.state.set_delayed_branch_target(
                 (next_program_counter & 0xF000_0000).wrapping_add(target << 2),
             ),
@@ -358,9 +439,10 @@ impl Core {
                 }
             }
             Instruction::Addi(rt, rs, imm) => {
-                // TODO exception on overflow
-                let temp = self.get_register::<u64>(rs).wrapping_add(imm.sign_extend());
-                self.set_register::<u64>(rt, (temp as u32).sign_extend());
+                let value = (self.get_register::<u32>(rs) as i32)
+                    .checked_add(imm as i16 as i32)
+                    .ok_or(Exception::Overflow)?;
+                self.set_register::<u64>(rt, (value as u32).sign_extend());
             }
             Instruction::Addiu(rt, rs, imm) => {
                 let temp = self.get_register::<u64>(rs).wrapping_add(imm.sign_extend());
@@ -403,6 +485,11 @@ impl Core {
             Instruction::Mtc0(rd, rt) => {
                 let value = self.get_register(rt);
                 self.state.control.set_register(rd, value);
+                match rd {
+                    control::Register::Status => self.update_mode(),
+                    control::Register::EntryHi => self.set_address_space_id(value as u8),
+                    _ => {}
+                }
             }
             Instruction::Mfc1(rt, fs) => {
                 let value = self.state.fpu.get_register::<u32>(fs);
@@ -412,52 +499,200 @@ impl Core {
                 let value = self.get_register::<u32>(rt);
                 self.state.fpu.set_register(fs, value);
             }
-            Instruction::Muls(fd, fs, ft) => self.state.fpu.set_register(
-                fd,
-                self.state.fpu.get_register::<f32>(fs) * self.state.fpu.get_register::<f32>(ft),
-            ),
-            // TODO flags
-            Instruction::Divs(fd, fs, ft) => self.state.fpu.set_register(
-                fd,
-                self.state.fpu.get_register::<f32>(fs) / self.state.fpu.get_register::<f32>(ft),
-            ),
-            // TODO flags
+            Instruction::Cfc1(rt, fs) => {
+                let value = self.state.fpu.get_control(fs);
+                self.set_register::<u64>(rt, value.sign_extend());
+            }
+            Instruction::Ctc1(rt, fs) => {
+                let value = self.get_register::<u32>(rt);
+                self.state.fpu.set_control(fs, value);
+            }
+            Instruction::Bc1f(offset) => {
+                if !self.state.fpu.condition() {
+                    let offset: u32 = offset.sign_extend();
+                    self.state
+                        .set_delayed_branch_target(next_program_counter.wrapping_add(offset << 2));
+                }
+            }
+            Instruction::Bc1t(offset) => {
+                if self.state.fpu.condition() {
+                    let offset: u32 = offset.sign_extend();
+                    self.state
+                        .set_delayed_branch_target(next_program_counter.wrapping_add(offset << 2));
+                }
+            }
+            Instruction::Bc1fl(offset) => {
+                if !self.state.fpu.condition() {
+                    let offset: u32 = offset.sign_extend();
+                    self.state
+                        .set_delayed_branch_target(next_program_counter.wrapping_add(offset << 2));
+                } else {
+                    next_program_counter += 4;
+                }
+            }
+            Instruction::Bc1tl(offset) => {
+                if self.state.fpu.condition() {
+                    let offset: u32 = offset.sign_extend();
+                    self.state
+                        .set_delayed_branch_target(next_program_counter.wrapping_add(offset << 2));
+                } else {
+                    next_program_counter += 4;
+                }
+            }
+            Instruction::Adds(fd, fs, ft) => {
+                let fpu = &mut self.state.fpu;
+                let value = fpu.add(fpu.get_register(fs), fpu.get_register(ft));
+                fpu.set_register(fd, value);
+            }
+            Instruction::Subs(fd, fs, ft) => {
+                let fpu = &mut self.state.fpu;
+                let value = fpu.sub(fpu.get_register(fs), fpu.get_register(ft));
+                fpu.set_register(fd, value);
+            }
+            Instruction::Muls(fd, fs, ft) => {
+                let fpu = &mut self.state.fpu;
+                let value = fpu.mul(fpu.get_register(fs), fpu.get_register(ft));
+                fpu.set_register(fd, value);
+            }
+            Instruction::Divs(fd, fs, ft) => {
+                let fpu = &mut self.state.fpu;
+                let value = fpu.div(fpu.get_register(fs), fpu.get_register(ft));
+                fpu.set_register(fd, value);
+            }
+            Instruction::Sqrts(fd, ft) => {
+                let fpu = &mut self.state.fpu;
+                let value = fpu.sqrt(fpu.get_register(ft));
+                fpu.set_register(fd, value);
+            }
+            Instruction::Abss(fd, fs) => {
+                let fpu = &mut self.state.fpu;
+                let value = fpu.abs(fpu.get_register(fs));
+                fpu.set_register(fd, value);
+            }
             Instruction::Movs(fd, fs) => {
                 let value = self.state.fpu.get_register::<f32>(fs);
                 self.state.fpu.set_register(fd, value);
             }
+            Instruction::Negs(fd, fs) => {
+                let fpu = &mut self.state.fpu;
+                let value = fpu.neg(fpu.get_register(fs));
+                fpu.set_register(fd, value);
+            }
+            Instruction::Rsqrts(fd, fs, ft) => {
+                let fpu = &mut self.state.fpu;
+                let value = fpu.rsqrt(fpu.get_register(fs), fpu.get_register(ft));
+                fpu.set_register(fd, value);
+            }
+            Instruction::Addas(fs, ft) => {
+                let fpu = &mut self.state.fpu;
+                let value = fpu.add(fpu.get_register(fs), fpu.get_register(ft));
+                fpu.set_accumulator(value);
+            }
+            Instruction::Subas(fs, ft) => {
+                let fpu = &mut self.state.fpu;
+                let value = fpu.sub(fpu.get_register(fs), fpu.get_register(ft));
+                fpu.set_accumulator(value);
+            }
+            Instruction::Mulas(fs, ft) => {
+                let fpu = &mut self.state.fpu;
+                let value = fpu.mul(fpu.get_register(fs), fpu.get_register(ft));
+                fpu.set_accumulator(value);
+            }
+            Instruction::Madds(fd, fs, ft) => {
+                let fpu = &mut self.state.fpu;
+                let value = fpu.multiply_add(fpu.get_register(fs), fpu.get_register(ft));
+                fpu.set_register(fd, value);
+            }
+            Instruction::Msubs(fd, fs, ft) => {
+                let fpu = &mut self.state.fpu;
+                let value = fpu.multiply_subtract(fpu.get_register(fs), fpu.get_register(ft));
+                fpu.set_register(fd, value);
+            }
+            Instruction::Maddas(fs, ft) => {
+                let fpu = &mut self.state.fpu;
+                let value = fpu.multiply_add(fpu.get_register(fs), fpu.get_register(ft));
+                fpu.set_accumulator(value);
+            }
+            Instruction::Msubas(fs, ft) => {
+                let fpu = &mut self.state.fpu;
+                let value = fpu.multiply_subtract(fpu.get_register(fs), fpu.get_register(ft));
+                fpu.set_accumulator(value);
+            }
             Instruction::Cvtws(fd, fs) => {
-                let value = self.state.fpu.get_register::<f32>(fs) as i32;
-                self.state.fpu.set_register(fd, value as u32);
+                let fpu = &mut self.state.fpu;
+                let value = fpu.to_word(fpu.get_register(fs));
+                fpu.set_register(fd, value);
+            }
+            Instruction::Maxs(fd, fs, ft) => {
+                let fpu = &mut self.state.fpu;
+                let value = fpu.max(fpu.get_register(fs), fpu.get_register(ft));
+                fpu.set_register(fd, value);
+            }
+            Instruction::Mins(fd, fs, ft) => {
+                let fpu = &mut self.state.fpu;
+                let value = fpu.min(fpu.get_register(fs), fpu.get_register(ft));
+                fpu.set_register(fd, value);
+            }
+            Instruction::Cfs(_, _) => self.state.fpu.compare(false),
+            Instruction::Ceqs(fs, ft) => {
+                let fpu = &mut self.state.fpu;
+                fpu.compare_equal(fpu.get_register(fs), fpu.get_register(ft));
+            }
+            Instruction::Clts(fs, ft) => {
+                let fpu = &mut self.state.fpu;
+                fpu.compare_less_than(fpu.get_register(fs), fpu.get_register(ft));
+            }
+            Instruction::Cles(fs, ft) => {
+                let fpu = &mut self.state.fpu;
+                fpu.compare_less_equal(fpu.get_register(fs), fpu.get_register(ft));
             }
             Instruction::Cvtsw(fd, fs) => {
-                let value = self.state.fpu.get_register::<u32>(fs) as i32;
-                self.state.fpu.set_register(fd, value as f32);
+                let fpu = &mut self.state.fpu;
+                let value = fpu.to_single(fpu.get_register(fs));
+                fpu.set_register(fd, value);
+            }
+            Instruction::Tlbr => {
+                let index = self.state.control.get_register(control::Register::Index);
+                let [page_mask, entry_hi, entry_lo0, entry_lo1] =
+                    self.mmu.read_index(index.bits(0..=5) as u8).to_registers();
+                let control = &mut self.state.control;
+                control.set_register(control::Register::PageMask, page_mask);
+                control.set_register(control::Register::EntryHi, entry_hi);
+                control.set_register(control::Register::EntryLo0, entry_lo0);
+                control.set_register(control::Register::EntryLo1, entry_lo1);
+                self.set_address_space_id(entry_hi as u8);
             }
-            Instruction::Tlbr => todo!(),
             Instruction::Tlbwi => {
-                let mut entry = 0;
                 let index = self.state.control.get_register(control::Register::Index);
-                let page_mask = self.state.control.get_register(control::Register::PageMask);
+                self.write_tlb_entry(index.bits(0..=5) as u8);
+            }
+            Instruction::Tlbwr => {
+                let index = self.state.control.get_register(control::Register::Random);
+                self.write_tlb_entry(index as u8);
+            }
+            Instruction::Tlbp => {
                 let entry_hi = self.state.control.get_register(control::Register::EntryHi);
-                let entry_lo0 = self.state.control.get_register(control::Register::EntryLo0);
-                let entry_lo1 = self.state.control.get_register(control::Register::EntryLo1);
-                entry.set_bits(TlbEntry::MASK, page_mask.bits(13..=24));
-                entry.set_bits(
-                    TlbEntry::VIRTUAL_PAGE_NUMBER_DIV_2,
-                    entry_hi.bits(13..=31) & !page_mask.bits(13..=24),
-                );
-                entry.set_bit(TlbEntry::GLOBAL, entry_lo0.bit(0) && entry_lo1.bit(0));
-                entry.set_bits(TlbEntry::ADDRESS_SPACE_ID, entry_hi.bits(0..=11));
-                entry.set_bits(33..=63, entry_lo0.bits(1..=31));
-                entry.set_bits(1..=31, entry_lo1.bits(1..=31));
-                self.mmu
-                    .write_index(index.bits(0..=5) as u8, TlbEntry::new(entry));
-            }
-            Instruction::Tlbwr => todo!(),
-            Instruction::Tlbp => todo!(),
+                // Index.P is set when there is no match.
+                let index = match self.mmu.probe(entry_hi, entry_hi as u8) {
+                    Some(index) => index as u32,
+                    None => 1 << 31,
+                };
+                self.state
+                    .control
+                    .set_register(control::Register::Index, index);
+            }
+            Instruction::Eret => {
+                next_program_counter = self.return_from_exception();
+            }
             Instruction::Ei => {
-                // TODO: Set status register
+                if self.mode == Mode::Kernel || self.state.control.enable_ei_di() {
+                    self.state.control.set_enable_interrupt_enable(true);
+                }
+            }
+            Instruction::Di => {
+                if self.mode == Mode::Kernel || self.state.control.enable_ei_di() {
+                    self.state.control.set_enable_interrupt_enable(false);
+                }
             }
             Instruction::Beql(rs, rt, offset) => {
                 if self.get_register::<u64>(rs) == self.get_register::<u64>(rt) {
@@ -477,6 +712,30 @@ impl Core {
                     next_program_counter += 4;
                 }
             }
+            Instruction::Blezl(rs, offset) => {
+                if (self.get_register::<u64>(rs) as i64) <= 0 {
+                    let offset: u32 = offset.sign_extend();
+                    self.state
+                        .set_delayed_branch_target(next_program_counter.wrapping_add(offset << 2));
+                } else {
+                    next_program_counter += 4;
+                }
+            }
+            Instruction::Bgtzl(rs, offset) => {
+                if (self.get_register::<u64>(rs) as i64) > 0 {
+                    let offset: u32 = offset.sign_extend();
+                    self.state
+                        .set_delayed_branch_target(next_program_counter.wrapping_add(offset << 2));
+                } else {
+                    next_program_counter += 4;
+                }
+            }
+            Instruction::Daddi(rt, rs, imm) => {
+                let value = (self.get_register::<u64>(rs) as i64)
+                    .checked_add(imm as i16 as i64)
+                    .ok_or(Exception::Overflow)?;
+                self.set_register(rt, value as u64);
+            }
             Instruction::Daddiu(rt, rs, imm) => {
                 let value = self.get_register::<u64>(rs).wrapping_add(imm.sign_extend());
                 self.set_register::<u64>(rt, value);
@@ -533,12 +792,681 @@ impl Core {
                 let value = !(self.get_register::<u128>(rs) | self.get_register::<u128>(rt));
                 self.set_register(rd, value);
             }
+            Instruction::Madd(rd, rs, rt) => {
+                let a = self.get_register::<u32>(rs) as i32 as i64;
+                let b = self.get_register::<u32>(rt) as i32 as i64;
+                let accumulator = (self.get_register::<u32>(Register::Hi) as u64) << 32
+                    | self.get_register::<u32>(Register::Lo) as u64;
+                let value = (accumulator as i64).wrapping_add(a * b) as u64;
+                let lo: u64 = (value as u32).sign_extend();
+                let hi: u64 = ((value >> 32) as u32).sign_extend();
+                self.set_register(rd, lo);
+                self.set_register(Register::Lo, lo);
+                self.set_register(Register::Hi, hi);
+            }
+            Instruction::Maddu(rd, rs, rt) => {
+                let a = self.get_register::<u32>(rs) as u64;
+                let b = self.get_register::<u32>(rt) as u64;
+                let accumulator = (self.get_register::<u32>(Register::Hi) as u64) << 32
+                    | self.get_register::<u32>(Register::Lo) as u64;
+                let value = accumulator.wrapping_add(a * b);
+                let lo: u64 = (value as u32).sign_extend();
+                let hi: u64 = ((value >> 32) as u32).sign_extend();
+                self.set_register(rd, lo);
+                self.set_register(Register::Lo, lo);
+                self.set_register(Register::Hi, hi);
+            }
+            Instruction::Plzcw(rd, rs) => {
+                let value = self.get_register::<u64>(rs);
+                let [low, high] = [value as u32, (value >> 32) as u32].map(|word| {
+                    let leading = if (word as i32) < 0 {
+                        word.leading_ones()
+                    } else {
+                        word.leading_zeros()
+                    };
+                    leading as u64 - 1
+                });
+                self.set_register(rd, low | high << 32);
+            }
+            Instruction::Multu1(rd, rs, rt) => {
+                let a = self.get_register::<u32>(rs) as u64;
+                let b = self.get_register::<u32>(rt) as u64;
+                let prod = a.wrapping_mul(b);
+                let lo: u64 = (prod as u32).sign_extend();
+                let hi: u64 = ((prod >> 32) as u32).sign_extend();
+                self.set_register(rd, lo);
+                self.set_upper(Register::Lo, lo);
+                self.set_upper(Register::Hi, hi);
+            }
+            Instruction::Madd1(rd, rs, rt) => {
+                let a = self.get_register::<u32>(rs) as i32 as i64;
+                let b = self.get_register::<u32>(rt) as i32 as i64;
+                let accumulator = self.get_upper::<u64>(Register::Hi) << 32
+                    | self.get_upper::<u64>(Register::Lo) as u32 as u64;
+                let value = (accumulator as i64).wrapping_add(a * b) as u64;
+                let lo: u64 = (value as u32).sign_extend();
+                let hi: u64 = ((value >> 32) as u32).sign_extend();
+                self.set_register(rd, lo);
+                self.set_upper(Register::Lo, lo);
+                self.set_upper(Register::Hi, hi);
+            }
+            Instruction::Maddu1(rd, rs, rt) => {
+                let a = self.get_register::<u32>(rs) as u64;
+                let b = self.get_register::<u32>(rt) as u64;
+                let accumulator = self.get_upper::<u64>(Register::Hi) << 32
+                    | self.get_upper::<u64>(Register::Lo) as u32 as u64;
+                let value = accumulator.wrapping_add(a * b);
+                let lo: u64 = (value as u32).sign_extend();
+                let hi: u64 = ((value >> 32) as u32).sign_extend();
+                self.set_register(rd, lo);
+                self.set_upper(Register::Lo, lo);
+                self.set_upper(Register::Hi, hi);
+            }
+            Instruction::Pmfhllw(rd) => {
+                let lo = words(self.get_register(Register::Lo));
+                let hi = words(self.get_register(Register::Hi));
+                self.set_register(rd, from_words([lo[0], hi[0], lo[2], hi[2]]));
+            }
+            Instruction::Pmfhluw(rd) => {
+                let lo = words(self.get_register(Register::Lo));
+                let hi = words(self.get_register(Register::Hi));
+                self.set_register(rd, from_words([lo[1], hi[1], lo[3], hi[3]]));
+            }
+            Instruction::Pmfhlslw(rd) => {
+                let value = self.word_accumulators().map(|accumulator| -> u64 {
+                    saturate_to_i32(accumulator as i64).sign_extend()
+                });
+                self.set_register(rd, from_doublewords(value));
+            }
+            Instruction::Pmfhllh(rd) => {
+                let lo = halfwords(self.get_register(Register::Lo));
+                let hi = halfwords(self.get_register(Register::Hi));
+                let value = [lo[0], lo[2], hi[0], hi[2], lo[4], lo[6], hi[4], hi[6]];
+                self.set_register(rd, from_halfwords(value));
+            }
+            Instruction::Pmfhlsh(rd) => {
+                let lo = words(self.get_register(Register::Lo));
+                let hi = words(self.get_register(Register::Hi));
+                let value = [lo[0], lo[1], hi[0], hi[1], lo[2], lo[3], hi[2], hi[3]]
+                    .map(|word| saturate_to_i16(word as i32));
+                self.set_register(rd, from_halfwords(value));
+            }
+            Instruction::Pmthllw(rs) => {
+                let value = words(self.get_register(rs));
+                let mut lo = words(self.get_register(Register::Lo));
+                let mut hi = words(self.get_register(Register::Hi));
+                (lo[0], hi[0], lo[2], hi[2]) = (value[0], value[1], value[2], value[3]);
+                self.set_register(Register::Lo, from_words(lo));
+                self.set_register(Register::Hi, from_words(hi));
+            }
+            Instruction::Psllh(rd, rt, sa) => {
+                let value = halfwords(self.get_register(rt)).map(|h| h << (sa & 0xF));
+                self.set_register(rd, from_halfwords(value));
+            }
+            Instruction::Psrlh(rd, rt, sa) => {
+                let value = halfwords(self.get_register(rt)).map(|h| h >> (sa & 0xF));
+                self.set_register(rd, from_halfwords(value));
+            }
+            Instruction::Psrah(rd, rt, sa) => {
+                let value =
+                    halfwords(self.get_register(rt)).map(|h| (h as i16 >> (sa & 0xF)) as u16);
+                self.set_register(rd, from_halfwords(value));
+            }
+            Instruction::Psllw(rd, rt, sa) => {
+                let value = words(self.get_register(rt)).map(|w| w << sa);
+                self.set_register(rd, from_words(value));
+            }
+            Instruction::Psrlw(rd, rt, sa) => {
+                let value = words(self.get_register(rt)).map(|w| w >> sa);
+                self.set_register(rd, from_words(value));
+            }
+            Instruction::Psraw(rd, rt, sa) => {
+                let value = words(self.get_register(rt)).map(|w| (w as i32 >> sa) as u32);
+                self.set_register(rd, from_words(value));
+            }
+            Instruction::Paddw(rd, rs, rt) => {
+                let value = map_words(
+                    self.get_register(rs),
+                    self.get_register(rt),
+                    u32::wrapping_add,
+                );
+                self.set_register(rd, value);
+            }
+            Instruction::Psubw(rd, rs, rt) => {
+                let value = map_words(
+                    self.get_register(rs),
+                    self.get_register(rt),
+                    u32::wrapping_sub,
+                );
+                self.set_register(rd, value);
+            }
+            Instruction::Pcgtw(rd, rs, rt) => {
+                let value = map_words(self.get_register(rs), self.get_register(rt), |a, b| {
+                    mask(a as i32 > b as i32)
+                });
+                self.set_register(rd, value);
+            }
+            Instruction::Pmaxw(rd, rs, rt) => {
+                let value = map_words(self.get_register(rs), self.get_register(rt), |a, b| {
+                    (a as i32).max(b as i32) as u32
+                });
+                self.set_register(rd, value);
+            }
+            Instruction::Paddh(rd, rs, rt) => {
+                let value = map_halfwords(
+                    self.get_register(rs),
+                    self.get_register(rt),
+                    u16::wrapping_add,
+                );
+                self.set_register(rd, value);
+            }
+            Instruction::Psubh(rd, rs, rt) => {
+                let value = map_halfwords(
+                    self.get_register(rs),
+                    self.get_register(rt),
+                    u16::wrapping_sub,
+                );
+                self.set_register(rd, value);
+            }
+            Instruction::Pcgth(rd, rs, rt) => {
+                let value = map_halfwords(self.get_register(rs), self.get_register(rt), |a, b| {
+                    mask(a as i16 > b as i16)
+                });
+                self.set_register(rd, value);
+            }
+            Instruction::Pmaxh(rd, rs, rt) => {
+                let value = map_halfwords(self.get_register(rs), self.get_register(rt), |a, b| {
+                    (a as i16).max(b as i16) as u16
+                });
+                self.set_register(rd, value);
+            }
+            Instruction::Paddb(rd, rs, rt) => {
+                let value = map_bytes(
+                    self.get_register(rs),
+                    self.get_register(rt),
+                    u8::wrapping_add,
+                );
+                self.set_register(rd, value);
+            }
+            Instruction::Psubb(rd, rs, rt) => {
+                let value = map_bytes(
+                    self.get_register(rs),
+                    self.get_register(rt),
+                    u8::wrapping_sub,
+                );
+                self.set_register(rd, value);
+            }
+            Instruction::Pcgtb(rd, rs, rt) => {
+                let value = map_bytes(self.get_register(rs), self.get_register(rt), |a, b| {
+                    mask(a as i8 > b as i8)
+                });
+                self.set_register(rd, value);
+            }
+            Instruction::Paddsw(rd, rs, rt) => {
+                let value = map_words(self.get_register(rs), self.get_register(rt), |a, b| {
+                    (a as i32).saturating_add(b as i32) as u32
+                });
+                self.set_register(rd, value);
+            }
+            Instruction::Psubsw(rd, rs, rt) => {
+                let value = map_words(self.get_register(rs), self.get_register(rt), |a, b| {
+                    (a as i32).saturating_sub(b as i32) as u32
+                });
+                self.set_register(rd, value);
+            }
+            Instruction::Pextlw(rd, rs, rt) => {
+                let (a, b) = (words(self.get_register(rs)), words(self.get_register(rt)));
+                self.set_register(rd, from_words([b[0], a[0], b[1], a[1]]));
+            }
+            Instruction::Ppacw(rd, rs, rt) => {
+                let (a, b) = (words(self.get_register(rs)), words(self.get_register(rt)));
+                self.set_register(rd, from_words([b[0], b[2], a[0], a[2]]));
+            }
+            Instruction::Paddsh(rd, rs, rt) => {
+                let value = map_halfwords(self.get_register(rs), self.get_register(rt), |a, b| {
+                    (a as i16).saturating_add(b as i16) as u16
+                });
+                self.set_register(rd, value);
+            }
+            Instruction::Psubsh(rd, rs, rt) => {
+                let value = map_halfwords(self.get_register(rs), self.get_register(rt), |a, b| {
+                    (a as i16).saturating_sub(b as i16) as u16
+                });
+                self.set_register(rd, value);
+            }
+            Instruction::Pextlh(rd, rs, rt) => {
+                let (a, b) = (
+                    halfwords(self.get_register(rs)),
+                    halfwords(self.get_register(rt)),
+                );
+                let value = std::array::from_fn(|i| if i % 2 == 0 { b[i / 2] } else { a[i / 2] });
+                self.set_register(rd, from_halfwords(value));
+            }
+            Instruction::Ppach(rd, rs, rt) => {
+                let (a, b) = (
+                    halfwords(self.get_register(rs)),
+                    halfwords(self.get_register(rt)),
+                );
+                let value = std::array::from_fn(|i| if i < 4 { b[2 * i] } else { a[2 * i - 8] });
+                self.set_register(rd, from_halfwords(value));
+            }
+            Instruction::Paddsb(rd, rs, rt) => {
+                let value = map_bytes(self.get_register(rs), self.get_register(rt), |a, b| {
+                    (a as i8).saturating_add(b as i8) as u8
+                });
+                self.set_register(rd, value);
+            }
+            Instruction::Psubsb(rd, rs, rt) => {
+                let value = map_bytes(self.get_register(rs), self.get_register(rt), |a, b| {
+                    (a as i8).saturating_sub(b as i8) as u8
+                });
+                self.set_register(rd, value);
+            }
+            Instruction::Pextlb(rd, rs, rt) => {
+                let (a, b) = (bytes(self.get_register(rs)), bytes(self.get_register(rt)));
+                let value = std::array::from_fn(|i| if i % 2 == 0 { b[i / 2] } else { a[i / 2] });
+                self.set_register(rd, from_bytes(value));
+            }
+            Instruction::Ppacb(rd, rs, rt) => {
+                let (a, b) = (bytes(self.get_register(rs)), bytes(self.get_register(rt)));
+                let value = std::array::from_fn(|i| if i < 8 { b[2 * i] } else { a[2 * i - 16] });
+                self.set_register(rd, from_bytes(value));
+            }
+            Instruction::Pext5(rd, rt) => {
+                // 1:5:5:5 colours to 8:8:8:8.
+                let value = words(self.get_register(rt)).map(|w| {
+                    w.bits(0..5) << 3
+                        | w.bits(5..10) << 11
+                        | w.bits(10..15) << 19
+                        | w.bits(15..16) << 31
+                });
+                self.set_register(rd, from_words(value));
+            }
+            Instruction::Ppac5(rd, rt) => {
+                // 8:8:8:8 colours to 1:5:5:5.
+                let value = words(self.get_register(rt)).map(|w| {
+                    w.bits(3..8) | w.bits(11..16) << 5 | w.bits(19..24) << 10 | w.bits(31..32) << 15
+                });
+                self.set_register(rd, from_words(value));
+            }
+            Instruction::Pabsw(rd, rt) => {
+                let value = words(self.get_register(rt))
+                    .map(|w| (w as i32).checked_abs().unwrap_or(i32::MAX) as u32);
+                self.set_register(rd, from_words(value));
+            }
+            Instruction::Pceqw(rd, rs, rt) => {
+                let value = map_words(self.get_register(rs), self.get_register(rt), |a, b| {
+                    mask(a == b)
+                });
+                self.set_register(rd, value);
+            }
+            Instruction::Pminw(rd, rs, rt) => {
+                let value = map_words(self.get_register(rs), self.get_register(rt), |a, b| {
+                    (a as i32).min(b as i32) as u32
+                });
+                self.set_register(rd, value);
+            }
+            Instruction::Padsbh(rd, rs, rt) => {
+                let (a, b) = (
+                    halfwords(self.get_register(rs)),
+                    halfwords(self.get_register(rt)),
+                );
+                let value = std::array::from_fn(|i| {
+                    if i < 4 {
+                        a[i].wrapping_sub(b[i])
+                    } else {
+                        a[i].wrapping_add(b[i])
+                    }
+                });
+                self.set_register(rd, from_halfwords(value));
+            }
+            Instruction::Pabsh(rd, rt) => {
+                let value = halfwords(self.get_register(rt))
+                    .map(|h| (h as i16).checked_abs().unwrap_or(i16::MAX) as u16);
+                self.set_register(rd, from_halfwords(value));
+            }
+            Instruction::Pceqh(rd, rs, rt) => {
+                let value = map_halfwords(self.get_register(rs), self.get_register(rt), |a, b| {
+                    mask(a == b)
+                });
+                self.set_register(rd, value);
+            }
+            Instruction::Pminh(rd, rs, rt) => {
+                let value = map_halfwords(self.get_register(rs), self.get_register(rt), |a, b| {
+                    (a as i16).min(b as i16) as u16
+                });
+                self.set_register(rd, value);
+            }
+            Instruction::Pceqb(rd, rs, rt) => {
+                let value = map_bytes(self.get_register(rs), self.get_register(rt), |a, b| {
+                    mask(a == b)
+                });
+                self.set_register(rd, value);
+            }
+            Instruction::Padduw(rd, rs, rt) => {
+                let value = map_words(
+                    self.get_register(rs),
+                    self.get_register(rt),
+                    u32::saturating_add,
+                );
+                self.set_register(rd, value);
+            }
+            Instruction::Psubuw(rd, rs, rt) => {
+                let value = map_words(
+                    self.get_register(rs),
+                    self.get_register(rt),
+                    u32::saturating_sub,
+                );
+                self.set_register(rd, value);
+            }
+            Instruction::Pextuw(rd, rs, rt) => {
+                let (a, b) = (words(self.get_register(rs)), words(self.get_register(rt)));
+                self.set_register(rd, from_words([b[2], a[2], b[3], a[3]]));
+            }
+            Instruction::Padduh(rd, rs, rt) => {
+                let value = map_halfwords(
+                    self.get_register(rs),
+                    self.get_register(rt),
+                    u16::saturating_add,
+                );
+                self.set_register(rd, value);
+            }
+            Instruction::Psubuh(rd, rs, rt) => {
+                let value = map_halfwords(
+                    self.get_register(rs),
+                    self.get_register(rt),
+                    u16::saturating_sub,
+                );
+                self.set_register(rd, value);
+            }
+            Instruction::Pextuh(rd, rs, rt) => {
+                let (a, b) = (
+                    halfwords(self.get_register(rs)),
+                    halfwords(self.get_register(rt)),
+                );
+                let value = std::array::from_fn(|i| {
+                    if i % 2 == 0 {
+                        b[4 + i / 2]
+                    } else {
+                        a[4 + i / 2]
+                    }
+                });
+                self.set_register(rd, from_halfwords(value));
+            }
+            Instruction::Paddub(rd, rs, rt) => {
+                let value = map_bytes(
+                    self.get_register(rs),
+                    self.get_register(rt),
+                    u8::saturating_add,
+                );
+                self.set_register(rd, value);
+            }
+            Instruction::Psubub(rd, rs, rt) => {
+                let value = map_bytes(
+                    self.get_register(rs),
+                    self.get_register(rt),
+                    u8::saturating_sub,
+                );
+                self.set_register(rd, value);
+            }
+            Instruction::Pextub(rd, rs, rt) => {
+                let (a, b) = (bytes(self.get_register(rs)), bytes(self.get_register(rt)));
+                let value = std::array::from_fn(|i| {
+                    if i % 2 == 0 {
+                        b[8 + i / 2]
+                    } else {
+                        a[8 + i / 2]
+                    }
+                });
+                self.set_register(rd, from_bytes(value));
+            }
+            Instruction::Qfsrv(rd, rs, rt) => {
+                // Funnel shift of rs:rt right by the SA register.
+                let shift = self.state.shift_amount & 0x7F;
+                let high = self.get_register::<u128>(rs);
+                let low = self.get_register::<u128>(rt);
+                let value = if shift == 0 {
+                    low
+                } else {
+                    low >> shift | high << (128 - shift)
+                };
+                self.set_register(rd, value);
+            }
+            Instruction::Pmaddw(rd, rs, rt) => {
+                let (a, b) = (words(self.get_register(rs)), words(self.get_register(rt)));
+                let accumulators = self.word_accumulators();
+                let products = [0, 1].map(|i| {
+                    let product = a[2 * i] as i32 as i64 * b[2 * i] as i32 as i64;
+                    (accumulators[i] as i64).wrapping_add(product) as u64
+                });
+                self.set_word_products(rd, products);
+            }
+            Instruction::Psllvw(rd, rt, rs) => {
+                let (a, b) = (words(self.get_register(rt)), words(self.get_register(rs)));
+                let value = [0, 2].map(|i| -> u64 { (a[i] << b[i].bits(0..5)).sign_extend() });
+                self.set_register(rd, from_doublewords(value));
+            }
+            Instruction::Psrlvw(rd, rt, rs) => {
+                let (a, b) = (words(self.get_register(rt)), words(self.get_register(rs)));
+                let value = [0, 2].map(|i| -> u64 { (a[i] >> b[i].bits(0..5)).sign_extend() });
+                self.set_register(rd, from_doublewords(value));
+            }
+            Instruction::Pmsubw(rd, rs, rt) => {
+                let (a, b) = (words(self.get_register(rs)), words(self.get_register(rt)));
+                let accumulators = self.word_accumulators();
+                let products = [0, 1].map(|i| {
+                    let product = a[2 * i] as i32 as i64 * b[2 * i] as i32 as i64;
+                    (accumulators[i] as i64).wrapping_sub(product) as u64
+                });
+                self.set_word_products(rd, products);
+            }
+            Instruction::Pmfhi(rd) => {
+                self.set_register(rd, self.get_register::<u128>(Register::Hi))
+            }
+            Instruction::Pmflo(rd) => {
+                self.set_register(rd, self.get_register::<u128>(Register::Lo))
+            }
+            Instruction::Pinth(rd, rs, rt) => {
+                let (a, b) = (
+                    halfwords(self.get_register(rs)),
+                    halfwords(self.get_register(rt)),
+                );
+                let value =
+                    std::array::from_fn(|i| if i % 2 == 0 { b[i / 2] } else { a[4 + i / 2] });
+                self.set_register(rd, from_halfwords(value));
+            }
+            Instruction::Pmultw(rd, rs, rt) => {
+                let (a, b) = (words(self.get_register(rs)), words(self.get_register(rt)));
+                let products =
+                    [0, 1].map(|i| (a[2 * i] as i32 as i64 * b[2 * i] as i32 as i64) as u64);
+                self.set_word_products(rd, products);
+            }
+            Instruction::Pdivw(rs, rt) => {
+                let (a, b) = (words(self.get_register(rs)), words(self.get_register(rt)));
+                let [low, high] = [0, 1].map(|i| {
+                    let (dividend, divisor) = (a[2 * i] as i32, b[2 * i] as i32);
+                    match (dividend, divisor) {
+                        (_, 0) => (i32::MAX, dividend),
+                        (i32::MIN, -1) => (i32::MIN, 0),
+                        (dividend, divisor) => (dividend / divisor, dividend % divisor),
+                    }
+                });
+                let quotients = [low.0, high.0].map(|quotient| -> u64 { quotient.sign_extend() });
+                let remainders =
+                    [low.1, high.1].map(|remainder| -> u64 { remainder.sign_extend() });
+                self.set_register(Register::Lo, from_doublewords(quotients));
+                self.set_register(Register::Hi, from_doublewords(remainders));
+            }
+            Instruction::Pcpyld(rd, rs, rt) => {
+                let (a, b) = (
+                    doublewords(self.get_register(rs)),
+                    doublewords(self.get_register(rt)),
+                );
+                self.set_register(rd, from_doublewords([b[0], a[0]]));
+            }
+            Instruction::Pmaddh(rd, rs, rt) => {
+                let (a, b) = (
+                    halfwords(self.get_register(rs)),
+                    halfwords(self.get_register(rt)),
+                );
+                let accumulators = self.halfword_accumulators();
+                let products = std::array::from_fn(|i| {
+                    let product = a[i] as i16 as i32 * b[i] as i16 as i32;
+                    (accumulators[i] as i32).wrapping_add(product) as u32
+                });
+                self.set_halfword_products(rd, products);
+            }
+            Instruction::Phmadh(rd, rs, rt) => {
+                let (a, b) = (
+                    halfwords(self.get_register(rs)),
+                    halfwords(self.get_register(rt)),
+                );
+                let product = |i: usize| a[i] as i16 as i32 * b[i] as i16 as i32;
+                let sums: [u32; 4] =
+                    std::array::from_fn(|i| product(2 * i + 1).wrapping_add(product(2 * i)) as u32);
+                self.set_register(Register::Lo, from_words([sums[0], 0, sums[2], 0]));
+                self.set_register(Register::Hi, from_words([sums[1], 0, sums[3], 0]));
+                self.set_register(rd, from_words(sums));
+            }
+            Instruction::Pmsubh(rd, rs, rt) => {
+                let (a, b) = (
+                    halfwords(self.get_register(rs)),
+                    halfwords(self.get_register(rt)),
+                );
+                let accumulators = self.halfword_accumulators();
+                let products = std::array::from_fn(|i| {
+                    let product = a[i] as i16 as i32 * b[i] as i16 as i32;
+                    (accumulators[i] as i32).wrapping_sub(product) as u32
+                });
+                self.set_halfword_products(rd, products);
+            }
+            Instruction::Phmsbh(rd, rs, rt) => {
+                let (a, b) = (
+                    halfwords(self.get_register(rs)),
+                    halfwords(self.get_register(rt)),
+                );
+                let product = |i: usize| a[i] as i16 as i32 * b[i] as i16 as i32;
+                let differences: [u32; 4] =
+                    std::array::from_fn(|i| product(2 * i + 1).wrapping_sub(product(2 * i)) as u32);
+                let d = differences;
+                self.set_register(Register::Lo, from_words([d[0], 0, d[2], 0]));
+                self.set_register(Register::Hi, from_words([d[1], 0, d[3], 0]));
+                self.set_register(rd, from_words(d));
+            }
+            Instruction::Pexeh(rd, rt) => {
+                let h = halfwords(self.get_register(rt));
+                let value = [h[2], h[1], h[0], h[3], h[6], h[5], h[4], h[7]];
+                self.set_register(rd, from_halfwords(value));
+            }
+            Instruction::Prevh(rd, rt) => {
+                let h = halfwords(self.get_register(rt));
+                let value = [h[3], h[2], h[1], h[0], h[7], h[6], h[5], h[4]];
+                self.set_register(rd, from_halfwords(value));
+            }
+            Instruction::Pmulth(rd, rs, rt) => {
+                let (a, b) = (
+                    halfwords(self.get_register(rs)),
+                    halfwords(self.get_register(rt)),
+                );
+                let products =
+                    std::array::from_fn(|i| (a[i] as i16 as i32 * b[i] as i16 as i32) as u32);
+                self.set_halfword_products(rd, products);
+            }
+            Instruction::Pdivbw(rs, rt) => {
+                let divisor = self.get_register::<u16>(rt) as i16 as i32;
+                let results =
+                    words(self.get_register(rs)).map(|dividend| match (dividend as i32, divisor) {
+                        (dividend, 0) => (i32::MAX, dividend),
+                        (i32::MIN, -1) => (i32::MIN, 0),
+                        (dividend, divisor) => (dividend / divisor, dividend % divisor),
+                    });
+                let quotients = results.map(|(quotient, _)| quotient as u32);
+                let remainders = results.map(|(_, remainder)| remainder as u32);
+                self.set_register(Register::Lo, from_words(quotients));
+                self.set_register(Register::Hi, from_words(remainders));
+            }
+            Instruction::Pexew(rd, rt) => {
+                let w = words(self.get_register(rt));
+                self.set_register(rd, from_words([w[2], w[1], w[0], w[3]]));
+            }
+            Instruction::Prot3w(rd, rt) => {
+                let w = words(self.get_register(rt));
+                self.set_register(rd, from_words([w[1], w[2], w[0], w[3]]));
+            }
+            Instruction::Pmadduw(rd, rs, rt) => {
+                let (a, b) = (words(self.get_register(rs)), words(self.get_register(rt)));
+                let accumulators = self.word_accumulators();
+                let products =
+                    [0, 1].map(|i| accumulators[i].wrapping_add(a[2 * i] as u64 * b[2 * i] as u64));
+                self.set_word_products(rd, products);
+            }
+            Instruction::Psravw(rd, rt, rs) => {
+                let (a, b) = (words(self.get_register(rt)), words(self.get_register(rs)));
+                let value =
+                    [0, 2].map(|i| -> u64 { ((a[i] as i32) >> b[i].bits(0..5)).sign_extend() });
+                self.set_register(rd, from_doublewords(value));
+            }
+            Instruction::Pmthi(rs) => {
+                self.set_register(Register::Hi, self.get_register::<u128>(rs))
+            }
+            Instruction::Pmtlo(rs) => {
+                self.set_register(Register::Lo, self.get_register::<u128>(rs))
+            }
+            Instruction::Pinteh(rd, rs, rt) => {
+                let (a, b) = (
+                    halfwords(self.get_register(rs)),
+                    halfwords(self.get_register(rt)),
+                );
+                let value = std::array::from_fn(|i| if i % 2 == 0 { b[i] } else { a[i - 1] });
+                self.set_register(rd, from_halfwords(value));
+            }
+            Instruction::Pmultuw(rd, rs, rt) => {
+                let (a, b) = (words(self.get_register(rs)), words(self.get_register(rt)));
+                let products = [0, 1].map(|i| a[2 * i] as u64 * b[2 * i] as u64);
+                self.set_word_products(rd, products);
+            }
+            Instruction::Pdivuw(rs, rt) => {
+                let (a, b) = (words(self.get_register(rs)), words(self.get_register(rt)));
+                let [low, high] = [0, 1].map(|i| {
+                    let (dividend, divisor) = (a[2 * i], b[2 * i]);
+                    match dividend.checked_div(divisor) {
+                        Some(quotient) => (quotient, dividend % divisor),
+                        None => (!0, dividend),
+                    }
+                });
+                let quotients = [low.0, high.0].map(|quotient| -> u64 { quotient.sign_extend() });
+                let remainders =
+                    [low.1, high.1].map(|remainder| -> u64 { remainder.sign_extend() });
+                self.set_register(Register::Lo, from_doublewords(quotients));
+                self.set_register(Register::Hi, from_doublewords(remainders));
+            }
+            Instruction::Pcpyud(rd, rs, rt) => {
+                let (a, b) = (
+                    doublewords(self.get_register(rs)),
+                    doublewords(self.get_register(rt)),
+                );
+                self.set_register(rd, from_doublewords([a[1], b[1]]));
+            }
+            Instruction::Pexch(rd, rt) => {
+                let h = halfwords(self.get_register(rt));
+                let value = [h[0], h[2], h[1], h[3], h[4], h[6], h[5], h[7]];
+                self.set_register(rd, from_halfwords(value));
+            }
+            Instruction::Pcpyh(rd, rt) => {
+                let h = halfwords(self.get_register(rt));
+                let value = std::array::from_fn(|i| h[i & !0b11]);
+                self.set_register(rd, from_halfwords(value));
+            }
+            Instruction::Pexcw(rd, rt) => {
+                let w = words(self.get_register(rt));
+                self.set_register(rd, from_words([w[0], w[2], w[1], w[3]]));
+            }
             Instruction::Lq(rt, offset, base) => {
                 let mut address = self
                     .get_register::<u32>(base)
                     .wrapping_add(offset.sign_extend());
                 address &= !0b1111;
-                let value = self.read_virtual(bus, address);
+                let value = self.read_virtual(bus, address)?;
                 self.set_register::<u128>(rt, value);
             }
             Instruction::Sq(rt, offset, base) => {
@@ -546,13 +1474,13 @@ impl Core {
                     .get_register::<u32>(base)
                     .wrapping_add(offset.sign_extend());
                 address &= !0b1111;
-                self.write_virtual(bus, address, self.get_register::<u128>(rt));
+                self.write_virtual(bus, address, self.get_register::<u128>(rt))?;
             }
             Instruction::Lb(rt, offset, base) => {
                 let address = self
                     .get_register::<u32>(base)
                     .wrapping_add(offset.sign_extend());
-                let value = self.read_virtual::<u8>(bus, address);
+                let value = self.read_virtual::<u8>(bus, address)?;
                 self.set_register::<u64>(rt, value.sign_extend());
             }
             Instruction::Lh(rt, offset, base) => {
@@ -560,9 +1488,9 @@ impl Core {
                     .get_register::<u32>(base)
                     .wrapping_add(offset.sign_extend());
                 if address.bits(0..1) != 0 {
-                    panic!("Unaligned load at {:#010x}", address);
+                    return Err(Exception::AddressErrorLoad(address));
                 }
-                let value = self.read_virtual::<u16>(bus, address);
+                let value = self.read_virtual::<u16>(bus, address)?;
                 self.set_register::<u64>(rt, value.sign_extend());
             }
             Instruction::Lw(rt, offset, base) => {
@@ -570,36 +1498,51 @@ impl Core {
                     .get_register::<u32>(base)
                     .wrapping_add(offset.sign_extend());
                 if address.bits(0..2) != 0 {
-                    panic!("Unaligned load at {:#010x}", address);
+                    return Err(Exception::AddressErrorLoad(address));
                 }
-                let value = self.read_virtual::<u32>(bus, address);
+                let value = self.read_virtual::<u32>(bus, address)?;
                 self.set_register::<u64>(rt, value.sign_extend());
             }
             Instruction::Lbu(rt, offset, base) => {
                 let address = self
                     .get_register::<u32>(base)
                     .wrapping_add(offset.sign_extend());
-                let value = self.read_virtual::<u8>(bus, address);
+                let value = self.read_virtual::<u8>(bus, address)?;
                 self.set_register(rt, value as u64);
             }
             Instruction::Lhu(rt, offset, base) => {
                 let address = self
                     .get_register::<u32>(base)
                     .wrapping_add(offset.sign_extend());
-                let value = self.read_virtual::<u16>(bus, address);
+                if address.bits(0..1) != 0 {
+                    return Err(Exception::AddressErrorLoad(address));
+                }
+                let value = self.read_virtual::<u16>(bus, address)?;
                 self.set_register(rt, value as u64);
             }
+            Instruction::Lwl(rt, offset, base) => {
+                let address = self
+                    .get_register::<u32>(base)
+                    .wrapping_add(offset.sign_extend());
+                let shift = (address & 0b11) * 8;
+                let memory_word = self.read_virtual::<u32>(bus, address & !0b11)?;
+                let value = self.get_register::<u32>(rt) & (0x00FF_FFFF >> shift)
+                    | memory_word << (24 - shift);
+                self.set_register::<u64>(rt, value.sign_extend());
+            }
             Instruction::Lwr(rt, offset, base) => {
                 let address = self
                     .get_register::<u32>(base)
                     .wrapping_add(offset.sign_extend());
-                let byte = address & 0b11;
-                let memory_word = self.read_virtual::<u32>(bus, address & !0b11);
-                let value = if byte == 0 {
-                    memory_word.sign_extend()
+                let shift = (address & 0b11) * 8;
+                let memory_word = self.read_virtual::<u32>(bus, address & !0b11)?;
+                let word = self.get_register::<u32>(rt) & (0xFFFF_FF00 << (24 - shift))
+                    | memory_word >> shift;
+                // A whole word is sign extended, otherwise the upper half of the register is kept.
+                let value = if shift == 0 {
+                    word.sign_extend()
                 } else {
-                    let existing = self.get_register::<u64>(rt);
-                    existing & u64::mask(byte * 8..64) | (memory_word >> (byte * 8)) as u64
+                    self.get_register::<u64>(rt) & u64::mask(32..64) | word as u64
                 };
                 self.set_register(rt, value);
             }
@@ -607,25 +1550,45 @@ impl Core {
                 let address = self
                     .get_register::<u32>(base)
                     .wrapping_add(offset.sign_extend());
-                self.write_virtual(bus, address, self.get_register::<u8>(rt));
+                self.write_virtual(bus, address, self.get_register::<u8>(rt))?;
             }
             Instruction::Sh(rt, offset, base) => {
                 let address = self
                     .get_register::<u32>(base)
                     .wrapping_add(offset.sign_extend());
                 if address.bits(0..1) != 0 {
-                    panic!("Unaligned store at {:#010x}", address);
+                    return Err(Exception::AddressErrorStore(address));
                 }
-                self.write_virtual(bus, address, self.get_register::<u16>(rt));
+                self.write_virtual(bus, address, self.get_register::<u16>(rt))?;
             }
             Instruction::Sw(rt, offset, base) => {
                 let address = self
                     .get_register::<u32>(base)
                     .wrapping_add(offset.sign_extend());
                 if address.bits(0..2) != 0 {
-                    panic!("Unaligned store at {:#010x}", address);
+                    return Err(Exception::AddressErrorStore(address));
                 }
-                self.write_virtual(bus, address, self.get_register::<u32>(rt));
+                self.write_virtual(bus, address, self.get_register::<u32>(rt))?;
+            }
+            Instruction::Swl(rt, offset, base) => {
+                let address = self
+                    .get_register::<u32>(base)
+                    .wrapping_add(offset.sign_extend());
+                let shift = (address & 0b11) * 8;
+                let memory_word = self.read_virtual::<u32>(bus, address & !0b11)?;
+                let value = memory_word & (0xFFFF_FF00 << shift)
+                    | self.get_register::<u32>(rt) >> (24 - shift);
+                self.write_virtual(bus, address & !0b11, value)?;
+            }
+            Instruction::Swr(rt, offset, base) => {
+                let address = self
+                    .get_register::<u32>(base)
+                    .wrapping_add(offset.sign_extend());
+                let shift = (address & 0b11) * 8;
+                let memory_word = self.read_virtual::<u32>(bus, address & !0b11)?;
+                let value = memory_word & (0x00FF_FFFF >> (24 - shift))
+                    | self.get_register::<u32>(rt) << shift;
+                self.write_virtual(bus, address & !0b11, value)?;
             }
             Instruction::Cache(op, offset, base) => match op {
                 CacheOperation::IXLTG => todo!(),
@@ -654,9 +1617,9 @@ impl Core {
                     .get_register::<u32>(base)
                     .wrapping_add(offset.sign_extend());
                 if address.bits(0..2) != 0 {
-                    panic!("Unaligned load at {:#010x}", address);
+                    return Err(Exception::AddressErrorLoad(address));
                 }
-                let value = self.read_virtual::<u32>(bus, address);
+                let value = self.read_virtual::<u32>(bus, address)?;
                 self.state.fpu.set_register(ft, value);
             }
             Instruction::Ld(rt, offset, base) => {
@@ -664,9 +1627,9 @@ impl Core {
                     .get_register::<u32>(base)
                     .wrapping_add(offset.sign_extend());
                 if address.bits(0..3) != 0 {
-                    panic!("Unaligned load at {:#010x}", address);
+                    return Err(Exception::AddressErrorLoad(address));
                 }
-                let value = self.read_virtual(bus, address);
+                let value = self.read_virtual(bus, address)?;
                 self.set_register::<u64>(rt, value);
             }
             Instruction::Swc1(ft, offset, base) => {
@@ -674,33 +1637,75 @@ impl Core {
                     .get_register::<u32>(base)
                     .wrapping_add(offset.sign_extend());
                 if address.bits(0..2) != 0 {
-                    panic!("Unaligned store at {:#010x}", address);
+                    return Err(Exception::AddressErrorStore(address));
                 }
-                self.write_virtual(bus, address, self.state.fpu.get_register::<u32>(ft));
+                self.write_virtual(bus, address, self.state.fpu.get_register::<u32>(ft))?;
             }
             Instruction::Sd(rt, offset, base) => {
                 let address = self
                     .get_register::<u32>(base)
                     .wrapping_add(offset.sign_extend());
                 if address.bits(0..3) != 0 {
-                    panic!("Unaligned store at {:#010x}", address);
+                    return Err(Exception::AddressErrorStore(address));
                 }
-                self.write_virtual(bus, address, self.get_register::<u64>(rt));
+                self.write_virtual(bus, address, self.get_register::<u64>(rt))?;
+            }
+            Instruction::Ldl(rt, offset, base) => {
+                let address = self
+                    .get_register::<u32>(base)
+                    .wrapping_add(offset.sign_extend());
+                let shift = (address & 0b111) * 8;
+                let memory_doubleword = self.read_virtual::<u64>(bus, address & !0b111)?;
+                let value = self.get_register::<u64>(rt) & (0x00FF_FFFF_FFFF_FFFF >> shift)
+                    | memory_doubleword << (56 - shift);
+                self.set_register(rt, value);
+            }
+            Instruction::Ldr(rt, offset, base) => {
+                let address = self
+                    .get_register::<u32>(base)
+                    .wrapping_add(offset.sign_extend());
+                let shift = (address & 0b111) * 8;
+                let memory_doubleword = self.read_virtual::<u64>(bus, address & !0b111)?;
+                let value = self.get_register::<u64>(rt) & (0xFFFF_FFFF_FFFF_FF00 << (56 - shift))
+                    | memory_doubleword >> shift;
+                self.set_register(rt, value);
+            }
+            Instruction::Sdl(rt, offset, base) => {
+                let address = self
+                    .get_register::<u32>(base)
+                    .wrapping_add(offset.sign_extend());
+                let shift = (address & 0b111) * 8;
+                let memory_doubleword = self.read_virtual::<u64>(bus, address & !0b111)?;
+                let value = memory_doubleword & (0xFFFF_FFFF_FFFF_FF00 << shift)
+                    | self.get_register::<u64>(rt) >> (56 - shift);
+                self.write_virtual(bus, address & !0b111, value)?;
+            }
+            Instruction::Sdr(rt, offset, base) => {
+                let address = self
+                    .get_register::<u32>(base)
+                    .wrapping_add(offset.sign_extend());
+                let shift = (address & 0b111) * 8;
+                let memory_doubleword = self.read_virtual::<u64>(bus, address & !0b111)?;
+                let value = memory_doubleword & (0x00FF_FFFF_FFFF_FFFF >> (56 - shift))
+                    | self.get_register::<u64>(rt) << shift;
+                self.write_virtual(bus, address & !0b111, value)?;
             }
         }
-        for reg in instruction.definitions() {
-            match reg {
-                Occurrence::Core(reg) => {
-                    let value = self.get_register::<u64>(reg);
-                    println!("{}={:#x}", reg, value);
-                }
-                Occurrence::Control(_) => {}
-                Occurrence::Fpu(reg) => {
-                    let value = self.state.fpu.get_register::<u32>(reg);
-                    println!("{}={:#x}", reg, value);
+        if log_enabled!(target: "ee.core", Level::Trace) {
+            for reg in instruction.definitions() {
+                match reg {
+                    Occurrence::Core(reg) => {
+                        let value = self.get_register::<u64>(reg);
+                        trace!(target: "ee.core", "{}={:#x}", reg, value);
+                    }
+                    Occurrence::Control(_) => {}
+                    Occurrence::Fpu(reg) => {
+                        let value = self.state.fpu.get_register::<u32>(reg);
+                        trace!(target: "ee.core", "{}={:#x}", reg, value);
+                    }
                 }
             }
         }
-        self.state.program_counter = next_program_counter;
+        Ok(next_program_counter)
     }
 }
@@ -1,19 +1,37 @@
-use super::{instruction_gen::Instruction, mmu::Mmu, register::Register, Mode, State};
+use super::{
+    fpu,
+    instruction::Occurrence,
+    instruction_gen::Instruction,
+    mmu::{Mmu, PAGE_SIZE},
+    profile::Profiler,
+    register::Register,
+    timing, Core, Mode, State,
+};
 use crate::{
-    bits::SignExtend,
+    bits::{Bits, SignExtend},
     bytes::Bytes,
-    emotion_engine::bus::{Bus, PhysicalAddress},
+    emotion_engine::{
+        bus::{Bus, PhysicalAddress},
+        fastmem,
+    },
     executable_memory_allocator::ExecutableMemoryAllocator,
 };
 use bitvec::vec::BitVec;
 use cranelift_codegen::{
     control::ControlPlane,
-    ir::{self, InstBuilder, Signature},
-    isa::OwnedTargetIsa,
+    ir::{self, condcodes::CondCode, InstBuilder, Signature},
+    isa::{CallConv, OwnedTargetIsa},
     settings::{self, Configurable},
 };
-use enum_map::EnumMap;
-use std::{collections::BTreeMap, fmt::LowerHex, ops::Range};
+use enum_map::{Enum, EnumMap};
+use log::{debug, trace};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt::{Display, LowerHex},
+    mem::offset_of,
+    ops::Range,
+    pin::Pin,
+};
 
 pub struct Jit {
     jitted_instructions: BitVec<usize>,
@@ -25,6 +43,141 @@ pub struct Jit {
     codegen_context: cranelift_codegen::Context,
     function_builder_context: cranelift_frontend::FunctionBuilderContext,
     executable_memory: ExecutableMemoryAllocator,
+    entry_trampoline: EntryTrampoline,
+    dispatch: Box<Dispatch>,
+    // The links jumping into each block, keyed by the start of the block.
+    incoming_links: BTreeMap<PhysicalAddress, Vec<*mut Link>>,
+    // The instructions whose memory accesses have faulted in fastmem, which are compiled to the
+    // slow path, and those that faulted during the current run, to be recompiled once it returns.
+    slow_accesses: BTreeSet<PhysicalAddress>,
+    faulted_accesses: Vec<PhysicalAddress>,
+    pub coverage: Coverage,
+    pub profiler: Profiler,
+    config: JitConfig,
+}
+
+#[derive(Clone, Copy)]
+pub struct JitConfig {
+    // Whether loops that only poll are compiled to give up their cycles, see `Jit::take_idle`.
+    pub idle_loop_detection: bool,
+    // Whether the hot registers stay in host registers from one linked block to the next, see
+    // `PINNED_REGISTERS`.
+    pub register_pinning: bool,
+    // Whether blocks are run one at a time by the lockstep checker, which needs them unlinked and
+    // every store to bump the page generations, see `Lockstep`.
+    pub lockstep: bool,
+}
+
+impl JitConfig {
+    fn pinned_registers(self) -> &'static [Register] {
+        if self.register_pinning {
+            &PINNED_REGISTERS
+        } else {
+            &[]
+        }
+    }
+}
+
+impl Default for JitConfig {
+    fn default() -> Self {
+        JitConfig {
+            idle_loop_detection: true,
+            register_pinning: false,
+            lockstep: false,
+        }
+    }
+}
+
+// The stack pointer, the return address and the registers arguments and results are passed in.
+// Their lower doublewords are passed from block to block as arguments, their upper doublewords
+// stay in memory.
+const PINNED_REGISTERS: [Register; 7] = [
+    Register::Sp,
+    Register::Ra,
+    Register::V0,
+    Register::A0,
+    Register::A1,
+    Register::A2,
+    Register::A3,
+];
+
+// Calls a block with the mode and the register file.
+type EntryTrampoline = extern "C" fn(*const u8, Mode, *const u128) -> bool;
+
+// How an instruction was compiled: to Cranelift IR, to a call into the interpreter from within
+// the block, or not at all, ending the block so that the dispatcher interprets it.
+#[derive(Enum, Clone, Copy)]
+pub enum Lowering {
+    Native,
+    Helper,
+    Fallback,
+}
+
+// The lowering of every compiled instruction, counted per mnemonic.
+#[derive(Default)]
+pub struct Coverage {
+    instructions: BTreeMap<String, EnumMap<Lowering, u64>>,
+}
+
+impl Coverage {
+    fn record(&mut self, instruction: Instruction, lowering: Lowering) {
+        let name = format!("{:?}", instruction);
+        let mnemonic = name.split('(').next().unwrap().to_lowercase();
+        self.instructions.entry(mnemonic).or_default()[lowering] += 1;
+    }
+}
+
+impl Display for Coverage {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(
+            f,
+            "{:<10} {:>10} {:>10} {:>10}",
+            "", "native", "helper", "fallback"
+        )?;
+        for (mnemonic, counts) in &self.instructions {
+            writeln!(
+                f,
+                "{:<10} {:>10} {:>10} {:>10}",
+                mnemonic,
+                counts[Lowering::Native],
+                counts[Lowering::Helper],
+                counts[Lowering::Fallback]
+            )?;
+        }
+        Ok(())
+    }
+}
+
+// Shared between the dispatcher and the compiled blocks.
+#[repr(C)]
+struct Dispatch {
+    // Blocks only jump to their successors while there are cycles left.
+    cycles_left: i64,
+    // The exit of the block that returned to the dispatcher, linked to the block run next.
+    exit: *mut Exit,
+    // Set by idle loops when they give up the cycles left.
+    idle: bool,
+}
+
+// A direct jump from a block exit to the compiled block at `target`, taken when the program counter
+// and mode match those it was linked with.
+#[repr(C)]
+#[derive(Default)]
+struct Link {
+    function: usize,
+    program_counter: u32,
+    mode: u8,
+    target: PhysicalAddress,
+}
+
+const EXIT_LINKS: usize = 2;
+
+// An exit with a static successor. Conditional branches leave through the same exit whether they
+// are taken or not, so it holds a link for each.
+#[repr(C)]
+#[derive(Default)]
+struct Exit {
+    links: [Link; EXIT_LINKS],
 }
 
 #[derive(Clone, Copy)]
@@ -58,20 +211,83 @@ impl CacheIndex {
 pub struct CacheEntry {
     pub address_range: Range<PhysicalAddress>,
     pub code: Code,
+    exits: Vec<Pin<Box<Exit>>>,
+    // The instructions the entry was built from, to tell writes to data sharing their pages from
+    // writes to the code itself.
+    instructions: Vec<u32>,
+    generations: PageGenerations,
+}
+
+// The generations of the pages an entry was built from when it was last known to be current.
+// Compiled blocks compare them with the bus on entry and return to the dispatcher if a page has
+// been written since, so that writes from any source (the cores, DMA, the kernel) are caught.
+pub struct PageGenerations {
+    pages: Vec<usize>,
+    // Boxed so that compiled code can refer to it while the cache moves entries around.
+    expected: Box<[u32]>,
+}
+
+impl PageGenerations {
+    fn new(bus: &Bus, range: Range<PhysicalAddress>) -> Self {
+        let pages = Bus::tracked_pages(range);
+        let expected = pages
+            .iter()
+            .map(|&page| bus.page_generation(page))
+            .collect();
+        PageGenerations { pages, expected }
+    }
+
+    fn is_current(&self, bus: &Bus) -> bool {
+        self.pages
+            .iter()
+            .zip(&self.expected)
+            .all(|(&page, &expected)| bus.page_generation(page) == expected)
+    }
+
+    fn refresh(&mut self, bus: &Bus) {
+        for (&page, expected) in self.pages.iter().zip(&mut self.expected) {
+            *expected = bus.page_generation(page);
+        }
+    }
 }
 
 pub enum Code {
-    Jitted(extern "C" fn(Mode)),
+    // A block using the tail calling convention, run through `Jit::run`.
+    Jitted(*const u8),
     Interpreted(Instruction),
 }
 
 const PHYSICAL_MEMORY_SIZE: usize = 0x1_0000_0000;
+// Polling loops are short, and longer loops are unlikely to be free of side effects.
+const IDLE_LOOP_MAX_INSTRUCTIONS: usize = 16;
 const INSTRUCTION_SIZE: usize = std::mem::size_of::<u32>();
 
 impl Jit {
     pub fn new() -> Self {
         let mut settings_builder = settings::builder();
         settings_builder.set("opt_level", "speed").unwrap();
+        // Tail calls between linked blocks rely on frame pointers.
+        settings_builder
+            .set("preserve_frame_pointers", "true")
+            .unwrap();
+        // 128-bit values are passed to the SQ trampolines like rustc does.
+        settings_builder
+            .set("enable_llvm_abi_extensions", "true")
+            .unwrap();
+        let isa = cranelift_native::builder()
+            .unwrap()
+            .finish(settings::Flags::new(settings_builder))
+            .unwrap();
+        let mut codegen_context = cranelift_codegen::Context::new();
+        let mut function_builder_context = cranelift_frontend::FunctionBuilderContext::new();
+        let mut executable_memory = ExecutableMemoryAllocator::default();
+        let entry_trampoline = Self::compile_entry_trampoline(
+            &isa,
+            &mut codegen_context,
+            &mut function_builder_context,
+            &mut executable_memory,
+            &[],
+        );
         Jit {
             jitted_instructions: BitVec::from_vec(vec![
                 0;
@@ -84,18 +300,192 @@ impl Jit {
                 .into_boxed_slice(),
             cache: Vec::new(),
             next_to_remove: 0,
-            isa: cranelift_native::builder()
-                .unwrap()
-                .finish(settings::Flags::new(settings_builder))
-                .unwrap(),
-            codegen_context: cranelift_codegen::Context::new(),
-            function_builder_context: cranelift_frontend::FunctionBuilderContext::new(),
-            executable_memory: ExecutableMemoryAllocator::default(),
+            isa,
+            codegen_context,
+            function_builder_context,
+            executable_memory,
+            entry_trampoline,
+            dispatch: Box::new(Dispatch {
+                cycles_left: 0,
+                exit: std::ptr::null_mut(),
+                idle: false,
+            }),
+            incoming_links: BTreeMap::new(),
+            slow_accesses: BTreeSet::new(),
+            faulted_accesses: Vec::new(),
+            coverage: Coverage::default(),
+            profiler: Profiler::default(),
+            config: JitConfig::default(),
+        }
+    }
+
+    pub fn config(&self) -> JitConfig {
+        self.config
+    }
+
+    // Changes how blocks are compiled. Pinning registers changes the signature of blocks, so this
+    // must be called before anything is compiled.
+    pub fn set_config(&mut self, config: JitConfig) {
+        assert!(self.cache.is_empty());
+        self.config = config;
+        self.entry_trampoline = Self::compile_entry_trampoline(
+            &self.isa,
+            &mut self.codegen_context,
+            &mut self.function_builder_context,
+            &mut self.executable_memory,
+            self.config.pinned_registers(),
+        );
+    }
+
+    // Blocks use the tail calling convention so that they can jump to each other, which Rust can't
+    // call directly. The trampoline calls a block on behalf of the dispatcher, loading the pinned
+    // registers from the register file.
+    fn compile_entry_trampoline(
+        isa: &OwnedTargetIsa,
+        codegen_context: &mut cranelift_codegen::Context,
+        function_builder_context: &mut cranelift_frontend::FunctionBuilderContext,
+        executable_memory: &mut ExecutableMemoryAllocator,
+        pinned_registers: &[Register],
+    ) -> EntryTrampoline {
+        codegen_context.clear();
+        let mut function_builder = cranelift_frontend::FunctionBuilder::new(
+            &mut codegen_context.func,
+            function_builder_context,
+        );
+        let mut signature = Signature::new(isa.default_call_conv());
+        signature.params.extend_from_slice(&[
+            ir::AbiParam::new(ir::types::I64),
+            ir::AbiParam::new(ir::types::I8),
+            ir::AbiParam::new(ir::types::I64),
+        ]);
+        signature.returns.push(ir::AbiParam::new(ir::types::I8));
+        function_builder.func.signature = signature;
+        let block_signature =
+            function_builder.import_signature(block_signature(pinned_registers.len()));
+        let block = function_builder.create_block();
+        function_builder.append_block_params_for_function_params(block);
+        function_builder.switch_to_block(block);
+        let [function, mode, registers] = function_builder.block_params(block).try_into().unwrap();
+        let mut arguments = vec![mode];
+        for &register in pinned_registers {
+            arguments.push(function_builder.ins().load(
+                ir::types::I64,
+                ir::MemFlags::trusted(),
+                registers,
+                (register.into_usize() * size_of::<u128>()) as i32,
+            ));
+        }
+        let call = function_builder
+            .ins()
+            .call_indirect(block_signature, function, &arguments);
+        let completed = function_builder.inst_results(call)[0];
+        function_builder.ins().return_(&[completed]);
+        function_builder.seal_all_blocks();
+        function_builder.finalize();
+        let compiled_code = codegen_context
+            .compile(isa.as_ref(), &mut ControlPlane::default())
+            .unwrap();
+        let pointer = executable_memory.allocate(compiled_code.code_buffer());
+        unsafe { std::mem::transmute::<*const u8, EntryTrampoline>(pointer) }
+    }
+
+    // Runs a block and the blocks linked after it until `cycles` runs out. Returns false if a block
+    // bailed out early, e.g. because of an exception. The program counter then points at an
+    // instruction that has to be interpreted.
+    pub fn run(
+        &mut self,
+        function: *const u8,
+        mode: Mode,
+        registers: *const u128,
+        cycles: &mut u64,
+    ) -> bool {
+        self.dispatch.cycles_left = *cycles as i64;
+        self.dispatch.exit = std::ptr::null_mut();
+        let completed = (self.entry_trampoline)(function, mode, registers);
+        *cycles = self.dispatch.cycles_left.max(0) as u64;
+        for address in std::mem::take(&mut self.faulted_accesses) {
+            self.slow_accesses.insert(address);
+            self.invalidate_range(address..address + INSTRUCTION_SIZE as u32);
+        }
+        completed
+    }
+
+    // What is left of the cycles given to the last run, negative if its last block overran them.
+    pub fn cycles_left(&self) -> i64 {
+        self.dispatch.cycles_left
+    }
+
+    // Whether the core has been spinning in an idle loop since the last call, in which case nothing
+    // changes until an event does and the scheduler can skip ahead.
+    pub fn take_idle(&mut self) -> bool {
+        std::mem::take(&mut self.dispatch.idle)
+    }
+
+    // Points the exit the last block left through at the block it was followed by.
+    fn link(&mut self, exit: *mut Exit, program_counter: u32, mode: Mode, target: u16) {
+        let target_entry = &self.cache[target as usize];
+        let Code::Jitted(function) = target_entry.code else {
+            return;
+        };
+        let target = target_entry.address_range.start;
+        let links = unsafe { &mut (*exit).links };
+        let slot = links
+            .iter()
+            .position(|link| link.function == 0)
+            .unwrap_or(EXIT_LINKS - 1);
+        let link = &mut links[slot] as *mut Link;
+        self.unlink(link);
+        unsafe {
+            *link = Link {
+                function: function as usize,
+                program_counter,
+                mode: mode as u8,
+                target,
+            };
+        }
+        self.incoming_links.entry(target).or_default().push(link);
+    }
+
+    fn unlink(&mut self, link: *mut Link) {
+        let link = unsafe { &mut *link };
+        if link.function == 0 {
+            return;
         }
+        link.function = 0;
+        if let Some(links) = self.incoming_links.get_mut(&link.target) {
+            links.retain(|&incoming| !std::ptr::eq(incoming, link));
+        }
+    }
+
+    // Translations are baked into links, so they all go when the TLB changes.
+    pub fn unlink_all(&mut self) {
+        for entry in &mut self.cache {
+            for exit in &mut entry.exits {
+                for link in &mut exit.links {
+                    link.function = 0;
+                }
+            }
+        }
+        self.incoming_links.clear();
     }
 
     fn remove(&mut self, cache_index: u16) {
-        let entry = self.cache.swap_remove(cache_index as usize);
+        let mut entry = self.cache.swap_remove(cache_index as usize);
+        for exit in &mut entry.exits {
+            for link in &mut exit.links {
+                self.unlink(link);
+            }
+            if std::ptr::eq(&**exit, self.dispatch.exit) {
+                self.dispatch.exit = std::ptr::null_mut();
+            }
+        }
+        for link in self
+            .incoming_links
+            .remove(&entry.address_range.start)
+            .unwrap_or_default()
+        {
+            unsafe { (*link).function = 0 };
+        }
         if let Some(moved_code) = self.cache.last() {
             let moved_index = (self.cache.len() - 1) as u16;
             self.jitted_starts_map
@@ -132,7 +522,7 @@ impl Jit {
                 .fill(false);
         }
         match entry.code {
-            Code::Jitted(function) => self.executable_memory.free(function as *const u8),
+            Code::Jitted(function) => self.executable_memory.free(function),
             Code::Interpreted(_) => {}
         }
     }
@@ -158,11 +548,15 @@ impl Jit {
     }
 
     #[inline(always)]
-    pub fn invalidate_range(&mut self, range: Range<PhysicalAddress>) {
-        if self.jitted_instructions[range.start.0 as usize / INSTRUCTION_SIZE
+    pub fn contains_code(&self, range: Range<PhysicalAddress>) -> bool {
+        self.jitted_instructions[range.start.0 as usize / INSTRUCTION_SIZE
             ..(range.end.0 as usize).div_ceil(INSTRUCTION_SIZE)]
-            .not_any()
-        {
+            .any()
+    }
+
+    #[inline(always)]
+    pub fn invalidate_range(&mut self, range: Range<PhysicalAddress>) {
+        if !self.contains_code(range.clone()) {
             return;
         }
 
@@ -170,7 +564,7 @@ impl Jit {
     }
 
     fn invalidate_range_slow(&mut self, range: Range<PhysicalAddress>) {
-        let to_remove = self
+        let mut to_remove = self
             .jitted_starts_map
             .range(..range.end)
             .rev()
@@ -180,69 +574,214 @@ impl Jit {
             })
             .map(|(_, index)| *index)
             .collect::<Vec<_>>();
-
+        // Removing an entry moves the last one into its place, so the highest indices go first
+        // to keep the others valid.
+        to_remove.sort_unstable_by(|a, b| b.cmp(a));
         for index in to_remove {
             self.remove(index);
         }
     }
 
-    pub fn cache_entry(&mut self, state: &State, mmu: &Mmu, bus: &Bus, mode: Mode) -> &CacheEntry {
-        let physical_program_counter = mmu.virtual_to_physical(state.program_counter, mode);
+    fn compile_entry(
+        &mut self,
+        physical_program_counter: PhysicalAddress,
+        core: *mut Core,
+        bus: &Bus,
+    ) -> u16 {
+        let jit_compiler = JitCompiler::new(
+            core,
+            &self.isa,
+            &mut self.codegen_context,
+            &mut self.function_builder_context,
+            bus,
+            &mut *self.dispatch,
+            &mut self.coverage,
+        );
+
+        let (address_range, code, exits, generations) = if let Some(CompiledBlock {
+            end_address,
+            exits,
+            generations,
+        }) =
+            jit_compiler.compile(physical_program_counter, self.config)
+        {
+            let dump = self.profiler.dumps_blocks();
+            let ir = dump.then(|| self.codegen_context.func.to_string());
+            self.codegen_context.set_disasm(dump);
+            let compiled_code = self
+                .codegen_context
+                .compile(self.isa.as_ref(), &mut ControlPlane::default())
+                .unwrap();
+            let pointer = self.executable_memory.allocate(compiled_code.code_buffer());
+            if self.profiler.records_compiled_blocks() {
+                let address_range = physical_program_counter..end_address;
+                let instructions =
+                    read_instructions(bus, address_range.clone()).collect::<Vec<_>>();
+                self.profiler.compiled(
+                    address_range,
+                    &instructions,
+                    ir,
+                    &self.codegen_context,
+                    pointer,
+                );
+            }
+            (
+                physical_program_counter..end_address,
+                Code::Jitted(pointer),
+                exits,
+                generations,
+            )
+        } else {
+            let address_range =
+                physical_program_counter..physical_program_counter + INSTRUCTION_SIZE as u32;
+            let generations = PageGenerations::new(bus, address_range.clone());
+            (
+                address_range,
+                Code::Interpreted(Instruction::decode(bus.read(physical_program_counter))),
+                Vec::new(),
+                generations,
+            )
+        };
+        unsafe {
+            (*core)
+                .mmu
+                .protect_fastmem_code(address_range.clone(), &bus.fastmem)
+        };
+        self.add(CacheEntry {
+            instructions: read_instructions(bus, address_range.clone()).collect(),
+            address_range,
+            code,
+            exits,
+            generations,
+        })
+    }
+
+    // The core is passed as a pointer as it owns the JIT. Compiled code calls back into it to
+    // interpret the instructions it has no lowering for.
+    pub fn cache_entry(
+        &mut self,
+        physical_program_counter: PhysicalAddress,
+        core: *mut Core,
+        bus: &Bus,
+    ) -> &CacheEntry {
+        let (state, mode) = unsafe { (&(*core).state, (*core).mode) };
         let cache_index = unsafe {
             self.jitted_starts
                 .get_unchecked(physical_program_counter.0 as usize / INSTRUCTION_SIZE)
         };
         let index = match cache_index.view() {
-            CacheIndexView::NotCached => {
-                let jit_compiler = JitCompiler::new(
-                    state,
-                    &self.isa,
-                    &mut self.codegen_context,
-                    &mut self.function_builder_context,
-                    mmu,
-                    bus,
-                );
-
-                let entry = if let Some(end_address) =
-                    jit_compiler.compile(physical_program_counter)
+            CacheIndexView::NotCached => self.compile_entry(physical_program_counter, core, bus),
+            CacheIndexView::Cached(index) => {
+                let entry = unsafe { self.cache.get_unchecked_mut(index as usize) };
+                if entry.generations.is_current(bus) {
+                    index
+                } else if read_instructions(bus, entry.address_range.clone())
+                    .eq(entry.instructions.iter().copied())
                 {
-                    // println!("Compiling {}", &self.codegen_context.func);
-                    let compiled_code = self
-                        .codegen_context
-                        .compile(self.isa.as_ref(), &mut ControlPlane::default())
-                        .unwrap();
-                    // println!("Compiled {}", &self.codegen_context.func);
-                    let pointer = self.executable_memory.allocate(compiled_code.code_buffer());
-                    let function =
-                        unsafe { std::mem::transmute::<*const u8, extern "C" fn(Mode)>(pointer) };
-                    CacheEntry {
-                        address_range: physical_program_counter..end_address,
-                        code: Code::Jitted(function),
-                    }
+                    // Only data sharing the pages was written.
+                    entry.generations.refresh(bus);
+                    index
                 } else {
-                    CacheEntry {
-                        address_range: physical_program_counter
-                            ..physical_program_counter + INSTRUCTION_SIZE as u32,
-                        code: Code::Interpreted(Instruction::decode(
-                            bus.read(physical_program_counter),
-                        )),
-                    }
-                };
-                self.add(entry)
+                    let address_range = entry.address_range.clone();
+                    self.invalidate_range(address_range);
+                    self.compile_entry(physical_program_counter, core, bus)
+                }
             }
-            CacheIndexView::Cached(index) => index,
         };
+        let exit = std::mem::replace(&mut self.dispatch.exit, std::ptr::null_mut());
+        // Blocks run one at a time to be checked or timed.
+        if !exit.is_null() && !self.config.lockstep && !self.profiler.times_blocks() {
+            self.link(exit, state.program_counter, mode, index);
+        }
         unsafe { self.cache.get_unchecked(index as usize) }
     }
 }
 
+// A block compiled into the codegen context, waiting to be emitted.
+struct CompiledBlock {
+    end_address: PhysicalAddress,
+    exits: Vec<Pin<Box<Exit>>>,
+    // The generations of the pages the block was compiled from.
+    generations: PageGenerations,
+}
+
+fn read_instructions(bus: &Bus, range: Range<PhysicalAddress>) -> impl Iterator<Item = u32> + '_ {
+    (range.start.0..range.end.0)
+        .step_by(INSTRUCTION_SIZE)
+        .map(|address| bus.read(PhysicalAddress(address)))
+}
+
 struct JitCompiler<'a> {
     function_builder: cranelift_frontend::FunctionBuilder<'a>,
+    core: *mut Core,
     state: &'a State,
     isa: &'a OwnedTargetIsa,
     mmu: &'a Mmu,
     bus: &'a Bus,
+    // The instruction being compiled.
+    address: PhysicalAddress,
     registers: EnumMap<Register, Option<RegisterState>>,
+    // Where to resume in the interpreter if the current instruction bails out.
+    restart_program_counter: Option<ir::Value>,
+    // The cycles of the instructions completed before the restart program counter, since the
+    // cycles were last consumed.
+    restart_cycles: i64,
+    // The registers modified before the restart program counter, which are all a bail-out stores,
+    // so that the restarted instruction sees them as they were.
+    restart_registers: Vec<(Register, ir::Value)>,
+    // The cycles of the instructions compiled since the cycles were last consumed, and of the
+    // current one.
+    cycles: i64,
+    instruction_cycles: i64,
+    // The cycles left when the cycles were last consumed, to leave the block in the middle once
+    // they run out.
+    cycles_left: Option<ir::Value>,
+    // The target of a branch back into the block, compiled as a loop.
+    loop_header: Option<PhysicalAddress>,
+    loop_block: Option<ir::Block>,
+    // Set in an idle loop by loads that miss fastmem. They may read devices, so the loop has to
+    // keep running them instead of giving up its cycles.
+    polled_device: Option<ir::StackSlot>,
+    dispatch: *mut Dispatch,
+    exits: Vec<Pin<Box<Exit>>>,
+    coverage: &'a mut Coverage,
+    // The registers passed from block to block, which are only written back when returning to the
+    // dispatcher or calling into the interpreter.
+    pinned_registers: &'static [Register],
+    // Whether fastmem stores bump the page generations like the bus does.
+    track_stores: bool,
+}
+
+// The signature of compiled blocks: the mode and the pinned registers in, whether the block
+// completed out.
+fn block_signature(pinned_registers: usize) -> Signature {
+    let mut signature = Signature::new(CallConv::Tail);
+    signature.params.push(ir::AbiParam::new(ir::types::I8)); // Mode
+    signature.params.extend(std::iter::repeat_n(
+        ir::AbiParam::new(ir::types::I64),
+        pinned_registers,
+    ));
+    signature.returns.push(ir::AbiParam::new(ir::types::I8)); // Completed
+    signature
+}
+
+// A branch waiting for its delay slot. Blocks end after their first branch and its delay slot.
+struct Branch {
+    // Whether a conditional branch is taken, evaluated before the delay slot.
+    condition: Option<ir::Value>,
+    target: ir::Value,
+    // The physical target of PC-relative branches.
+    target_address: Option<PhysicalAddress>,
+}
+
+impl Branch {
+    fn jump(target: ir::Value) -> Self {
+        Branch {
+            condition: None,
+            target,
+            target_address: None,
+        }
+    }
 }
 
 struct RegisterState {
@@ -284,31 +823,42 @@ impl Size {
 
 impl<'a> JitCompiler<'a> {
     pub fn new(
-        state: &'a State,
+        core: *mut Core,
         isa: &'a OwnedTargetIsa,
         codegen_context: &'a mut cranelift_codegen::Context,
         function_builder_context: &'a mut cranelift_frontend::FunctionBuilderContext,
-        mmu: &'a Mmu,
         bus: &'a Bus,
+        dispatch: *mut Dispatch,
+        coverage: &'a mut Coverage,
     ) -> Self {
         codegen_context.clear();
         let function_builder = cranelift_frontend::FunctionBuilder::new(
             &mut codegen_context.func,
             function_builder_context,
         );
-        function_builder.func.signature = Signature::new(isa.default_call_conv());
-        function_builder
-            .func
-            .signature
-            .params
-            .push(ir::AbiParam::new(ir::types::I8)); // Mode
         JitCompiler {
             function_builder,
-            state,
+            core,
+            state: unsafe { &(*core).state },
             isa,
-            mmu,
+            mmu: unsafe { &(*core).mmu },
             bus,
+            address: PhysicalAddress::default(),
             registers: EnumMap::default(),
+            restart_program_counter: None,
+            restart_cycles: 0,
+            restart_registers: Vec::new(),
+            cycles: 0,
+            instruction_cycles: 0,
+            cycles_left: None,
+            loop_header: None,
+            loop_block: None,
+            polled_device: None,
+            dispatch,
+            exits: Vec::new(),
+            coverage,
+            pinned_registers: &[],
+            track_stores: false,
         }
     }
 
@@ -336,8 +886,215 @@ impl<'a> JitCompiler<'a> {
         }
     }
 
+    // Leave the block if `succeeded` is false, writing back the registers as they were before the
+    // current instruction.
+    fn bail_out_unless(&mut self, succeeded: ir::Value) {
+        let bail_out_block = self.function_builder.create_block();
+        let continue_block = self.function_builder.create_block();
+        self.function_builder
+            .ins()
+            .brif(succeeded, continue_block, &[], bail_out_block, &[]);
+        self.function_builder.switch_to_block(bail_out_block);
+        for (register, value) in self.restart_registers.clone() {
+            self.store_register(register, value);
+        }
+        let restart_program_counter = self.restart_program_counter.unwrap();
+        self.store_program_counter(restart_program_counter);
+        self.consume_cycles(self.restart_cycles);
+        self.return_(false);
+        self.function_builder.switch_to_block(continue_block);
+    }
+
+    // Leave the block before the current instruction if the cycles ran out, so that the scheduler
+    // gets to run on time.
+    fn exit_unless_cycles_left(&mut self, program_counter: ir::Value) {
+        let out_of_cycles = self.function_builder.ins().icmp_imm(
+            ir::condcodes::IntCC::SignedLessThanOrEqual,
+            self.cycles_left.unwrap(),
+            self.cycles,
+        );
+        let out_of_cycles_block = self.function_builder.create_block();
+        let continue_block = self.function_builder.create_block();
+        self.function_builder.ins().brif(
+            out_of_cycles,
+            out_of_cycles_block,
+            &[],
+            continue_block,
+            &[],
+        );
+        self.function_builder.switch_to_block(out_of_cycles_block);
+        self.store_dirty_registers();
+        self.store_program_counter(program_counter);
+        self.consume_cycles(self.cycles);
+        self.return_(true);
+        self.function_builder.switch_to_block(continue_block);
+    }
+
+    // Stores the registers modified so far on a path leaving the block, keeping them cached for
+    // the rest of it.
+    fn store_dirty_registers(&mut self) {
+        for (register, value) in self.dirty_registers() {
+            self.store_register(register, value);
+        }
+    }
+
+    fn dirty_registers(&self) -> Vec<(Register, ir::Value)> {
+        Register::all()
+            .filter_map(|register| {
+                let state = self.registers[register].as_ref()?;
+                state.dirty.then_some((register, state.value))
+            })
+            .collect()
+    }
+
+    fn store_register(&mut self, register: Register, value: ir::Value) {
+        let register_address = self.register_address(register);
+        self.function_builder
+            .ins()
+            .store(ir::MemFlags::trusted(), value, register_address, 0);
+    }
+
+    fn dispatch_address(&mut self) -> ir::Value {
+        self.function_builder
+            .ins()
+            .iconst(ir::types::I64, self.dispatch as i64)
+    }
+
+    fn load_cycles_left(&mut self) -> ir::Value {
+        let dispatch_address = self.dispatch_address();
+        self.function_builder.ins().load(
+            ir::types::I64,
+            ir::MemFlags::trusted(),
+            dispatch_address,
+            offset_of!(Dispatch, cycles_left) as i32,
+        )
+    }
+
+    // Takes the cycles of the instructions run from the cycles left, returning what remains.
+    fn consume_cycles(&mut self, cycles: i64) -> ir::Value {
+        let cycles_left = self.load_cycles_left();
+        let dispatch_address = self.dispatch_address();
+        let offset = offset_of!(Dispatch, cycles_left) as i32;
+        let cycles_left = self.function_builder.ins().iadd_imm(cycles_left, -cycles);
+        self.function_builder.ins().store(
+            ir::MemFlags::trusted(),
+            cycles_left,
+            dispatch_address,
+            offset,
+        );
+        cycles_left
+    }
+
+    // Leave the block towards `program_counter`, jumping straight to the next block if the exit
+    // is linked to it. Registers other than the pinned ones must have been written back.
+    fn exit(&mut self, program_counter: ir::Value, cycles: i64, mode: ir::Value) {
+        let pinned_values = self.pinned_values();
+        self.store_program_counter(program_counter);
+        let cycles_left = self.consume_cycles(cycles);
+        let exit = Box::pin(Exit::default());
+        let exit_address = self
+            .function_builder
+            .ins()
+            .iconst(ir::types::I64, &*exit as *const Exit as i64);
+        self.exits.push(exit);
+        let return_block = self.function_builder.create_block();
+        let mut next_block = self.function_builder.create_block();
+        let has_cycles_left = self.function_builder.ins().icmp_imm(
+            ir::condcodes::IntCC::SignedGreaterThan,
+            cycles_left,
+            0,
+        );
+        self.function_builder
+            .ins()
+            .brif(has_cycles_left, next_block, &[], return_block, &[]);
+        for index in 0..EXIT_LINKS {
+            self.function_builder.switch_to_block(next_block);
+            let link_offset = (offset_of!(Exit, links) + index * size_of::<Link>()) as i32;
+            let load =
+                |function_builder: &mut cranelift_frontend::FunctionBuilder, type_, offset| {
+                    function_builder.ins().load(
+                        type_,
+                        ir::MemFlags::trusted(),
+                        exit_address,
+                        link_offset + offset as i32,
+                    )
+                };
+            let function = load(
+                &mut self.function_builder,
+                ir::types::I64,
+                offset_of!(Link, function),
+            );
+            let link_program_counter = load(
+                &mut self.function_builder,
+                ir::types::I32,
+                offset_of!(Link, program_counter),
+            );
+            let link_mode = load(
+                &mut self.function_builder,
+                ir::types::I8,
+                offset_of!(Link, mode),
+            );
+            let ins = self.function_builder.ins();
+            let linked = ins.icmp_imm(ir::condcodes::IntCC::NotEqual, function, 0);
+            let same_program_counter = self.function_builder.ins().icmp(
+                ir::condcodes::IntCC::Equal,
+                link_program_counter,
+                program_counter,
+            );
+            let same_mode =
+                self.function_builder
+                    .ins()
+                    .icmp(ir::condcodes::IntCC::Equal, link_mode, mode);
+            let matches = self
+                .function_builder
+                .ins()
+                .band(linked, same_program_counter);
+            let matches = self.function_builder.ins().band(matches, same_mode);
+            let jump_block = self.function_builder.create_block();
+            next_block = if index + 1 < EXIT_LINKS {
+                self.function_builder.create_block()
+            } else {
+                return_block
+            };
+            self.function_builder
+                .ins()
+                .brif(matches, jump_block, &[], next_block, &[]);
+            self.function_builder.switch_to_block(jump_block);
+            let signature_ref = self
+                .function_builder
+                .import_signature(block_signature(self.pinned_registers.len()));
+            let mut arguments = vec![mode];
+            arguments.extend_from_slice(&pinned_values);
+            self.function_builder
+                .ins()
+                .return_call_indirect(signature_ref, function, &arguments);
+        }
+        self.function_builder.switch_to_block(return_block);
+        self.store_pinned_values(&pinned_values);
+        let dispatch_address = self.dispatch_address();
+        self.function_builder.ins().store(
+            ir::MemFlags::trusted(),
+            exit_address,
+            dispatch_address,
+            offset_of!(Dispatch, exit) as i32,
+        );
+        self.return_(true);
+    }
+
+    fn return_(&mut self, completed: bool) {
+        let completed = self
+            .function_builder
+            .ins()
+            .iconst(ir::types::I8, completed as i64);
+        self.function_builder.ins().return_(&[completed]);
+    }
+
     fn get_register(&mut self, register: Register, size: Size) -> ir::Value {
         if register == Register::Zero {
+            if size == Size::S128 {
+                let zero = self.function_builder.ins().iconst(ir::types::I64, 0);
+                return self.function_builder.ins().uextend(ir::types::I128, zero);
+            }
             return self.function_builder.ins().iconst(size.type_(), 0);
         }
         if let Some(state) = &self.registers[register] {
@@ -429,15 +1186,31 @@ impl<'a> JitCompiler<'a> {
         );
     }
 
+    // The memory access trampolines return false if the access would raise an exception, leaving
+    // it to the interpreter to raise it. Stores to compiled code are left to the interpreter too,
+    // so that the code is invalidated once the block has returned.
     pub extern "C" fn jit_write_virtual<T: Bytes + LowerHex>(
-        mmu: &Mmu,
+        core: &Core,
         bus: &mut Bus,
         address: u32,
         value: T,
         mode: Mode,
-    ) {
-        let physical_address = mmu.virtual_to_physical(address, mode);
-        bus.write(physical_address, value)
+    ) -> bool {
+        let size = std::mem::size_of::<T>() as u32;
+        if address & (size - 1) != 0 {
+            return false;
+        }
+        let Some(physical_address) = core.mmu.virtual_to_physical_writable(address, mode) else {
+            return false;
+        };
+        if core
+            .jit
+            .contains_code(physical_address..physical_address + size)
+        {
+            return false;
+        }
+        bus.write_with_cache_mode(physical_address, value, core.mmu.cache_mode(address));
+        true
     }
 
     pub extern "C" fn jit_read_virtual<T: Bytes + LowerHex + Default>(
@@ -445,22 +1218,63 @@ impl<'a> JitCompiler<'a> {
         bus: &mut Bus,
         address: u32,
         mode: Mode,
-    ) -> T {
-        let physical_address = mmu.virtual_to_physical(address, mode);
-        bus.read(physical_address)
+        value: &mut T,
+    ) -> bool {
+        if address & (std::mem::size_of::<T>() as u32 - 1) != 0 {
+            return false;
+        }
+        let Some(physical_address) = mmu.virtual_to_physical(address, mode) else {
+            return false;
+        };
+        *value = bus.read_with_cache_mode(physical_address, mmu.cache_mode(address));
+        true
     }
 
+    // Reads through the fastmem window of the mode, falling back to the trampoline for misaligned
+    // accesses and for accesses that fault.
     fn load(&mut self, address: ir::Value, offset: u16, size: Size, mode: ir::Value) -> ir::Value {
         let offset: u64 = offset.sign_extend();
         let address = self.function_builder.ins().iadd_imm(address, offset as i64);
+        let slow_block = self.function_builder.create_block();
+        let done_block = self.function_builder.create_block();
+        self.function_builder
+            .append_block_param(done_block, size.type_());
+        if self.fastmem_allowed() {
+            let host_address = self.fastmem_address(address, size, mode, slow_block);
+            let value = self.function_builder.ins().load(
+                size.type_(),
+                ir::MemFlags::new(),
+                host_address,
+                0,
+            );
+            self.fall_back_on_fault(slow_block);
+            self.function_builder.ins().jump(done_block, &[value]);
+        } else {
+            self.function_builder.ins().jump(slow_block, &[]);
+        }
+        self.function_builder.switch_to_block(slow_block);
+        if let Some(polled_device) = self.polled_device {
+            let polled = self.function_builder.ins().iconst(ir::types::I8, 1);
+            self.function_builder
+                .ins()
+                .stack_store(polled, polled_device, 0);
+        }
+        let value = self.load_slow(address, size, mode);
+        self.function_builder.ins().jump(done_block, &[value]);
+        self.function_builder.switch_to_block(done_block);
+        self.function_builder.block_params(done_block)[0]
+    }
+
+    fn load_slow(&mut self, address: ir::Value, size: Size, mode: ir::Value) -> ir::Value {
         let mut signature = Signature::new(self.isa.default_call_conv());
         signature.params.extend_from_slice(&[
             ir::AbiParam::new(ir::types::I64),
             ir::AbiParam::new(ir::types::I64),
             ir::AbiParam::new(ir::types::I32),
             ir::AbiParam::new(ir::types::I8),
+            ir::AbiParam::new(ir::types::I64),
         ]);
-        signature.returns.push(ir::AbiParam::new(size.type_()));
+        signature.returns.push(ir::AbiParam::new(ir::types::I8));
         let signature_ref = self.function_builder.import_signature(signature);
 
         let function_ptr = match size {
@@ -482,14 +1296,31 @@ impl<'a> JitCompiler<'a> {
             .function_builder
             .ins()
             .iconst(ir::types::I64, self.bus as *const Bus as i64);
+        let value_slot = self
+            .function_builder
+            .create_sized_stack_slot(ir::StackSlotData::new(
+                ir::StackSlotKind::ExplicitSlot,
+                Size::S128.bits() as u32 / 8,
+                4,
+            ));
+        let value_ptr = self
+            .function_builder
+            .ins()
+            .stack_addr(ir::types::I64, value_slot, 0);
         let call = self.function_builder.ins().call_indirect(
             signature_ref,
             function_ptr,
-            &[mmu_ptr, bus_ptr, address, mode],
+            &[mmu_ptr, bus_ptr, address, mode, value_ptr],
         );
-        self.function_builder.inst_results(call)[0]
+        let succeeded = self.function_builder.inst_results(call)[0];
+        self.bail_out_unless(succeeded);
+        self.function_builder
+            .ins()
+            .stack_load(size.type_(), value_slot, 0)
     }
 
+    // Writes through the fastmem window of the mode like `load` reads. Pages holding compiled code
+    // are read only, so that stores to them fault too.
     fn store(
         &mut self,
         value: ir::Value,
@@ -500,74 +1331,1225 @@ impl<'a> JitCompiler<'a> {
     ) {
         let offset: u64 = offset.sign_extend();
         let address = self.function_builder.ins().iadd_imm(address, offset as i64);
+        let slow_block = self.function_builder.create_block();
+        let done_block = self.function_builder.create_block();
+        // The lockstep checker needs the page generations bumped like the bus does, which only
+        // the slow path knows how to.
+        if self.fastmem_allowed() && !self.track_stores {
+            let host_address = self.fastmem_address(address, size, mode, slow_block);
+            self.function_builder
+                .ins()
+                .store(ir::MemFlags::new(), value, host_address, 0);
+            self.fall_back_on_fault(slow_block);
+            self.function_builder.ins().jump(done_block, &[]);
+        } else {
+            self.function_builder.ins().jump(slow_block, &[]);
+        }
+        self.function_builder.switch_to_block(slow_block);
+        self.store_slow(value, address, size, mode);
+        self.function_builder.ins().jump(done_block, &[]);
+        self.function_builder.switch_to_block(done_block);
+    }
+
+    // Whether the accesses of the current instruction go through fastmem, i.e. haven't faulted
+    // before.
+    fn fastmem_allowed(&self) -> bool {
+        unsafe { !(*self.core).jit.slow_accesses.contains(&self.address) }
+    }
+
+    // The host address of an aligned access in the fastmem window of the mode. Jumps to
+    // `slow_block` for misaligned accesses, which the trampolines raise the address errors for.
+    fn fastmem_address(
+        &mut self,
+        address: ir::Value,
+        size: Size,
+        mode: ir::Value,
+        slow_block: ir::Block,
+    ) -> ir::Value {
+        let alignment_mask = size.bits() as i64 / 8 - 1;
+        if alignment_mask != 0 {
+            let misaligned = self
+                .function_builder
+                .ins()
+                .band_imm(address, alignment_mask);
+            let aligned_block = self.function_builder.create_block();
+            self.function_builder
+                .ins()
+                .brif(misaligned, slow_block, &[], aligned_block, &[]);
+            self.function_builder.switch_to_block(aligned_block);
+        }
+        let mode = self.function_builder.ins().uextend(ir::types::I64, mode);
+        let window_offset = self
+            .function_builder
+            .ins()
+            .ishl_imm(mode, fastmem::WINDOW_BITS as i64);
+        let base = self.host_address(self.bus.fastmem.base());
+        let window = self.function_builder.ins().iadd(base, window_offset);
+        let address = self.function_builder.ins().uextend(ir::types::I64, address);
+        self.function_builder.ins().iadd(window, address)
+    }
+
+    // Jumps to `slow_block` if the access just made faulted. The fault handler has let it complete
+    // on a scratch page, which is put back here before the slow path redoes the access. The
+    // instruction is compiled to the slow path once the block has returned, as its accesses are
+    // likely to go on faulting, e.g. because they reach devices.
+    fn fall_back_on_fault(&mut self, slow_block: ir::Block) {
+        // An atomic load, so that it isn't merged with the check after an earlier access.
+        let faulted_page = self.host_address(self.bus.fastmem.faulted_page());
+        let faulted_page = self.function_builder.ins().atomic_load(
+            ir::types::I64,
+            ir::MemFlags::trusted(),
+            faulted_page,
+        );
+        let fault_block = self.function_builder.create_block();
+        let continue_block = self.function_builder.create_block();
+        self.function_builder.set_cold_block(fault_block);
+        self.function_builder
+            .ins()
+            .brif(faulted_page, fault_block, &[], continue_block, &[]);
+        self.function_builder.switch_to_block(fault_block);
         let mut signature = Signature::new(self.isa.default_call_conv());
         signature.params.extend_from_slice(&[
             ir::AbiParam::new(ir::types::I64),
             ir::AbiParam::new(ir::types::I64),
             ir::AbiParam::new(ir::types::I32),
-            ir::AbiParam::new(size.type_()),
-            ir::AbiParam::new(ir::types::I8),
         ]);
         let signature_ref = self.function_builder.import_signature(signature);
-
-        let function_ptr = match size {
-            Size::S8 => Self::jit_write_virtual::<u8> as *const u8,
-            Size::S16 => Self::jit_write_virtual::<u16> as *const u8,
-            Size::S32 => Self::jit_write_virtual::<u32> as *const u8,
-            Size::S64 => Self::jit_write_virtual::<u64> as *const u8,
-            Size::S128 => Self::jit_write_virtual::<u128> as *const u8,
-        };
         let function_ptr = self
             .function_builder
             .ins()
-            .iconst(ir::types::I64, function_ptr as i64);
-        let mmu_ptr = self
+            .iconst(ir::types::I64, Self::jit_fastmem_fault as *const u8 as i64);
+        let core_ptr = self
             .function_builder
             .ins()
-            .iconst(ir::types::I64, self.mmu as *const Mmu as i64);
+            .iconst(ir::types::I64, self.core as i64);
         let bus_ptr = self
             .function_builder
             .ins()
             .iconst(ir::types::I64, self.bus as *const Bus as i64);
+        let address = self
+            .function_builder
+            .ins()
+            .iconst(ir::types::I32, self.address.0 as i64);
         self.function_builder.ins().call_indirect(
             signature_ref,
             function_ptr,
-            &[mmu_ptr, bus_ptr, address, value, mode],
+            &[core_ptr, bus_ptr, address],
         );
+        self.function_builder.ins().jump(slow_block, &[]);
+        self.function_builder.switch_to_block(continue_block);
     }
 
-    pub fn compile(mut self, mut address: PhysicalAddress) -> Option<PhysicalAddress> {
-        assert!(self.state.delayed_branch_target.is_none());
-        let block = self.function_builder.create_block();
-        self.function_builder
-            .append_block_params_for_function_params(block);
-        let mode = self.function_builder.block_params(block)[0];
-        self.function_builder.switch_to_block(block);
-        // println!("Compiling at {:#010x}", address.0);
-        let start_address = address;
-        let mut program_counter = self.load_program_counter();
-        let mut delayed_branch_target = None;
+    pub extern "C" fn jit_fastmem_fault(core: &mut Core, bus: &Bus, address: u32) {
+        core.restore_fastmem_page(bus);
+        core.jit.faulted_accesses.push(PhysicalAddress(address));
+    }
+
+    fn store_slow(&mut self, value: ir::Value, address: ir::Value, size: Size, mode: ir::Value) {
+        let mut signature = Signature::new(self.isa.default_call_conv());
+        signature.params.extend_from_slice(&[
+            ir::AbiParam::new(ir::types::I64),
+            ir::AbiParam::new(ir::types::I64),
+            ir::AbiParam::new(ir::types::I32),
+            ir::AbiParam::new(size.type_()),
+            ir::AbiParam::new(ir::types::I8),
+        ]);
+        signature.returns.push(ir::AbiParam::new(ir::types::I8));
+        let signature_ref = self.function_builder.import_signature(signature);
+
+        let function_ptr = match size {
+            Size::S8 => Self::jit_write_virtual::<u8> as *const u8,
+            Size::S16 => Self::jit_write_virtual::<u16> as *const u8,
+            Size::S32 => Self::jit_write_virtual::<u32> as *const u8,
+            Size::S64 => Self::jit_write_virtual::<u64> as *const u8,
+            Size::S128 => Self::jit_write_virtual::<u128> as *const u8,
+        };
+        let function_ptr = self
+            .function_builder
+            .ins()
+            .iconst(ir::types::I64, function_ptr as i64);
+        let core_ptr = self
+            .function_builder
+            .ins()
+            .iconst(ir::types::I64, self.core as i64);
+        let bus_ptr = self
+            .function_builder
+            .ins()
+            .iconst(ir::types::I64, self.bus as *const Bus as i64);
+        let call = self.function_builder.ins().call_indirect(
+            signature_ref,
+            function_ptr,
+            &[core_ptr, bus_ptr, address, value, mode],
+        );
+        let succeeded = self.function_builder.inst_results(call)[0];
+        self.bail_out_unless(succeeded);
+    }
+
+    // The naturally aligned unit containing an unaligned access. Returns its address and the bit
+    // offset of the access within it.
+    fn split_unaligned(
+        &mut self,
+        base: ir::Value,
+        offset: u16,
+        size: Size,
+    ) -> (ir::Value, ir::Value) {
+        let offset: u64 = offset.sign_extend();
+        let address = self.function_builder.ins().iadd_imm(base, offset as i64);
+        let bytes = size.bits() as i64 / 8;
+        let aligned = self.function_builder.ins().band_imm(address, !(bytes - 1));
+        let byte = self.function_builder.ins().band_imm(address, bytes - 1);
+        let shift = self.function_builder.ins().ishl_imm(byte, 3);
+        (aligned, shift)
+    }
+
+    // (register & (ONES >> 8 >> shift)) | (memory << (BITS - 8 - shift)), as done by LWL and LDL.
+    fn merge_left(
+        &mut self,
+        register: ir::Value,
+        memory: ir::Value,
+        shift: ir::Value,
+        size: Size,
+    ) -> ir::Value {
+        let low_ones = self
+            .function_builder
+            .ins()
+            .iconst(size.type_(), ((1u64 << (size.bits() - 8)) - 1) as i64);
+        let mask = self.function_builder.ins().ushr(low_ones, shift);
+        let width = self
+            .function_builder
+            .ins()
+            .iconst(ir::types::I32, size.bits() as i64 - 8);
+        let memory_shift = self.function_builder.ins().isub(width, shift);
+        let kept = self.function_builder.ins().band(register, mask);
+        let loaded = self.function_builder.ins().ishl(memory, memory_shift);
+        self.function_builder.ins().bor(kept, loaded)
+    }
+
+    // (register & (ONES << 8 << (BITS - 8 - shift))) | (memory >> shift), as done by LWR and LDR.
+    fn merge_right(
+        &mut self,
+        register: ir::Value,
+        memory: ir::Value,
+        shift: ir::Value,
+        size: Size,
+    ) -> ir::Value {
+        let low_byte = self.function_builder.ins().iconst(size.type_(), 0xFF);
+        let high_ones = self.function_builder.ins().bnot(low_byte);
+        let width = self
+            .function_builder
+            .ins()
+            .iconst(ir::types::I32, size.bits() as i64 - 8);
+        let mask_shift = self.function_builder.ins().isub(width, shift);
+        let mask = self.function_builder.ins().ishl(high_ones, mask_shift);
+        let kept = self.function_builder.ins().band(register, mask);
+        let loaded = self.function_builder.ins().ushr(memory, shift);
+        self.function_builder.ins().bor(kept, loaded)
+    }
+
+    // JAL, JALR and the linking branches return to the instruction after the delay slot.
+    fn link(&mut self, register: Register, next_program_counter: ir::Value) {
+        let return_address = self
+            .function_builder
+            .ins()
+            .iadd_imm(next_program_counter, INSTRUCTION_SIZE as i64);
+        let return_address = self
+            .function_builder
+            .ins()
+            .uextend(ir::types::I64, return_address);
+        self.set_register(register, return_address, Size::S64);
+    }
+
+    // Compares FCR31.C with zero.
+    fn fpu_condition(&mut self, condition: ir::condcodes::IntCC) -> ir::Value {
+        let address = self
+            .function_builder
+            .ins()
+            .iconst(ir::types::I64, self.state.fpu.control_address() as i64);
+        let control =
+            self.function_builder
+                .ins()
+                .load(ir::types::I32, ir::MemFlags::trusted(), address, 0);
+        let bit = self
+            .function_builder
+            .ins()
+            .band_imm(control, 1 << fpu::CONDITION);
+        self.function_builder.ins().icmp_imm(condition, bit, 0)
+    }
+
+    // A PC-relative branch. Likely branches leave the block right away when not taken, skipping
+    // the delay slot.
+    fn branch(
+        &mut self,
+        condition: ir::Value,
+        likely: bool,
+        next_program_counter: ir::Value,
+        offset: u16,
+        address: PhysicalAddress,
+        mode: ir::Value,
+    ) -> Branch {
+        let offset: u32 = offset.sign_extend();
+        let target = self
+            .function_builder
+            .ins()
+            .iadd_imm(next_program_counter, (offset << 2) as i64);
+        let target_address =
+            PhysicalAddress((address.0 + INSTRUCTION_SIZE as u32).wrapping_add(offset << 2));
+        if !likely {
+            return Branch {
+                condition: Some(condition),
+                target,
+                target_address: Some(target_address),
+            };
+        }
+        let not_taken = self
+            .function_builder
+            .ins()
+            .iadd_imm(next_program_counter, INSTRUCTION_SIZE as i64);
+        let not_taken_block = self.function_builder.create_block();
+        let taken_block = self.function_builder.create_block();
+        self.writeback_unpinned_registers();
+        self.function_builder
+            .ins()
+            .brif(condition, taken_block, &[], not_taken_block, &[]);
+        self.function_builder.switch_to_block(not_taken_block);
+        let cycles =
+            self.cycles + self.instruction_cycles + timing::NULLIFIED_DELAY_SLOT_CYCLES as i64;
+        self.exit(not_taken, cycles, mode);
+        self.function_builder.switch_to_block(taken_block);
+        Branch {
+            condition: None,
+            target,
+            target_address: Some(target_address),
+        }
+    }
+
+    // The target of the first branch of a block starting at `address`, if it branches back into
+    // the block.
+    fn find_loop_header(&self, start_address: PhysicalAddress) -> Option<PhysicalAddress> {
+        let mut address = start_address;
         loop {
-            let next_program_counter = delayed_branch_target.unwrap_or_else(|| {
+            let instruction = Instruction::decode(self.bus.read(address));
+            if instruction.is_branch() {
+                let offset: u32 = instruction.branch_offset()?.sign_extend();
+                let target = PhysicalAddress(
+                    (address.0 + INSTRUCTION_SIZE as u32).wrapping_add(offset << 2),
+                );
+                return (start_address..=address)
+                    .contains(&target)
+                    .then_some(target);
+            }
+            address += INSTRUCTION_SIZE as u32;
+            if address.0.is_multiple_of(PAGE_SIZE) {
+                return None;
+            }
+        }
+    }
+
+    // Whether the loop starting at `loop_header` only polls: it has no side effects and no
+    // register it writes carries over to the next iteration, so every iteration reads the same
+    // values and makes the same decision until something outside the core changes them.
+    fn is_idle_loop(&self, loop_header: PhysicalAddress) -> bool {
+        let mut written = Vec::new();
+        let mut read_first = Vec::new();
+        let mut address = loop_header;
+        let mut delay_slot = false;
+        for _ in 0..IDLE_LOOP_MAX_INSTRUCTIONS {
+            let instruction = Instruction::decode(self.bus.read(address));
+            if !instruction.is_side_effect_free() {
+                return false;
+            }
+            for occurrence in instruction.uses() {
+                if !written.contains(&occurrence) {
+                    read_first.push(occurrence);
+                }
+            }
+            written.extend(instruction.definitions());
+            if delay_slot {
+                return !written
+                    .iter()
+                    .any(|occurrence| read_first.contains(occurrence));
+            }
+            delay_slot = instruction.is_branch();
+            address += INSTRUCTION_SIZE as u32;
+        }
+        false
+    }
+
+    fn give_up_cycles(&mut self) {
+        let dispatch_address = self.dispatch_address();
+        let zero = self.function_builder.ins().iconst(ir::types::I64, 0);
+        self.function_builder.ins().store(
+            ir::MemFlags::trusted(),
+            zero,
+            dispatch_address,
+            offset_of!(Dispatch, cycles_left) as i32,
+        );
+        let idle = self.function_builder.ins().iconst(ir::types::I8, 1);
+        self.function_builder.ins().store(
+            ir::MemFlags::trusted(),
+            idle,
+            dispatch_address,
+            offset_of!(Dispatch, idle) as i32,
+        );
+    }
+
+    // Starts the loop body: registers are flushed so that the back edge can jump here with the
+    // program counter and the pinned registers as the only live values.
+    fn start_loop(&mut self, program_counter: ir::Value) -> ir::Value {
+        self.writeback_unpinned_registers();
+        let mut arguments = vec![program_counter];
+        arguments.extend(self.pinned_values());
+        self.registers = EnumMap::default();
+        if self.cycles > 0 {
+            self.consume_cycles(self.cycles);
+            self.cycles = 0;
+        }
+        let loop_block = self.function_builder.create_block();
+        self.function_builder
+            .append_block_param(loop_block, ir::types::I32);
+        for _ in self.pinned_registers {
+            self.function_builder
+                .append_block_param(loop_block, ir::types::I64);
+        }
+        self.function_builder.ins().jump(loop_block, &arguments);
+        self.function_builder.switch_to_block(loop_block);
+        self.loop_block = Some(loop_block);
+        self.cycles_left = Some(self.load_cycles_left());
+        let parameters = self.function_builder.block_params(loop_block).to_vec();
+        self.receive_pinned_values(&parameters[1..]);
+        parameters[0]
+    }
+
+    fn writeback_unpinned_registers(&mut self) {
+        for register in Register::all() {
+            if !self.pinned_registers.contains(&register) {
+                self.writeback_register(register);
+            }
+        }
+    }
+
+    // The lower doublewords of the pinned registers, to pass to the next block or iteration. This
+    // may be emitted on one of several paths, so it leaves the cached registers as they are.
+    fn pinned_values(&mut self) -> Vec<ir::Value> {
+        let pinned_registers = self.pinned_registers;
+        pinned_registers
+            .iter()
+            .map(|&register| {
+                let register_address = self.register_address(register);
+                let (value, size, dirty) = match &self.registers[register] {
+                    Some(state) => (Some(state.value), state.size, state.dirty),
+                    None => (None, Size::S64, false),
+                };
+                match value {
+                    Some(value) if size == Size::S64 => return value,
+                    // The upper doubleword is passed through memory.
+                    Some(value) if size > Size::S64 => {
+                        if dirty {
+                            self.function_builder.ins().store(
+                                ir::MemFlags::trusted(),
+                                value,
+                                register_address,
+                                0,
+                            );
+                        }
+                        return self.function_builder.ins().ireduce(ir::types::I64, value);
+                    }
+                    Some(value) if dirty => {
+                        self.function_builder.ins().store(
+                            ir::MemFlags::trusted(),
+                            value,
+                            register_address,
+                            0,
+                        );
+                    }
+                    _ => {}
+                }
+                self.function_builder.ins().load(
+                    ir::types::I64,
+                    ir::MemFlags::trusted(),
+                    register_address,
+                    0,
+                )
+            })
+            .collect()
+    }
+
+    // Caches the pinned registers received from the previous block, which are newer than memory.
+    fn receive_pinned_values(&mut self, values: &[ir::Value]) {
+        for (&register, &value) in self.pinned_registers.iter().zip(values) {
+            self.registers[register] = Some(RegisterState {
+                value,
+                size: Size::S64,
+                dirty: true,
+            });
+        }
+    }
+
+    fn store_pinned_values(&mut self, values: &[ir::Value]) {
+        for (&register, &value) in self.pinned_registers.iter().zip(values) {
+            let register_address = self.register_address(register);
+            self.function_builder
+                .ins()
+                .store(ir::MemFlags::trusted(), value, register_address, 0);
+        }
+    }
+
+    // SWL/SWR and SDL/SDR are the loads with the roles of register and memory swapped and the
+    // shift mirrored.
+    fn mirror_shift(&mut self, shift: ir::Value, size: Size) -> ir::Value {
+        let width = self
+            .function_builder
+            .ins()
+            .iconst(ir::types::I32, size.bits() as i64 - 8);
+        self.function_builder.ins().isub(width, shift)
+    }
+
+    // Runs an instruction without a lowering through the interpreter, from within the block. It
+    // must not raise exceptions or touch the JIT.
+    fn interpret(&mut self, raw_instruction: u32) {
+        for register in Register::all() {
+            self.writeback_register(register);
+        }
+        self.registers = EnumMap::default();
+        let mut signature = Signature::new(self.isa.default_call_conv());
+        signature.params.extend_from_slice(&[
+            ir::AbiParam::new(ir::types::I64),
+            ir::AbiParam::new(ir::types::I64),
+            ir::AbiParam::new(ir::types::I32),
+        ]);
+        let signature_ref = self.function_builder.import_signature(signature);
+        let function_ptr = self
+            .function_builder
+            .ins()
+            .iconst(ir::types::I64, Self::jit_interpret as *const u8 as i64);
+        let core_ptr = self
+            .function_builder
+            .ins()
+            .iconst(ir::types::I64, self.core as i64);
+        let bus_ptr = self
+            .function_builder
+            .ins()
+            .iconst(ir::types::I64, self.bus as *const Bus as i64);
+        let raw_instruction = self
+            .function_builder
+            .ins()
+            .iconst(ir::types::I32, raw_instruction as i64);
+        self.function_builder.ins().call_indirect(
+            signature_ref,
+            function_ptr,
+            &[core_ptr, bus_ptr, raw_instruction],
+        );
+    }
+
+    pub extern "C" fn jit_interpret(core: &mut Core, bus: &mut Bus, raw_instruction: u32) {
+        let instruction = Instruction::decode(raw_instruction);
+        if let Err(exception) = core.execute_instruction(instruction, bus) {
+            panic!(
+                "{:?} raised by {} from compiled code",
+                exception, instruction
+            );
+        }
+    }
+
+    fn host_address<T>(&mut self, address: *const T) -> ir::Value {
+        self.function_builder
+            .ins()
+            .iconst(ir::types::I64, address as i64)
+    }
+
+    fn load_host_word<T>(&mut self, address: *const T) -> ir::Value {
+        let address = self.host_address(address);
+        self.function_builder
+            .ins()
+            .load(ir::types::I32, ir::MemFlags::trusted(), address, 0)
+    }
+
+    fn store_host_word<T>(&mut self, address: *const T, value: ir::Value) {
+        let address = self.host_address(address);
+        self.function_builder
+            .ins()
+            .store(ir::MemFlags::trusted(), value, address, 0);
+    }
+
+    // The upper doubleword of a register, used by the pipeline 1 instructions.
+    fn get_upper(&mut self, register: Register) -> ir::Value {
+        let value = self.get_register(register, Size::S128);
+        self.function_builder.ins().isplit(value).1
+    }
+
+    fn set_upper(&mut self, register: Register, value: ir::Value) {
+        let lower = self.get_register(register, Size::S64);
+        let value = self.function_builder.ins().iconcat(lower, value);
+        self.set_register(register, value, Size::S128);
+    }
+
+    fn set_lo_hi(&mut self, lo: ir::Value, hi: ir::Value, upper: bool) {
+        if upper {
+            self.set_upper(Register::Lo, lo);
+            self.set_upper(Register::Hi, hi);
+        } else {
+            self.set_register(Register::Lo, lo, Size::S64);
+            self.set_register(Register::Hi, hi, Size::S64);
+        }
+    }
+
+    // The 64-bit product of the lower words of two registers.
+    fn product(&mut self, rs: Register, rt: Register, signed: bool) -> ir::Value {
+        let mut operands = [rs, rt].map(|register| self.get_register(register, Size::S32));
+        for operand in &mut operands {
+            *operand = if signed {
                 self.function_builder
                     .ins()
-                    .iadd_imm(program_counter, INSTRUCTION_SIZE as i64)
-            });
-            let instruction = Instruction::decode(self.bus.read(address));
-            // println!("Instruction: {:#010x} {}", address.0, instruction);
-            let delay_slot = delayed_branch_target.is_some();
+                    .sextend(ir::types::I64, *operand)
+            } else {
+                self.function_builder
+                    .ins()
+                    .uextend(ir::types::I64, *operand)
+            };
+        }
+        self.function_builder.ins().imul(operands[0], operands[1])
+    }
+
+    // HI:LO as a 64-bit accumulator, from the lower words of either half of the registers.
+    fn accumulator(&mut self, upper: bool) -> ir::Value {
+        let [lo, hi] = [Register::Lo, Register::Hi].map(|register| {
+            if upper {
+                self.get_upper(register)
+            } else {
+                self.get_register(register, Size::S64)
+            }
+        });
+        let lo = self.function_builder.ins().ireduce(ir::types::I32, lo);
+        let lo = self.function_builder.ins().uextend(ir::types::I64, lo);
+        let hi = self.function_builder.ins().ishl_imm(hi, 32);
+        self.function_builder.ins().bor(hi, lo)
+    }
+
+    // Splits a 64-bit result into sign extended words in LO and HI, with the lower one in `rd`.
+    fn set_product(&mut self, rd: Register, value: ir::Value, upper: bool) {
+        let lo = self.function_builder.ins().ireduce(ir::types::I32, value);
+        let lo = self.function_builder.ins().sextend(ir::types::I64, lo);
+        let hi = self.function_builder.ins().sshr_imm(value, 32);
+        let hi = self.function_builder.ins().ireduce(ir::types::I32, hi);
+        let hi = self.function_builder.ins().sextend(ir::types::I64, hi);
+        self.set_register(rd, lo, Size::S64);
+        self.set_lo_hi(lo, hi, upper);
+    }
+
+    // DIV and DIVU into LO and HI. Division by zero and overflow give the results the interpreter
+    // gives rather than trapping.
+    fn divide(&mut self, rs: Register, rt: Register, signed: bool, upper: bool) {
+        let dividend = self.get_register(rs, Size::S32);
+        let divisor = self.get_register(rt, Size::S32);
+        let ins = self.function_builder.ins();
+        let by_zero = ins.icmp_imm(ir::condcodes::IntCC::Equal, divisor, 0);
+        let invalid = if signed {
+            let minimum = self.function_builder.ins().icmp_imm(
+                ir::condcodes::IntCC::Equal,
+                dividend,
+                i32::MIN as u32 as i64,
+            );
+            let minus_one = self.function_builder.ins().icmp_imm(
+                ir::condcodes::IntCC::Equal,
+                divisor,
+                u32::MAX as i64,
+            );
+            let overflow = self.function_builder.ins().band(minimum, minus_one);
+            self.function_builder.ins().bor(by_zero, overflow)
+        } else {
+            by_zero
+        };
+        let one = self.function_builder.ins().iconst(ir::types::I32, 1);
+        let divisor = self.function_builder.ins().select(invalid, one, divisor);
+        let (quotient, remainder) = if signed {
+            (
+                self.function_builder.ins().sdiv(dividend, divisor),
+                self.function_builder.ins().srem(dividend, divisor),
+            )
+        } else {
+            (
+                self.function_builder.ins().udiv(dividend, divisor),
+                self.function_builder.ins().urem(dividend, divisor),
+            )
+        };
+        let by_zero_quotient = self.function_builder.ins().iconst(
+            ir::types::I32,
+            if signed {
+                i32::MAX as i64
+            } else {
+                u32::MAX as i64
+            },
+        );
+        let quotient = self
+            .function_builder
+            .ins()
+            .select(by_zero, by_zero_quotient, quotient);
+        let remainder = self
+            .function_builder
+            .ins()
+            .select(by_zero, dividend, remainder);
+        let quotient = self
+            .function_builder
+            .ins()
+            .sextend(ir::types::I64, quotient);
+        let remainder = self
+            .function_builder
+            .ins()
+            .sextend(ir::types::I64, remainder);
+        self.set_lo_hi(quotient, remainder, upper);
+    }
+
+    // MOVZ and MOVN: rd = rs if rt compares to zero with `condition`.
+    fn conditional_move(
+        &mut self,
+        rd: Register,
+        rs: Register,
+        rt: Register,
+        condition: ir::condcodes::IntCC,
+    ) {
+        let rt_value = self.get_register(rt, Size::S64);
+        let moved = self.function_builder.ins().icmp_imm(condition, rt_value, 0);
+        let rs_value = self.get_register(rs, Size::S64);
+        let rd_value = self.get_register(rd, Size::S64);
+        let value = self
+            .function_builder
+            .ins()
+            .select(moved, rs_value, rd_value);
+        self.set_register(rd, value, Size::S64);
+    }
+
+    // Leave the block before a trap instruction if `trapped`, for the interpreter to raise it.
+    // Adds or subtracts as signed integers, leaving the block on overflow for the interpreter to
+    // raise the exception.
+    fn trapping_arithmetic(&mut self, a: ir::Value, b: ir::Value, subtract: bool) -> ir::Value {
+        let (value, overflow) = if subtract {
+            self.function_builder.ins().ssub_overflow(a, b)
+        } else {
+            self.function_builder.ins().sadd_overflow(a, b)
+        };
+        let succeeded =
+            self.function_builder
+                .ins()
+                .icmp_imm(ir::condcodes::IntCC::Equal, overflow, 0);
+        self.bail_out_unless(succeeded);
+        value
+    }
+
+    fn trap(&mut self, rs: Register, rt: Register, trapped: ir::condcodes::IntCC) {
+        let rs_value = self.get_register(rs, Size::S64);
+        let rt_value = self.get_register(rt, Size::S64);
+        let succeeded = self
+            .function_builder
+            .ins()
+            .icmp(trapped.complement(), rs_value, rt_value);
+        self.bail_out_unless(succeeded);
+    }
+
+    // The multimedia instructions work on registers as vectors, which are loaded and stored in
+    // place rather than cached.
+    fn get_vector(&mut self, register: Register, type_: ir::Type) -> ir::Value {
+        self.writeback_register(register);
+        let register_address = self.register_address(register);
+        self.function_builder
+            .ins()
+            .load(type_, ir::MemFlags::trusted(), register_address, 0)
+    }
+
+    fn set_vector(&mut self, register: Register, value: ir::Value) {
+        if register == Register::Zero {
+            return;
+        }
+        self.registers[register] = None;
+        let register_address = self.register_address(register);
+        self.function_builder
+            .ins()
+            .store(ir::MemFlags::trusted(), value, register_address, 0);
+    }
+
+    fn cast_vector(&mut self, value: ir::Value, type_: ir::Type) -> ir::Value {
+        let flags = ir::MemFlags::new().with_endianness(ir::Endianness::Little);
+        self.function_builder.ins().bitcast(type_, flags, value)
+    }
+
+    fn splat(&mut self, type_: ir::Type, value: i64) -> ir::Value {
+        let lane = self.function_builder.ins().iconst(type_.lane_type(), value);
+        self.function_builder.ins().splat(type_, lane)
+    }
+
+    // Picks lanes of `lane_bytes` bytes from `a` (indices below the lane count) and `b` (the
+    // others), both byte vectors.
+    fn shuffle(
+        &mut self,
+        a: ir::Value,
+        b: ir::Value,
+        lane_bytes: usize,
+        lanes: &[usize],
+    ) -> ir::Value {
+        assert_eq!(lanes.len() * lane_bytes, 16);
+        let mask = lanes
+            .iter()
+            .flat_map(|lane| (0..lane_bytes).map(move |byte| (lane * lane_bytes + byte) as u8))
+            .collect::<Vec<_>>();
+        let mask = self
+            .function_builder
+            .func
+            .dfg
+            .immediates
+            .push(ir::ConstantData::from(mask));
+        self.function_builder.ins().shuffle(a, b, mask)
+    }
+
+    // The interleaving and packing instructions, picking lanes from rs and rt.
+    fn permute(
+        &mut self,
+        rd: Register,
+        rs: Register,
+        rt: Register,
+        lane_bytes: usize,
+        lanes: &[usize],
+    ) {
+        let a = self.get_vector(rs, ir::types::I8X16);
+        let b = self.get_vector(rt, ir::types::I8X16);
+        let value = self.shuffle(a, b, lane_bytes, lanes);
+        self.set_vector(rd, value);
+    }
+
+    fn lanewise(
+        &mut self,
+        rd: Register,
+        rs: Register,
+        rt: Register,
+        type_: ir::Type,
+        operation: impl FnOnce(&mut Self, ir::Value, ir::Value) -> ir::Value,
+    ) {
+        let a = self.get_vector(rs, type_);
+        let b = self.get_vector(rt, type_);
+        let value = operation(self, a, b);
+        self.set_vector(rd, value);
+    }
+
+    // Saturates the lanes of `sum`, the wrapping result of a signed addition or subtraction, where
+    // `overflow` has its sign bit set.
+    fn saturate_signed(
+        &mut self,
+        a: ir::Value,
+        sum: ir::Value,
+        overflow: ir::Value,
+        type_: ir::Type,
+    ) -> ir::Value {
+        let bits = type_.lane_bits() as i64;
+        let overflow = self.function_builder.ins().sshr_imm(overflow, bits - 1);
+        let sign = self.function_builder.ins().sshr_imm(a, bits - 1);
+        let maximum = self.splat(type_, (1 << (bits - 1)) - 1);
+        let saturated = self.function_builder.ins().bxor(sign, maximum);
+        self.function_builder
+            .ins()
+            .bitselect(overflow, saturated, sum)
+    }
+
+    fn shift_lanes(
+        &mut self,
+        rd: Register,
+        rt: Register,
+        type_: ir::Type,
+        operation: impl FnOnce(&mut Self, ir::Value) -> ir::Value,
+    ) {
+        let value = self.get_vector(rt, type_);
+        let value = operation(self, value);
+        self.set_vector(rd, value);
+    }
+
+    // Absolute values, saturating the most negative one.
+    fn absolute(&mut self, rd: Register, rt: Register, type_: ir::Type) {
+        let value = self.get_vector(rt, type_);
+        let value = self.function_builder.ins().iabs(value);
+        let overflow = self
+            .function_builder
+            .ins()
+            .sshr_imm(value, type_.lane_bits() as i64 - 1);
+        let value = self.function_builder.ins().bxor(value, overflow);
+        self.set_vector(rd, value);
+    }
+
+    fn fpu_register_address(&mut self, register: fpu::Register) -> *const f32 {
+        self.state.fpu.register_address(register)
+    }
+
+    // FPU registers aren't cached, they are loaded and stored as raw bits in place.
+    fn get_fpu_register(&mut self, register: fpu::Register) -> ir::Value {
+        let address = self.fpu_register_address(register);
+        self.load_host_word(address)
+    }
+
+    fn set_fpu_register(&mut self, register: fpu::Register, value: ir::Value) {
+        let address = self.fpu_register_address(register);
+        self.store_host_word(address, value);
+    }
+
+    fn get_fpu_accumulator(&mut self) -> ir::Value {
+        self.load_host_word(self.state.fpu.accumulator_address())
+    }
+
+    fn set_fpu_accumulator(&mut self, value: ir::Value) {
+        self.store_host_word(self.state.fpu.accumulator_address(), value);
+    }
+
+    fn update_fpu_control(&mut self, update: impl FnOnce(&mut Self, ir::Value) -> ir::Value) {
+        let address = self.state.fpu.control_address();
+        let control = self.load_host_word(address);
+        let control = update(self, control);
+        self.store_host_word(address, control);
+    }
+
+    fn clear_fpu_flags(&mut self, flags: &[usize]) {
+        let mask = flags.iter().fold(0u32, |mask, flag| mask | 1 << flag);
+        self.update_fpu_control(|jit, control| {
+            jit.function_builder.ins().band_imm(control, !mask as i64)
+        });
+    }
+
+    // Sets a flag and its sticky counterpart if `condition` holds.
+    fn raise_fpu_flag(&mut self, condition: ir::Value, flag: usize, sticky: usize) {
+        let flags = self
+            .function_builder
+            .ins()
+            .iconst(ir::types::I32, (1 << flag | 1 << sticky) as i64);
+        let zero = self.function_builder.ins().iconst(ir::types::I32, 0);
+        let flags = self.function_builder.ins().select(condition, flags, zero);
+        self.update_fpu_control(|jit, control| jit.function_builder.ins().bor(control, flags));
+    }
+
+    fn set_fpu_condition(&mut self, condition: ir::Value) {
+        let condition = self
+            .function_builder
+            .ins()
+            .uextend(ir::types::I32, condition);
+        let condition = self
+            .function_builder
+            .ins()
+            .ishl_imm(condition, fpu::CONDITION as i64);
+        self.update_fpu_control(|jit, control| {
+            let control = jit
+                .function_builder
+                .ins()
+                .band_imm(control, !(1u32 << fpu::CONDITION) as i64);
+            jit.function_builder.ins().bor(control, condition)
+        });
+    }
+
+    // The bits of a register as an f64 operand, like `fpu::operand`: denormals read as zero and
+    // the exponent 255 encodings as the largest magnitude.
+    fn fpu_operand(&mut self, bits: ir::Value) -> ir::Value {
+        let ins = self.function_builder.ins();
+        let sign = ins.band_imm(bits, 0x8000_0000);
+        let exponent = self.function_builder.ins().band_imm(bits, 0x7F80_0000);
+        let is_zero =
+            self.function_builder
+                .ins()
+                .icmp_imm(ir::condcodes::IntCC::Equal, exponent, 0);
+        let is_clamped = self.function_builder.ins().icmp_imm(
+            ir::condcodes::IntCC::Equal,
+            exponent,
+            0x7F80_0000,
+        );
+        let clamped = self.function_builder.ins().bor_imm(sign, 0x7F7F_FFFF);
+        let bits = self
+            .function_builder
+            .ins()
+            .select(is_clamped, clamped, bits);
+        let bits = self.function_builder.ins().select(is_zero, sign, bits);
+        let value = self
+            .function_builder
+            .ins()
+            .bitcast(ir::types::F32, ir::MemFlags::new(), bits);
+        self.function_builder.ins().fpromote(ir::types::F64, value)
+    }
+
+    fn get_fpu_operand(&mut self, register: fpu::Register) -> ir::Value {
+        let bits = self.get_fpu_register(register);
+        self.fpu_operand(bits)
+    }
+
+    // The sign bit of an f64 in single precision.
+    fn fpu_sign(&mut self, value: ir::Value) -> ir::Value {
+        let bits = self
+            .function_builder
+            .ins()
+            .bitcast(ir::types::I64, ir::MemFlags::new(), value);
+        let bits = self.function_builder.ins().ushr_imm(bits, 32);
+        let bits = self.function_builder.ins().ireduce(ir::types::I32, bits);
+        self.function_builder.ins().band_imm(bits, 0x8000_0000)
+    }
+
+    // Like `fpu::truncate`, returning bits: rounds to single precision toward zero.
+    fn fpu_truncate(&mut self, value: ir::Value) -> ir::Value {
+        let result = self.function_builder.ins().fdemote(ir::types::F32, value);
+        let widened = self.function_builder.ins().fpromote(ir::types::F64, result);
+        let widened = self.function_builder.ins().fabs(widened);
+        let magnitude = self.function_builder.ins().fabs(value);
+        let rounded_up = self.function_builder.ins().fcmp(
+            ir::condcodes::FloatCC::GreaterThan,
+            widened,
+            magnitude,
+        );
+        let bits = self
+            .function_builder
+            .ins()
+            .bitcast(ir::types::I32, ir::MemFlags::new(), result);
+        let truncated = self.function_builder.ins().iadd_imm(bits, -1);
+        self.function_builder
+            .ins()
+            .select(rounded_up, truncated, bits)
+    }
+
+    // Like `Fpu::round`, returning bits: rounds toward zero, clamping overflows and flushing
+    // underflows to zero.
+    fn fpu_round(&mut self, value: ir::Value) -> ir::Value {
+        let magnitude = self.function_builder.ins().fabs(value);
+        let maximum = self.function_builder.ins().f64const(f32::MAX as f64);
+        let overflow = self.function_builder.ins().fcmp(
+            ir::condcodes::FloatCC::GreaterThan,
+            magnitude,
+            maximum,
+        );
+        let sign = self.fpu_sign(value);
+        let clamped = self.function_builder.ins().bor_imm(sign, 0x7F7F_FFFF);
+        let bits = self.fpu_truncate(value);
+        let exponent = self.function_builder.ins().band_imm(bits, 0x7F80_0000);
+        let tiny = self
+            .function_builder
+            .ins()
+            .icmp_imm(ir::condcodes::IntCC::Equal, exponent, 0);
+        let zero = self.function_builder.ins().f64const(0.0);
+        let non_zero =
+            self.function_builder
+                .ins()
+                .fcmp(ir::condcodes::FloatCC::NotEqual, value, zero);
+        let underflow = self.function_builder.ins().band(tiny, non_zero);
+        let bits = self.function_builder.ins().select(underflow, sign, bits);
+        let bits = self.function_builder.ins().select(overflow, clamped, bits);
+        self.raise_fpu_flag(overflow, fpu::OVERFLOW, fpu::OVERFLOW_STICKY);
+        self.raise_fpu_flag(underflow, fpu::UNDERFLOW, fpu::UNDERFLOW_STICKY);
+        bits
+    }
+
+    // ADD.S, SUB.S and MUL.S, which clear the exponent flags before rounding.
+    fn fpu_arithmetic(
+        &mut self,
+        a: ir::Value,
+        b: ir::Value,
+        operation: fn(&mut Self, ir::Value, ir::Value) -> ir::Value,
+    ) -> ir::Value {
+        self.clear_fpu_flags(&[fpu::OVERFLOW, fpu::UNDERFLOW]);
+        let a = self.fpu_operand(a);
+        let b = self.fpu_operand(b);
+        let value = operation(self, a, b);
+        self.fpu_round(value)
+    }
+
+    fn fadd(&mut self, a: ir::Value, b: ir::Value) -> ir::Value {
+        self.function_builder.ins().fadd(a, b)
+    }
+
+    fn fsub(&mut self, a: ir::Value, b: ir::Value) -> ir::Value {
+        self.function_builder.ins().fsub(a, b)
+    }
+
+    fn fmul(&mut self, a: ir::Value, b: ir::Value) -> ir::Value {
+        self.function_builder.ins().fmul(a, b)
+    }
+
+    // MADD.S and MSUB.S: ACC +/- a * b, keeping the exponent flags raised by the product.
+    fn fpu_multiply_accumulate(
+        &mut self,
+        fs: fpu::Register,
+        ft: fpu::Register,
+        operation: fn(&mut Self, ir::Value, ir::Value) -> ir::Value,
+    ) -> ir::Value {
+        let a = self.get_fpu_register(fs);
+        let b = self.get_fpu_register(ft);
+        let product = self.fpu_arithmetic(a, b, Self::fmul);
+        let control = self.load_host_word(self.state.fpu.control_address());
+        let flags = self
+            .function_builder
+            .ins()
+            .band_imm(control, (1 << fpu::UNDERFLOW | 1 << fpu::OVERFLOW) as i64);
+        let accumulator = self.get_fpu_accumulator();
+        let value = self.fpu_arithmetic(accumulator, product, operation);
+        self.update_fpu_control(|jit, control| jit.function_builder.ins().bor(control, flags));
+        value
+    }
+
+    // DIV.S and RSQRT.S: a divided by `divisor`, derived from b. A zero b raises a flag and gives
+    // the largest magnitude. Zero divided by zero is invalid for DIV.S.
+    fn fpu_divide(
+        &mut self,
+        a: ir::Value,
+        b: ir::Value,
+        divisor: ir::Value,
+        zero_by_zero_invalid: bool,
+    ) -> ir::Value {
+        self.clear_fpu_flags(&[fpu::INVALID, fpu::DIVIDE]);
+        let zero = self.function_builder.ins().f64const(0.0);
+        let by_zero = self
+            .function_builder
+            .ins()
+            .fcmp(ir::condcodes::FloatCC::Equal, b, zero);
+        let one = self.function_builder.ins().f64const(1.0);
+        let divisor = self.function_builder.ins().select(by_zero, one, divisor);
+        let quotient = self.function_builder.ins().fdiv(a, divisor);
+        let bits = self.fpu_round(quotient);
+        let a_sign = self.fpu_sign(a);
+        let b_sign = self.fpu_sign(b);
+        let sign = self.function_builder.ins().bxor(a_sign, b_sign);
+        let clamped = self.function_builder.ins().bor_imm(sign, 0x7F7F_FFFF);
+        let divide = if zero_by_zero_invalid {
+            let a_zero = self
+                .function_builder
+                .ins()
+                .fcmp(ir::condcodes::FloatCC::Equal, a, zero);
+            let invalid = self.function_builder.ins().band(by_zero, a_zero);
+            self.raise_fpu_flag(invalid, fpu::INVALID, fpu::INVALID_STICKY);
+            let a_non_zero = self.function_builder.ins().bnot(a_zero);
+            self.function_builder.ins().band(by_zero, a_non_zero)
+        } else {
+            by_zero
+        };
+        self.raise_fpu_flag(divide, fpu::DIVIDE, fpu::DIVIDE_STICKY);
+        self.function_builder.ins().select(by_zero, clamped, bits)
+    }
+
+    fn fpu_compare(
+        &mut self,
+        fs: fpu::Register,
+        ft: fpu::Register,
+        condition: ir::condcodes::FloatCC,
+    ) {
+        let a = self.get_fpu_operand(fs);
+        let b = self.get_fpu_operand(ft);
+        let condition = self.function_builder.ins().fcmp(condition, a, b);
+        self.set_fpu_condition(condition);
+    }
+
+    // MAX.S and MIN.S compare as operands but return the register unchanged.
+    fn fpu_select(
+        &mut self,
+        fd: fpu::Register,
+        fs: fpu::Register,
+        ft: fpu::Register,
+        condition: ir::condcodes::FloatCC,
+    ) {
+        self.clear_fpu_flags(&[fpu::OVERFLOW, fpu::UNDERFLOW]);
+        let a = self.get_fpu_register(fs);
+        let b = self.get_fpu_register(ft);
+        let a_operand = self.fpu_operand(a);
+        let b_operand = self.fpu_operand(b);
+        let condition = self
+            .function_builder
+            .ins()
+            .fcmp(condition, a_operand, b_operand);
+        let value = self.function_builder.ins().select(condition, a, b);
+        self.set_fpu_register(fd, value);
+    }
+
+    // Returns None if the first instruction can't be compiled.
+    pub fn compile(
+        mut self,
+        mut address: PhysicalAddress,
+        config: JitConfig,
+    ) -> Option<CompiledBlock> {
+        assert!(self.state.delayed_branch_target.is_none());
+        self.pinned_registers = config.pinned_registers();
+        self.track_stores = config.lockstep;
+        self.function_builder.func.signature = block_signature(self.pinned_registers.len());
+        // The entry block checks the page generations, which are only known once the whole block
+        // has been compiled, so it is laid out first and filled last.
+        let entry_block = self.function_builder.create_block();
+        self.function_builder
+            .append_block_params_for_function_params(entry_block);
+        let parameters = self.function_builder.block_params(entry_block).to_vec();
+        let mode = parameters[0];
+        self.function_builder.func.layout.append_block(entry_block);
+        let block = self.function_builder.create_block();
+        self.function_builder.switch_to_block(block);
+        self.receive_pinned_values(&parameters[1..]);
+        debug!(target: "ee.jit", "Compiling at {:#010x}", address.0);
+        let start_address = address;
+        // Cycles are consumed at the loop header, counting restarts from there.
+        self.loop_header = self.find_loop_header(start_address);
+        let idle_loop = config.idle_loop_detection
+            && self
+                .loop_header
+                .is_some_and(|loop_header| self.is_idle_loop(loop_header));
+        self.cycles_left = Some(self.load_cycles_left());
+        let mut program_counter = self.load_program_counter();
+        let mut branch = None;
+        let mut delay_slot_compiled = false;
+        loop {
+            let delay_slot = branch.is_some();
+            if self.loop_header == Some(address) && !delay_slot {
+                program_counter = self.start_loop(program_counter);
+                if idle_loop {
+                    let polled_device =
+                        self.function_builder
+                            .create_sized_stack_slot(ir::StackSlotData::new(
+                                ir::StackSlotKind::ExplicitSlot,
+                                1,
+                                0,
+                            ));
+                    let polled = self.function_builder.ins().iconst(ir::types::I8, 0);
+                    self.function_builder
+                        .ins()
+                        .stack_store(polled, polled_device, 0);
+                    self.polled_device = Some(polled_device);
+                }
+            }
+            let next_program_counter = self
+                .function_builder
+                .ins()
+                .iadd_imm(program_counter, INSTRUCTION_SIZE as i64);
+            self.address = address;
+            let raw_instruction = self.bus.read(address);
+            let instruction = Instruction::decode(raw_instruction);
+            trace!(target: "ee.jit", "Instruction: {:#010x} {}", address.0, instruction);
             if delay_slot && instruction.is_branch() {
                 break;
             }
-            let mut next_delayed_branch_target = None;
-            let unhandled = || {
-                // println!("Unhandled instruction at {:#010x} {}", address.0, instruction);
-            };
+            if !delay_slot && address != start_address {
+                self.exit_unless_cycles_left(program_counter);
+            }
+            // An instruction in a delay slot is restarted from its branch.
+            if !delay_slot {
+                self.restart_cycles = self.cycles;
+                self.restart_registers = self.dirty_registers();
+                // Likely branches write their links back before the delay slot, so a bail-out
+                // there puts back the values they had.
+                let links = instruction
+                    .definitions()
+                    .filter(|_| instruction.is_branch())
+                    .filter_map(|occurrence| match occurrence {
+                        Occurrence::Core(register) => Some(register),
+                        _ => None,
+                    });
+                for register in links.collect::<Vec<_>>() {
+                    if !self.restart_registers.iter().any(|&(r, _)| r == register) {
+                        let value = self.get_register(register, Size::S64);
+                        self.restart_registers.push((register, value));
+                    }
+                }
+            }
+            self.instruction_cycles = instruction.cycles() as i64;
+            self.restart_program_counter = Some(if delay_slot {
+                self.function_builder
+                    .ins()
+                    .iadd_imm(program_counter, -(INSTRUCTION_SIZE as i64))
+            } else {
+                program_counter
+            });
+            let mut next_branch = None;
+            let mut lowering = Lowering::Native;
             match instruction {
                 _ if instruction.is_nop() => {}
-                Instruction::Unknown => {
-                    println!("Unknown instruction at {:#010x}", address.0)
-                }
                 Instruction::Sll(rd, rt, shamt) => {
                     let rt_value = self.get_register(rt, Size::S32);
                     let value = self.function_builder.ins().ishl_imm(rt_value, shamt as i64);
@@ -612,43 +2594,21 @@ impl<'a> JitCompiler<'a> {
                 }
                 Instruction::Jr(rs) => {
                     let target = self.get_register(rs, Size::S32);
-                    next_delayed_branch_target = Some(target);
+                    next_branch = Some(Branch::jump(target));
                 }
                 Instruction::Jalr(rd, rs) => {
                     let target = self.get_register(rs, Size::S32);
-                    next_delayed_branch_target = Some(target);
-
-                    let next_next_pc = self
-                        .function_builder
-                        .ins()
-                        .iadd_imm(next_program_counter, 4);
-                    let next_next_pc = self
-                        .function_builder
-                        .ins()
-                        .uextend(ir::types::I64, next_next_pc);
-                    self.set_register(rd, next_next_pc, Size::S64);
+                    next_branch = Some(Branch::jump(target));
+                    self.link(rd, next_program_counter);
                 }
                 Instruction::Movz(rd, rs, rt) => {
-                    // if self.get_register::<u64>(rt) == 0 {
-                    //     let value = self.get_register::<u64>(rs);
-                    //     self.set_register(rd, value);
-                    // }
-                    unhandled();
-                    break;
+                    self.conditional_move(rd, rs, rt, ir::condcodes::IntCC::Equal);
                 }
                 Instruction::Movn(rd, rs, rt) => {
-                    // if self.get_register::<u64>(rt) != 0 {
-                    //     let value = self.get_register::<u64>(rs);
-                    //     self.set_register(rd, value);
-                    // }
-                    unhandled();
-                    break;
-                }
-                Instruction::Syscall => {
-                    break;
+                    self.conditional_move(rd, rs, rt, ir::condcodes::IntCC::NotEqual);
                 }
-                Instruction::Break => {
-                    unhandled();
+                Instruction::Syscall | Instruction::Break | Instruction::Unknown => {
+                    self.coverage.record(instruction, Lowering::Fallback);
                     break;
                 }
                 Instruction::Sync => {
@@ -692,78 +2652,19 @@ impl<'a> JitCompiler<'a> {
                     self.set_register(rd, result, Size::S64);
                 }
                 Instruction::Mult(rd, rs, rt) => {
-                    let rs_value = self.get_register(rs, Size::S32);
-                    let rs_value = self
-                        .function_builder
-                        .ins()
-                        .sextend(ir::types::I64, rs_value);
-                    let rt_value = self.get_register(rt, Size::S32);
-                    let rt_value = self
-                        .function_builder
-                        .ins()
-                        .sextend(ir::types::I64, rt_value);
-                    let prod = self.function_builder.ins().imul(rs_value, rt_value);
-                    let lo = self.function_builder.ins().ireduce(ir::types::I32, prod);
-                    let lo = self.function_builder.ins().sextend(ir::types::I64, lo);
-                    let hi = self.function_builder.ins().ushr_imm(prod, 32);
-                    let hi = self.function_builder.ins().ireduce(ir::types::I32, hi);
-                    let hi = self.function_builder.ins().sextend(ir::types::I64, hi);
-                    self.set_register(rd, lo, Size::S64);
-                    self.set_register(Register::Lo, lo, Size::S64);
-                    self.set_register(Register::Hi, hi, Size::S64);
+                    let product = self.product(rs, rt, true);
+                    self.set_product(rd, product, false);
                 }
                 Instruction::Multu(rd, rs, rt) => {
-                    let rs_value = self.get_register(rs, Size::S32);
-                    let rs_value = self
-                        .function_builder
-                        .ins()
-                        .uextend(ir::types::I64, rs_value);
-                    let rt_value = self.get_register(rt, Size::S32);
-                    let rt_value = self
-                        .function_builder
-                        .ins()
-                        .uextend(ir::types::I64, rt_value);
-                    let prod = self.function_builder.ins().imul(rs_value, rt_value);
-                    let lo = self.function_builder.ins().ireduce(ir::types::I32, prod);
-                    let lo = self.function_builder.ins().sextend(ir::types::I64, lo);
-                    let hi = self.function_builder.ins().ushr_imm(prod, 32);
-                    let hi = self.function_builder.ins().ireduce(ir::types::I32, hi);
-                    let hi = self.function_builder.ins().sextend(ir::types::I64, hi);
-                    self.set_register(rd, lo, Size::S64);
-                    self.set_register(Register::Lo, lo, Size::S64);
-                    self.set_register(Register::Hi, hi, Size::S64);
-                }
-                Instruction::Div(rs, rt) => {
-                    // let dividend = self.get_register::<u32>(rs) as i32;
-                    // let divisor = self.get_register::<u32>(rt) as i32;
-                    // let (quotient, remainder) = match (dividend, divisor) {
-                    //     (_, 0) => (i32::MAX as _, dividend),
-                    //     (i32::MIN, -1) => (i32::MIN as _, 0),
-                    //     (dividend, divisor) => (dividend / divisor, dividend % divisor),
-                    // };
-                    // self.set_register::<u64>(Register::Lo, quotient.sign_extend());
-                    // self.set_register::<u64>(Register::Hi, remainder.sign_extend());
-                    unhandled();
-                    break;
-                }
-                Instruction::Divu(rs, rt) => {
-                    // let dividend = self.get_register::<u32>(rs);
-                    // let divisor = self.get_register::<u32>(rt);
-                    // let (quotient, remainder) = if divisor == 0 {
-                    //     (!0, dividend)
-                    // } else {
-                    //     (dividend / divisor, dividend % divisor)
-                    // };
-                    // self.set_register::<u64>(Register::Lo, quotient.sign_extend());
-                    // self.set_register::<u64>(Register::Hi, remainder.sign_extend());
-                    unhandled();
-                    break;
+                    let product = self.product(rs, rt, false);
+                    self.set_product(rd, product, false);
                 }
+                Instruction::Div(rs, rt) => self.divide(rs, rt, true, false),
+                Instruction::Divu(rs, rt) => self.divide(rs, rt, false, false),
                 Instruction::Add(rd, rs, rt) => {
-                    // TODO: Exception on overflow
                     let rs_value = self.get_register(rs, Size::S32);
                     let rt_value = self.get_register(rt, Size::S32);
-                    let value = self.function_builder.ins().iadd(rs_value, rt_value);
+                    let value = self.trapping_arithmetic(rs_value, rt_value, false);
                     let value = self.function_builder.ins().sextend(ir::types::I64, value);
                     self.set_register(rd, value, Size::S64);
                 }
@@ -775,10 +2676,9 @@ impl<'a> JitCompiler<'a> {
                     self.set_register(rd, value, Size::S64);
                 }
                 Instruction::Sub(rd, rs, rt) => {
-                    // TODO: Exception on overflow
                     let rs_value = self.get_register(rs, Size::S32);
                     let rt_value = self.get_register(rt, Size::S32);
-                    let value = self.function_builder.ins().isub(rs_value, rt_value);
+                    let value = self.trapping_arithmetic(rs_value, rt_value, true);
                     let value = self.function_builder.ins().sextend(ir::types::I64, value);
                     self.set_register(rd, value, Size::S64);
                 }
@@ -814,45 +2714,73 @@ impl<'a> JitCompiler<'a> {
                     let value = self.function_builder.ins().bnot(value);
                     self.set_register(rd, value, Size::S64);
                 }
-                Instruction::Mfsa(_) => todo!(),
-                Instruction::Mtsa(_) => todo!(),
+                Instruction::Mfsa(rd) => {
+                    let value = self.load_host_word(&self.state.shift_amount);
+                    let value = self.function_builder.ins().uextend(ir::types::I64, value);
+                    self.set_register(rd, value, Size::S64);
+                }
+                Instruction::Mtsa(rs) => {
+                    let value = self.get_register(rs, Size::S32);
+                    self.store_host_word(&self.state.shift_amount, value);
+                }
                 Instruction::Slt(rd, rs, rt) => {
-                    // let value = if (self.get_register::<u64>(rs) as i64)
-                    //     < (self.get_register::<u64>(rt) as i64)
-                    // {
-                    //     1
-                    // } else {
-                    //     0
-                    // };
-                    // self.set_register::<u64>(rd, value);
-                    unhandled();
-                    break;
+                    let rs_value = self.get_register(rs, Size::S64);
+                    let rt_value = self.get_register(rt, Size::S64);
+                    let value = self.function_builder.ins().icmp(
+                        ir::condcodes::IntCC::SignedLessThan,
+                        rs_value,
+                        rt_value,
+                    );
+                    let value = self.function_builder.ins().uextend(ir::types::I64, value);
+                    self.set_register(rd, value, Size::S64);
                 }
                 Instruction::Sltu(rd, rs, rt) => {
-                    // let value = if self.get_register::<u64>(rs) < self.get_register::<u64>(rt) {
-                    //     1
-                    // } else {
-                    //     0
-                    // };
-                    // self.set_register::<u64>(rd, value);
-                    unhandled();
-                    break;
+                    let rs_value = self.get_register(rs, Size::S64);
+                    let rt_value = self.get_register(rt, Size::S64);
+                    let value = self.function_builder.ins().icmp(
+                        ir::condcodes::IntCC::UnsignedLessThan,
+                        rs_value,
+                        rt_value,
+                    );
+                    let value = self.function_builder.ins().uextend(ir::types::I64, value);
+                    self.set_register(rd, value, Size::S64);
+                }
+                Instruction::Dadd(rd, rs, rt) => {
+                    let rs_value = self.get_register(rs, Size::S64);
+                    let rt_value = self.get_register(rt, Size::S64);
+                    let value = self.trapping_arithmetic(rs_value, rt_value, false);
+                    self.set_register(rd, value, Size::S64);
                 }
-                Instruction::Dadd(_, _, _) => todo!(),
                 Instruction::Daddu(rd, rs, rt) => {
                     let rs_value = self.get_register(rs, Size::S64);
                     let rt_value = self.get_register(rt, Size::S64);
                     let value = self.function_builder.ins().iadd(rs_value, rt_value);
                     self.set_register(rd, value, Size::S64);
                 }
-                Instruction::Dsub(_, _, _) => todo!(),
-                Instruction::Dsubu(_, _, _) => todo!(),
-                Instruction::Tge(_, _) => todo!(),
-                Instruction::Tgeu(_, _) => todo!(),
-                Instruction::Tlt(_, _) => todo!(),
-                Instruction::Tltu(_, _) => todo!(),
-                Instruction::Teq(_, _) => todo!(),
-                Instruction::Tne(_, _) => todo!(),
+                Instruction::Dsub(rd, rs, rt) => {
+                    let rs_value = self.get_register(rs, Size::S64);
+                    let rt_value = self.get_register(rt, Size::S64);
+                    let value = self.trapping_arithmetic(rs_value, rt_value, true);
+                    self.set_register(rd, value, Size::S64);
+                }
+                Instruction::Dsubu(rd, rs, rt) => {
+                    let rs_value = self.get_register(rs, Size::S64);
+                    let rt_value = self.get_register(rt, Size::S64);
+                    let value = self.function_builder.ins().isub(rs_value, rt_value);
+                    self.set_register(rd, value, Size::S64);
+                }
+                Instruction::Tge(rs, rt) => {
+                    self.trap(rs, rt, ir::condcodes::IntCC::SignedGreaterThanOrEqual)
+                }
+                Instruction::Tgeu(rs, rt) => {
+                    self.trap(rs, rt, ir::condcodes::IntCC::UnsignedGreaterThanOrEqual)
+                }
+                Instruction::Tlt(rs, rt) => self.trap(rs, rt, ir::condcodes::IntCC::SignedLessThan),
+                Instruction::Tltu(rs, rt) => {
+                    self.trap(rs, rt, ir::condcodes::IntCC::UnsignedLessThan)
+                }
+                Instruction::Teq(rs, rt) => self.trap(rs, rt, ir::condcodes::IntCC::Equal),
+                Instruction::Tne(rs, rt) => self.trap(rs, rt, ir::condcodes::IntCC::NotEqual),
                 Instruction::Dsll(rd, rt, shamt) => {
                     let rt_value = self.get_register(rt, Size::S64);
                     let value = self.function_builder.ins().ishl_imm(rt_value, shamt as i64);
@@ -893,24 +2821,136 @@ impl<'a> JitCompiler<'a> {
                     self.set_register(rd, value, Size::S64);
                 }
                 Instruction::Bltz(rs, offset) => {
-                    // if (self.get_register::<u64>(rs) as i64) < 0 {
-                    //     let offset: u32 = offset.sign_extend();
-                    //     self.set_delayed_branch_target(
-                    //         next_program_counter.wrapping_add(offset << 2),
-                    //     );
-                    // }
-                    unhandled();
-                    break;
+                    let rs_value = self.get_register(rs, Size::S64);
+                    let condition = self.function_builder.ins().icmp_imm(
+                        ir::condcodes::IntCC::SignedLessThan,
+                        rs_value,
+                        0,
+                    );
+                    next_branch = Some(self.branch(
+                        condition,
+                        false,
+                        next_program_counter,
+                        offset,
+                        address,
+                        mode,
+                    ));
+                }
+                Instruction::Bltzl(rs, offset) => {
+                    let rs_value = self.get_register(rs, Size::S64);
+                    let condition = self.function_builder.ins().icmp_imm(
+                        ir::condcodes::IntCC::SignedLessThan,
+                        rs_value,
+                        0,
+                    );
+                    next_branch = Some(self.branch(
+                        condition,
+                        true,
+                        next_program_counter,
+                        offset,
+                        address,
+                        mode,
+                    ));
+                }
+                Instruction::Bltzal(rs, offset) => {
+                    let rs_value = self.get_register(rs, Size::S64);
+                    let condition = self.function_builder.ins().icmp_imm(
+                        ir::condcodes::IntCC::SignedLessThan,
+                        rs_value,
+                        0,
+                    );
+                    self.link(Register::Ra, next_program_counter);
+                    next_branch = Some(self.branch(
+                        condition,
+                        false,
+                        next_program_counter,
+                        offset,
+                        address,
+                        mode,
+                    ));
+                }
+                Instruction::Bltzall(rs, offset) => {
+                    let rs_value = self.get_register(rs, Size::S64);
+                    let condition = self.function_builder.ins().icmp_imm(
+                        ir::condcodes::IntCC::SignedLessThan,
+                        rs_value,
+                        0,
+                    );
+                    self.link(Register::Ra, next_program_counter);
+                    next_branch = Some(self.branch(
+                        condition,
+                        true,
+                        next_program_counter,
+                        offset,
+                        address,
+                        mode,
+                    ));
                 }
                 Instruction::Bgez(rs, offset) => {
-                    // if self.get_register::<u64>(rs) as i64 >= 0 {
-                    //     let offset: u32 = offset.sign_extend();
-                    //     self.set_delayed_branch_target(
-                    //         next_program_counter.wrapping_add(offset << 2),
-                    //     );
-                    // }
-                    unhandled();
-                    break;
+                    let rs_value = self.get_register(rs, Size::S64);
+                    let condition = self.function_builder.ins().icmp_imm(
+                        ir::condcodes::IntCC::SignedGreaterThanOrEqual,
+                        rs_value,
+                        0,
+                    );
+                    next_branch = Some(self.branch(
+                        condition,
+                        false,
+                        next_program_counter,
+                        offset,
+                        address,
+                        mode,
+                    ));
+                }
+                Instruction::Bgezl(rs, offset) => {
+                    let rs_value = self.get_register(rs, Size::S64);
+                    let condition = self.function_builder.ins().icmp_imm(
+                        ir::condcodes::IntCC::SignedGreaterThanOrEqual,
+                        rs_value,
+                        0,
+                    );
+                    next_branch = Some(self.branch(
+                        condition,
+                        true,
+                        next_program_counter,
+                        offset,
+                        address,
+                        mode,
+                    ));
+                }
+                Instruction::Bgezal(rs, offset) => {
+                    let rs_value = self.get_register(rs, Size::S64);
+                    let condition = self.function_builder.ins().icmp_imm(
+                        ir::condcodes::IntCC::SignedGreaterThanOrEqual,
+                        rs_value,
+                        0,
+                    );
+                    self.link(Register::Ra, next_program_counter);
+                    next_branch = Some(self.branch(
+                        condition,
+                        false,
+                        next_program_counter,
+                        offset,
+                        address,
+                        mode,
+                    ));
+                }
+                Instruction::Bgezall(rs, offset) => {
+                    let rs_value = self.get_register(rs, Size::S64);
+                    let condition = self.function_builder.ins().icmp_imm(
+                        ir::condcodes::IntCC::SignedGreaterThanOrEqual,
+                        rs_value,
+                        0,
+                    );
+                    self.link(Register::Ra, next_program_counter);
+                    next_branch = Some(self.branch(
+                        condition,
+                        true,
+                        next_program_counter,
+                        offset,
+                        address,
+                        mode,
+                    ));
                 }
                 Instruction::J(target) => {
                     let upper_next_pc = self
@@ -921,7 +2961,7 @@ impl<'a> JitCompiler<'a> {
                         .function_builder
                         .ins()
                         .iadd_imm(upper_next_pc, (target << 2) as i64);
-                    next_delayed_branch_target = Some(target);
+                    next_branch = Some(Branch::jump(target));
                 }
                 Instruction::Jal(target) => {
                     let upper_next_pc = self
@@ -932,88 +2972,114 @@ impl<'a> JitCompiler<'a> {
                         .function_builder
                         .ins()
                         .iadd_imm(upper_next_pc, (target << 2) as i64);
-                    next_delayed_branch_target = Some(target);
-                    let next_next_pc = self
-                        .function_builder
-                        .ins()
-                        .iadd_imm(next_program_counter, 4);
-                    let next_next_pc = self
-                        .function_builder
-                        .ins()
-                        .uextend(ir::types::I64, next_next_pc);
-                    self.set_register(Register::Ra, next_next_pc, Size::S64);
+                    next_branch = Some(Branch::jump(target));
+                    self.link(Register::Ra, next_program_counter);
                 }
                 Instruction::Beq(rs, rt, offset) => {
-                    let offset: u32 = offset.sign_extend();
                     let rs_value = self.get_register(rs, Size::S64);
                     let rt_value = self.get_register(rt, Size::S64);
-                    let conditional = self.function_builder.ins().icmp(
+                    let condition = self.function_builder.ins().icmp(
                         ir::condcodes::IntCC::Equal,
                         rs_value,
                         rt_value,
                     );
-                    let taken = self
-                        .function_builder
-                        .ins()
-                        .iadd_imm(next_program_counter, (offset << 2) as i64);
-                    let not_taken = self
-                        .function_builder
-                        .ins()
-                        .iadd_imm(next_program_counter, INSTRUCTION_SIZE as i64);
-                    let target = self
-                        .function_builder
-                        .ins()
-                        .select(conditional, taken, not_taken);
-                    next_delayed_branch_target = Some(target);
+                    next_branch = Some(self.branch(
+                        condition,
+                        false,
+                        next_program_counter,
+                        offset,
+                        address,
+                        mode,
+                    ));
                 }
                 Instruction::Bne(rs, rt, offset) => {
-                    let offset: u32 = offset.sign_extend();
                     let rs_value = self.get_register(rs, Size::S64);
                     let rt_value = self.get_register(rt, Size::S64);
-                    let conditional = self.function_builder.ins().icmp(
+                    let condition = self.function_builder.ins().icmp(
                         ir::condcodes::IntCC::NotEqual,
-                        rs_value,
-                        rt_value,
-                    );
-                    let taken = self
-                        .function_builder
-                        .ins()
-                        .iadd_imm(next_program_counter, (offset << 2) as i64);
-                    let not_taken = self
-                        .function_builder
-                        .ins()
-                        .iadd_imm(next_program_counter, INSTRUCTION_SIZE as i64);
-                    let target = self
-                        .function_builder
-                        .ins()
-                        .select(conditional, taken, not_taken);
-                    next_delayed_branch_target = Some(target);
+                        rs_value,
+                        rt_value,
+                    );
+                    next_branch = Some(self.branch(
+                        condition,
+                        false,
+                        next_program_counter,
+                        offset,
+                        address,
+                        mode,
+                    ));
                 }
                 Instruction::Blez(rs, offset) => {
-                    // if (self.get_register::<u64>(rs) as i64) <= 0 {
-                    //     let offset: u32 = offset.sign_extend();
-                    //     self.set_delayed_branch_target(
-                    //         next_program_counter.wrapping_add(offset << 2),
-                    //     );
-                    // }
-                    unhandled();
-                    break;
+                    let rs_value = self.get_register(rs, Size::S64);
+                    let condition = self.function_builder.ins().icmp_imm(
+                        ir::condcodes::IntCC::SignedLessThanOrEqual,
+                        rs_value,
+                        0,
+                    );
+                    next_branch = Some(self.branch(
+                        condition,
+                        false,
+                        next_program_counter,
+                        offset,
+                        address,
+                        mode,
+                    ));
+                }
+                Instruction::Blezl(rs, offset) => {
+                    let rs_value = self.get_register(rs, Size::S64);
+                    let condition = self.function_builder.ins().icmp_imm(
+                        ir::condcodes::IntCC::SignedLessThanOrEqual,
+                        rs_value,
+                        0,
+                    );
+                    next_branch = Some(self.branch(
+                        condition,
+                        true,
+                        next_program_counter,
+                        offset,
+                        address,
+                        mode,
+                    ));
                 }
                 Instruction::Bgtz(rs, offset) => {
-                    // if (self.get_register::<u64>(rs) as i64) > 0 {
-                    //     let offset: u32 = offset.sign_extend();
-                    //     self.set_delayed_branch_target(
-                    //         next_program_counter.wrapping_add(offset << 2),
-                    //     );
-                    // }
-                    unhandled();
-                    break;
+                    let rs_value = self.get_register(rs, Size::S64);
+                    let condition = self.function_builder.ins().icmp_imm(
+                        ir::condcodes::IntCC::SignedGreaterThan,
+                        rs_value,
+                        0,
+                    );
+                    next_branch = Some(self.branch(
+                        condition,
+                        false,
+                        next_program_counter,
+                        offset,
+                        address,
+                        mode,
+                    ));
+                }
+                Instruction::Bgtzl(rs, offset) => {
+                    let rs_value = self.get_register(rs, Size::S64);
+                    let condition = self.function_builder.ins().icmp_imm(
+                        ir::condcodes::IntCC::SignedGreaterThan,
+                        rs_value,
+                        0,
+                    );
+                    next_branch = Some(self.branch(
+                        condition,
+                        true,
+                        next_program_counter,
+                        offset,
+                        address,
+                        mode,
+                    ));
                 }
                 Instruction::Addi(rt, rs, imm) => {
-                    // TODO exception on overflow
                     let rs_value = self.get_register(rs, Size::S32);
-                    let imm: u64 = imm.sign_extend();
-                    let value = self.function_builder.ins().iadd_imm(rs_value, imm as i64);
+                    let imm = self
+                        .function_builder
+                        .ins()
+                        .iconst(ir::types::I32, imm as i16 as i64);
+                    let value = self.trapping_arithmetic(rs_value, imm, false);
                     let value = self
                         .function_builder
                         .ins()
@@ -1075,142 +3141,273 @@ impl<'a> JitCompiler<'a> {
                         .iconst(ir::types::I64, value as i64);
                     self.set_register(rt, value, Size::S64);
                 }
-                Instruction::Mfc0(rt, rs) => {
-                    // let value = self.state.control.get_register(rs);
-                    // self.set_register::<u64>(rt, value.sign_extend());
-                    unhandled();
-                    break;
+                Instruction::Mfc0(_, _) => {
+                    self.interpret(raw_instruction);
+                    lowering = Lowering::Helper;
                 }
-                Instruction::Mtc0(rt, rs) => {
-                    // let value = self.get_register(rt);
-                    // self.state.control.set_register(rs, value);
-                    unhandled();
+                Instruction::Mtc0(_, _) => {
+                    self.coverage.record(instruction, Lowering::Fallback);
                     break;
                 }
                 Instruction::Mfc1(rt, fs) => {
-                    // let value = self.fpu.get_register::<u32>(fs);
-                    // self.set_register::<u64>(rt, value.sign_extend());
-                    unhandled();
-                    break;
+                    let value = self.get_fpu_register(fs);
+                    let value = self.function_builder.ins().sextend(ir::types::I64, value);
+                    self.set_register(rt, value, Size::S64);
                 }
-                Instruction::Mtc1(rt, fs) => {
-                    // let value = self.get_register::<u32>(rt);
-                    // self.fpu.set_register(fs, value);
-                    unhandled();
-                    break;
+                Instruction::Mtc1(fs, rt) => {
+                    let value = self.get_register(rt, Size::S32);
+                    self.set_fpu_register(fs, value);
+                }
+                Instruction::Adds(fd, fs, ft) => {
+                    let a = self.get_fpu_register(fs);
+                    let b = self.get_fpu_register(ft);
+                    let value = self.fpu_arithmetic(a, b, Self::fadd);
+                    self.set_fpu_register(fd, value);
+                }
+                Instruction::Subs(fd, fs, ft) => {
+                    let a = self.get_fpu_register(fs);
+                    let b = self.get_fpu_register(ft);
+                    let value = self.fpu_arithmetic(a, b, Self::fsub);
+                    self.set_fpu_register(fd, value);
                 }
                 Instruction::Muls(fd, fs, ft) => {
-                    //     self.fpu.set_register(
-                    //     fd,
-                    //     self.fpu.get_register::<f32>(fs) * self.fpu.get_register::<f32>(ft),
-                    // )
-                    unhandled();
-                    break;
+                    let a = self.get_fpu_register(fs);
+                    let b = self.get_fpu_register(ft);
+                    let value = self.fpu_arithmetic(a, b, Self::fmul);
+                    self.set_fpu_register(fd, value);
                 }
-                // TODO flags
                 Instruction::Divs(fd, fs, ft) => {
-                    //     self.fpu.set_register(
-                    //     fd,
-                    //     self.fpu.get_register::<f32>(fs) / self.fpu.get_register::<f32>(ft),
-                    // )
-                    unhandled();
-                    break;
+                    let a = self.get_fpu_operand(fs);
+                    let b = self.get_fpu_operand(ft);
+                    let value = self.fpu_divide(a, b, b, true);
+                    self.set_fpu_register(fd, value);
+                }
+                Instruction::Sqrts(fd, ft) => {
+                    self.clear_fpu_flags(&[fpu::INVALID, fpu::DIVIDE]);
+                    let a = self.get_fpu_operand(ft);
+                    let zero = self.function_builder.ins().f64const(0.0);
+                    let negative =
+                        self.function_builder
+                            .ins()
+                            .fcmp(ir::condcodes::FloatCC::LessThan, a, zero);
+                    self.raise_fpu_flag(negative, fpu::INVALID, fpu::INVALID_STICKY);
+                    // The square root of -0 is -0, negative inputs otherwise use their magnitude.
+                    let is_zero =
+                        self.function_builder
+                            .ins()
+                            .fcmp(ir::condcodes::FloatCC::Equal, a, zero);
+                    let magnitude = self.function_builder.ins().fabs(a);
+                    let root = self.function_builder.ins().sqrt(magnitude);
+                    let root = self.fpu_round(root);
+                    let single = self.function_builder.ins().fdemote(ir::types::F32, a);
+                    let single = self.function_builder.ins().bitcast(
+                        ir::types::I32,
+                        ir::MemFlags::new(),
+                        single,
+                    );
+                    let value = self.function_builder.ins().select(is_zero, single, root);
+                    self.set_fpu_register(fd, value);
+                }
+                Instruction::Rsqrts(fd, fs, ft) => {
+                    let a = self.get_fpu_operand(fs);
+                    let b = self.get_fpu_operand(ft);
+                    let magnitude = self.function_builder.ins().fabs(b);
+                    let root = self.function_builder.ins().sqrt(magnitude);
+                    let value = self.fpu_divide(a, b, root, false);
+                    let zero = self.function_builder.ins().f64const(0.0);
+                    let negative =
+                        self.function_builder
+                            .ins()
+                            .fcmp(ir::condcodes::FloatCC::LessThan, b, zero);
+                    self.raise_fpu_flag(negative, fpu::INVALID, fpu::INVALID_STICKY);
+                    self.set_fpu_register(fd, value);
+                }
+                Instruction::Abss(fd, fs) => {
+                    self.clear_fpu_flags(&[fpu::OVERFLOW, fpu::UNDERFLOW]);
+                    let value = self.get_fpu_register(fs);
+                    let value = self.function_builder.ins().band_imm(value, 0x7FFF_FFFF);
+                    self.set_fpu_register(fd, value);
+                }
+                Instruction::Negs(fd, fs) => {
+                    self.clear_fpu_flags(&[fpu::OVERFLOW, fpu::UNDERFLOW]);
+                    let value = self.get_fpu_register(fs);
+                    let value = self.function_builder.ins().bxor_imm(value, 0x8000_0000);
+                    self.set_fpu_register(fd, value);
                 }
-                // TODO flags
                 Instruction::Movs(fd, fs) => {
-                    // let value = self.fpu.get_register::<f32>(fs);
-                    // self.fpu.set_register(fd, value);
-                    unhandled();
-                    break;
+                    let value = self.get_fpu_register(fs);
+                    self.set_fpu_register(fd, value);
+                }
+                Instruction::Addas(fs, ft) => {
+                    let a = self.get_fpu_register(fs);
+                    let b = self.get_fpu_register(ft);
+                    let value = self.fpu_arithmetic(a, b, Self::fadd);
+                    self.set_fpu_accumulator(value);
+                }
+                Instruction::Subas(fs, ft) => {
+                    let a = self.get_fpu_register(fs);
+                    let b = self.get_fpu_register(ft);
+                    let value = self.fpu_arithmetic(a, b, Self::fsub);
+                    self.set_fpu_accumulator(value);
+                }
+                Instruction::Mulas(fs, ft) => {
+                    let a = self.get_fpu_register(fs);
+                    let b = self.get_fpu_register(ft);
+                    let value = self.fpu_arithmetic(a, b, Self::fmul);
+                    self.set_fpu_accumulator(value);
+                }
+                Instruction::Madds(fd, fs, ft) => {
+                    let value = self.fpu_multiply_accumulate(fs, ft, Self::fadd);
+                    self.set_fpu_register(fd, value);
+                }
+                Instruction::Msubs(fd, fs, ft) => {
+                    let value = self.fpu_multiply_accumulate(fs, ft, Self::fsub);
+                    self.set_fpu_register(fd, value);
+                }
+                Instruction::Maddas(fs, ft) => {
+                    let value = self.fpu_multiply_accumulate(fs, ft, Self::fadd);
+                    self.set_fpu_accumulator(value);
+                }
+                Instruction::Msubas(fs, ft) => {
+                    let value = self.fpu_multiply_accumulate(fs, ft, Self::fsub);
+                    self.set_fpu_accumulator(value);
+                }
+                Instruction::Maxs(fd, fs, ft) => {
+                    self.fpu_select(fd, fs, ft, ir::condcodes::FloatCC::GreaterThanOrEqual)
+                }
+                Instruction::Mins(fd, fs, ft) => {
+                    self.fpu_select(fd, fs, ft, ir::condcodes::FloatCC::LessThanOrEqual)
+                }
+                Instruction::Cfs(_, _) => {
+                    let condition = self.function_builder.ins().iconst(ir::types::I8, 0);
+                    self.set_fpu_condition(condition);
+                }
+                Instruction::Ceqs(fs, ft) => {
+                    self.fpu_compare(fs, ft, ir::condcodes::FloatCC::Equal)
+                }
+                Instruction::Clts(fs, ft) => {
+                    self.fpu_compare(fs, ft, ir::condcodes::FloatCC::LessThan)
+                }
+                Instruction::Cles(fs, ft) => {
+                    self.fpu_compare(fs, ft, ir::condcodes::FloatCC::LessThanOrEqual)
                 }
                 Instruction::Cvtws(fd, fs) => {
-                    // let value = self.fpu.get_register::<f32>(fs) as i32;
-                    // self.fpu.set_register(fd, value as u32);
-                    unhandled();
-                    break;
+                    // Operands are always finite, so saturating matches the interpreter's cast.
+                    let value = self.get_fpu_operand(fs);
+                    let value = self
+                        .function_builder
+                        .ins()
+                        .fcvt_to_sint_sat(ir::types::I32, value);
+                    self.set_fpu_register(fd, value);
                 }
                 Instruction::Cvtsw(fd, fs) => {
-                    // let value = self.fpu.get_register::<u32>(fs) as i32;
-                    // self.fpu.set_register(fd, value as f32);
-                    unhandled();
-                    break;
+                    let value = self.get_fpu_register(fs);
+                    let value = self
+                        .function_builder
+                        .ins()
+                        .fcvt_from_sint(ir::types::F64, value);
+                    let value = self.fpu_truncate(value);
+                    self.set_fpu_register(fd, value);
                 }
-                Instruction::Tlbr | Instruction::Tlbwi | Instruction::Tlbwr | Instruction::Tlbp => {
-                    unhandled();
-                    break;
+                Instruction::Bc1f(offset) => {
+                    let condition = self.fpu_condition(ir::condcodes::IntCC::Equal);
+                    next_branch = Some(self.branch(
+                        condition,
+                        false,
+                        next_program_counter,
+                        offset,
+                        address,
+                        mode,
+                    ));
+                }
+                Instruction::Bc1t(offset) => {
+                    let condition = self.fpu_condition(ir::condcodes::IntCC::NotEqual);
+                    next_branch = Some(self.branch(
+                        condition,
+                        false,
+                        next_program_counter,
+                        offset,
+                        address,
+                        mode,
+                    ));
+                }
+                Instruction::Bc1fl(offset) => {
+                    let condition = self.fpu_condition(ir::condcodes::IntCC::Equal);
+                    next_branch = Some(self.branch(
+                        condition,
+                        true,
+                        next_program_counter,
+                        offset,
+                        address,
+                        mode,
+                    ));
+                }
+                Instruction::Bc1tl(offset) => {
+                    let condition = self.fpu_condition(ir::condcodes::IntCC::NotEqual);
+                    next_branch = Some(self.branch(
+                        condition,
+                        true,
+                        next_program_counter,
+                        offset,
+                        address,
+                        mode,
+                    ));
+                }
+                Instruction::Cfc1(_, _) | Instruction::Ctc1(_, _) | Instruction::Tlbp => {
+                    self.interpret(raw_instruction);
+                    lowering = Lowering::Helper;
                 }
-                Instruction::Ei => {
-                    // TODO: Set status register
+                Instruction::Tlbr
+                | Instruction::Tlbwi
+                | Instruction::Tlbwr
+                | Instruction::Eret
+                | Instruction::Ei
+                | Instruction::Di => {
+                    self.coverage.record(instruction, Lowering::Fallback);
+                    break;
                 }
                 Instruction::Beql(rs, rt, offset) => {
-                    let offset: u32 = offset.sign_extend();
                     let rs_value = self.get_register(rs, Size::S64);
                     let rt_value = self.get_register(rt, Size::S64);
-                    let conditional = self.function_builder.ins().icmp(
+                    let condition = self.function_builder.ins().icmp(
                         ir::condcodes::IntCC::Equal,
                         rs_value,
                         rt_value,
                     );
-                    let taken = self
-                        .function_builder
-                        .ins()
-                        .iadd_imm(next_program_counter, (offset << 2) as i64);
-                    let not_taken = self
-                        .function_builder
-                        .ins()
-                        .iadd_imm(next_program_counter, INSTRUCTION_SIZE as i64);
-                    next_delayed_branch_target = Some(taken);
-                    let not_taken_block = self.function_builder.create_block();
-                    let taken_block = self.function_builder.create_block();
-                    for register in Register::all() {
-                        self.writeback_register(register);
-                    }
-                    self.function_builder.ins().brif(
-                        conditional,
-                        taken_block,
-                        &[],
-                        not_taken_block,
-                        &[],
-                    );
-                    self.function_builder.switch_to_block(not_taken_block);
-                    self.store_program_counter(not_taken);
-                    self.function_builder.ins().return_(&[]);
-                    self.function_builder.switch_to_block(taken_block);
+                    next_branch = Some(self.branch(
+                        condition,
+                        true,
+                        next_program_counter,
+                        offset,
+                        address,
+                        mode,
+                    ));
                 }
                 Instruction::Bnel(rs, rt, offset) => {
-                    let offset: u32 = offset.sign_extend();
                     let rs_value = self.get_register(rs, Size::S64);
                     let rt_value = self.get_register(rt, Size::S64);
-                    let conditional = self.function_builder.ins().icmp(
+                    let condition = self.function_builder.ins().icmp(
                         ir::condcodes::IntCC::NotEqual,
                         rs_value,
                         rt_value,
                     );
-                    let taken = self
-                        .function_builder
-                        .ins()
-                        .iadd_imm(next_program_counter, (offset << 2) as i64);
-                    let not_taken = self
+                    next_branch = Some(self.branch(
+                        condition,
+                        true,
+                        next_program_counter,
+                        offset,
+                        address,
+                        mode,
+                    ));
+                }
+                Instruction::Daddi(rt, rs, imm) => {
+                    let rs_value = self.get_register(rs, Size::S64);
+                    let imm = self
                         .function_builder
                         .ins()
-                        .iadd_imm(next_program_counter, INSTRUCTION_SIZE as i64);
-                    next_delayed_branch_target = Some(taken);
-                    let not_taken_block = self.function_builder.create_block();
-                    let taken_block = self.function_builder.create_block();
-                    for register in Register::all() {
-                        self.writeback_register(register);
-                    }
-                    self.function_builder.ins().brif(
-                        conditional,
-                        taken_block,
-                        &[],
-                        not_taken_block,
-                        &[],
-                    );
-                    self.function_builder.switch_to_block(not_taken_block);
-                    self.store_program_counter(not_taken);
-                    self.function_builder.ins().return_(&[]);
-                    self.function_builder.switch_to_block(taken_block);
+                        .iconst(ir::types::I64, imm as i16 as i64);
+                    let value = self.trapping_arithmetic(rs_value, imm, false);
+                    self.set_register(rt, value, Size::S64);
                 }
                 Instruction::Daddiu(rt, rs, imm) => {
                     let rs_value = self.get_register(rs, Size::S64);
@@ -1219,61 +3416,478 @@ impl<'a> JitCompiler<'a> {
                     self.set_register(rt, value, Size::S64);
                 }
                 Instruction::Mfhi1(rd) => {
-                    unhandled();
-                    break;
+                    let value = self.get_upper(Register::Hi);
+                    self.set_register(rd, value, Size::S64);
                 }
                 Instruction::Mthi1(rs) => {
-                    unhandled();
-                    break;
+                    let value = self.get_register(rs, Size::S64);
+                    self.set_upper(Register::Hi, value);
                 }
                 Instruction::Mflo1(rd) => {
-                    unhandled();
-                    break;
+                    let value = self.get_upper(Register::Lo);
+                    self.set_register(rd, value, Size::S64);
                 }
                 Instruction::Mtlo1(rs) => {
-                    unhandled();
-                    break;
+                    let value = self.get_register(rs, Size::S64);
+                    self.set_upper(Register::Lo, value);
                 }
                 Instruction::Mult1(rd, rs, rt) => {
-                    unhandled();
-                    break;
+                    let product = self.product(rs, rt, true);
+                    self.set_product(rd, product, true);
                 }
-                Instruction::Div1(rs, rt) => {
-                    unhandled();
-                    break;
+                Instruction::Multu1(rd, rs, rt) => {
+                    let product = self.product(rs, rt, false);
+                    self.set_product(rd, product, true);
                 }
-                Instruction::Divu1(rs, rt) => {
-                    unhandled();
-                    break;
+                Instruction::Div1(rs, rt) => self.divide(rs, rt, true, true),
+                Instruction::Divu1(rs, rt) => self.divide(rs, rt, false, true),
+                Instruction::Madd(rd, rs, rt) => {
+                    let product = self.product(rs, rt, true);
+                    let accumulator = self.accumulator(false);
+                    let value = self.function_builder.ins().iadd(accumulator, product);
+                    self.set_product(rd, value, false);
+                }
+                Instruction::Maddu(rd, rs, rt) => {
+                    let product = self.product(rs, rt, false);
+                    let accumulator = self.accumulator(false);
+                    let value = self.function_builder.ins().iadd(accumulator, product);
+                    self.set_product(rd, value, false);
+                }
+                Instruction::Madd1(rd, rs, rt) => {
+                    let product = self.product(rs, rt, true);
+                    let accumulator = self.accumulator(true);
+                    let value = self.function_builder.ins().iadd(accumulator, product);
+                    self.set_product(rd, value, true);
+                }
+                Instruction::Maddu1(rd, rs, rt) => {
+                    let product = self.product(rs, rt, false);
+                    let accumulator = self.accumulator(true);
+                    let value = self.function_builder.ins().iadd(accumulator, product);
+                    self.set_product(rd, value, true);
+                }
+                Instruction::Plzcw(rd, rs) => {
+                    // The leading bits equal to the sign bit, not counting the sign bit itself.
+                    let value = self.get_register(rs, Size::S64);
+                    let high = self.function_builder.ins().ushr_imm(value, 32);
+                    let [low, high] = [value, high].map(|word| {
+                        let ins = self.function_builder.ins();
+                        let word = ins.ireduce(ir::types::I32, word);
+                        let sign = self.function_builder.ins().sshr_imm(word, 31);
+                        let word = self.function_builder.ins().bxor(word, sign);
+                        let leading = self.function_builder.ins().clz(word);
+                        let leading = self.function_builder.ins().iadd_imm(leading, -1);
+                        self.function_builder.ins().uextend(ir::types::I64, leading)
+                    });
+                    let high = self.function_builder.ins().ishl_imm(high, 32);
+                    let value = self.function_builder.ins().bor(low, high);
+                    self.set_register(rd, value, Size::S64);
                 }
                 Instruction::Pand(rd, rs, rt) => {
-                    unhandled();
-                    break;
+                    let rs_value = self.get_register(rs, Size::S128);
+                    let rt_value = self.get_register(rt, Size::S128);
+                    let value = self.function_builder.ins().band(rs_value, rt_value);
+                    self.set_register(rd, value, Size::S128);
                 }
                 Instruction::Por(rd, rs, rt) => {
-                    unhandled();
-                    break;
+                    let rs_value = self.get_register(rs, Size::S128);
+                    let rt_value = self.get_register(rt, Size::S128);
+                    let value = self.function_builder.ins().bor(rs_value, rt_value);
+                    self.set_register(rd, value, Size::S128);
                 }
                 Instruction::Pxor(rd, rs, rt) => {
-                    unhandled();
-                    break;
+                    let rs_value = self.get_register(rs, Size::S128);
+                    let rt_value = self.get_register(rt, Size::S128);
+                    let value = self.function_builder.ins().bxor(rs_value, rt_value);
+                    self.set_register(rd, value, Size::S128);
                 }
                 Instruction::Pnor(rd, rs, rt) => {
-                    unhandled();
-                    break;
+                    let rs_value = self.get_register(rs, Size::S128);
+                    let rt_value = self.get_register(rt, Size::S128);
+                    let value = self.function_builder.ins().bor(rs_value, rt_value);
+                    let value = self.function_builder.ins().bnot(value);
+                    self.set_register(rd, value, Size::S128);
+                }
+                Instruction::Pmfhi(rd) => {
+                    let value = self.get_register(Register::Hi, Size::S128);
+                    self.set_register(rd, value, Size::S128);
+                }
+                Instruction::Pmflo(rd) => {
+                    let value = self.get_register(Register::Lo, Size::S128);
+                    self.set_register(rd, value, Size::S128);
+                }
+                Instruction::Pmthi(rs) => {
+                    let value = self.get_register(rs, Size::S128);
+                    self.set_register(Register::Hi, value, Size::S128);
+                }
+                Instruction::Pmtlo(rs) => {
+                    let value = self.get_register(rs, Size::S128);
+                    self.set_register(Register::Lo, value, Size::S128);
+                }
+                Instruction::Pmfhllw(rd) => {
+                    self.permute(rd, Register::Lo, Register::Hi, 4, &[0, 4, 2, 6])
+                }
+                Instruction::Pmfhluw(rd) => {
+                    self.permute(rd, Register::Lo, Register::Hi, 4, &[1, 5, 3, 7])
+                }
+                Instruction::Pmfhllh(rd) => self.permute(
+                    rd,
+                    Register::Lo,
+                    Register::Hi,
+                    2,
+                    &[0, 2, 8, 10, 4, 6, 12, 14],
+                ),
+                Instruction::Pmfhlsh(rd) => {
+                    let lo = self.get_vector(Register::Lo, ir::types::I8X16);
+                    let hi = self.get_vector(Register::Hi, ir::types::I8X16);
+                    let [low, high] = [[0, 1, 4, 5], [2, 3, 6, 7]].map(|lanes| {
+                        let words = self.shuffle(lo, hi, 4, &lanes);
+                        self.cast_vector(words, ir::types::I32X4)
+                    });
+                    let value = self.function_builder.ins().snarrow(low, high);
+                    self.set_vector(rd, value);
+                }
+                Instruction::Pmthllw(rs) => {
+                    let value = self.get_vector(rs, ir::types::I8X16);
+                    let lo = self.get_vector(Register::Lo, ir::types::I8X16);
+                    let hi = self.get_vector(Register::Hi, ir::types::I8X16);
+                    let lo = self.shuffle(lo, value, 4, &[4, 1, 6, 3]);
+                    let hi = self.shuffle(hi, value, 4, &[5, 1, 7, 3]);
+                    self.set_vector(Register::Lo, lo);
+                    self.set_vector(Register::Hi, hi);
+                }
+                Instruction::Psllh(rd, rt, sa) => {
+                    self.shift_lanes(rd, rt, ir::types::I16X8, |jit, value| {
+                        jit.function_builder
+                            .ins()
+                            .ishl_imm(value, (sa & 0xF) as i64)
+                    })
+                }
+                Instruction::Psrlh(rd, rt, sa) => {
+                    self.shift_lanes(rd, rt, ir::types::I16X8, |jit, value| {
+                        jit.function_builder
+                            .ins()
+                            .ushr_imm(value, (sa & 0xF) as i64)
+                    })
+                }
+                Instruction::Psrah(rd, rt, sa) => {
+                    self.shift_lanes(rd, rt, ir::types::I16X8, |jit, value| {
+                        jit.function_builder
+                            .ins()
+                            .sshr_imm(value, (sa & 0xF) as i64)
+                    })
+                }
+                Instruction::Psllw(rd, rt, sa) => {
+                    self.shift_lanes(rd, rt, ir::types::I32X4, |jit, value| {
+                        jit.function_builder.ins().ishl_imm(value, sa as i64)
+                    })
+                }
+                Instruction::Psrlw(rd, rt, sa) => {
+                    self.shift_lanes(rd, rt, ir::types::I32X4, |jit, value| {
+                        jit.function_builder.ins().ushr_imm(value, sa as i64)
+                    })
+                }
+                Instruction::Psraw(rd, rt, sa) => {
+                    self.shift_lanes(rd, rt, ir::types::I32X4, |jit, value| {
+                        jit.function_builder.ins().sshr_imm(value, sa as i64)
+                    })
+                }
+                Instruction::Paddw(rd, rs, rt) => {
+                    self.lanewise(rd, rs, rt, ir::types::I32X4, |jit, a, b| {
+                        jit.function_builder.ins().iadd(a, b)
+                    })
+                }
+                Instruction::Paddh(rd, rs, rt) => {
+                    self.lanewise(rd, rs, rt, ir::types::I16X8, |jit, a, b| {
+                        jit.function_builder.ins().iadd(a, b)
+                    })
+                }
+                Instruction::Paddb(rd, rs, rt) => {
+                    self.lanewise(rd, rs, rt, ir::types::I8X16, |jit, a, b| {
+                        jit.function_builder.ins().iadd(a, b)
+                    })
+                }
+                Instruction::Psubw(rd, rs, rt) => {
+                    self.lanewise(rd, rs, rt, ir::types::I32X4, |jit, a, b| {
+                        jit.function_builder.ins().isub(a, b)
+                    })
+                }
+                Instruction::Psubh(rd, rs, rt) => {
+                    self.lanewise(rd, rs, rt, ir::types::I16X8, |jit, a, b| {
+                        jit.function_builder.ins().isub(a, b)
+                    })
+                }
+                Instruction::Psubb(rd, rs, rt) => {
+                    self.lanewise(rd, rs, rt, ir::types::I8X16, |jit, a, b| {
+                        jit.function_builder.ins().isub(a, b)
+                    })
+                }
+                Instruction::Paddsw(rd, rs, rt) => {
+                    self.lanewise(rd, rs, rt, ir::types::I32X4, |jit, a, b| {
+                        // Overflow when the sum has the opposite sign of both operands.
+                        let sum = jit.function_builder.ins().iadd(a, b);
+                        let a_flipped = jit.function_builder.ins().bxor(a, sum);
+                        let b_flipped = jit.function_builder.ins().bxor(b, sum);
+                        let overflow = jit.function_builder.ins().band(a_flipped, b_flipped);
+                        jit.saturate_signed(a, sum, overflow, ir::types::I32X4)
+                    })
+                }
+                Instruction::Psubsw(rd, rs, rt) => {
+                    self.lanewise(rd, rs, rt, ir::types::I32X4, |jit, a, b| {
+                        // Overflow when the operands differ in sign and the difference's sign differs from a's.
+                        let difference = jit.function_builder.ins().isub(a, b);
+                        let signs_differ = jit.function_builder.ins().bxor(a, b);
+                        let a_flipped = jit.function_builder.ins().bxor(a, difference);
+                        let overflow = jit.function_builder.ins().band(signs_differ, a_flipped);
+                        jit.saturate_signed(a, difference, overflow, ir::types::I32X4)
+                    })
+                }
+                Instruction::Paddsh(rd, rs, rt) => {
+                    self.lanewise(rd, rs, rt, ir::types::I16X8, |jit, a, b| {
+                        jit.function_builder.ins().sadd_sat(a, b)
+                    })
+                }
+                Instruction::Paddsb(rd, rs, rt) => {
+                    self.lanewise(rd, rs, rt, ir::types::I8X16, |jit, a, b| {
+                        jit.function_builder.ins().sadd_sat(a, b)
+                    })
+                }
+                Instruction::Psubsh(rd, rs, rt) => {
+                    self.lanewise(rd, rs, rt, ir::types::I16X8, |jit, a, b| {
+                        jit.function_builder.ins().ssub_sat(a, b)
+                    })
+                }
+                Instruction::Psubsb(rd, rs, rt) => {
+                    self.lanewise(rd, rs, rt, ir::types::I8X16, |jit, a, b| {
+                        jit.function_builder.ins().ssub_sat(a, b)
+                    })
+                }
+                Instruction::Padduw(rd, rs, rt) => {
+                    self.lanewise(rd, rs, rt, ir::types::I32X4, |jit, a, b| {
+                        // A carry wraps the sum below a.
+                        let sum = jit.function_builder.ins().iadd(a, b);
+                        let carry = jit.function_builder.ins().icmp(
+                            ir::condcodes::IntCC::UnsignedLessThan,
+                            sum,
+                            a,
+                        );
+                        jit.function_builder.ins().bor(sum, carry)
+                    })
+                }
+                Instruction::Padduh(rd, rs, rt) => {
+                    self.lanewise(rd, rs, rt, ir::types::I16X8, |jit, a, b| {
+                        jit.function_builder.ins().uadd_sat(a, b)
+                    })
+                }
+                Instruction::Paddub(rd, rs, rt) => {
+                    self.lanewise(rd, rs, rt, ir::types::I8X16, |jit, a, b| {
+                        jit.function_builder.ins().uadd_sat(a, b)
+                    })
+                }
+                Instruction::Psubuw(rd, rs, rt) => {
+                    self.lanewise(rd, rs, rt, ir::types::I32X4, |jit, a, b| {
+                        let a = jit.function_builder.ins().umax(a, b);
+                        jit.function_builder.ins().isub(a, b)
+                    })
+                }
+                Instruction::Psubuh(rd, rs, rt) => {
+                    self.lanewise(rd, rs, rt, ir::types::I16X8, |jit, a, b| {
+                        jit.function_builder.ins().usub_sat(a, b)
+                    })
+                }
+                Instruction::Psubub(rd, rs, rt) => {
+                    self.lanewise(rd, rs, rt, ir::types::I8X16, |jit, a, b| {
+                        jit.function_builder.ins().usub_sat(a, b)
+                    })
+                }
+                Instruction::Pcgtw(rd, rs, rt) => {
+                    self.lanewise(rd, rs, rt, ir::types::I32X4, |jit, a, b| {
+                        jit.function_builder.ins().icmp(
+                            ir::condcodes::IntCC::SignedGreaterThan,
+                            a,
+                            b,
+                        )
+                    })
+                }
+                Instruction::Pcgth(rd, rs, rt) => {
+                    self.lanewise(rd, rs, rt, ir::types::I16X8, |jit, a, b| {
+                        jit.function_builder.ins().icmp(
+                            ir::condcodes::IntCC::SignedGreaterThan,
+                            a,
+                            b,
+                        )
+                    })
+                }
+                Instruction::Pcgtb(rd, rs, rt) => {
+                    self.lanewise(rd, rs, rt, ir::types::I8X16, |jit, a, b| {
+                        jit.function_builder.ins().icmp(
+                            ir::condcodes::IntCC::SignedGreaterThan,
+                            a,
+                            b,
+                        )
+                    })
+                }
+                Instruction::Pceqw(rd, rs, rt) => {
+                    self.lanewise(rd, rs, rt, ir::types::I32X4, |jit, a, b| {
+                        jit.function_builder
+                            .ins()
+                            .icmp(ir::condcodes::IntCC::Equal, a, b)
+                    })
+                }
+                Instruction::Pceqh(rd, rs, rt) => {
+                    self.lanewise(rd, rs, rt, ir::types::I16X8, |jit, a, b| {
+                        jit.function_builder
+                            .ins()
+                            .icmp(ir::condcodes::IntCC::Equal, a, b)
+                    })
+                }
+                Instruction::Pceqb(rd, rs, rt) => {
+                    self.lanewise(rd, rs, rt, ir::types::I8X16, |jit, a, b| {
+                        jit.function_builder
+                            .ins()
+                            .icmp(ir::condcodes::IntCC::Equal, a, b)
+                    })
+                }
+                Instruction::Pmaxw(rd, rs, rt) => {
+                    self.lanewise(rd, rs, rt, ir::types::I32X4, |jit, a, b| {
+                        jit.function_builder.ins().smax(a, b)
+                    })
+                }
+                Instruction::Pmaxh(rd, rs, rt) => {
+                    self.lanewise(rd, rs, rt, ir::types::I16X8, |jit, a, b| {
+                        jit.function_builder.ins().smax(a, b)
+                    })
+                }
+                Instruction::Pminw(rd, rs, rt) => {
+                    self.lanewise(rd, rs, rt, ir::types::I32X4, |jit, a, b| {
+                        jit.function_builder.ins().smin(a, b)
+                    })
+                }
+                Instruction::Pminh(rd, rs, rt) => {
+                    self.lanewise(rd, rs, rt, ir::types::I16X8, |jit, a, b| {
+                        jit.function_builder.ins().smin(a, b)
+                    })
+                }
+                Instruction::Padsbh(rd, rs, rt) => {
+                    self.lanewise(rd, rs, rt, ir::types::I16X8, |jit, a, b| {
+                        // Differences in the lower four halfwords, sums in the upper four.
+                        let difference = jit.function_builder.ins().isub(a, b);
+                        let sum = jit.function_builder.ins().iadd(a, b);
+                        let difference = jit.cast_vector(difference, ir::types::I8X16);
+                        let sum = jit.cast_vector(sum, ir::types::I8X16);
+                        let value = jit.shuffle(difference, sum, 2, &[0, 1, 2, 3, 12, 13, 14, 15]);
+                        jit.cast_vector(value, ir::types::I16X8)
+                    })
+                }
+                Instruction::Pabsw(rd, rt) => self.absolute(rd, rt, ir::types::I32X4),
+                Instruction::Pabsh(rd, rt) => self.absolute(rd, rt, ir::types::I16X8),
+                Instruction::Pextlw(rd, rs, rt) => self.permute(rd, rs, rt, 4, &[4, 0, 5, 1]),
+                Instruction::Pextuw(rd, rs, rt) => self.permute(rd, rs, rt, 4, &[6, 2, 7, 3]),
+                Instruction::Ppacw(rd, rs, rt) => self.permute(rd, rs, rt, 4, &[4, 6, 0, 2]),
+                Instruction::Pextlh(rd, rs, rt) => {
+                    self.permute(rd, rs, rt, 2, &[8, 0, 9, 1, 10, 2, 11, 3])
+                }
+                Instruction::Pextuh(rd, rs, rt) => {
+                    self.permute(rd, rs, rt, 2, &[12, 4, 13, 5, 14, 6, 15, 7])
+                }
+                Instruction::Ppach(rd, rs, rt) => {
+                    self.permute(rd, rs, rt, 2, &[8, 10, 12, 14, 0, 2, 4, 6])
+                }
+                Instruction::Pinth(rd, rs, rt) => {
+                    self.permute(rd, rs, rt, 2, &[8, 4, 9, 5, 10, 6, 11, 7])
+                }
+                Instruction::Pinteh(rd, rs, rt) => {
+                    self.permute(rd, rs, rt, 2, &[8, 0, 10, 2, 12, 4, 14, 6])
+                }
+                Instruction::Pextlb(rd, rs, rt) => {
+                    let lanes: [usize; 16] = std::array::from_fn(|i| i / 2 + 16 * (1 - i % 2));
+                    self.permute(rd, rs, rt, 1, &lanes)
+                }
+                Instruction::Pextub(rd, rs, rt) => {
+                    let lanes: [usize; 16] = std::array::from_fn(|i| 8 + i / 2 + 16 * (1 - i % 2));
+                    self.permute(rd, rs, rt, 1, &lanes)
+                }
+                Instruction::Ppacb(rd, rs, rt) => {
+                    let lanes: [usize; 16] = std::array::from_fn(|i| (2 * i + 16) % 32);
+                    self.permute(rd, rs, rt, 1, &lanes)
+                }
+                Instruction::Pcpyld(rd, rs, rt) => self.permute(rd, rs, rt, 8, &[2, 0]),
+                Instruction::Pcpyud(rd, rs, rt) => self.permute(rd, rs, rt, 8, &[1, 3]),
+                Instruction::Pcpyh(rd, rt) => {
+                    self.permute(rd, rt, rt, 2, &[0, 0, 0, 0, 4, 4, 4, 4])
+                }
+                Instruction::Pexeh(rd, rt) => {
+                    self.permute(rd, rt, rt, 2, &[2, 1, 0, 3, 6, 5, 4, 7])
+                }
+                Instruction::Prevh(rd, rt) => {
+                    self.permute(rd, rt, rt, 2, &[3, 2, 1, 0, 7, 6, 5, 4])
+                }
+                Instruction::Pexch(rd, rt) => {
+                    self.permute(rd, rt, rt, 2, &[0, 2, 1, 3, 4, 6, 5, 7])
+                }
+                Instruction::Pexew(rd, rt) => self.permute(rd, rt, rt, 4, &[2, 1, 0, 3]),
+                Instruction::Prot3w(rd, rt) => self.permute(rd, rt, rt, 4, &[1, 2, 0, 3]),
+                Instruction::Pexcw(rd, rt) => self.permute(rd, rt, rt, 4, &[0, 2, 1, 3]),
+                Instruction::Qfsrv(rd, rs, rt) => {
+                    // Funnel shift of rs:rt right by the SA register.
+                    let shift_amount = self.load_host_word(&self.state.shift_amount);
+                    let shift = self.function_builder.ins().band_imm(shift_amount, 0x7F);
+                    let high = self.get_register(rs, Size::S128);
+                    let low = self.get_register(rt, Size::S128);
+                    let low_part = self.function_builder.ins().ushr(low, shift);
+                    let width = self.function_builder.ins().iconst(ir::types::I32, 128);
+                    let high_shift = self.function_builder.ins().isub(width, shift);
+                    let high_part = self.function_builder.ins().ishl(high, high_shift);
+                    let value = self.function_builder.ins().bor(low_part, high_part);
+                    let is_zero =
+                        self.function_builder
+                            .ins()
+                            .icmp_imm(ir::condcodes::IntCC::Equal, shift, 0);
+                    let value = self.function_builder.ins().select(is_zero, low, value);
+                    self.set_register(rd, value, Size::S128);
+                }
+                Instruction::Pmfhlslw(_)
+                | Instruction::Pext5(_, _)
+                | Instruction::Ppac5(_, _)
+                | Instruction::Psllvw(_, _, _)
+                | Instruction::Psrlvw(_, _, _)
+                | Instruction::Psravw(_, _, _)
+                | Instruction::Pmultw(_, _, _)
+                | Instruction::Pmultuw(_, _, _)
+                | Instruction::Pmaddw(_, _, _)
+                | Instruction::Pmadduw(_, _, _)
+                | Instruction::Pmsubw(_, _, _)
+                | Instruction::Pmulth(_, _, _)
+                | Instruction::Pmaddh(_, _, _)
+                | Instruction::Pmsubh(_, _, _)
+                | Instruction::Phmadh(_, _, _)
+                | Instruction::Phmsbh(_, _, _)
+                | Instruction::Pdivw(_, _)
+                | Instruction::Pdivuw(_, _)
+                | Instruction::Pdivbw(_, _) => {
+                    self.interpret(raw_instruction);
+                    lowering = Lowering::Helper;
                 }
                 Instruction::Lq(rt, offset, base) => {
-                    unhandled();
-                    break;
+                    let base_value = self.get_register(base, Size::S32);
+                    let offset: u64 = offset.sign_extend();
+                    let address = self
+                        .function_builder
+                        .ins()
+                        .iadd_imm(base_value, offset as i64);
+                    let address = self.function_builder.ins().band_imm(address, !0xF);
+                    let value = self.load(address, 0, Size::S128, mode);
+                    self.set_register(rt, value, Size::S128);
                 }
                 Instruction::Sq(rt, offset, base) => {
-                    // let mut address = self
-                    //     .get_register::<u32>(base)
-                    //     .wrapping_add(offset.sign_extend());
-                    // address &= !0b1111;
-                    // self.write_virtual(bus, address, self.get_register::<u128>(rt));
-                    unhandled();
-                    break;
+                    let rt_value = self.get_register(rt, Size::S128);
+                    let base_value = self.get_register(base, Size::S32);
+                    let offset: u64 = offset.sign_extend();
+                    let address = self
+                        .function_builder
+                        .ins()
+                        .iadd_imm(base_value, offset as i64);
+                    let address = self.function_builder.ins().band_imm(address, !0xF);
+                    self.store(rt_value, address, 0, Size::S128, mode);
                 }
                 Instruction::Lb(rt, offset, base) => {
                     let base_value = self.get_register(base, Size::S32);
@@ -1305,21 +3919,40 @@ impl<'a> JitCompiler<'a> {
                     let value = self.function_builder.ins().uextend(ir::types::I64, value);
                     self.set_register(rt, value, Size::S64);
                 }
+                Instruction::Lwl(rt, offset, base) => {
+                    let base_value = self.get_register(base, Size::S32);
+                    let (aligned, shift) = self.split_unaligned(base_value, offset, Size::S32);
+                    let memory_word = self.load(aligned, 0, Size::S32, mode);
+                    let rt_value = self.get_register(rt, Size::S32);
+                    let value = self.merge_left(rt_value, memory_word, shift, Size::S32);
+                    let value = self.function_builder.ins().sextend(ir::types::I64, value);
+                    self.set_register(rt, value, Size::S64);
+                }
                 Instruction::Lwr(rt, offset, base) => {
-                    // let address = self
-                    //     .get_register::<u32>(base)
-                    //     .wrapping_add(offset.sign_extend());
-                    // let byte = address & 0b11;
-                    // let memory_word = self.read_virtual::<u32>(bus, address & !0b11);
-                    // let value = if byte == 0 {
-                    //     memory_word.sign_extend()
-                    // } else {
-                    //     let existing = self.get_register::<u64>(rt);
-                    //     existing & u64::mask(byte * 8..64) | (memory_word >> (byte * 8)) as u64
-                    // };
-                    // self.set_register(rt, value);
-                    unhandled();
-                    break;
+                    let base_value = self.get_register(base, Size::S32);
+                    let (aligned, shift) = self.split_unaligned(base_value, offset, Size::S32);
+                    let memory_word = self.load(aligned, 0, Size::S32, mode);
+                    let rt_value = self.get_register(rt, Size::S64);
+                    let rt_word = self
+                        .function_builder
+                        .ins()
+                        .ireduce(ir::types::I32, rt_value);
+                    let word = self.merge_right(rt_word, memory_word, shift, Size::S32);
+                    // A whole word is sign extended, otherwise the upper half of the register is
+                    // kept.
+                    let whole = self.function_builder.ins().sextend(ir::types::I64, word);
+                    let upper = self
+                        .function_builder
+                        .ins()
+                        .band_imm(rt_value, u64::mask(32..64) as i64);
+                    let word = self.function_builder.ins().uextend(ir::types::I64, word);
+                    let merged = self.function_builder.ins().bor(upper, word);
+                    let is_whole =
+                        self.function_builder
+                            .ins()
+                            .icmp_imm(ir::condcodes::IntCC::Equal, shift, 0);
+                    let value = self.function_builder.ins().select(is_whole, whole, merged);
+                    self.set_register(rt, value, Size::S64);
                 }
                 Instruction::Sb(rt, offset, base) => {
                     let rt_value = self.get_register(rt, Size::S8);
@@ -1336,21 +3969,32 @@ impl<'a> JitCompiler<'a> {
                     let base_value = self.get_register(base, Size::S32);
                     self.store(rt_value, base_value, offset, Size::S32, mode);
                 }
-                Instruction::Cache(op, offset, base) => {
-                    unhandled();
+                Instruction::Swl(rt, offset, base) => {
+                    let base_value = self.get_register(base, Size::S32);
+                    let (aligned, shift) = self.split_unaligned(base_value, offset, Size::S32);
+                    let memory_word = self.load(aligned, 0, Size::S32, mode);
+                    let rt_value = self.get_register(rt, Size::S32);
+                    let shift = self.mirror_shift(shift, Size::S32);
+                    let value = self.merge_right(memory_word, rt_value, shift, Size::S32);
+                    self.store(value, aligned, 0, Size::S32, mode);
+                }
+                Instruction::Swr(rt, offset, base) => {
+                    let base_value = self.get_register(base, Size::S32);
+                    let (aligned, shift) = self.split_unaligned(base_value, offset, Size::S32);
+                    let memory_word = self.load(aligned, 0, Size::S32, mode);
+                    let rt_value = self.get_register(rt, Size::S32);
+                    let shift = self.mirror_shift(shift, Size::S32);
+                    let value = self.merge_left(memory_word, rt_value, shift, Size::S32);
+                    self.store(value, aligned, 0, Size::S32, mode);
+                }
+                Instruction::Cache(_, _, _) => {
+                    self.coverage.record(instruction, Lowering::Fallback);
                     break;
                 }
                 Instruction::Lwc1(ft, offset, base) => {
-                    // let address = self
-                    //     .get_register::<u32>(base)
-                    //     .wrapping_add(offset.sign_extend());
-                    // if address.bits(0..2) != 0 {
-                    //     panic!("Unaligned load at {:#010x}", address);
-                    // }
-                    // let value = self.read_virtual::<u32>(bus, address);
-                    // self.fpu.set_register(ft, value);
-                    unhandled();
-                    break;
+                    let base_value = self.get_register(base, Size::S32);
+                    let value = self.load(base_value, offset, Size::S32, mode);
+                    self.set_fpu_register(ft, value);
                 }
                 Instruction::Ld(rt, offset, base) => {
                     let base_value = self.get_register(base, Size::S32);
@@ -1358,37 +4002,155 @@ impl<'a> JitCompiler<'a> {
                     self.set_register(rt, value, Size::S64);
                 }
                 Instruction::Swc1(ft, offset, base) => {
-                    // let address = self
-                    //     .get_register::<u32>(base)
-                    //     .wrapping_add(offset.sign_extend());
-                    // if address.bits(0..2) != 0 {
-                    //     panic!("Unaligned store at {:#010x}", address);
-                    // }
-                    // self.write_virtual(bus, address, self.fpu.get_register::<u32>(ft));
-                    unhandled();
-                    break;
+                    let value = self.get_fpu_register(ft);
+                    let base_value = self.get_register(base, Size::S32);
+                    self.store(value, base_value, offset, Size::S32, mode);
                 }
                 Instruction::Sd(rt, offset, base) => {
                     let rt_value = self.get_register(rt, Size::S64);
                     let base_value = self.get_register(base, Size::S32);
                     self.store(rt_value, base_value, offset, Size::S64, mode);
                 }
+                Instruction::Ldl(rt, offset, base) => {
+                    let base_value = self.get_register(base, Size::S32);
+                    let (aligned, shift) = self.split_unaligned(base_value, offset, Size::S64);
+                    let memory_doubleword = self.load(aligned, 0, Size::S64, mode);
+                    let rt_value = self.get_register(rt, Size::S64);
+                    let value = self.merge_left(rt_value, memory_doubleword, shift, Size::S64);
+                    self.set_register(rt, value, Size::S64);
+                }
+                Instruction::Ldr(rt, offset, base) => {
+                    let base_value = self.get_register(base, Size::S32);
+                    let (aligned, shift) = self.split_unaligned(base_value, offset, Size::S64);
+                    let memory_doubleword = self.load(aligned, 0, Size::S64, mode);
+                    let rt_value = self.get_register(rt, Size::S64);
+                    let value = self.merge_right(rt_value, memory_doubleword, shift, Size::S64);
+                    self.set_register(rt, value, Size::S64);
+                }
+                Instruction::Sdl(rt, offset, base) => {
+                    let base_value = self.get_register(base, Size::S32);
+                    let (aligned, shift) = self.split_unaligned(base_value, offset, Size::S64);
+                    let memory_doubleword = self.load(aligned, 0, Size::S64, mode);
+                    let rt_value = self.get_register(rt, Size::S64);
+                    let shift = self.mirror_shift(shift, Size::S64);
+                    let value = self.merge_right(memory_doubleword, rt_value, shift, Size::S64);
+                    self.store(value, aligned, 0, Size::S64, mode);
+                }
+                Instruction::Sdr(rt, offset, base) => {
+                    let base_value = self.get_register(base, Size::S32);
+                    let (aligned, shift) = self.split_unaligned(base_value, offset, Size::S64);
+                    let memory_doubleword = self.load(aligned, 0, Size::S64, mode);
+                    let rt_value = self.get_register(rt, Size::S64);
+                    let shift = self.mirror_shift(shift, Size::S64);
+                    let value = self.merge_left(memory_doubleword, rt_value, shift, Size::S64);
+                    self.store(value, aligned, 0, Size::S64, mode);
+                }
             }
+            self.coverage.record(instruction, lowering);
+            self.cycles += self.instruction_cycles;
             address += INSTRUCTION_SIZE as u32;
             program_counter = next_program_counter;
-            delayed_branch_target = next_delayed_branch_target;
             if delay_slot {
+                delay_slot_compiled = true;
                 break;
             }
+            branch = next_branch;
         }
-        for register in Register::all() {
-            self.writeback_register(register);
-        }
-        self.store_program_counter(program_counter);
-        if let Some(delayed_branch_target) = delayed_branch_target {
-            self.store_delayed_branch_target(delayed_branch_target);
+        self.writeback_unpinned_registers();
+        let cycles = self.cycles;
+        match branch {
+            None => self.exit(program_counter, cycles, mode),
+            Some(branch) if !delay_slot_compiled => {
+                // The delay slot is interpreted, so there's no block to link to.
+                let target = match branch.condition {
+                    Some(condition) => {
+                        let not_taken = self
+                            .function_builder
+                            .ins()
+                            .iadd_imm(program_counter, INSTRUCTION_SIZE as i64);
+                        self.function_builder
+                            .ins()
+                            .select(condition, branch.target, not_taken)
+                    }
+                    None => branch.target,
+                };
+                self.store_dirty_registers();
+                self.store_program_counter(program_counter);
+                self.store_delayed_branch_target(target);
+                self.consume_cycles(cycles);
+                self.return_(true);
+            }
+            Some(branch) => {
+                if let Some(condition) = branch.condition {
+                    let not_taken_block = self.function_builder.create_block();
+                    let taken_block = self.function_builder.create_block();
+                    self.function_builder.ins().brif(
+                        condition,
+                        taken_block,
+                        &[],
+                        not_taken_block,
+                        &[],
+                    );
+                    self.function_builder.switch_to_block(not_taken_block);
+                    self.exit(program_counter, cycles, mode);
+                    self.function_builder.switch_to_block(taken_block);
+                }
+                match self.loop_block {
+                    Some(_) if idle_loop && branch.target_address == self.loop_header => {
+                        // Going around again would read the same values until an event changes
+                        // them, so give the cycles left up, unless a device was read.
+                        self.consume_cycles(cycles);
+                        let polled_device = self.function_builder.ins().stack_load(
+                            ir::types::I8,
+                            self.polled_device.unwrap(),
+                            0,
+                        );
+                        let idle_block = self.function_builder.create_block();
+                        let polling_block = self.function_builder.create_block();
+                        self.function_builder.ins().brif(
+                            polled_device,
+                            polling_block,
+                            &[],
+                            idle_block,
+                            &[],
+                        );
+                        self.function_builder.switch_to_block(polling_block);
+                        self.exit(branch.target, 0, mode);
+                        self.function_builder.switch_to_block(idle_block);
+                        self.give_up_cycles();
+                        self.exit(branch.target, 0, mode);
+                    }
+                    Some(loop_block) if branch.target_address == self.loop_header => {
+                        // Go around again while there are cycles left.
+                        let mut arguments = vec![branch.target];
+                        arguments.extend(self.pinned_values());
+                        let cycles_left = self.consume_cycles(cycles);
+                        let has_cycles_left = self.function_builder.ins().icmp_imm(
+                            ir::condcodes::IntCC::SignedGreaterThan,
+                            cycles_left,
+                            0,
+                        );
+                        let exit_block = self.function_builder.create_block();
+                        self.function_builder.ins().brif(
+                            has_cycles_left,
+                            loop_block,
+                            &arguments,
+                            exit_block,
+                            &[],
+                        );
+                        self.function_builder.switch_to_block(exit_block);
+                        self.exit(branch.target, 0, mode);
+                    }
+                    _ => self.exit(branch.target, cycles, mode),
+                }
+            }
         }
-        self.function_builder.ins().return_(&[]);
+        let generations = PageGenerations::new(
+            self.bus,
+            start_address..address.max(start_address + INSTRUCTION_SIZE as u32),
+        );
+        self.function_builder.switch_to_block(entry_block);
+        self.check_page_generations(&generations, block, &parameters[1..]);
         self.function_builder.seal_all_blocks();
         self.function_builder.finalize();
 
@@ -1396,6 +4158,50 @@ impl<'a> JitCompiler<'a> {
             return None;
         }
 
-        Some(address)
+        Some(CompiledBlock {
+            end_address: address,
+            exits: self.exits,
+            generations,
+        })
+    }
+
+    // Returns to the dispatcher if a page the block was compiled from has been written since.
+    fn check_page_generations(
+        &mut self,
+        generations: &PageGenerations,
+        body: ir::Block,
+        pinned_values: &[ir::Value],
+    ) {
+        let current_generations = self.host_address(self.bus.page_generations_address());
+        let expected_generations = self.host_address(generations.expected.as_ptr());
+        let stale_block = self.function_builder.create_block();
+        for (index, &page) in generations.pages.iter().enumerate() {
+            let current = self.function_builder.ins().load(
+                ir::types::I32,
+                ir::MemFlags::trusted(),
+                current_generations,
+                (page * std::mem::size_of::<u32>()) as i32,
+            );
+            let expected = self.function_builder.ins().load(
+                ir::types::I32,
+                ir::MemFlags::trusted(),
+                expected_generations,
+                (index * std::mem::size_of::<u32>()) as i32,
+            );
+            let stale =
+                self.function_builder
+                    .ins()
+                    .icmp(ir::condcodes::IntCC::NotEqual, current, expected);
+            let next_block = self.function_builder.create_block();
+            self.function_builder
+                .ins()
+                .brif(stale, stale_block, &[], next_block, &[]);
+            self.function_builder.switch_to_block(next_block);
+        }
+        self.function_builder.ins().jump(body, &[]);
+        // The program counter is still the block's start, which the dispatcher will recompile.
+        self.function_builder.switch_to_block(stale_block);
+        self.store_pinned_values(pinned_values);
+        self.return_(true);
     }
 }
@@ -0,0 +1,1086 @@
+use std::{
+    collections::VecDeque,
+    fmt::LowerHex,
+    path::{Path, PathBuf},
+};
+
+use elf::{endian::LittleEndian, ElfBytes};
+use enum_map::{Enum, EnumMap};
+use log::{debug, info, warn};
+
+use crate::{
+    bits::{Bits, SignExtend},
+    bytes::Bytes,
+    emotion_engine::{
+        bus::{Bus, PhysicalAddress},
+        dmac::Channel,
+        intc::Interrupt,
+        mmio::MmioDevice,
+    },
+};
+
+use super::{control, exception::Exception, fpu::Fpu, mmu::TlbEntry, register::Register, Core};
+
+mod thread;
+
+use thread::{Thread, Wait, IDLE_LOOP_ADDRESS, THREAD_EXIT_ADDRESS};
+
+// A high-level emulation of the EE kernel, used when running an ELF without a BIOS. Exceptions
+// are handled in Rust instead of by jumping to the BIOS exception vectors.
+pub struct Kernel {
+    threads: Vec<Option<Thread>>,
+    ready_queues: Vec<VecDeque<u32>>,
+    current_thread: Option<u32>,
+    heap_end: u32,
+    gs_interrupt_mask: u32,
+    osd_config: u32,
+    vsync_flag: Option<VSyncFlag>,
+    intc_handlers: Vec<InterruptHandler>,
+    dmac_handlers: Vec<InterruptHandler>,
+    next_handler_id: u32,
+    semaphores: Vec<Option<Semaphore>>,
+    alarms: Vec<Option<Alarm>>,
+    custom_syscalls: Vec<(i32, u32)>,
+    sif_registers: Vec<(u32, u32)>,
+    next_sif_dma_id: u32,
+    callbacks: VecDeque<Callback>,
+    interrupted_context: Option<Context>,
+    // Where the paths passed to LoadExecPS2 are looked up.
+    host_directory: PathBuf,
+}
+
+#[derive(Debug)]
+struct VSyncFlag {
+    flag_address: u32,
+    status_address: u32,
+}
+
+#[derive(Debug)]
+struct InterruptHandler {
+    id: u32,
+    cause: u32,
+    handler: u32,
+    argument: u32,
+    enabled: bool,
+}
+
+#[derive(Debug)]
+struct Semaphore {
+    count: i32,
+    max_count: i32,
+    initial_count: i32,
+    attribute: u32,
+    option: u32,
+    waiting_threads: VecDeque<u32>,
+}
+
+#[derive(Debug)]
+struct Alarm {
+    remaining_cycles: u64,
+    time: u16,
+    handler: u32,
+    argument: u32,
+}
+
+// A call into guest code from the kernel, e.g. an interrupt handler.
+#[derive(Debug)]
+struct Callback {
+    function: u32,
+    arguments: [u32; 3],
+}
+
+#[derive(Debug)]
+struct Context {
+    registers: EnumMap<Register, u128>,
+    fpu: Fpu,
+    program_counter: u32,
+}
+
+impl Context {
+    fn save(core: &Core) -> Context {
+        Context {
+            registers: core.state.registers,
+            fpu: core.state.fpu.clone(),
+            program_counter: core.state.program_counter,
+        }
+    }
+
+    fn restore(&self, core: &mut Core) {
+        core.state.registers = self.registers;
+        core.state.fpu = self.fpu.clone();
+        core.state.program_counter = self.program_counter;
+    }
+}
+
+type SyscallHandler = fn(&mut Kernel, &mut Core, &mut Bus) -> u32;
+
+// Exceptions the kernel doesn't handle end up here.
+const TLB_REFILL_VECTOR: u32 = 0x8000_0000;
+const COMMON_EXCEPTION_VECTOR: u32 = 0x8000_0180;
+// Callbacks return to a syscall instruction at this address, which hands control back to the
+// kernel. The first 512 KiB of main memory are reserved for the kernel.
+const CALLBACK_RETURN_ADDRESS: u32 = 0x8000_1000;
+const CALLBACK_STACK_POINTER: u32 = 0x8008_0000;
+// LoadExecPS2 copies the program's arguments here.
+const ARGUMENTS_ADDRESS: u32 = 0x8000_2000;
+const SYSCALL_INSTRUCTION: u32 = 0x0000_000C;
+// b .
+const BRANCH_TO_SELF_INSTRUCTION: u32 = 0x1000_FFFF;
+// addiu v1, zero, ExitThread
+const LOAD_EXIT_THREAD_INSTRUCTION: u32 = 0x2403_0023;
+
+// The TLB entries set up by the kernel before running a program, as PageMask, EntryHi, EntryLo0
+// and EntryLo1: the scratchpad, and main memory through the cached, uncached and uncached
+// accelerated segments in 16 MiB pages.
+const TLB_ENTRIES: [(u32, u32, u32, u32); 4] = [
+    (0x0000_0000, 0x7000_0000, 0x8000_0007, 0x0000_0001),
+    (0x01FF_E000, 0x0000_0000, 0x0000_001F, 0x0004_001F),
+    (0x01FF_E000, 0x2000_0000, 0x0000_0017, 0x0004_0017),
+    (0x01FF_E000, 0x3000_0000, 0x0000_003F, 0x0004_003F),
+];
+
+// Alarm times are measured in horizontal blanks.
+const CYCLES_PER_HSYNC: u64 = 18743;
+
+const INTC_STAT: u32 = 0x1000_F000;
+const INTC_MASK: u32 = 0x1000_F010;
+const D_CTRL: u32 = 0x1000_E000;
+const D_STAT: u32 = 0x1000_E010;
+const GS_CSR: u32 = 0x1200_1000;
+const GS_IMR: u32 = 0x1200_1010;
+
+const ARGUMENT_REGISTERS: [Register; 8] = [
+    Register::A0,
+    Register::A1,
+    Register::A2,
+    Register::A3,
+    Register::T0,
+    Register::T1,
+    Register::T2,
+    Register::T3,
+];
+
+impl Kernel {
+    pub fn install(core: &mut Core, bus: &mut Bus, host_directory: &Path) {
+        let kernel_code = [
+            (TLB_REFILL_VECTOR, BRANCH_TO_SELF_INSTRUCTION),
+            (TLB_REFILL_VECTOR + 4, 0),
+            (COMMON_EXCEPTION_VECTOR, BRANCH_TO_SELF_INSTRUCTION),
+            (COMMON_EXCEPTION_VECTOR + 4, 0),
+            (CALLBACK_RETURN_ADDRESS, SYSCALL_INSTRUCTION),
+            (IDLE_LOOP_ADDRESS, BRANCH_TO_SELF_INSTRUCTION),
+            (IDLE_LOOP_ADDRESS + 4, 0),
+            (THREAD_EXIT_ADDRESS, LOAD_EXIT_THREAD_INSTRUCTION),
+            (THREAD_EXIT_ADDRESS + 4, SYSCALL_INSTRUCTION),
+        ];
+        for (address, instruction) in kernel_code {
+            bus.write(PhysicalAddress::memory(address & 0x1FFF_FFFF), instruction);
+        }
+        for (index, (page_mask, entry_hi, entry_lo0, entry_lo1)) in
+            TLB_ENTRIES.into_iter().enumerate()
+        {
+            let entry = TlbEntry::from_registers(page_mask, entry_hi, entry_lo0, entry_lo1);
+            core.mmu.write_index(index as u8, entry);
+        }
+        core.state
+            .control
+            .set_register(control::Register::Wired, TLB_ENTRIES.len() as u32);
+        // IE, EIE, IM2 (INTC) and IM3 (DMAC)
+        let mut status = core.state.control.get_register(control::Register::Status);
+        status.set_bit(0, true);
+        status.set_bit(10, true);
+        status.set_bit(11, true);
+        status.set_bit(16, true);
+        core.state
+            .control
+            .set_register(control::Register::Status, status);
+        core.update_mode();
+        // Like the BIOS, enable the DMAC.
+        bus.dmac.write32(D_CTRL, 1);
+        let mut kernel = Kernel {
+            threads: Vec::new(),
+            ready_queues: Kernel::new_ready_queues(),
+            current_thread: None,
+            heap_end: 0,
+            gs_interrupt_mask: 0,
+            osd_config: 0,
+            vsync_flag: None,
+            intc_handlers: Vec::new(),
+            dmac_handlers: Vec::new(),
+            next_handler_id: 1,
+            semaphores: Vec::new(),
+            alarms: Vec::new(),
+            custom_syscalls: Vec::new(),
+            sif_registers: Vec::new(),
+            next_sif_dma_id: 1,
+            callbacks: VecDeque::new(),
+            interrupted_context: None,
+            host_directory: host_directory.to_owned(),
+        };
+        kernel.create_main_thread();
+        core.kernel = Some(kernel);
+    }
+
+    // Loads the segments of an ELF file into main memory, returning its entry point.
+    pub fn load_elf(bus: &mut Bus, data: &[u8]) -> Result<u32, elf::ParseError> {
+        let elf = ElfBytes::<LittleEndian>::minimal_parse(data)?;
+        for program_header in elf.segments().into_iter().flatten() {
+            let physical_address = program_header.p_paddr as u32;
+            let data = elf.segment_data(&program_header)?;
+            debug!(
+                target: "kernel",
+                "Loading {:#x} bytes at {physical_address:#010x}",
+                program_header.p_memsz
+            );
+            // The part of the segment past the file data is zeroed.
+            for offset in 0..program_header.p_memsz as u32 {
+                let byte = data.get(offset as usize).copied().unwrap_or(0);
+                bus.write(PhysicalAddress::memory(physical_address + offset), byte);
+            }
+        }
+        Ok(elf.ehdr.e_entry as u32)
+    }
+
+    pub fn step(&mut self, cycles: u64, bus: &mut Bus) {
+        let mut expired = false;
+        for alarm in self.alarms.iter_mut().flatten() {
+            if alarm.remaining_cycles > 0 {
+                alarm.remaining_cycles = alarm.remaining_cycles.saturating_sub(cycles);
+                expired |= alarm.remaining_cycles == 0;
+            }
+        }
+        // Like the BIOS, deliver alarms through the timer 3 interrupt.
+        if expired {
+            bus.intc.request(Interrupt::Timer3);
+        }
+    }
+
+    // The cycles until the next alarm goes off, if any is set.
+    pub fn cycles_until_next_alarm(&self) -> Option<u64> {
+        self.alarms
+            .iter()
+            .flatten()
+            .map(|alarm| alarm.remaining_cycles)
+            .filter(|&remaining_cycles| remaining_cycles > 0)
+            .min()
+    }
+
+    pub fn handle_exception(
+        &mut self,
+        core: &mut Core,
+        bus: &mut Bus,
+        exception: Exception,
+        in_delay_slot: bool,
+    ) {
+        match exception {
+            Exception::Syscall => {
+                assert!(!in_delay_slot, "Syscall in delay slot");
+                if core.state.program_counter == CALLBACK_RETURN_ADDRESS {
+                    self.dispatch_callbacks(core);
+                } else {
+                    core.state.program_counter += 4;
+                    self.syscall(core, bus);
+                }
+            }
+            Exception::Interrupt => self.interrupt(core, bus),
+            // The rest goes through COP0 to the exception vectors, which loop to themselves
+            // unless the program has put its own handler there.
+            _ => {
+                warn!(
+                    target: "kernel",
+                    "Unhandled exception {:?} at {:#010x}, jumping to the exception vector",
+                    exception,
+                    core.state.program_counter
+                );
+                core.enter_exception(exception, in_delay_slot);
+            }
+        }
+    }
+
+    fn syscall(&mut self, core: &mut Core, bus: &mut Bus) {
+        let number = core.get_register::<u32>(Register::V1) as i32;
+        if let Some(&(_, address)) = self
+            .custom_syscalls
+            .iter()
+            .find(|(custom_number, _)| *custom_number == number)
+        {
+            debug!(target: "kernel", "Syscall {number:#x} (custom handler at {address:#010x})");
+            // The handler returns straight to the caller.
+            core.set_register::<u64>(Register::Ra, core.state.program_counter.sign_extend());
+            core.state.program_counter = address;
+            return;
+        }
+        let (name, handler) = Kernel::syscall_table(number);
+        debug!(
+            target: "kernel",
+            "Syscall {name} ({number:#x}) a0={:#x} a1={:#x} a2={:#x} a3={:#x}",
+            argument(core, 0),
+            argument(core, 1),
+            argument(core, 2),
+            argument(core, 3)
+        );
+        let result = match handler {
+            Some(handler) => handler(self, core, bus),
+            None => {
+                warn!(target: "kernel", "Unimplemented syscall {name} ({number:#x})");
+                -1i32 as u32
+            }
+        };
+        core.set_register::<u64>(Register::V0, result.sign_extend());
+        // Interrupt handlers can make threads ready, but the switch happens when they return.
+        if self.interrupted_context.is_none() {
+            self.reschedule(core);
+        }
+    }
+
+    // The i-prefixed variants are called from interrupt handlers and have negative numbers.
+    fn syscall_table(number: i32) -> (&'static str, Option<SyscallHandler>) {
+        match number {
+            0x01 => ("ResetEE", Some(Kernel::reset_ee)),
+            0x02 => ("SetGsCrt", Some(Kernel::set_gs_crt)),
+            0x04 => ("Exit", Some(Kernel::exit)),
+            0x05 => ("_ExceptionEpilogue", None),
+            0x06 => ("LoadExecPS2", Some(Kernel::load_exec_ps2)),
+            0x07 => ("ExecPS2", Some(Kernel::exec_ps2)),
+            0x0A => ("AddSbusIntcHandler", Some(Kernel::ignore)),
+            0x0B => ("RemoveSbusIntcHandler", Some(Kernel::ignore)),
+            0x0C => ("Interrupt2Iop", Some(Kernel::ignore)),
+            0x0D => ("SetVTLBRefillHandler", None),
+            0x0E => ("SetVCommonHandler", None),
+            0x0F => ("SetVInterruptHandler", None),
+            0x10 | -0x10 => ("AddIntcHandler", Some(Kernel::add_intc_handler)),
+            0x11 | -0x11 => ("RemoveIntcHandler", Some(Kernel::remove_intc_handler)),
+            0x12 | -0x12 => ("AddDmacHandler", Some(Kernel::add_dmac_handler)),
+            0x13 | -0x13 => ("RemoveDmacHandler", Some(Kernel::remove_dmac_handler)),
+            0x14 => ("_EnableIntc", Some(Kernel::enable_intc)),
+            0x15 => ("_DisableIntc", Some(Kernel::disable_intc)),
+            0x16 => ("_EnableDmac", Some(Kernel::enable_dmac)),
+            0x17 => ("_DisableDmac", Some(Kernel::disable_dmac)),
+            0x18 => ("_SetAlarm", Some(Kernel::set_alarm)),
+            0x19 => ("_ReleaseAlarm", Some(Kernel::release_alarm)),
+            -0x1A => ("_iEnableIntc", Some(Kernel::enable_intc)),
+            -0x1B => ("_iDisableIntc", Some(Kernel::disable_intc)),
+            -0x1C => ("_iEnableDmac", Some(Kernel::enable_dmac)),
+            -0x1D => ("_iDisableDmac", Some(Kernel::disable_dmac)),
+            -0x1E => ("_iSetAlarm", Some(Kernel::set_alarm)),
+            -0x1F => ("_iReleaseAlarm", Some(Kernel::release_alarm)),
+            0x20 => ("CreateThread", Some(Kernel::create_thread)),
+            0x21 => ("DeleteThread", Some(Kernel::delete_thread)),
+            0x22 => ("StartThread", Some(Kernel::start_thread)),
+            0x23 => ("ExitThread", Some(Kernel::exit_thread)),
+            0x24 => ("ExitDeleteThread", Some(Kernel::exit_delete_thread)),
+            0x25 => ("TerminateThread", Some(Kernel::terminate_thread)),
+            -0x26 => ("iTerminateThread", Some(Kernel::terminate_thread)),
+            0x27 => ("DisableDispatchThread", Some(Kernel::ignore)),
+            0x28 => ("EnableDispatchThread", Some(Kernel::ignore)),
+            0x29 => ("ChangeThreadPriority", Some(Kernel::change_thread_priority)),
+            -0x2A => (
+                "iChangeThreadPriority",
+                Some(Kernel::change_thread_priority),
+            ),
+            0x2B => (
+                "RotateThreadReadyQueue",
+                Some(Kernel::rotate_thread_ready_queue),
+            ),
+            -0x2C => (
+                "_iRotateThreadReadyQueue",
+                Some(Kernel::rotate_thread_ready_queue),
+            ),
+            0x2D => ("ReleaseWaitThread", Some(Kernel::release_wait_thread)),
+            -0x2E => ("iReleaseWaitThread", Some(Kernel::release_wait_thread)),
+            0x2F | -0x2F => ("GetThreadId", Some(Kernel::get_thread_id)),
+            0x30 => ("ReferThreadStatus", Some(Kernel::refer_thread_status)),
+            -0x31 => ("iReferThreadStatus", Some(Kernel::refer_thread_status)),
+            0x32 => ("SleepThread", Some(Kernel::sleep_thread)),
+            0x33 => ("WakeupThread", Some(Kernel::wakeup_thread)),
+            -0x34 => ("_iWakeupThread", Some(Kernel::wakeup_thread)),
+            0x35 => ("CancelWakeupThread", Some(Kernel::cancel_wakeup_thread)),
+            -0x36 => ("iCancelWakeupThread", Some(Kernel::cancel_wakeup_thread)),
+            0x37 => ("SuspendThread", Some(Kernel::suspend_thread)),
+            -0x38 => ("_iSuspendThread", Some(Kernel::suspend_thread)),
+            0x39 => ("ResumeThread", Some(Kernel::resume_thread)),
+            -0x3A => ("iResumeThread", Some(Kernel::resume_thread)),
+            0x3B => ("JoinThread", None),
+            0x3C => ("SetupThread", Some(Kernel::setup_thread)),
+            0x3D => ("SetupHeap", Some(Kernel::setup_heap)),
+            0x3E => ("EndOfHeap", Some(Kernel::end_of_heap)),
+            0x40 => ("CreateSema", Some(Kernel::create_semaphore)),
+            0x41 => ("DeleteSema", Some(Kernel::delete_semaphore)),
+            0x42 => ("SignalSema", Some(Kernel::signal_semaphore)),
+            -0x43 => ("iSignalSema", Some(Kernel::signal_semaphore)),
+            0x44 => ("WaitSema", Some(Kernel::wait_semaphore)),
+            0x45 => ("PollSema", Some(Kernel::poll_semaphore)),
+            -0x46 => ("iPollSema", Some(Kernel::poll_semaphore)),
+            0x47 => ("ReferSemaStatus", Some(Kernel::refer_semaphore_status)),
+            -0x48 => ("iReferSemaStatus", Some(Kernel::refer_semaphore_status)),
+            -0x49 => ("iDeleteSema", Some(Kernel::delete_semaphore)),
+            0x4A => ("SetOsdConfigParam", Some(Kernel::set_osd_config_param)),
+            0x4B => ("GetOsdConfigParam", Some(Kernel::get_osd_config_param)),
+            0x4C => ("GetGsHParam", Some(Kernel::ignore)),
+            0x4D => ("GetGsVParam", Some(Kernel::ignore)),
+            0x4E => ("SetGsHParam", Some(Kernel::ignore)),
+            0x4F => ("SetGsVParam", Some(Kernel::ignore)),
+            // There are no event flag syscalls on the EE; the SDK builds event flags on top of
+            // semaphores.
+            0x5A => ("GetEntryAddress", Some(Kernel::get_entry_address)),
+            0x5C | -0x5C => ("EnableIntcHandler", Some(Kernel::enable_intc_handler)),
+            0x5D | -0x5D => ("DisableIntcHandler", Some(Kernel::disable_intc_handler)),
+            0x5E | -0x5E => ("EnableDmacHandler", Some(Kernel::enable_dmac_handler)),
+            0x5F | -0x5F => ("DisableDmacHandler", Some(Kernel::disable_dmac_handler)),
+            0x60 => ("KSeg0", Some(Kernel::ignore)),
+            0x61 => ("EnableCache", Some(Kernel::ignore)),
+            0x62 => ("DisableCache", Some(Kernel::ignore)),
+            0x63 | -0x67 => ("GetCop0", Some(Kernel::get_cop0)),
+            0x64 | -0x68 => ("FlushCache", Some(Kernel::ignore)),
+            0x66 | -0x6A => ("CpuConfig", Some(Kernel::ignore)),
+            0x6B => ("SifStopDma", Some(Kernel::ignore)),
+            0x6C => ("SetCPUTimerHandler", None),
+            0x6D => ("SetCPUTimer", None),
+            0x6E => ("SetOsdConfigParam2", Some(Kernel::ignore)),
+            0x6F => ("GetOsdConfigParam2", Some(Kernel::get_osd_config_param2)),
+            0x70 | -0x70 => ("GsGetIMR", Some(Kernel::gs_get_imr)),
+            0x71 | -0x71 => ("GsPutIMR", Some(Kernel::gs_put_imr)),
+            0x72 => ("SetPgifHandler", Some(Kernel::ignore)),
+            0x73 => ("SetVSyncFlag", Some(Kernel::set_vsync_flag)),
+            0x74 => ("SetSyscall", Some(Kernel::set_syscall)),
+            0x76 | -0x76 => ("SifDmaStat", Some(Kernel::sif_dma_status)),
+            0x77 | -0x77 => ("SifSetDma", Some(Kernel::sif_set_dma)),
+            0x78 | -0x78 => ("SifSetDChain", Some(Kernel::ignore)),
+            0x79 => ("SifSetReg", Some(Kernel::sif_set_register)),
+            0x7A => ("SifGetReg", Some(Kernel::sif_get_register)),
+            0x7B => ("ExecOSD", None),
+            0x7C => ("Deci2Call", Some(Kernel::ignore)),
+            0x7D => ("PSMode", Some(Kernel::ignore)),
+            0x7E => ("MachineType", Some(Kernel::ignore)),
+            0x7F => ("GetMemorySize", Some(Kernel::get_memory_size)),
+            _ => ("Unknown", None),
+        }
+    }
+
+    fn ignore(&mut self, _core: &mut Core, _bus: &mut Bus) -> u32 {
+        0
+    }
+
+    fn reset_ee(&mut self, core: &mut Core, _bus: &mut Bus) -> u32 {
+        info!(target: "kernel", "ResetEE {:#x}", argument(core, 0));
+        0
+    }
+
+    fn set_gs_crt(&mut self, core: &mut Core, _bus: &mut Bus) -> u32 {
+        // TODO: Configure the CRTC
+        info!(
+            target: "kernel",
+            "GS CRT: interlaced={}, mode={:#x}, field mode={}",
+            argument(core, 0) != 0,
+            argument(core, 1),
+            argument(core, 2) != 0
+        );
+        0
+    }
+
+    fn exit(&mut self, core: &mut Core, _bus: &mut Bus) -> u32 {
+        let status = argument(core, 0) as i32;
+        info!(target: "kernel", "Exit {status}");
+        log::logger().flush();
+        std::process::exit(status)
+    }
+
+    fn load_exec_ps2(&mut self, core: &mut Core, bus: &mut Bus) -> u32 {
+        let file_name = read_string(core, bus, argument(core, 0));
+        let argument_count = argument(core, 1);
+        let argument_pointers = argument(core, 2);
+        // The arguments may live in memory the new program overwrites.
+        let mut arguments = vec![file_name.clone()];
+        for index in 0..argument_count {
+            let pointer = read(core, bus, argument_pointers + index * 4);
+            arguments.push(read_string(core, bus, pointer));
+        }
+        let path = self.host_path(&file_name);
+        info!(target: "kernel", "LoadExecPS2 {file_name} ({})", path.display());
+        let entry = match std::fs::read(&path) {
+            Ok(data) => Kernel::load_elf(bus, &data).map_err(|error| error.to_string()),
+            Err(error) => Err(error.to_string()),
+        };
+        let entry = match entry {
+            Ok(entry) => entry,
+            Err(error) => {
+                warn!(target: "kernel", "LoadExecPS2 {file_name}: {error}");
+                return -1i32 as u32;
+            }
+        };
+        self.reset_threads();
+        // Lay out the argument vector followed by the strings it points to.
+        let mut string_address = ARGUMENTS_ADDRESS + arguments.len() as u32 * 4;
+        for (index, argument) in arguments.iter().enumerate() {
+            write(
+                core,
+                bus,
+                ARGUMENTS_ADDRESS + index as u32 * 4,
+                string_address,
+            );
+            for byte in argument.bytes().chain([0]) {
+                write(core, bus, string_address, byte);
+                string_address += 1;
+            }
+        }
+        core.set_register::<u64>(Register::A0, (arguments.len() as u32).sign_extend());
+        core.set_register::<u64>(Register::A1, ARGUMENTS_ADDRESS.sign_extend());
+        core.state.program_counter = entry;
+        arguments.len() as u32
+    }
+
+    // Maps a path such as `cdrom0:\SLUS_200.02;1` or `host:demo.elf` to a file in the host
+    // directory.
+    fn host_path(&self, file_name: &str) -> PathBuf {
+        let path = file_name
+            .split_once(':')
+            .map_or(file_name, |(_device, path)| path);
+        let path = path.split(';').next().unwrap_or_default();
+        self.host_directory
+            .join(path.replace('\\', "/").trim_start_matches('/'))
+    }
+
+    fn exec_ps2(&mut self, core: &mut Core, _bus: &mut Bus) -> u32 {
+        let entry = argument(core, 0);
+        let global_pointer = argument(core, 1);
+        let argument_count = argument(core, 2);
+        let arguments = argument(core, 3);
+        core.set_register::<u64>(Register::Gp, global_pointer.sign_extend());
+        core.set_register::<u64>(Register::A0, argument_count.sign_extend());
+        core.set_register::<u64>(Register::A1, arguments.sign_extend());
+        core.state.program_counter = entry;
+        argument_count
+    }
+
+    fn add_handler(
+        handlers: &mut Vec<InterruptHandler>,
+        next_handler_id: &mut u32,
+        core: &Core,
+    ) -> u32 {
+        let cause = argument(core, 0);
+        let handler = argument(core, 1);
+        let next = argument(core, 2) as i32;
+        let argument = argument(core, 3);
+        let id = *next_handler_id;
+        *next_handler_id += 1;
+        let handler = InterruptHandler {
+            id,
+            cause,
+            handler,
+            argument,
+            enabled: true,
+        };
+        // A negative `next` appends the handler, anything else puts it first.
+        if next < 0 {
+            handlers.push(handler);
+        } else {
+            handlers.insert(0, handler);
+        }
+        id
+    }
+
+    fn remove_handler(handlers: &mut Vec<InterruptHandler>, core: &Core) -> u32 {
+        let cause = argument(core, 0);
+        let id = argument(core, 1);
+        handlers.retain(|handler| !(handler.cause == cause && handler.id == id));
+        0
+    }
+
+    fn set_handler_enabled(handlers: &mut [InterruptHandler], core: &Core, enabled: bool) -> u32 {
+        let cause = argument(core, 0);
+        let id = argument(core, 1);
+        match handlers
+            .iter_mut()
+            .find(|handler| handler.cause == cause && handler.id == id)
+        {
+            Some(handler) => {
+                handler.enabled = enabled;
+                0
+            }
+            None => -1i32 as u32,
+        }
+    }
+
+    fn add_intc_handler(&mut self, core: &mut Core, _bus: &mut Bus) -> u32 {
+        Kernel::add_handler(&mut self.intc_handlers, &mut self.next_handler_id, core)
+    }
+
+    fn remove_intc_handler(&mut self, core: &mut Core, _bus: &mut Bus) -> u32 {
+        Kernel::remove_handler(&mut self.intc_handlers, core)
+    }
+
+    fn enable_intc_handler(&mut self, core: &mut Core, _bus: &mut Bus) -> u32 {
+        Kernel::set_handler_enabled(&mut self.intc_handlers, core, true)
+    }
+
+    fn disable_intc_handler(&mut self, core: &mut Core, _bus: &mut Bus) -> u32 {
+        Kernel::set_handler_enabled(&mut self.intc_handlers, core, false)
+    }
+
+    fn add_dmac_handler(&mut self, core: &mut Core, _bus: &mut Bus) -> u32 {
+        Kernel::add_handler(&mut self.dmac_handlers, &mut self.next_handler_id, core)
+    }
+
+    fn remove_dmac_handler(&mut self, core: &mut Core, _bus: &mut Bus) -> u32 {
+        Kernel::remove_handler(&mut self.dmac_handlers, core)
+    }
+
+    fn enable_dmac_handler(&mut self, core: &mut Core, _bus: &mut Bus) -> u32 {
+        Kernel::set_handler_enabled(&mut self.dmac_handlers, core, true)
+    }
+
+    fn disable_dmac_handler(&mut self, core: &mut Core, _bus: &mut Bus) -> u32 {
+        Kernel::set_handler_enabled(&mut self.dmac_handlers, core, false)
+    }
+
+    // Returns whether the mask bit changed.
+    fn set_intc_mask(bus: &mut Bus, cause: u32, enabled: bool) -> u32 {
+        if bus.intc.read32(INTC_MASK).bit(cause) == enabled {
+            return 0;
+        }
+        // Writing a mask bit toggles it.
+        bus.intc.write32(INTC_MASK, 1 << cause);
+        1
+    }
+
+    fn enable_intc(&mut self, core: &mut Core, bus: &mut Bus) -> u32 {
+        Kernel::set_intc_mask(bus, argument(core, 0), true)
+    }
+
+    fn disable_intc(&mut self, core: &mut Core, bus: &mut Bus) -> u32 {
+        Kernel::set_intc_mask(bus, argument(core, 0), false)
+    }
+
+    // Returns whether the mask bit changed.
+    fn set_dmac_mask(bus: &mut Bus, channel: u32, enabled: bool) -> u32 {
+        if bus.dmac.read32(D_STAT).bit(channel + 16) == enabled {
+            return 0;
+        }
+        // Writing a mask bit toggles it.
+        bus.dmac.write32(D_STAT, 1 << (channel + 16));
+        1
+    }
+
+    fn enable_dmac(&mut self, core: &mut Core, bus: &mut Bus) -> u32 {
+        Kernel::set_dmac_mask(bus, argument(core, 0), true)
+    }
+
+    fn disable_dmac(&mut self, core: &mut Core, bus: &mut Bus) -> u32 {
+        Kernel::set_dmac_mask(bus, argument(core, 0), false)
+    }
+
+    fn set_alarm(&mut self, core: &mut Core, bus: &mut Bus) -> u32 {
+        let time = argument(core, 0) as u16;
+        let alarm = Alarm {
+            remaining_cycles: (time as u64).max(1) * CYCLES_PER_HSYNC,
+            time,
+            handler: argument(core, 1),
+            argument: argument(core, 2),
+        };
+        Kernel::set_intc_mask(bus, Interrupt::Timer3.into_usize() as u32, true);
+        insert(&mut self.alarms, alarm)
+    }
+
+    fn release_alarm(&mut self, core: &mut Core, _bus: &mut Bus) -> u32 {
+        match self.alarms.get_mut(argument(core, 0) as usize) {
+            Some(alarm @ Some(_)) => {
+                *alarm = None;
+                0
+            }
+            _ => -1i32 as u32,
+        }
+    }
+
+    fn setup_heap(&mut self, core: &mut Core, _bus: &mut Bus) -> u32 {
+        let base = argument(core, 0);
+        let size = argument(core, 1);
+        debug!(target: "kernel", "Argument base={:#010x}, size={:#010x}", base, size);
+        let base = if base == 0xFFFF_FFFF {
+            self.current_thread()
+                .expect("SetupHeap without a thread")
+                .stack_end()
+        } else {
+            base
+        };
+        debug!(target: "kernel", "Heap base={:#010x}, size={:#010x}", base, size);
+        self.heap_end = base + size;
+        self.heap_end
+    }
+
+    fn end_of_heap(&mut self, _core: &mut Core, _bus: &mut Bus) -> u32 {
+        self.heap_end
+    }
+
+    fn create_semaphore(&mut self, core: &mut Core, bus: &mut Bus) -> u32 {
+        let parameters = argument(core, 0);
+        let semaphore = Semaphore {
+            count: read::<u32>(core, bus, parameters + 8) as i32,
+            max_count: read::<u32>(core, bus, parameters + 4) as i32,
+            initial_count: read::<u32>(core, bus, parameters + 8) as i32,
+            attribute: read(core, bus, parameters + 16),
+            option: read(core, bus, parameters + 20),
+            waiting_threads: VecDeque::new(),
+        };
+        insert(&mut self.semaphores, semaphore)
+    }
+
+    fn semaphore(&mut self, core: &Core) -> Option<&mut Semaphore> {
+        self.semaphores
+            .get_mut(argument(core, 0) as usize)
+            .and_then(Option::as_mut)
+    }
+
+    fn delete_semaphore(&mut self, core: &mut Core, _bus: &mut Bus) -> u32 {
+        let id = argument(core, 0);
+        let Some(semaphore) = self.semaphores.get_mut(id as usize).and_then(Option::take) else {
+            return -1i32 as u32;
+        };
+        for thread in semaphore.waiting_threads {
+            self.release_wait(thread);
+        }
+        id
+    }
+
+    fn signal_semaphore(&mut self, core: &mut Core, _bus: &mut Bus) -> u32 {
+        let id = argument(core, 0);
+        let Some(semaphore) = self.semaphore(core) else {
+            return -1i32 as u32;
+        };
+        if let Some(thread) = semaphore.waiting_threads.pop_front() {
+            self.release_wait(thread);
+        } else if semaphore.count < semaphore.max_count {
+            semaphore.count += 1;
+        } else {
+            return -1i32 as u32;
+        }
+        id
+    }
+
+    fn wait_semaphore(&mut self, core: &mut Core, _bus: &mut Bus) -> u32 {
+        let id = argument(core, 0);
+        let thread = self.current_thread.expect("Waiting without a thread");
+        let Some(semaphore) = self.semaphore(core) else {
+            return -1i32 as u32;
+        };
+        if semaphore.count > 0 {
+            semaphore.count -= 1;
+        } else {
+            semaphore.waiting_threads.push_back(thread);
+            self.wait(Wait::Semaphore(id));
+        }
+        id
+    }
+
+    fn poll_semaphore(&mut self, core: &mut Core, _bus: &mut Bus) -> u32 {
+        let id = argument(core, 0);
+        let Some(semaphore) = self.semaphore(core) else {
+            return -1i32 as u32;
+        };
+        if semaphore.count == 0 {
+            return -1i32 as u32;
+        }
+        semaphore.count -= 1;
+        id
+    }
+
+    fn refer_semaphore_status(&mut self, core: &mut Core, bus: &mut Bus) -> u32 {
+        let id = argument(core, 0);
+        let parameters = argument(core, 1);
+        let Some(semaphore) = self.semaphore(core) else {
+            return -1i32 as u32;
+        };
+        let fields = [
+            semaphore.count as u32,
+            semaphore.max_count as u32,
+            semaphore.initial_count as u32,
+            semaphore.waiting_threads.len() as u32,
+            semaphore.attribute,
+            semaphore.option,
+        ];
+        for (index, field) in fields.into_iter().enumerate() {
+            write(core, bus, parameters + index as u32 * 4, field);
+        }
+        id
+    }
+
+    fn set_osd_config_param(&mut self, core: &mut Core, bus: &mut Bus) -> u32 {
+        self.osd_config = read(core, bus, argument(core, 0));
+        0
+    }
+
+    fn get_osd_config_param(&mut self, core: &mut Core, bus: &mut Bus) -> u32 {
+        write(core, bus, argument(core, 0), self.osd_config);
+        0
+    }
+
+    fn get_osd_config_param2(&mut self, core: &mut Core, bus: &mut Bus) -> u32 {
+        let address = argument(core, 0);
+        let size = argument(core, 1);
+        for offset in 0..size {
+            write(core, bus, address + offset, 0u8);
+        }
+        0
+    }
+
+    fn get_entry_address(&mut self, core: &mut Core, _bus: &mut Bus) -> u32 {
+        let number = argument(core, 0) as i32;
+        self.custom_syscalls
+            .iter()
+            .find(|(custom_number, _)| *custom_number == number)
+            .map_or(0, |&(_, address)| address)
+    }
+
+    fn get_cop0(&mut self, core: &mut Core, _bus: &mut Bus) -> u32 {
+        let register = control::Register::from(argument(core, 0));
+        core.state.control.get_register(register)
+    }
+
+    fn gs_get_imr(&mut self, _core: &mut Core, _bus: &mut Bus) -> u32 {
+        self.gs_interrupt_mask
+    }
+
+    fn gs_put_imr(&mut self, core: &mut Core, bus: &mut Bus) -> u32 {
+        self.gs_interrupt_mask = argument(core, 0);
+        bus.gs
+            .write_privileged64(GS_IMR, self.gs_interrupt_mask as u64);
+        0
+    }
+
+    fn set_vsync_flag(&mut self, core: &mut Core, bus: &mut Bus) -> u32 {
+        self.vsync_flag = Some(VSyncFlag {
+            flag_address: argument(core, 0),
+            status_address: argument(core, 1),
+        });
+        Kernel::set_intc_mask(bus, Interrupt::VBlankStart.into_usize() as u32, true);
+        0
+    }
+
+    fn set_syscall(&mut self, core: &mut Core, _bus: &mut Bus) -> u32 {
+        let number = argument(core, 0) as i32;
+        let address = argument(core, 1);
+        self.custom_syscalls
+            .retain(|(custom_number, _)| *custom_number != number);
+        self.custom_syscalls.push((number, address));
+        0
+    }
+
+    // There is no IOP, so SIF DMA transfers complete immediately.
+    fn sif_dma_status(&mut self, _core: &mut Core, _bus: &mut Bus) -> u32 {
+        -1i32 as u32
+    }
+
+    fn sif_set_dma(&mut self, _core: &mut Core, _bus: &mut Bus) -> u32 {
+        let id = self.next_sif_dma_id;
+        self.next_sif_dma_id = self.next_sif_dma_id.wrapping_add(1).max(1);
+        id
+    }
+
+    fn sif_set_register(&mut self, core: &mut Core, _bus: &mut Bus) -> u32 {
+        let register = argument(core, 0);
+        let value = argument(core, 1);
+        self.sif_registers.retain(|&(index, _)| index != register);
+        self.sif_registers.push((register, value));
+        0
+    }
+
+    fn sif_get_register(&mut self, core: &mut Core, _bus: &mut Bus) -> u32 {
+        let register = argument(core, 0);
+        self.sif_registers
+            .iter()
+            .find(|&&(index, _)| index == register)
+            .map_or(0, |&(_, value)| value)
+    }
+
+    fn get_memory_size(&mut self, _core: &mut Core, _bus: &mut Bus) -> u32 {
+        32 * 1024 * 1024
+    }
+
+    fn interrupt(&mut self, core: &mut Core, bus: &mut Bus) {
+        let intc_status = bus.intc.read32(INTC_STAT) & bus.intc.read32(INTC_MASK);
+        for cause in 0..Interrupt::LENGTH as u32 {
+            if !intc_status.bit(cause) {
+                continue;
+            }
+            bus.intc.write32(INTC_STAT, 1 << cause);
+            if cause == Interrupt::VBlankStart.into_usize() as u32 {
+                if let Some(vsync_flag) = &self.vsync_flag {
+                    let status = bus.gs.read_privileged64(GS_CSR);
+                    write(core, bus, vsync_flag.flag_address, 1u32);
+                    write(core, bus, vsync_flag.status_address, status);
+                }
+            }
+            if cause == Interrupt::Timer3.into_usize() as u32 {
+                for (id, alarm) in self.alarms.iter_mut().enumerate() {
+                    if alarm
+                        .as_ref()
+                        .is_some_and(|alarm| alarm.remaining_cycles == 0)
+                    {
+                        let alarm = alarm.take().unwrap();
+                        self.callbacks.push_back(Callback {
+                            function: alarm.handler,
+                            arguments: [id as u32, alarm.time as u32, alarm.argument],
+                        });
+                    }
+                }
+            }
+            for handler in &self.intc_handlers {
+                if handler.cause == cause && handler.enabled {
+                    self.callbacks.push_back(Callback {
+                        function: handler.handler,
+                        arguments: [cause, handler.argument, 0],
+                    });
+                }
+            }
+        }
+        let dmac_status = bus.dmac.read32(D_STAT);
+        // The channels, then the stall (SIS), MFIFO empty (MEIS) and bus error (BEIS) causes. BEIS
+        // has no mask bit.
+        for cause in (0..Channel::LENGTH as u32).chain(13..=15) {
+            if !dmac_status.bit(cause) || (cause != 15 && !dmac_status.bit(cause + 16)) {
+                continue;
+            }
+            bus.dmac.write32(D_STAT, 1 << cause);
+            for handler in &self.dmac_handlers {
+                if handler.cause == cause && handler.enabled {
+                    self.callbacks.push_back(Callback {
+                        function: handler.handler,
+                        arguments: [cause, handler.argument, 0],
+                    });
+                }
+            }
+        }
+        self.dispatch_callbacks(core);
+    }
+
+    // Run the next pending callback, or go back to the interrupted code when there are none left.
+    // Callbacks run at exception level so they can't be interrupted.
+    fn dispatch_callbacks(&mut self, core: &mut Core) {
+        if self.interrupted_context.is_none() {
+            if self.callbacks.is_empty() {
+                return;
+            }
+            self.interrupted_context = Some(Context::save(core));
+            core.state.control.set_exception_level(true);
+            core.update_mode();
+        }
+        if let Some(callback) = self.callbacks.pop_front() {
+            for (register, value) in ARGUMENT_REGISTERS.into_iter().zip(callback.arguments) {
+                core.set_register::<u64>(register, value.sign_extend());
+            }
+            core.set_register::<u64>(Register::Sp, CALLBACK_STACK_POINTER.sign_extend());
+            core.set_register::<u64>(Register::Ra, CALLBACK_RETURN_ADDRESS.sign_extend());
+            core.state.program_counter = callback.function;
+        } else {
+            self.interrupted_context.take().unwrap().restore(core);
+            core.state.control.set_exception_level(false);
+            core.update_mode();
+            self.reschedule(core);
+        }
+    }
+}
+
+fn argument(core: &Core, index: usize) -> u32 {
+    core.get_register(ARGUMENT_REGISTERS[index])
+}
+
+// Put a kernel object in the first free slot, returning its ID. ID 0 is never used.
+fn insert<T>(objects: &mut Vec<Option<T>>, object: T) -> u32 {
+    if objects.is_empty() {
+        objects.push(None);
+    }
+    match objects.iter().skip(1).position(Option::is_none) {
+        Some(index) => {
+            objects[index + 1] = Some(object);
+            index as u32 + 1
+        }
+        None => {
+            objects.push(Some(object));
+            objects.len() as u32 - 1
+        }
+    }
+}
+
+fn read<T: Bytes + LowerHex + Default>(core: &Core, bus: &mut Bus, address: u32) -> T {
+    core.read_virtual(bus, address)
+        .unwrap_or_else(|exception| panic!("Kernel read at {address:#010x}: {exception:?}"))
+}
+
+fn write<T: Bytes + LowerHex>(core: &mut Core, bus: &mut Bus, address: u32, value: T) {
+    core.write_virtual(bus, address, value)
+        .unwrap_or_else(|exception| panic!("Kernel write at {address:#010x}: {exception:?}"))
+}
+
+fn read_string(core: &Core, bus: &mut Bus, address: u32) -> String {
+    let mut bytes = Vec::new();
+    for address in address.. {
+        let byte = read::<u8>(core, bus, address);
+        if byte == 0 {
+            break;
+        }
+        bytes.push(byte);
+    }
+    String::from_utf8_lossy(&bytes).into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::emotion_engine::dmac::Dmac;
+
+    const D_STADR: u32 = 0x1000_E060;
+    const D2_CHCR: u32 = 0x1000_A000;
+    const D2_TADR: u32 = 0x1000_A030;
+    const HANDLER_ADDRESS: u32 = 0x0010_0000;
+
+    #[test]
+    fn stall_interrupt_runs_dmac_handler() {
+        let mut core = Core::new();
+        let mut bus = Bus::new();
+        Kernel::install(&mut core, &mut bus, Path::new("."));
+        core.kernel
+            .as_mut()
+            .unwrap()
+            .dmac_handlers
+            .push(InterruptHandler {
+                id: 1,
+                cause: 13,
+                handler: HANDLER_ADDRESS,
+                argument: 7,
+                enabled: true,
+            });
+        // GIF drains behind a stall address nothing has written past, and SIM is unmasked.
+        bus.dmac.write32(D_CTRL, 0b10 << 6 | 1);
+        bus.dmac.write32(D_STADR, 0);
+        bus.dmac.write32(D_STAT, 1 << 29);
+        // refs QWC=1 ADDR=0x2000
+        bus.write(PhysicalAddress(0x1000), 0x2000u128 << 32 | 0b100 << 28 | 1);
+        bus.dmac.write32(D2_TADR, 0x1000);
+        bus.dmac.write32(D2_CHCR, 1 << 8 | 0b01 << 2);
+        Dmac::step(&mut bus);
+        assert!(bus.dmac.interrupt_pending());
+
+        core.raise_exception(&mut bus, Exception::Interrupt, false);
+        assert!(!bus.dmac.interrupt_pending());
+        assert!(!bus.dmac.read32(D_STAT).bit(13));
+        assert_eq!(core.state.program_counter, HANDLER_ADDRESS);
+        assert_eq!(argument(&core, 0), 13);
+        assert_eq!(argument(&core, 1), 7);
+    }
+
+    #[test]
+    fn unhandled_exception_enters_guest_vector() {
+        let mut core = Core::new();
+        let mut bus = Bus::new();
+        Kernel::install(&mut core, &mut bus, Path::new("."));
+        core.state.program_counter = 0x0010_0000;
+        core.raise_exception(&mut bus, Exception::Break, false);
+        assert_eq!(core.state.program_counter, COMMON_EXCEPTION_VECTOR);
+        let control = &core.state.control;
+        assert!(control.exception_level());
+        assert_eq!(control.get_register(control::Register::Epc), 0x0010_0000);
+        assert_eq!(
+            control.get_register(control::Register::Cause).bits(2..7),
+            Exception::Break.code()
+        );
+    }
+}
@@ -0,0 +1,454 @@
+use std::collections::VecDeque;
+
+use enum_map::enum_map;
+use log::debug;
+
+use crate::{
+    bits::SignExtend,
+    emotion_engine::{
+        bus::Bus,
+        core::{fpu::Fpu, register::Register, Core},
+    },
+};
+
+use super::{argument, insert, read, write, Context, Kernel};
+
+pub const PRIORITIES: usize = 128;
+// The kernel runs this loop when no thread is ready.
+pub const IDLE_LOOP_ADDRESS: u32 = 0x8000_1008;
+// Threads return here from their entry function, which exits the thread.
+pub const THREAD_EXIT_ADDRESS: u32 = 0x8000_1010;
+// StartThread reserves space at the top of the stack for the thread's context.
+const CONTEXT_SAVE_AREA_SIZE: u32 = 0x2A0;
+// Thread ID 0 refers to the calling thread.
+const SELF: u32 = 0;
+
+pub struct Thread {
+    status: ThreadStatus,
+    entry: u32,
+    stack: u32,
+    stack_size: u32,
+    global_pointer: u32,
+    initial_priority: u32,
+    priority: u32,
+    attribute: u32,
+    option: u32,
+    wakeup_count: u32,
+    context: Context,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ThreadStatus {
+    Ready,
+    Waiting(Wait),
+    Suspended,
+    WaitingSuspended(Wait),
+    Dormant,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Wait {
+    Sleep,
+    Semaphore(u32),
+}
+
+impl Thread {
+    fn new(entry: u32, stack: u32, stack_size: u32, global_pointer: u32, priority: u32) -> Thread {
+        Thread {
+            status: ThreadStatus::Dormant,
+            entry,
+            stack,
+            stack_size,
+            global_pointer,
+            initial_priority: priority,
+            priority,
+            attribute: 0,
+            option: 0,
+            wakeup_count: 0,
+            context: Context {
+                registers: enum_map! { _ => 0 },
+                fpu: Fpu::new(),
+                program_counter: entry,
+            },
+        }
+    }
+
+    pub fn stack_end(&self) -> u32 {
+        self.stack + self.stack_size
+    }
+}
+
+impl Kernel {
+    // The thread that's running when the ELF is entered.
+    pub(super) fn create_main_thread(&mut self) {
+        let id = insert(&mut self.threads, Thread::new(0, 0, 0, 0, 0));
+        self.make_ready(id);
+        self.current_thread = Some(id);
+    }
+
+    // Drops every thread and the kernel objects that refer to them, leaving a fresh main thread
+    // for a new program.
+    pub(super) fn reset_threads(&mut self) {
+        self.threads.clear();
+        self.ready_queues = Kernel::new_ready_queues();
+        self.semaphores.clear();
+        self.alarms.clear();
+        self.intc_handlers.clear();
+        self.dmac_handlers.clear();
+        self.callbacks.clear();
+        self.interrupted_context = None;
+        self.create_main_thread();
+    }
+
+    pub(super) fn current_thread(&mut self) -> Option<&mut Thread> {
+        let id = self.current_thread?;
+        self.threads[id as usize].as_mut()
+    }
+
+    fn thread_id(&self, id: u32) -> Option<u32> {
+        if id == SELF {
+            self.current_thread
+        } else {
+            Some(id)
+        }
+    }
+
+    fn thread(&mut self, id: u32) -> Option<&mut Thread> {
+        let id = self.thread_id(id)?;
+        self.threads.get_mut(id as usize).and_then(Option::as_mut)
+    }
+
+    fn make_ready(&mut self, id: u32) {
+        let thread = self.threads[id as usize].as_mut().unwrap();
+        thread.status = ThreadStatus::Ready;
+        self.ready_queues[thread.priority as usize].push_back(id);
+    }
+
+    fn remove_from_ready_queue(&mut self, id: u32) {
+        for queue in &mut self.ready_queues {
+            queue.retain(|&ready| ready != id);
+        }
+    }
+
+    // Block the calling thread. It keeps running until the next reschedule.
+    pub(super) fn wait(&mut self, wait: Wait) {
+        let id = self.current_thread.expect("Waiting without a thread");
+        self.remove_from_ready_queue(id);
+        self.threads[id as usize].as_mut().unwrap().status = ThreadStatus::Waiting(wait);
+    }
+
+    pub(super) fn release_wait(&mut self, id: u32) {
+        let thread = self.threads[id as usize].as_mut().unwrap();
+        match thread.status {
+            ThreadStatus::Waiting(_) => self.make_ready(id),
+            ThreadStatus::WaitingSuspended(_) => thread.status = ThreadStatus::Suspended,
+            status => panic!("Releasing thread {id} that isn't waiting: {status:?}"),
+        }
+    }
+
+    // Stop waiting on whatever the thread is waiting on, e.g. because it was terminated.
+    fn cancel_wait(&mut self, id: u32) {
+        if let Some(
+            ThreadStatus::Waiting(Wait::Semaphore(semaphore))
+            | ThreadStatus::WaitingSuspended(Wait::Semaphore(semaphore)),
+        ) = self.threads[id as usize]
+            .as_ref()
+            .map(|thread| thread.status)
+        {
+            if let Some(Some(semaphore)) = self.semaphores.get_mut(semaphore as usize) {
+                semaphore.waiting_threads.retain(|&waiting| waiting != id);
+            }
+        }
+    }
+
+    // Switch to the highest priority ready thread. Threads of the same priority run in the
+    // order of the ready queue, with the running thread at the front.
+    pub(super) fn reschedule(&mut self, core: &mut Core) {
+        let next_thread = self
+            .ready_queues
+            .iter()
+            .find_map(|queue| queue.front().copied());
+        if next_thread == self.current_thread {
+            return;
+        }
+        debug!(
+            target: "kernel",
+            "Switching from thread {:?} to {:?}",
+            self.current_thread,
+            next_thread
+        );
+        if let Some(thread) = self.current_thread() {
+            thread.context = Context::save(core);
+        }
+        self.current_thread = next_thread;
+        match self.current_thread() {
+            Some(thread) => thread.context.restore(core),
+            None => core.state.program_counter = IDLE_LOOP_ADDRESS,
+        }
+    }
+
+    pub(super) fn create_thread(&mut self, core: &mut Core, bus: &mut Bus) -> u32 {
+        let parameters = argument(core, 0);
+        let mut thread = Thread::new(
+            read(core, bus, parameters + 4),
+            read(core, bus, parameters + 8),
+            read(core, bus, parameters + 12),
+            read(core, bus, parameters + 16),
+            read(core, bus, parameters + 20),
+        );
+        if thread.priority as usize >= PRIORITIES {
+            return -1i32 as u32;
+        }
+        thread.attribute = read(core, bus, parameters + 28);
+        thread.option = read(core, bus, parameters + 32);
+        insert(&mut self.threads, thread)
+    }
+
+    pub(super) fn delete_thread(&mut self, core: &mut Core, _bus: &mut Bus) -> u32 {
+        let id = argument(core, 0);
+        if id == SELF || Some(id) == self.current_thread {
+            return -1i32 as u32;
+        }
+        match self.threads.get_mut(id as usize) {
+            Some(thread @ Some(_)) if thread.as_ref().unwrap().status == ThreadStatus::Dormant => {
+                *thread = None;
+                id
+            }
+            _ => -1i32 as u32,
+        }
+    }
+
+    pub(super) fn start_thread(&mut self, core: &mut Core, _bus: &mut Bus) -> u32 {
+        let id = argument(core, 0);
+        let arguments = argument(core, 1);
+        let Some(thread) = self.thread(id) else {
+            return -1i32 as u32;
+        };
+        if thread.status != ThreadStatus::Dormant {
+            return -1i32 as u32;
+        }
+        thread.priority = thread.initial_priority;
+        thread.wakeup_count = 0;
+        let sign_extend = |value: u32| SignExtend::<u64>::sign_extend(value) as u128;
+        let stack_pointer = thread.stack_end() - CONTEXT_SAVE_AREA_SIZE;
+        let registers = &mut thread.context.registers;
+        *registers = enum_map! { _ => 0 };
+        registers[Register::Sp] = sign_extend(stack_pointer);
+        registers[Register::Gp] = sign_extend(thread.global_pointer);
+        registers[Register::A0] = sign_extend(arguments);
+        registers[Register::Ra] = sign_extend(THREAD_EXIT_ADDRESS);
+        thread.context.fpu = Fpu::new();
+        thread.context.program_counter = thread.entry;
+        self.make_ready(id);
+        id
+    }
+
+    pub(super) fn exit_thread(&mut self, _core: &mut Core, _bus: &mut Bus) -> u32 {
+        let id = self.current_thread.expect("Exiting without a thread");
+        self.remove_from_ready_queue(id);
+        self.threads[id as usize].as_mut().unwrap().status = ThreadStatus::Dormant;
+        0
+    }
+
+    pub(super) fn exit_delete_thread(&mut self, core: &mut Core, bus: &mut Bus) -> u32 {
+        self.exit_thread(core, bus);
+        // The thread stays current until the reschedule, which won't save its context.
+        let id = self.current_thread.unwrap();
+        self.threads[id as usize] = None;
+        0
+    }
+
+    pub(super) fn terminate_thread(&mut self, core: &mut Core, _bus: &mut Bus) -> u32 {
+        let id = argument(core, 0);
+        if id == SELF || Some(id) == self.current_thread {
+            return -1i32 as u32;
+        }
+        if self.thread(id).is_none() {
+            return -1i32 as u32;
+        }
+        self.cancel_wait(id);
+        self.remove_from_ready_queue(id);
+        self.threads[id as usize].as_mut().unwrap().status = ThreadStatus::Dormant;
+        id
+    }
+
+    pub(super) fn change_thread_priority(&mut self, core: &mut Core, _bus: &mut Bus) -> u32 {
+        let Some(id) = self.thread_id(argument(core, 0)) else {
+            return -1i32 as u32;
+        };
+        let priority = argument(core, 1);
+        if priority as usize >= PRIORITIES {
+            return -1i32 as u32;
+        }
+        let Some(thread) = self.thread(id) else {
+            return -1i32 as u32;
+        };
+        let old_priority = thread.priority;
+        thread.priority = priority;
+        if thread.status == ThreadStatus::Ready {
+            self.remove_from_ready_queue(id);
+            self.make_ready(id);
+        }
+        old_priority
+    }
+
+    pub(super) fn rotate_thread_ready_queue(&mut self, core: &mut Core, _bus: &mut Bus) -> u32 {
+        let priority = argument(core, 0);
+        let Some(queue) = self.ready_queues.get_mut(priority as usize) else {
+            return -1i32 as u32;
+        };
+        queue.rotate_left(1.min(queue.len()));
+        priority
+    }
+
+    pub(super) fn release_wait_thread(&mut self, core: &mut Core, _bus: &mut Bus) -> u32 {
+        let id = argument(core, 0);
+        match self.thread(id).map(|thread| thread.status) {
+            Some(ThreadStatus::Waiting(_) | ThreadStatus::WaitingSuspended(_)) => {
+                self.cancel_wait(id);
+                self.release_wait(id);
+                id
+            }
+            _ => -1i32 as u32,
+        }
+    }
+
+    // Sets up the main thread's stack, returning its stack pointer.
+    pub(super) fn setup_thread(&mut self, core: &mut Core, _bus: &mut Bus) -> u32 {
+        let global_pointer = argument(core, 0);
+        let base = argument(core, 1);
+        let size = argument(core, 2);
+        debug!(target: "kernel", "Argument base={:#010x}, size={:#010x}", base, size);
+        let base = if base == 0xFFFF_FFFF {
+            0x0200_0000 - size
+        } else {
+            base
+        };
+        debug!(target: "kernel", "Stack base={:#010x}, size={:#010x}", base, size);
+        let thread = self.current_thread().expect("SetupThread without a thread");
+        thread.stack = base;
+        thread.stack_size = size;
+        thread.global_pointer = global_pointer;
+        thread.stack_end()
+    }
+
+    pub(super) fn get_thread_id(&mut self, _core: &mut Core, _bus: &mut Bus) -> u32 {
+        self.current_thread.unwrap_or(0)
+    }
+
+    pub(super) fn refer_thread_status(&mut self, core: &mut Core, bus: &mut Bus) -> u32 {
+        let Some(id) = self.thread_id(argument(core, 0)) else {
+            return -1i32 as u32;
+        };
+        let status_address = argument(core, 1);
+        let running = self.current_thread == Some(id);
+        let Some(thread) = self.thread(id) else {
+            return -1i32 as u32;
+        };
+        // THS_RUN, THS_READY, THS_WAIT, THS_SUSPEND, THS_WAITSUSPEND and THS_DORMANT
+        let (status, wait) = match thread.status {
+            ThreadStatus::Ready if running => (0x01, None),
+            ThreadStatus::Ready => (0x02, None),
+            ThreadStatus::Waiting(wait) => (0x04, Some(wait)),
+            ThreadStatus::Suspended => (0x08, None),
+            ThreadStatus::WaitingSuspended(wait) => (0x0C, Some(wait)),
+            ThreadStatus::Dormant => (0x10, None),
+        };
+        let (wait_type, wait_id) = match wait {
+            None => (0, 0),
+            Some(Wait::Sleep) => (1, 0),
+            Some(Wait::Semaphore(semaphore)) => (2, semaphore),
+        };
+        if status_address != 0 {
+            let fields = [
+                status,
+                thread.entry,
+                thread.stack,
+                thread.stack_size,
+                thread.global_pointer,
+                thread.initial_priority,
+                thread.priority,
+                thread.attribute,
+                thread.option,
+                wait_type,
+                wait_id,
+                thread.wakeup_count,
+            ];
+            for (index, field) in fields.into_iter().enumerate() {
+                write(core, bus, status_address + index as u32 * 4, field);
+            }
+        }
+        status
+    }
+
+    pub(super) fn sleep_thread(&mut self, _core: &mut Core, _bus: &mut Bus) -> u32 {
+        let thread = self.current_thread().expect("Sleeping without a thread");
+        if thread.wakeup_count > 0 {
+            thread.wakeup_count -= 1;
+        } else {
+            self.wait(Wait::Sleep);
+        }
+        self.current_thread.unwrap()
+    }
+
+    pub(super) fn wakeup_thread(&mut self, core: &mut Core, _bus: &mut Bus) -> u32 {
+        let id = argument(core, 0);
+        if id == SELF || Some(id) == self.current_thread {
+            return -1i32 as u32;
+        }
+        let Some(thread) = self.thread(id) else {
+            return -1i32 as u32;
+        };
+        match thread.status {
+            ThreadStatus::Waiting(Wait::Sleep) | ThreadStatus::WaitingSuspended(Wait::Sleep) => {
+                self.release_wait(id)
+            }
+            ThreadStatus::Dormant => return -1i32 as u32,
+            _ => thread.wakeup_count += 1,
+        }
+        id
+    }
+
+    pub(super) fn cancel_wakeup_thread(&mut self, core: &mut Core, _bus: &mut Bus) -> u32 {
+        let Some(thread) = self.thread(argument(core, 0)) else {
+            return -1i32 as u32;
+        };
+        std::mem::take(&mut thread.wakeup_count)
+    }
+
+    pub(super) fn suspend_thread(&mut self, core: &mut Core, _bus: &mut Bus) -> u32 {
+        let id = argument(core, 0);
+        if id == SELF || Some(id) == self.current_thread {
+            return -1i32 as u32;
+        }
+        let Some(thread) = self.thread(id) else {
+            return -1i32 as u32;
+        };
+        match thread.status {
+            ThreadStatus::Ready => {
+                thread.status = ThreadStatus::Suspended;
+                self.remove_from_ready_queue(id);
+            }
+            ThreadStatus::Waiting(wait) => thread.status = ThreadStatus::WaitingSuspended(wait),
+            _ => return -1i32 as u32,
+        }
+        id
+    }
+
+    pub(super) fn resume_thread(&mut self, core: &mut Core, _bus: &mut Bus) -> u32 {
+        let id = argument(core, 0);
+        let Some(thread) = self.thread(id) else {
+            return -1i32 as u32;
+        };
+        match thread.status {
+            ThreadStatus::Suspended => self.make_ready(id),
+            ThreadStatus::WaitingSuspended(wait) => thread.status = ThreadStatus::Waiting(wait),
+            _ => return -1i32 as u32,
+        }
+        id
+    }
+
+    pub(super) fn new_ready_queues() -> Vec<VecDeque<u32>> {
+        vec![VecDeque::new(); PRIORITIES]
+    }
+}
@@ -0,0 +1,611 @@
+use std::fmt::Display;
+
+use crate::emotion_engine::bus::{Bus, DeviceLog, PhysicalAddress};
+
+use super::{
+    fpu, instruction::Occurrence, instruction_gen::Instruction, jit::JitConfig, register::Register,
+    Core, Mode, State,
+};
+
+// Chunks of memory are compared and reported at the width of the widest access.
+const MEMORY_CHUNK_SIZE: usize = std::mem::size_of::<u128>();
+// Loops can keep a block going for a long time, so only the start of the trace is reported.
+const REPORTED_TRACE_LENGTH: usize = 64;
+
+// Runs every compiled block a second time through the interpreter and compares the two, to catch
+// miscompilations where they happen. Compiled code refers to the core and the bus by address, so
+// the block runs first, on the real ones. The interpreter then runs from the state the block
+// started in, on a shadow bus whose memory is kept in sync with the real one through the page
+// generations, and whose device accesses are answered from those the block made.
+pub struct Lockstep {
+    bus: Bus,
+    // The page generations of both buses when the shadow memory was last synced.
+    generations: Box<[u32]>,
+    shadow_generations: Box<[u32]>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Location {
+    ProgramCounter,
+    DelayedBranchTarget,
+    ShiftAmount,
+    Mode,
+    Register(Register),
+    Control,
+    Fpu(fpu::Register),
+    FpuAccumulator,
+    FpuControl,
+    Memory(PhysicalAddress),
+    DeviceAccess(usize),
+    Cycles,
+}
+
+impl Display for Location {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Location::ProgramCounter => write!(f, "pc"),
+            Location::DelayedBranchTarget => write!(f, "delayed branch target"),
+            Location::ShiftAmount => write!(f, "sa"),
+            Location::Mode => write!(f, "mode"),
+            Location::Register(register) => write!(f, "{}", register),
+            Location::Control => write!(f, "cop0"),
+            Location::Fpu(register) => write!(f, "{}", register),
+            Location::FpuAccumulator => write!(f, "acc"),
+            Location::FpuControl => write!(f, "fcr31"),
+            Location::Memory(address) => write!(f, "memory at 0x{:08x}", address.0),
+            Location::DeviceAccess(index) => write!(f, "device access #{}", index),
+            Location::Cycles => write!(f, "cycles"),
+        }
+    }
+}
+
+impl Location {
+    // Whether an instruction may have produced the value at the location. Used to point at the
+    // instruction a divergence most likely comes from.
+    fn written_by(self, instruction: Instruction) -> bool {
+        let defines =
+            |occurrence: &dyn Fn(Occurrence) -> bool| instruction.definitions().any(occurrence);
+        match self {
+            Location::ProgramCounter | Location::DelayedBranchTarget => instruction.is_branch(),
+            Location::ShiftAmount => matches!(instruction, Instruction::Mtsa(..)),
+            Location::Register(register) => {
+                defines(&|definition| definition == Occurrence::Core(register))
+            }
+            Location::Control => {
+                defines(&|definition| matches!(definition, Occurrence::Control(_)))
+            }
+            Location::Fpu(register) => {
+                defines(&|definition| definition == Occurrence::Fpu(register))
+            }
+            Location::FpuAccumulator => matches!(
+                instruction,
+                Instruction::Addas(..)
+                    | Instruction::Subas(..)
+                    | Instruction::Mulas(..)
+                    | Instruction::Maddas(..)
+                    | Instruction::Msubas(..)
+            ),
+            Location::FpuControl => defines(&|definition| matches!(definition, Occurrence::Fpu(_))),
+            Location::Memory(_) => matches!(
+                instruction,
+                Instruction::Sb(..)
+                    | Instruction::Sh(..)
+                    | Instruction::Sw(..)
+                    | Instruction::Sd(..)
+                    | Instruction::Sq(..)
+                    | Instruction::Swl(..)
+                    | Instruction::Swr(..)
+                    | Instruction::Sdl(..)
+                    | Instruction::Sdr(..)
+                    | Instruction::Swc1(..)
+            ),
+            Location::Mode | Location::DeviceAccess(_) | Location::Cycles => false,
+        }
+    }
+}
+
+struct Mismatch {
+    location: Location,
+    compiled: String,
+    interpreted: String,
+}
+
+// How a compiled block and the interpreter disagreed.
+struct Divergence {
+    start: u32,
+    // The instructions the interpreter ran, with their addresses.
+    trace: Vec<(u32, Instruction)>,
+    mismatches: Vec<Mismatch>,
+}
+
+impl Divergence {
+    // The earliest instruction whose result differs, taking the last instruction writing each
+    // mismatching location to be the one that got it wrong.
+    fn first_diverging_instruction(&self) -> Option<usize> {
+        self.mismatches
+            .iter()
+            .filter_map(|mismatch| {
+                self.trace
+                    .iter()
+                    .rposition(|&(_, instruction)| mismatch.location.written_by(instruction))
+            })
+            .min()
+    }
+}
+
+impl Display for Divergence {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(
+            f,
+            "The block compiled at 0x{:08x} diverged from the interpreter",
+            self.start
+        )?;
+        let first = self.first_diverging_instruction();
+        match first {
+            Some(index) => {
+                let (program_counter, instruction) = self.trace[index];
+                writeln!(
+                    f,
+                    "First diverging instruction: 0x{:08x}: {}",
+                    program_counter, instruction
+                )?;
+            }
+            None => writeln!(f, "First diverging instruction: unknown")?,
+        }
+        for mismatch in &self.mismatches {
+            writeln!(
+                f,
+                "  {}: compiled {}, interpreted {}",
+                mismatch.location, mismatch.compiled, mismatch.interpreted
+            )?;
+        }
+        writeln!(f, "Interpreted:")?;
+        for (index, (program_counter, instruction)) in
+            self.trace.iter().enumerate().take(REPORTED_TRACE_LENGTH)
+        {
+            let marker = if Some(index) == first { ">" } else { " " };
+            writeln!(f, "{} 0x{:08x}: {}", marker, program_counter, instruction)?;
+        }
+        if self.trace.len() > REPORTED_TRACE_LENGTH {
+            writeln!(f, "  ... {} more", self.trace.len() - REPORTED_TRACE_LENGTH)?;
+        }
+        Ok(())
+    }
+}
+
+impl Lockstep {
+    fn new(bus: &Bus) -> Self {
+        let mut shadow_bus = Bus::new();
+        shadow_bus.lockstep = true;
+        shadow_bus.main_memory.copy_from_slice(&bus.main_memory);
+        shadow_bus.boot_memory.copy_from_slice(&bus.boot_memory);
+        shadow_bus.scratchpad.copy_from_slice(&bus.scratchpad);
+        let generations = |bus: &Bus| {
+            (0..bus.tracked_page_count())
+                .map(|page| bus.page_generation(page))
+                .collect()
+        };
+        Lockstep {
+            generations: generations(bus),
+            shadow_generations: generations(&shadow_bus),
+            bus: shadow_bus,
+        }
+    }
+
+    // The pages written on either bus since the last sync.
+    fn written_pages(&self, bus: &Bus) -> Vec<usize> {
+        (0..bus.tracked_page_count())
+            .filter(|&page| {
+                bus.page_generation(page) != self.generations[page]
+                    || self.bus.page_generation(page) != self.shadow_generations[page]
+            })
+            .collect()
+    }
+
+    // Copies the pages written since the last sync to the shadow bus, whatever wrote them.
+    fn sync(&mut self, bus: &Bus) {
+        for page in self.written_pages(bus) {
+            self.bus
+                .tracked_page_mut(page)
+                .copy_from_slice(bus.tracked_page(page));
+            self.generations[page] = bus.page_generation(page);
+            self.shadow_generations[page] = self.bus.page_generation(page);
+        }
+    }
+
+    fn compare_memory(&self, bus: &Bus, mismatches: &mut Vec<Mismatch>) {
+        for page in self.written_pages(bus) {
+            let compiled = bus.tracked_page(page).chunks(MEMORY_CHUNK_SIZE);
+            let interpreted = self.bus.tracked_page(page).chunks(MEMORY_CHUNK_SIZE);
+            for (index, (compiled, interpreted)) in compiled.zip(interpreted).enumerate() {
+                if compiled != interpreted {
+                    mismatches.push(Mismatch {
+                        location: Location::Memory(
+                            Bus::tracked_page_address(page) + (index * MEMORY_CHUNK_SIZE) as u32,
+                        ),
+                        compiled: format!("{:02x?}", compiled),
+                        interpreted: format!("{:02x?}", interpreted),
+                    });
+                }
+            }
+        }
+    }
+}
+
+fn compare<T: PartialEq>(
+    mismatches: &mut Vec<Mismatch>,
+    location: Location,
+    compiled: T,
+    interpreted: T,
+    format: impl Fn(T) -> String,
+) {
+    if compiled != interpreted {
+        mismatches.push(Mismatch {
+            location,
+            compiled: format(compiled),
+            interpreted: format(interpreted),
+        });
+    }
+}
+
+fn compare_states(compiled: &State, interpreted: &State, mismatches: &mut Vec<Mismatch>) {
+    let hex = |value: u128| format!("0x{:x}", value);
+    compare(
+        mismatches,
+        Location::ProgramCounter,
+        compiled.program_counter as u128,
+        interpreted.program_counter as u128,
+        hex,
+    );
+    compare(
+        mismatches,
+        Location::DelayedBranchTarget,
+        compiled.delayed_branch_target,
+        interpreted.delayed_branch_target,
+        |target| format!("{:x?}", target),
+    );
+    compare(
+        mismatches,
+        Location::ShiftAmount,
+        compiled.shift_amount as u128,
+        interpreted.shift_amount as u128,
+        hex,
+    );
+    for register in Register::all() {
+        compare(
+            mismatches,
+            Location::Register(register),
+            compiled.registers[register],
+            interpreted.registers[register],
+            hex,
+        );
+    }
+    compare(
+        mismatches,
+        Location::Control,
+        &compiled.control,
+        &interpreted.control,
+        |control| format!("{:x?}", control),
+    );
+    for index in 0..32 {
+        let register = fpu::Register::from(index);
+        compare(
+            mismatches,
+            Location::Fpu(register),
+            compiled.fpu.get_register::<u32>(register) as u128,
+            interpreted.fpu.get_register::<u32>(register) as u128,
+            hex,
+        );
+    }
+    compare(
+        mismatches,
+        Location::FpuAccumulator,
+        compiled.fpu.accumulator().to_bits() as u128,
+        interpreted.fpu.accumulator().to_bits() as u128,
+        hex,
+    );
+    let fcr31 = fpu::Register::from(31);
+    compare(
+        mismatches,
+        Location::FpuControl,
+        compiled.fpu.get_control(fcr31) as u128,
+        interpreted.fpu.get_control(fcr31) as u128,
+        hex,
+    );
+}
+
+impl Core {
+    // Checks every compiled block against the interpreter from now on. Blocks are no longer linked
+    // or skip idle loops, so that each is checked on its own.
+    pub fn enable_lockstep(&mut self, bus: &mut Bus) {
+        bus.lockstep = true;
+        self.jit.set_config(JitConfig {
+            idle_loop_detection: false,
+            lockstep: true,
+            ..self.jit.config()
+        });
+        self.lockstep = Some(Box::new(Lockstep::new(bus)));
+    }
+
+    // Runs a compiled block like `Jit::run`, then interprets it from the same state, panicking
+    // with a report if the two differ.
+    pub(super) fn run_lockstep(
+        &mut self,
+        function: *const u8,
+        bus: &mut Bus,
+        cycles: &mut u64,
+    ) -> bool {
+        let mut lockstep = self.lockstep.take().unwrap();
+        lockstep.sync(bus);
+        let start = self.state.program_counter;
+        let (state, mode) = (self.state.clone(), self.mode);
+        let budget = *cycles as i64;
+
+        *bus.device_log.get_mut() = DeviceLog::Recording(Vec::new());
+        let completed = self.jit.run(
+            function,
+            self.mode,
+            self.state.registers.as_slice().as_ptr(),
+            cycles,
+        );
+        let DeviceLog::Recording(recorded) = bus.device_log.take() else {
+            unreachable!()
+        };
+        let compiled_cycles = budget - self.jit.cycles_left();
+        let compiled_state = std::mem::replace(&mut self.state, state);
+        let compiled_mode = std::mem::replace(&mut self.mode, mode);
+
+        *lockstep.bus.device_log.get_mut() = DeviceLog::Replaying {
+            recorded,
+            replayed: Vec::new(),
+        };
+        let mut trace = Vec::new();
+        let mut interpreted_cycles = 0;
+        while interpreted_cycles < compiled_cycles {
+            let program_counter = self.state.program_counter;
+            let Some(physical_program_counter) =
+                self.mmu.virtual_to_physical(program_counter, self.mode)
+            else {
+                break;
+            };
+            let instruction = Instruction::decode(lockstep.bus.read(physical_program_counter));
+            trace.push((program_counter, instruction));
+            interpreted_cycles += self.interpret_timed(instruction, &mut lockstep.bus) as i64;
+        }
+        let DeviceLog::Replaying { recorded, replayed } = lockstep.bus.device_log.take() else {
+            unreachable!()
+        };
+        let interpreted_state = std::mem::replace(&mut self.state, compiled_state);
+        let interpreted_mode = std::mem::replace(&mut self.mode, compiled_mode);
+
+        let mut mismatches = Vec::new();
+        compare_states(&self.state, &interpreted_state, &mut mismatches);
+        compare(
+            &mut mismatches,
+            Location::Mode,
+            self.mode,
+            interpreted_mode,
+            |mode: Mode| format!("{:?}", mode),
+        );
+        lockstep.compare_memory(bus, &mut mismatches);
+        for index in 0..recorded.len().max(replayed.len()) {
+            compare(
+                &mut mismatches,
+                Location::DeviceAccess(index),
+                recorded.get(index),
+                replayed.get(index),
+                |access| access.map_or("none".to_string(), |access| access.to_string()),
+            );
+        }
+        compare(
+            &mut mismatches,
+            Location::Cycles,
+            compiled_cycles,
+            interpreted_cycles,
+            |cycles| cycles.to_string(),
+        );
+        lockstep.sync(bus);
+        self.lockstep = Some(lockstep);
+        if !mismatches.is_empty() {
+            panic!(
+                "{}",
+                Divergence {
+                    start,
+                    trace,
+                    mismatches,
+                }
+            );
+        }
+        completed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{bits::SignExtend, emotion_engine::core::instruction_gen::ENCODINGS};
+
+    const SEQUENCES: u32 = 200;
+    const SEQUENCE_LENGTH: u32 = 16;
+    // Sequences are laid out one after the other in kseg0, each followed by a loop to itself.
+    const SEQUENCE_START: u32 = 0x8010_0000;
+    const SEQUENCE_STRIDE: u32 = 0x100;
+    // beq zero, zero, -1
+    const BRANCH_TO_SELF: u32 = 0x1000_FFFF;
+    // Exceptions end up in a loop to itself at the general exception vector.
+    const EXCEPTION_VECTOR: PhysicalAddress = PhysicalAddress(0x180);
+    const CACHE_OPCODE: u32 = 0b101111;
+    // Loads and stores go through these registers, which point into a window of main memory away
+    // from the code and no other instruction writes.
+    const BASE_REGISTERS: [Register; 2] = [Register::S6, Register::S7];
+    const SCRATCH_WINDOW: u32 = 0x8030_0000;
+
+    // xorshift64*, seeded so that failures can be reproduced.
+    struct Random(u64);
+
+    impl Random {
+        fn next(&mut self) -> u64 {
+            self.0 ^= self.0 >> 12;
+            self.0 ^= self.0 << 25;
+            self.0 ^= self.0 >> 27;
+            self.0.wrapping_mul(0x2545_F491_4F6C_DD1D)
+        }
+
+        // Random instructions to run from `start`. The last one isn't a branch, as its delay slot
+        // would be the loop ending the sequence, and neither are delay slots.
+        fn sequence(&mut self, start: u32) -> Vec<u32> {
+            let mut sequence = Vec::new();
+            let mut delay_slot = false;
+            for index in 0..SEQUENCE_LENGTH {
+                let branch = !delay_slot && index + 1 < SEQUENCE_LENGTH;
+                let raw_instruction = self.instruction(start, start + index * 4, branch);
+                delay_slot = Instruction::decode(raw_instruction).is_branch();
+                sequence.push(raw_instruction);
+            }
+            sequence
+        }
+
+        // A random instruction at `address` that runs the same whatever the registers hold: no
+        // traps or system control, branches that land in the sequence starting at `start`, and
+        // memory accesses through the base registers. The trapping additions and subtractions
+        // may overflow and misaligned accesses raise address errors, leaving the block for the
+        // interpreter to raise the exception.
+        fn instruction(&mut self, start: u32, address: u32, branch: bool) -> u32 {
+            loop {
+                let (mask, bits) = ENCODINGS[self.next() as usize % ENCODINGS.len()];
+                let mut raw_instruction = bits | self.next() as u32 & !mask;
+                // Reserved cache operations don't decode.
+                if raw_instruction >> 26 == CACHE_OPCODE {
+                    continue;
+                }
+                let instruction = Instruction::decode(raw_instruction);
+                if matches!(
+                    instruction,
+                    Instruction::Unknown
+                        | Instruction::Syscall
+                        | Instruction::Break
+                        | Instruction::Tge(..)
+                        | Instruction::Tgeu(..)
+                        | Instruction::Tlt(..)
+                        | Instruction::Tltu(..)
+                        | Instruction::Teq(..)
+                        | Instruction::Tne(..)
+                        | Instruction::Mfc0(..)
+                        | Instruction::Mtc0(..)
+                        | Instruction::Tlbr
+                        | Instruction::Tlbwi
+                        | Instruction::Tlbwr
+                        | Instruction::Tlbp
+                        | Instruction::Eret
+                        | Instruction::Ei
+                        | Instruction::Di
+                        | Instruction::Cfc1(..)
+                        | Instruction::Ctc1(..)
+                        | Instruction::Jr(..)
+                        | Instruction::Jalr(..)
+                ) || instruction
+                    .definitions()
+                    .any(|occurrence| BASE_REGISTERS.map(Occurrence::Core).contains(&occurrence))
+                {
+                    continue;
+                }
+                if instruction.is_branch() {
+                    if !branch {
+                        continue;
+                    }
+                    // Anywhere from the start of the sequence to the loop after it.
+                    let target = start + (self.next() as u32 % (SEQUENCE_LENGTH + 1)) * 4;
+                    raw_instruction = match instruction {
+                        Instruction::J(_) | Instruction::Jal(_) => {
+                            raw_instruction & !0x03FF_FFFF | (target >> 2) & 0x03FF_FFFF
+                        }
+                        _ => {
+                            let offset = target.wrapping_sub(address + 4) >> 2;
+                            raw_instruction & !0xFFFF | offset & 0xFFFF
+                        }
+                    };
+                } else if matches!(
+                    instruction,
+                    Instruction::Lb(..)
+                        | Instruction::Lbu(..)
+                        | Instruction::Lh(..)
+                        | Instruction::Lhu(..)
+                        | Instruction::Lw(..)
+                        | Instruction::Lwl(..)
+                        | Instruction::Lwr(..)
+                        | Instruction::Ld(..)
+                        | Instruction::Ldl(..)
+                        | Instruction::Ldr(..)
+                        | Instruction::Lq(..)
+                        | Instruction::Lwc1(..)
+                        | Instruction::Sb(..)
+                        | Instruction::Sh(..)
+                        | Instruction::Sw(..)
+                        | Instruction::Swl(..)
+                        | Instruction::Swr(..)
+                        | Instruction::Sd(..)
+                        | Instruction::Sdl(..)
+                        | Instruction::Sdr(..)
+                        | Instruction::Sq(..)
+                        | Instruction::Swc1(..)
+                ) {
+                    // Offsets are mostly aligned, so that few accesses raise address errors.
+                    let base = BASE_REGISTERS[self.next() as usize % BASE_REGISTERS.len()];
+                    let offset_mask = if self.next().is_multiple_of(8) {
+                        0xFFFF
+                    } else {
+                        0xFFF0
+                    };
+                    raw_instruction = raw_instruction & !(0x1F << 21 | 0xFFFF)
+                        | (base as u32) << 21
+                        | raw_instruction & offset_mask;
+                }
+                return raw_instruction;
+            }
+        }
+    }
+
+    // Runs the sequences with and without the registers pinned, as that changes how registers
+    // flow through the blocks.
+    #[test]
+    fn random_sequences_match_interpreter() {
+        for register_pinning in [false, true] {
+            let mut core = Core::new();
+            let mut bus = Bus::new();
+            core.jit.set_config(JitConfig {
+                register_pinning,
+                ..JitConfig::default()
+            });
+            bus.write(EXCEPTION_VECTOR, BRANCH_TO_SELF);
+            bus.write(EXCEPTION_VECTOR + 4, 0u32);
+            core.enable_lockstep(&mut bus);
+            let mut random = Random(0x9E37_79B9_7F4A_7C15);
+            for sequence in 0..SEQUENCES {
+                let start = SEQUENCE_START + sequence * SEQUENCE_STRIDE;
+                let physical_start = PhysicalAddress::memory(start & 0x1FFF_FFFF);
+                for (index, raw_instruction) in random.sequence(start).into_iter().enumerate() {
+                    bus.write(physical_start + index as u32 * 4, raw_instruction);
+                }
+                let end = physical_start + SEQUENCE_LENGTH * 4;
+                bus.write(end, BRANCH_TO_SELF);
+                bus.write(end + 4, 0u32);
+                for register in Register::all().filter(|&register| register != Register::Zero) {
+                    core.state.registers[register] =
+                        (random.next() as u128) << 64 | random.next() as u128;
+                }
+                for register in BASE_REGISTERS {
+                    let address = SCRATCH_WINDOW + (random.next() as u32 % 0x100) * 16;
+                    core.state.registers[register] =
+                        SignExtend::<u64>::sign_extend(address) as u128;
+                }
+                for index in 0..32 {
+                    core.state
+                        .fpu
+                        .set_register(fpu::Register::from(index), random.next() as u32);
+                }
+                core.state.program_counter = start;
+                core.step(SEQUENCE_LENGTH as u64 * 64, &mut bus);
+            }
+        }
+    }
+}
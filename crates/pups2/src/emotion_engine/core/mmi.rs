@@ -0,0 +1,110 @@
+use super::{register::Register, Core};
+use crate::bits::SignExtend;
+
+// Lane views of the 128-bit registers used by the multimedia instructions. Lane 0 is the least
+// significant one.
+
+pub fn doublewords(value: u128) -> [u64; 2] {
+    std::array::from_fn(|i| (value >> (64 * i)) as u64)
+}
+
+pub fn from_doublewords(lanes: [u64; 2]) -> u128 {
+    lanes
+        .iter()
+        .rev()
+        .fold(0, |value, &lane| value << 64 | lane as u128)
+}
+
+pub fn words(value: u128) -> [u32; 4] {
+    std::array::from_fn(|i| (value >> (32 * i)) as u32)
+}
+
+pub fn from_words(lanes: [u32; 4]) -> u128 {
+    lanes
+        .iter()
+        .rev()
+        .fold(0, |value, &lane| value << 32 | lane as u128)
+}
+
+pub fn halfwords(value: u128) -> [u16; 8] {
+    std::array::from_fn(|i| (value >> (16 * i)) as u16)
+}
+
+pub fn from_halfwords(lanes: [u16; 8]) -> u128 {
+    lanes
+        .iter()
+        .rev()
+        .fold(0, |value, &lane| value << 16 | lane as u128)
+}
+
+pub fn bytes(value: u128) -> [u8; 16] {
+    value.to_le_bytes()
+}
+
+pub fn from_bytes(lanes: [u8; 16]) -> u128 {
+    u128::from_le_bytes(lanes)
+}
+
+pub fn map_words(a: u128, b: u128, f: impl Fn(u32, u32) -> u32) -> u128 {
+    let (a, b) = (words(a), words(b));
+    from_words(std::array::from_fn(|i| f(a[i], b[i])))
+}
+
+pub fn map_halfwords(a: u128, b: u128, f: impl Fn(u16, u16) -> u16) -> u128 {
+    let (a, b) = (halfwords(a), halfwords(b));
+    from_halfwords(std::array::from_fn(|i| f(a[i], b[i])))
+}
+
+pub fn map_bytes(a: u128, b: u128, f: impl Fn(u8, u8) -> u8) -> u128 {
+    let (a, b) = (bytes(a), bytes(b));
+    from_bytes(std::array::from_fn(|i| f(a[i], b[i])))
+}
+
+// All ones when the condition holds, the representation used by the compare instructions.
+pub fn mask<T: From<u8> + std::ops::Not<Output = T>>(condition: bool) -> T {
+    if condition {
+        !T::from(0)
+    } else {
+        T::from(0)
+    }
+}
+
+pub fn saturate_to_i16(value: i32) -> u16 {
+    value.clamp(i16::MIN as i32, i16::MAX as i32) as u16
+}
+
+pub fn saturate_to_i32(value: i64) -> u32 {
+    value.clamp(i32::MIN as i64, i32::MAX as i64) as u32
+}
+
+impl Core {
+    // The 64-bit accumulators of the parallel word multiplies, split into 32-bit halves across LO
+    // and HI.
+    pub fn word_accumulators(&self) -> [u64; 2] {
+        let lo = words(self.get_register(Register::Lo));
+        let hi = words(self.get_register(Register::Hi));
+        [0, 2].map(|i| (hi[i] as u64) << 32 | lo[i] as u64)
+    }
+
+    pub fn set_word_products(&mut self, rd: Register, products: [u64; 2]) {
+        let lo = products.map(|product| -> u64 { (product as u32).sign_extend() });
+        let hi = products.map(|product| -> u64 { ((product >> 32) as u32).sign_extend() });
+        self.set_register(Register::Lo, from_doublewords(lo));
+        self.set_register(Register::Hi, from_doublewords(hi));
+        self.set_register(rd, from_doublewords(products));
+    }
+
+    // The 32-bit accumulators of the parallel halfword multiplies, in halfword lane order.
+    pub fn halfword_accumulators(&self) -> [u32; 8] {
+        let lo = words(self.get_register(Register::Lo));
+        let hi = words(self.get_register(Register::Hi));
+        [lo[0], lo[1], hi[0], hi[1], lo[2], lo[3], hi[2], hi[3]]
+    }
+
+    pub fn set_halfword_products(&mut self, rd: Register, products: [u32; 8]) {
+        let p = products;
+        self.set_register(Register::Lo, from_words([p[0], p[1], p[4], p[5]]));
+        self.set_register(Register::Hi, from_words([p[2], p[3], p[6], p[7]]));
+        self.set_register(rd, from_words([p[0], p[2], p[4], p[6]]));
+    }
+}
@@ -2,25 +2,51 @@ use std::{fmt::LowerHex, ops::Range};
 
 use bitvec::vec::BitVec;
 use enum_map::{enum_map, EnumMap};
+use log::debug;
 
 use crate::{
     bits::Bits,
     bytes::Bytes,
-    emotion_engine::bus::{Bus, PhysicalAddress},
+    emotion_engine::{
+        bus::{Bus, CacheMode, PhysicalAddress},
+        fastmem::{self, Fastmem},
+    },
 };
 
-use super::{Core, Mode};
+use super::{control, exception::Exception, Core, Mode};
 
 const PAGE_BITS: u32 = 20;
-const OFFSET_BITS: u32 = 32 - PAGE_BITS;
-const PAGE_SIZE: u32 = 1 << OFFSET_BITS;
+pub const OFFSET_BITS: u32 = 32 - PAGE_BITS;
+pub const PAGE_SIZE: u32 = 1 << OFFSET_BITS;
 const OFFSET_MASK: u32 = PAGE_SIZE - 1;
-const PAGES: u32 = 1 << PAGE_BITS;
+pub const PAGES: u32 = 1 << PAGE_BITS;
+
+const TLB_ENTRIES: usize = 48;
+
+// The segments of the virtual address space. kseg0 and kseg1 are mapped directly onto the first
+// 512 MiB of physical memory, the others are translated by the TLB.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Segment {
+    Kuseg,
+    Kseg0,
+    Kseg1,
+    Ksseg,
+    Kseg3,
+}
 
 pub struct Mmu {
     tlb_entries: Box<[TlbEntry]>,
+    address_space_id: u8,
     pages: EnumMap<Mode, Box<[PhysicalAddress]>>,
+    // Segments are mode independent once accessible, so the cache mode is tracked per page only.
+    cache_modes: Box<[CacheMode]>,
     mapped: EnumMap<Mode, BitVec<usize>>,
+    // Mapped pages that can be stored to, i.e. kseg0/kseg1 and TLB pages with the dirty bit set.
+    writable: EnumMap<Mode, BitVec<usize>>,
+    // The pages whose translation changed since the fastmem windows were last brought in line,
+    // see `Core::update_fastmem`.
+    stale_fastmem_pages: EnumMap<Mode, BitVec<usize>>,
+    fastmem_stale: bool,
 }
 
 #[derive(Clone, Debug)]
@@ -36,32 +62,144 @@ impl Mmu {
         let mut mapped = enum_map! { _ =>
             BitVec::repeat(false, PAGES as usize)
         };
+        let mut cache_modes = vec![CacheMode::Cached; PAGES as usize].into_boxed_slice();
         let kernel_pages = &mut pages[Mode::Kernel];
         let kernel_mapped = &mut mapped[Mode::Kernel];
-        // kseg0 and kseg1 are mapped directly to physical memory.
-        let kseg_pages = 0x8000_0000 >> OFFSET_BITS..0xC000_0000 >> OFFSET_BITS;
-        for page in kseg_pages.clone() {
+        for page in 0..PAGES {
             let address = page << OFFSET_BITS;
+            let Some(cache_mode) = Segment::decode(address).direct_cache_mode() else {
+                continue;
+            };
             kernel_pages[page as usize] = PhysicalAddress::memory(address & 0x1FFF_FFFF);
+            kernel_mapped.set(page as usize, true);
+            cache_modes[page as usize] = cache_mode;
         }
-        kernel_mapped[kseg_pages.start as usize..kseg_pages.end as usize].fill(true);
-        Mmu {
-            tlb_entries: vec![TlbEntry::new(0); 48].into_boxed_slice(),
+        let writable = mapped.clone();
+        let mut mmu = Mmu {
+            tlb_entries: vec![TlbEntry::new(0); TLB_ENTRIES].into_boxed_slice(),
+            address_space_id: 0,
             pages,
+            cache_modes,
             mapped,
+            writable,
+            stale_fastmem_pages: enum_map! { _ =>
+                BitVec::repeat(false, PAGES as usize)
+            },
+            fastmem_stale: false,
+        };
+        for page in 0..PAGES {
+            mmu.invalidate_fastmem_page(page, Mode::Kernel);
+        }
+        mmu
+    }
+
+    fn invalidate_fastmem_page(&mut self, page: u32, mode: Mode) {
+        self.stale_fastmem_pages[mode].set(page as usize, true);
+        self.fastmem_stale = true;
+    }
+
+    // The pages mapped onto a physical frame, in any mode.
+    fn aliases(&self, frame: PhysicalAddress) -> Vec<(Mode, u32)> {
+        let mut virtual_addresses = Vec::new();
+        if frame.0 < 0x2000_0000 {
+            virtual_addresses.extend([0x8000_0000 | frame.0, 0xA000_0000 | frame.0]);
+        }
+        for entry in self.tlb_entries.iter() {
+            if !entry.global() && entry.address_space_id() != self.address_space_id {
+                continue;
+            }
+            let len = entry.len();
+            for (virtual_page, physical_frame, _, _) in entry.mappings() {
+                if frame >= physical_frame && frame - physical_frame < len {
+                    virtual_addresses.push(virtual_page + (frame - physical_frame));
+                }
+            }
+        }
+        let mut aliases = Vec::new();
+        for virtual_address in virtual_addresses {
+            let page = virtual_address >> OFFSET_BITS;
+            for mode in [Mode::Kernel, Mode::Supervisor, Mode::User] {
+                if self.mapped[mode][page as usize] && self.pages[mode][page as usize] == frame {
+                    aliases.push((mode, page));
+                }
+            }
+        }
+        aliases
+    }
+
+    // Makes the pages mapped onto freshly compiled code read only in the fastmem windows, so that
+    // stores to them go through the slow path, which leaves invalidating the code to the
+    // interpreter.
+    pub fn protect_fastmem_code(&self, code: Range<PhysicalAddress>, fastmem: &Fastmem) {
+        let first_frame = code.start.0 >> OFFSET_BITS;
+        let last_frame = (code.end.0 - 1) >> OFFSET_BITS;
+        for frame in first_frame..=last_frame {
+            let frame = PhysicalAddress(frame << OFFSET_BITS);
+            if Fastmem::page_flags(frame) & fastmem::WRITABLE == 0 {
+                continue;
+            }
+            for (mode, page) in self.aliases(frame) {
+                if self.writable[mode][page as usize] {
+                    fastmem.make_read_only(mode, page);
+                }
+            }
         }
     }
 
-    pub fn virtual_to_physical(&self, virtual_address: u32, mode: Mode) -> PhysicalAddress {
+    pub fn virtual_to_physical(&self, virtual_address: u32, mode: Mode) -> Option<PhysicalAddress> {
         let page = virtual_address >> OFFSET_BITS;
         if !self.mapped[mode][page as usize] {
-            panic!(
-                "Virtual address {:#x} not mapped in mode {:?}",
-                virtual_address, mode
-            );
+            return None;
         }
         let physical_frame_start = unsafe { *self.pages[mode].get_unchecked(page as usize) };
-        PhysicalAddress(physical_frame_start.0 | (virtual_address & OFFSET_MASK))
+        Some(PhysicalAddress(
+            physical_frame_start.0 | (virtual_address & OFFSET_MASK),
+        ))
+    }
+
+    pub fn virtual_to_physical_writable(
+        &self,
+        virtual_address: u32,
+        mode: Mode,
+    ) -> Option<PhysicalAddress> {
+        let page = virtual_address >> OFFSET_BITS;
+        if !self.writable[mode][page as usize] {
+            return None;
+        }
+        self.virtual_to_physical(virtual_address, mode)
+    }
+
+    // The cache mode of a mapped address: fixed for kseg0 and kseg1, taken from the C field of
+    // the TLB entry otherwise.
+    pub fn cache_mode(&self, virtual_address: u32) -> CacheMode {
+        let page = virtual_address >> OFFSET_BITS;
+        unsafe { *self.cache_modes.get_unchecked(page as usize) }
+    }
+
+    // The exception raised by an access that failed to translate.
+    pub fn fault(&self, virtual_address: u32, mode: Mode, store: bool) -> Exception {
+        if !Segment::decode(virtual_address).accessible(mode) {
+            return if store {
+                Exception::AddressErrorStore(virtual_address)
+            } else {
+                Exception::AddressErrorLoad(virtual_address)
+            };
+        }
+        let entry = self
+            .tlb_entries
+            .iter()
+            .find(|entry| entry.matches(virtual_address, self.address_space_id));
+        match entry.map(|entry| entry.page_flags(virtual_address)) {
+            None if store => Exception::TlbRefillStore(virtual_address),
+            None => Exception::TlbRefillLoad(virtual_address),
+            Some((false, _)) if store => Exception::TlbInvalidStore(virtual_address),
+            Some((false, _)) => Exception::TlbInvalidLoad(virtual_address),
+            Some((true, false)) if store => Exception::TlbModified(virtual_address),
+            Some(_) => panic!(
+                "Mapped address {:#010x} failed to translate in {:?} mode",
+                virtual_address, mode
+            ),
+        }
     }
 
     pub fn physically_consecutive(&self, virtual_range: Range<u32>, mode: Mode) -> bool {
@@ -77,91 +215,148 @@ impl Mmu {
         true
     }
 
+    pub fn read_index(&self, index: u8) -> TlbEntry {
+        self.tlb_entries[index as usize].clone()
+    }
+
     pub fn write_index(&mut self, index: u8, entry: TlbEntry) {
-        assert!(index < 48);
-        println!(
-            "Writing TLB index {:#02x} with entry {:#018x}, mask {:#02x}",
+        assert!((index as usize) < TLB_ENTRIES);
+        debug!(
+            target: "ee.core",
+            "Writing TLB index {:#02x} with entry {:#034x}, mask {:#02x}",
             index,
             entry.raw,
             entry.mask()
         );
-        println!(
-            "Virtual page number: {:#x}, size: {:#x}, scratchpad: {}",
-            entry.virtual_page_number_even(),
+        debug!(
+            target: "ee.core",
+            "Virtual address: {:#010x}, size: {:#x}, scratchpad: {}, ASID: {:#x}, global: {}",
+            entry.virtual_address_even(),
             entry.len(),
             entry.scratchpad(),
+            entry.address_space_id(),
+            entry.global()
         );
-        assert!(entry.address_space_id() == 0);
         self.unmap_entry(self.tlb_entries[index as usize].clone());
-        self.tlb_entries[index as usize] = entry.clone();
-        self.map_entry(entry);
+        self.tlb_entries[index as usize] = entry;
+        // Other entries may overlap the pages that were unmapped.
+        self.map_entries();
+    }
+
+    // The index of the entry matching EntryHi, as searched by TLBP.
+    pub fn probe(&self, virtual_address: u32, address_space_id: u8) -> Option<u8> {
+        self.tlb_entries
+            .iter()
+            .position(|entry| entry.matches(virtual_address, address_space_id))
+            .map(|index| index as u8)
+    }
+
+    // EntryHi.ASID selects which non-global entries are mapped.
+    pub fn set_address_space_id(&mut self, address_space_id: u8) {
+        if address_space_id == self.address_space_id {
+            return;
+        }
+        for entry in self.tlb_entries.clone() {
+            self.unmap_entry(entry);
+        }
+        self.address_space_id = address_space_id;
+        self.map_entries();
     }
 
     fn unmap_entry(&mut self, entry: TlbEntry) {
         let len = entry.len();
-        for (virtual_page, _) in entry.mappings() {
+        for (virtual_page, _, _, _) in entry.mappings() {
             self.unmap(virtual_page, len);
         }
     }
 
     fn unmap(&mut self, virtual_page: u32, len: u32) {
-        let start_page = virtual_page >> OFFSET_BITS;
-        let end_page = (virtual_page + len) >> OFFSET_BITS;
-        self.mapped[Mode::Kernel][start_page as usize..end_page as usize].fill(false);
-        self.mapped[Mode::Supervisor][start_page as usize..end_page as usize].fill(false);
-        self.mapped[Mode::User][start_page as usize..end_page as usize].fill(false);
+        for page in Self::mappable_pages(virtual_page, len) {
+            for mode in [Mode::Kernel, Mode::Supervisor, Mode::User] {
+                self.mapped[mode].set(page as usize, false);
+                self.writable[mode].set(page as usize, false);
+                self.invalidate_fastmem_page(page, mode);
+            }
+        }
+    }
+
+    fn map_entries(&mut self) {
+        for entry in self.tlb_entries.clone() {
+            if entry.global() || entry.address_space_id() == self.address_space_id {
+                self.map_entry(entry);
+            }
+        }
     }
 
     fn map_entry(&mut self, entry: TlbEntry) {
         let len = entry.len();
-        for (virtual_page, physical_frame) in entry.mappings() {
-            self.map(virtual_page, physical_frame, len);
+        for (virtual_page, physical_frame, dirty, cache_mode) in entry.mappings() {
+            self.map(virtual_page, physical_frame, len, dirty, cache_mode);
         }
     }
 
-    fn map(&mut self, virtual_page: u32, physical_frame: PhysicalAddress, len: u32) {
+    fn map(
+        &mut self,
+        virtual_page: u32,
+        physical_frame: PhysicalAddress,
+        len: u32,
+        dirty: bool,
+        cache_mode: CacheMode,
+    ) {
         let start_page = virtual_page >> OFFSET_BITS;
-        let end_page = (virtual_page + len) >> OFFSET_BITS;
-        self.mapped[Mode::Kernel][start_page as usize..end_page as usize].fill(true);
-        for page in start_page..end_page {
-            let virtual_page = page << OFFSET_BITS;
+        for page in Self::mappable_pages(virtual_page, len) {
+            let segment = Segment::decode(page << OFFSET_BITS);
             let physical_frame = physical_frame + ((page - start_page) << OFFSET_BITS);
-            println!(
-                "Mapping virtual page {:#x} to physical frame {:#x}",
-                virtual_page, physical_frame.0
-            );
-            self.pages[Mode::Kernel][page as usize] = physical_frame;
-            match virtual_page {
-                0x0000_0000..0x8000_0000 => {
-                    self.pages[Mode::Supervisor][page as usize] = physical_frame;
-                    self.mapped[Mode::Supervisor].set(page as usize, true);
-                    self.pages[Mode::User][page as usize] = physical_frame;
-                    self.mapped[Mode::User].set(page as usize, true);
+            for mode in [Mode::Kernel, Mode::Supervisor, Mode::User] {
+                if segment.accessible(mode) {
+                    self.pages[mode][page as usize] = physical_frame;
+                    self.mapped[mode].set(page as usize, true);
+                    self.writable[mode].set(page as usize, dirty);
+                    self.invalidate_fastmem_page(page, mode);
                 }
-                0xC000_0000..0xE000_0000 => {
-                    self.pages[Mode::Supervisor][page as usize] = physical_frame;
-                    self.mapped[Mode::Supervisor].set(page as usize, true);
-                }
-                _ => {}
             }
+            self.cache_modes[page as usize] = cache_mode;
         }
     }
 
-    // TODO: This is for testing
-    pub fn mmap(&mut self, virtual_address: u32, size: u32, physical_address: u32) {
-        assert!(virtual_address & OFFSET_MASK == 0);
-        assert!(physical_address & OFFSET_MASK == 0);
-        let start_page = virtual_address >> OFFSET_BITS;
-        let end_page = (virtual_address + size - 1) >> OFFSET_BITS;
-        for page in start_page..=end_page {
-            let physical_frame = physical_address + ((page - start_page) << OFFSET_BITS);
-            self.pages[Mode::Kernel][page as usize] = PhysicalAddress::memory(physical_frame);
-            self.pages[Mode::Supervisor][page as usize] = PhysicalAddress::memory(physical_frame);
-            self.pages[Mode::User][page as usize] = PhysicalAddress::memory(physical_frame);
+    // The pages of a TLB mapping, skipping kseg0 and kseg1 which are never translated.
+    fn mappable_pages(virtual_page: u32, len: u32) -> impl Iterator<Item = u32> {
+        let start_page = virtual_page >> OFFSET_BITS;
+        let end_page = ((virtual_page as u64 + len as u64) >> OFFSET_BITS) as u32;
+        (start_page..end_page).filter(|page| {
+            Segment::decode(page << OFFSET_BITS)
+                .direct_cache_mode()
+                .is_none()
+        })
+    }
+}
+
+impl Segment {
+    pub fn decode(virtual_address: u32) -> Segment {
+        match virtual_address >> 29 {
+            0b000..=0b011 => Segment::Kuseg,
+            0b100 => Segment::Kseg0,
+            0b101 => Segment::Kseg1,
+            0b110 => Segment::Ksseg,
+            _ => Segment::Kseg3,
+        }
+    }
+
+    pub fn accessible(&self, mode: Mode) -> bool {
+        match mode {
+            Mode::Kernel => true,
+            Mode::Supervisor => matches!(self, Segment::Kuseg | Segment::Ksseg),
+            Mode::User => *self == Segment::Kuseg,
+        }
+    }
+
+    // The cache mode of the segments that bypass the TLB.
+    pub fn direct_cache_mode(&self) -> Option<CacheMode> {
+        match self {
+            Segment::Kseg0 => Some(CacheMode::Cached),
+            Segment::Kseg1 => Some(CacheMode::Uncached),
+            _ => None,
         }
-        self.mapped[Mode::Kernel][start_page as usize..=end_page as usize].fill(true);
-        self.mapped[Mode::Supervisor][start_page as usize..=end_page as usize].fill(true);
-        self.mapped[Mode::User][start_page as usize..=end_page as usize].fill(true);
     }
 }
 
@@ -188,12 +383,57 @@ impl TlbEntry {
         self.raw.bits(Self::MASK) as u16
     }
 
-    pub fn virtual_page_number_even(&self) -> u32 {
-        self.raw.bits(Self::VIRTUAL_PAGE_NUMBER_DIV_2) as u32 * 2
+    // Assembles an entry from PageMask, EntryHi, EntryLo0 and EntryLo1, as written by TLBWI and
+    // TLBWR.
+    pub fn from_registers(page_mask: u32, entry_hi: u32, entry_lo0: u32, entry_lo1: u32) -> Self {
+        let mut entry = 0;
+        entry.set_bits(TlbEntry::MASK, page_mask.bits(13..=24));
+        entry.set_bits(
+            TlbEntry::VIRTUAL_PAGE_NUMBER_DIV_2,
+            entry_hi.bits(13..=31) & !page_mask.bits(13..=24),
+        );
+        entry.set_bit(TlbEntry::GLOBAL, entry_lo0.bit(0) && entry_lo1.bit(0));
+        entry.set_bits(TlbEntry::ADDRESS_SPACE_ID, entry_hi.bits(0..=7));
+        entry.set_bits(33..=63, entry_lo0.bits(1..=31));
+        entry.set_bits(1..=31, entry_lo1.bits(1..=31));
+        TlbEntry::new(entry)
+    }
+
+    // PageMask, EntryHi, EntryLo0 and EntryLo1, as read back by TLBR.
+    pub fn to_registers(&self) -> [u32; 4] {
+        let global = self.global() as u32;
+        [
+            (self.mask() as u32) << 13,
+            self.virtual_address_even() | self.address_space_id() as u32,
+            (self.raw.bits(33..=63) as u32) << 1 | global,
+            (self.raw.bits(1..=31) as u32) << 1 | global,
+        ]
+    }
+
+    pub fn virtual_address_even(&self) -> u32 {
+        (self.raw.bits(Self::VIRTUAL_PAGE_NUMBER_DIV_2) as u32) << 13
+    }
+
+    pub fn virtual_address_odd(&self) -> u32 {
+        self.virtual_address_even() + self.len()
+    }
+
+    pub fn matches(&self, virtual_address: u32, address_space_id: u8) -> bool {
+        let mask = !(self.mask() as u32);
+        (self.global() || self.address_space_id() == address_space_id)
+            && virtual_address.bits(13..32) & mask
+                == self.raw.bits(Self::VIRTUAL_PAGE_NUMBER_DIV_2) as u32 & mask
     }
 
-    pub fn virtual_page_number_odd(&self) -> u32 {
-        self.raw.bits(Self::VIRTUAL_PAGE_NUMBER_DIV_2) as u32 * 2 + 1
+    // The valid and dirty bits of the half of the entry containing a matching address.
+    pub fn page_flags(&self, virtual_address: u32) -> (bool, bool) {
+        if self.scratchpad() {
+            (true, true)
+        } else if virtual_address & self.len() == 0 {
+            (self.valid_even(), self.dirty_even())
+        } else {
+            (self.valid_odd(), self.dirty_odd())
+        }
     }
 
     pub fn global(&self) -> bool {
@@ -256,12 +496,16 @@ impl TlbEntry {
         }
     }
 
-    pub fn mappings(&self) -> impl Iterator<Item = (u32, PhysicalAddress)> {
+    // The scratchpad bit overrides the page frames: the whole entry maps the scratchpad, always
+    // valid and dirty, and is never cached.
+    pub fn mappings(&self) -> impl Iterator<Item = (u32, PhysicalAddress, bool, CacheMode)> {
         (if self.scratchpad() {
             [
                 Some((
-                    self.virtual_page_number_even() << OFFSET_BITS,
+                    self.virtual_address_even(),
                     PhysicalAddress::scratchpad(0),
+                    true,
+                    CacheMode::Uncached,
                 )),
                 None,
             ]
@@ -274,14 +518,18 @@ impl TlbEntry {
             [
                 self.valid_even().then(|| {
                     (
-                        self.virtual_page_number_even() << OFFSET_BITS,
+                        self.virtual_address_even(),
                         PhysicalAddress::memory(even),
+                        self.dirty_even(),
+                        CacheMode::from_bits(self.cache_mode_even()),
                     )
                 }),
                 self.valid_odd().then(|| {
                     (
-                        self.virtual_page_number_odd() << OFFSET_BITS,
+                        self.virtual_address_odd(),
                         PhysicalAddress::memory(odd),
+                        self.dirty_odd(),
+                        CacheMode::from_bits(self.cache_mode_odd()),
                     )
                 }),
             ]
@@ -292,13 +540,260 @@ impl TlbEntry {
 }
 
 impl Core {
-    pub fn write_virtual<T: Bytes + LowerHex>(&self, bus: &mut Bus, address: u32, value: T) {
-        let physical_address = self.mmu.virtual_to_physical(address, self.mode);
-        bus.write(physical_address, value);
+    // Brings the fastmem windows in line with the translations that changed, in runs of pages
+    // mapped alike.
+    pub fn update_fastmem(&mut self, bus: &Bus) {
+        if !std::mem::take(&mut self.mmu.fastmem_stale) {
+            return;
+        }
+        for mode in [Mode::Kernel, Mode::Supervisor, Mode::User] {
+            let mut run: Option<(Range<u32>, PhysicalAddress, u32)> = None;
+            for page in self.mmu.stale_fastmem_pages[mode].iter_ones() {
+                let page = page as u32;
+                let (frame, access) = self.fastmem_access(page, mode);
+                match &mut run {
+                    Some((pages, run_frame, run_access))
+                        if pages.end == page
+                            && *run_access == access
+                            && (access == 0
+                                || *run_frame + (pages.len() as u32 * PAGE_SIZE) == frame) =>
+                    {
+                        pages.end += 1;
+                    }
+                    _ => {
+                        if let Some((pages, frame, access)) =
+                            run.replace((page..page + 1, frame, access))
+                        {
+                            bus.fastmem.map(mode, pages, frame, access);
+                        }
+                    }
+                }
+            }
+            if let Some((pages, frame, access)) = run {
+                bus.fastmem.map(mode, pages, frame, access);
+            }
+            self.mmu.stale_fastmem_pages[mode].fill(false);
+        }
+    }
+
+    // Puts back the page compiled code faulted on, which the fault handler covered with a scratch
+    // page to let the access complete.
+    pub fn restore_fastmem_page(&self, bus: &Bus) {
+        if let Some((mode, page)) = bus.fastmem.take_faulted_page() {
+            let (frame, access) = self.fastmem_access(page, mode);
+            bus.fastmem.map(mode, page..page + 1, frame, access);
+        }
+    }
+
+    // The frame behind a page and the accesses compiled code may make to it directly. Pages
+    // holding compiled code are read only, see `Mmu::protect_fastmem_code`.
+    fn fastmem_access(&self, page: u32, mode: Mode) -> (PhysicalAddress, u32) {
+        if !self.mmu.mapped[mode][page as usize] {
+            return (PhysicalAddress(0), 0);
+        }
+        let frame = self.mmu.pages[mode][page as usize];
+        let mut access = Fastmem::page_flags(frame);
+        if access & fastmem::WRITABLE != 0
+            && (!self.mmu.writable[mode][page as usize]
+                || self.jit.contains_code(frame..frame + PAGE_SIZE))
+        {
+            access &= !fastmem::WRITABLE;
+        }
+        (frame, access)
+    }
+
+    // TLBWI and TLBWR
+    pub fn write_tlb_entry(&mut self, index: u8) {
+        let control = &self.state.control;
+        let entry = TlbEntry::from_registers(
+            control.get_register(control::Register::PageMask),
+            control.get_register(control::Register::EntryHi),
+            control.get_register(control::Register::EntryLo0),
+            control.get_register(control::Register::EntryLo1),
+        );
+        self.mmu.write_index(index, entry);
+        self.jit.unlink_all();
+    }
+
+    pub fn set_address_space_id(&mut self, address_space_id: u8) {
+        if address_space_id != self.mmu.address_space_id {
+            self.mmu.set_address_space_id(address_space_id);
+            self.jit.unlink_all();
+        }
     }
 
-    pub fn read_virtual<T: Bytes + LowerHex + Default>(&self, bus: &mut Bus, address: u32) -> T {
-        let physical_address = self.mmu.virtual_to_physical(address, self.mode);
-        bus.read(physical_address)
+    pub fn write_virtual<T: Bytes + LowerHex>(
+        &mut self,
+        bus: &mut Bus,
+        address: u32,
+        value: T,
+    ) -> Result<(), Exception> {
+        let physical_address = self
+            .mmu
+            .virtual_to_physical_writable(address, self.mode)
+            .ok_or_else(|| self.mmu.fault(address, self.mode, true))?;
+        bus.write_with_cache_mode(physical_address, value, self.mmu.cache_mode(address));
+        Ok(())
+    }
+
+    pub fn read_virtual<T: Bytes + LowerHex + Default>(
+        &self,
+        bus: &mut Bus,
+        address: u32,
+    ) -> Result<T, Exception> {
+        let physical_address = self
+            .mmu
+            .virtual_to_physical(address, self.mode)
+            .ok_or_else(|| self.mmu.fault(address, self.mode, false))?;
+        Ok(bus.read_with_cache_mode(physical_address, self.mmu.cache_mode(address)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::emotion_engine::core::instruction_gen::Instruction;
+
+    const VALID: u32 = 1 << 1;
+    const DIRTY: u32 = 1 << 2;
+    const GLOBAL: u32 = 1 << 0;
+
+    // EntryLo for a cached 4 KiB frame.
+    fn entry_lo(frame: u32, flags: u32) -> u32 {
+        frame >> OFFSET_BITS << 6 | 0b011 << 3 | flags
+    }
+
+    fn entry(entry_hi: u32, entry_lo0: u32, entry_lo1: u32) -> TlbEntry {
+        TlbEntry::from_registers(0, entry_hi, entry_lo0, entry_lo1)
+    }
+
+    #[test]
+    fn translate_in_address_space() {
+        let mut mmu = Mmu::new();
+        mmu.set_address_space_id(1);
+        mmu.write_index(
+            0,
+            entry(
+                0x0001_0000 | 1,
+                entry_lo(0x2_0000, VALID | DIRTY),
+                entry_lo(0x2_1000, VALID),
+            ),
+        );
+        mmu.write_index(
+            1,
+            entry(
+                0x0002_0000 | 1,
+                entry_lo(0x3_0000, VALID | DIRTY | GLOBAL),
+                entry_lo(0x3_1000, VALID | DIRTY | GLOBAL),
+            ),
+        );
+        assert_eq!(
+            mmu.virtual_to_physical(0x0001_0123, Mode::User),
+            Some(PhysicalAddress(0x2_0123))
+        );
+        assert_eq!(
+            mmu.virtual_to_physical(0x0001_1123, Mode::User),
+            Some(PhysicalAddress(0x2_1123))
+        );
+        assert_eq!(
+            mmu.virtual_to_physical_writable(0x0001_0123, Mode::User),
+            Some(PhysicalAddress(0x2_0123))
+        );
+        assert_eq!(mmu.cache_mode(0x0001_0123), CacheMode::Cached);
+
+        // Only the global entry stays mapped in another address space.
+        mmu.set_address_space_id(2);
+        assert_eq!(mmu.virtual_to_physical(0x0001_0123, Mode::User), None);
+        assert_eq!(
+            mmu.fault(0x0001_0123, Mode::User, false),
+            Exception::TlbRefillLoad(0x0001_0123)
+        );
+        assert_eq!(
+            mmu.virtual_to_physical(0x0002_1123, Mode::User),
+            Some(PhysicalAddress(0x3_1123))
+        );
+
+        mmu.set_address_space_id(1);
+        assert_eq!(
+            mmu.virtual_to_physical(0x0001_0123, Mode::User),
+            Some(PhysicalAddress(0x2_0123))
+        );
+    }
+
+    #[test]
+    fn fault_exceptions() {
+        let mut mmu = Mmu::new();
+        mmu.write_index(
+            0,
+            entry(
+                0x0001_0000,
+                entry_lo(0x2_0000, VALID),
+                entry_lo(0x2_1000, 0),
+            ),
+        );
+        // No matching entry.
+        assert_eq!(
+            mmu.fault(0x0004_0000, Mode::User, false),
+            Exception::TlbRefillLoad(0x0004_0000)
+        );
+        assert_eq!(
+            mmu.fault(0x0004_0000, Mode::User, true),
+            Exception::TlbRefillStore(0x0004_0000)
+        );
+        // A matching entry without the valid bit for the odd page.
+        assert_eq!(mmu.virtual_to_physical(0x0001_1000, Mode::User), None);
+        assert_eq!(
+            mmu.fault(0x0001_1000, Mode::User, false),
+            Exception::TlbInvalidLoad(0x0001_1000)
+        );
+        assert_eq!(
+            mmu.fault(0x0001_1000, Mode::User, true),
+            Exception::TlbInvalidStore(0x0001_1000)
+        );
+        // A valid page without the dirty bit can only be loaded from.
+        assert_eq!(
+            mmu.virtual_to_physical(0x0001_0010, Mode::User),
+            Some(PhysicalAddress(0x2_0010))
+        );
+        assert_eq!(
+            mmu.virtual_to_physical_writable(0x0001_0010, Mode::User),
+            None
+        );
+        assert_eq!(
+            mmu.fault(0x0001_0010, Mode::User, true),
+            Exception::TlbModified(0x0001_0010)
+        );
+        // kseg0 isn't accessible outside kernel mode.
+        assert_eq!(
+            mmu.fault(0x8000_0000, Mode::User, false),
+            Exception::AddressErrorLoad(0x8000_0000)
+        );
+    }
+
+    #[test]
+    fn probe() {
+        let mut core = Core::new();
+        let mut bus = Bus::new();
+        core.mmu.write_index(
+            3,
+            entry(
+                0x0001_0000 | 5,
+                entry_lo(0x2_0000, VALID),
+                entry_lo(0x2_1000, VALID),
+            ),
+        );
+        let control = &mut core.state.control;
+        control.set_register(control::Register::EntryHi, 0x0001_0000 | 5);
+        core.interpret_instruction(Instruction::Tlbp, &mut bus);
+        assert_eq!(core.state.control.get_register(control::Register::Index), 3);
+
+        // A mismatched ASID misses.
+        let control = &mut core.state.control;
+        control.set_register(control::Register::EntryHi, 0x0001_0000 | 6);
+        core.interpret_instruction(Instruction::Tlbp, &mut bus);
+        assert_eq!(
+            core.state.control.get_register(control::Register::Index),
+            1 << 31
+        );
     }
 }
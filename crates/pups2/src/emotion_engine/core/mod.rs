@@ -1,17 +1,25 @@
 pub mod control;
+pub mod exception;
 pub mod execution;
 pub mod fpu;
 pub mod instruction;
 pub mod instruction_gen;
 pub mod interpreter;
 pub mod jit;
+pub mod kernel;
+pub mod lockstep;
+pub mod mmi;
 pub mod mmu;
+pub mod profile;
 pub mod register;
+pub mod timing;
 
 use control::Control;
 use enum_map::{enum_map, Enum, EnumMap};
 use fpu::Fpu;
 use jit::Jit;
+use kernel::Kernel;
+use lockstep::Lockstep;
 use register::{GetUpper, SetUpper};
 
 use {
@@ -19,7 +27,7 @@ use {
     register::{GetRegister, Register, SetRegister},
 };
 
-#[derive(Enum, Copy, Clone, Debug)]
+#[derive(Enum, Copy, Clone, Debug, PartialEq, Eq)]
 #[repr(u8)]
 pub enum Mode {
     Kernel,
@@ -31,17 +39,19 @@ pub struct Core {
     pub state: State,
     pub mode: Mode,
     pub mmu: Mmu,
-    pub main_thread_stack_pointer: u32, // TODO: This should be in the thread state
     pub jit: Jit,
+    pub kernel: Option<Kernel>,
+    lockstep: Option<Box<Lockstep>>,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct State {
     pub program_counter: u32,
     pub registers: EnumMap<Register, u128>,
     pub control: Control,
     pub fpu: Fpu,
     pub delayed_branch_target: Option<u32>,
+    pub shift_amount: u32, // SA
 }
 
 impl Core {
@@ -54,10 +64,12 @@ impl Core {
                 control: Control::new(),
                 fpu: Fpu::new(),
                 delayed_branch_target: None,
+                shift_amount: 0,
             },
             mmu: Mmu::new(),
-            main_thread_stack_pointer: 0,
             jit: Jit::new(),
+            kernel: None,
+            lockstep: None,
         }
     }
 
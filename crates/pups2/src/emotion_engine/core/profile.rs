@@ -0,0 +1,193 @@
+use super::instruction_gen::Instruction;
+use crate::emotion_engine::bus::PhysicalAddress;
+use cranelift_codegen::Context;
+use std::{
+    collections::BTreeMap,
+    fmt::{Display, Write as _},
+    fs::File,
+    io::Write,
+    ops::Range,
+    path::PathBuf,
+    time::Duration,
+};
+
+// What the JIT records about the blocks it compiles and runs.
+#[derive(Default)]
+pub struct ProfilerConfig {
+    // The directory every compiled block is dumped to, as guest instructions, Cranelift IR and
+    // host code.
+    pub dump_directory: Option<PathBuf>,
+    // Whether compiled blocks are listed in `/tmp/perf-<pid>.map` for Linux `perf` to symbolise.
+    pub perf_map: bool,
+    // Whether runs of blocks are counted and timed, see `Profiler::hot_blocks`.
+    pub block_statistics: bool,
+}
+
+#[derive(Default)]
+pub struct Profiler {
+    dump_directory: Option<PathBuf>,
+    perf_map: Option<File>,
+    // Keyed by the start of the block, adding up all blocks compiled there.
+    blocks: Option<BTreeMap<PhysicalAddress, BlockStatistics>>,
+    // Tells apart the dumps of blocks recompiled at the same address.
+    compiled_blocks: u64,
+}
+
+#[derive(Default)]
+struct BlockStatistics {
+    instructions: usize,
+    runs: u64,
+    time: Duration,
+}
+
+impl Profiler {
+    pub fn new(config: ProfilerConfig) -> std::io::Result<Self> {
+        if let Some(dump_directory) = &config.dump_directory {
+            std::fs::create_dir_all(dump_directory)?;
+        }
+        let perf_map = if config.perf_map {
+            Some(File::create(format!(
+                "/tmp/perf-{}.map",
+                std::process::id()
+            ))?)
+        } else {
+            None
+        };
+        Ok(Profiler {
+            dump_directory: config.dump_directory,
+            perf_map,
+            blocks: config.block_statistics.then(BTreeMap::new),
+            compiled_blocks: 0,
+        })
+    }
+
+    // Whether compiled blocks have to be shown to `compiled`.
+    pub fn records_compiled_blocks(&self) -> bool {
+        self.dumps_blocks() || self.perf_map.is_some()
+    }
+
+    // Whether the IR and host code of compiled blocks have to be kept for `compiled`.
+    pub fn dumps_blocks(&self) -> bool {
+        self.dump_directory.is_some()
+    }
+
+    // Runs are timed from the dispatcher, so blocks have to return to it instead of jumping to
+    // each other to be timed separately.
+    pub fn times_blocks(&self) -> bool {
+        self.blocks.is_some()
+    }
+
+    // Records a block compiled from `instructions` to the host code at `pointer`. The IR is
+    // the function as built, before the context optimised and compiled it.
+    pub(super) fn compiled(
+        &mut self,
+        address_range: Range<PhysicalAddress>,
+        instructions: &[u32],
+        ir: Option<String>,
+        context: &Context,
+        pointer: *const u8,
+    ) {
+        let compiled_code = context.compiled_code().unwrap();
+        let code = compiled_code.code_buffer();
+        if let Some(perf_map) = &mut self.perf_map {
+            writeln!(
+                perf_map,
+                "{:x} {:x} pups2_block_{:08x}",
+                pointer as usize,
+                code.len(),
+                address_range.start.0
+            )
+            .expect("Failed to write perf map");
+        }
+        if let Some(dump_directory) = &self.dump_directory {
+            let mut dump = String::new();
+            writeln!(dump, "; Guest instructions").unwrap();
+            for (address, &instruction) in (address_range.start.0..)
+                .step_by(size_of::<u32>())
+                .zip(instructions)
+            {
+                writeln!(
+                    dump,
+                    "{:08x}: {:08x}    {}",
+                    address,
+                    instruction,
+                    Instruction::decode(instruction)
+                )
+                .unwrap();
+            }
+            if let Some(ir) = ir {
+                writeln!(dump, "\n; Cranelift IR\n{}", ir).unwrap();
+            }
+            writeln!(dump, "; Optimised Cranelift IR\n{}", context.func).unwrap();
+            writeln!(dump, "; Host code at {:#x}", pointer as usize).unwrap();
+            if let Some(disassembly) = &compiled_code.vcode {
+                writeln!(dump, "{}", disassembly).unwrap();
+            }
+            for (offset, bytes) in code.chunks(16).enumerate() {
+                write!(dump, "{:06x}:", offset * 16).unwrap();
+                for byte in bytes {
+                    write!(dump, " {:02x}", byte).unwrap();
+                }
+                writeln!(dump).unwrap();
+            }
+            let path = dump_directory.join(format!(
+                "{:08x}-{}.txt",
+                address_range.start.0, self.compiled_blocks
+            ));
+            std::fs::write(path, dump).expect("Failed to write JIT dump");
+        }
+        self.compiled_blocks += 1;
+    }
+
+    pub(super) fn ran(&mut self, address_range: Range<PhysicalAddress>, time: Duration) {
+        if let Some(blocks) = &mut self.blocks {
+            let statistics = blocks.entry(address_range.start).or_default();
+            statistics.instructions =
+                (address_range.end - address_range.start) as usize / size_of::<u32>();
+            statistics.runs += 1;
+            statistics.time += time;
+        }
+    }
+
+    // The `count` blocks the most time was spent in.
+    pub fn hot_blocks(&self, count: usize) -> HotBlocks<'_> {
+        let mut blocks = self
+            .blocks
+            .iter()
+            .flatten()
+            .map(|(&address, statistics)| (address, statistics))
+            .collect::<Vec<_>>();
+        blocks.sort_by_key(|(_, statistics)| std::cmp::Reverse(statistics.time));
+        let total_time = blocks.iter().map(|(_, statistics)| statistics.time).sum();
+        blocks.truncate(count);
+        HotBlocks { blocks, total_time }
+    }
+}
+
+pub struct HotBlocks<'a> {
+    blocks: Vec<(PhysicalAddress, &'a BlockStatistics)>,
+    total_time: Duration,
+}
+
+impl Display for HotBlocks<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(
+            f,
+            "{:<10} {:>12} {:>12} {:>12} {:>10} {:>8}",
+            "block", "instructions", "runs", "time (ms)", "ns/run", "time"
+        )?;
+        for (address, statistics) in &self.blocks {
+            writeln!(
+                f,
+                "{:08x}   {:>12} {:>12} {:>12.3} {:>10.1} {:>7.2}%",
+                address.0,
+                statistics.instructions,
+                statistics.runs,
+                statistics.time.as_secs_f64() * 1000.0,
+                statistics.time.as_nanos() as f64 / statistics.runs as f64,
+                statistics.time.as_secs_f64() / self.total_time.as_secs_f64() * 100.0
+            )?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,85 @@
+use super::instruction_gen::Instruction;
+
+// The EE issues one instruction per cycle unless it waits on a long latency unit, which the
+// costs below fold into the instruction that starts the operation.
+const DEFAULT_CYCLES: u64 = 1;
+const MULTIPLY_CYCLES: u64 = 4;
+const DIVIDE_CYCLES: u64 = 37;
+const FPU_CYCLES: u64 = 4;
+const FPU_DIVIDE_CYCLES: u64 = 8;
+const FPU_RECIPROCAL_SQUARE_ROOT_CYCLES: u64 = 14;
+// The load delay slot, assuming the consumer follows immediately.
+const LOAD_CYCLES: u64 = 2;
+// Stores retire through the write back buffer.
+const STORE_CYCLES: u64 = 1;
+// A likely branch not taken still spends a cycle on the delay slot it discards.
+pub const NULLIFIED_DELAY_SLOT_CYCLES: u64 = 1;
+
+impl Instruction {
+    pub fn cycles(self) -> u64 {
+        match self {
+            Instruction::Mult(..)
+            | Instruction::Multu(..)
+            | Instruction::Mult1(..)
+            | Instruction::Multu1(..)
+            | Instruction::Madd(..)
+            | Instruction::Maddu(..)
+            | Instruction::Madd1(..)
+            | Instruction::Maddu1(..)
+            | Instruction::Pmultw(..)
+            | Instruction::Pmultuw(..)
+            | Instruction::Pmaddw(..)
+            | Instruction::Pmadduw(..)
+            | Instruction::Pmsubw(..)
+            | Instruction::Pmulth(..)
+            | Instruction::Pmaddh(..)
+            | Instruction::Pmsubh(..)
+            | Instruction::Phmadh(..)
+            | Instruction::Phmsbh(..) => MULTIPLY_CYCLES,
+            Instruction::Div(..)
+            | Instruction::Divu(..)
+            | Instruction::Div1(..)
+            | Instruction::Divu1(..)
+            | Instruction::Pdivw(..)
+            | Instruction::Pdivuw(..)
+            | Instruction::Pdivbw(..) => DIVIDE_CYCLES,
+            Instruction::Adds(..)
+            | Instruction::Subs(..)
+            | Instruction::Muls(..)
+            | Instruction::Addas(..)
+            | Instruction::Subas(..)
+            | Instruction::Mulas(..)
+            | Instruction::Madds(..)
+            | Instruction::Msubs(..)
+            | Instruction::Maddas(..)
+            | Instruction::Msubas(..)
+            | Instruction::Cvtws(..)
+            | Instruction::Cvtsw(..) => FPU_CYCLES,
+            Instruction::Divs(..) | Instruction::Sqrts(..) => FPU_DIVIDE_CYCLES,
+            Instruction::Rsqrts(..) => FPU_RECIPROCAL_SQUARE_ROOT_CYCLES,
+            Instruction::Lb(..)
+            | Instruction::Lbu(..)
+            | Instruction::Lh(..)
+            | Instruction::Lhu(..)
+            | Instruction::Lw(..)
+            | Instruction::Lwl(..)
+            | Instruction::Lwr(..)
+            | Instruction::Ld(..)
+            | Instruction::Ldl(..)
+            | Instruction::Ldr(..)
+            | Instruction::Lq(..)
+            | Instruction::Lwc1(..) => LOAD_CYCLES,
+            Instruction::Sb(..)
+            | Instruction::Sh(..)
+            | Instruction::Sw(..)
+            | Instruction::Swl(..)
+            | Instruction::Swr(..)
+            | Instruction::Sd(..)
+            | Instruction::Sdl(..)
+            | Instruction::Sdr(..)
+            | Instruction::Sq(..)
+            | Instruction::Swc1(..) => STORE_CYCLES,
+            _ => DEFAULT_CYCLES,
+        }
+    }
+}
@@ -1,10 +1,14 @@
 use enum_map::{Enum, EnumMap};
+use log::{debug, trace, warn};
 use num_derive::FromPrimitive;
 use num_traits::FromPrimitive;
 
-use crate::{bits::Bits, bytes::Bytes, enum_set::EnumSet};
+use crate::{bits::Bits, enum_set::EnumSet};
 
-use super::bus::{Bus, PhysicalAddress};
+use super::{
+    bus::{Bus, PhysicalAddress},
+    mmio::MmioDevice,
+};
 
 #[derive(Debug, Default)]
 pub struct Dmac {
@@ -20,7 +24,7 @@ pub struct Dmac {
     active_channels: EnumSet<u16, Channel>,
 }
 
-#[derive(Debug, Enum, Copy, Clone)]
+#[derive(Debug, Enum, Copy, Clone, PartialEq, Eq)]
 pub enum Channel {
     Vif0,
     Vif1,
@@ -50,34 +54,436 @@ pub struct ChannelRegisters {
     tag_address_save_1: PhysicalAddress, // ASR1
     scratchpad_memory_address: u32,      // SADR
     process_next_tag: bool,
+    // Quad words transferred since the last skip in interleave mode.
+    interleaved_quad_words: u32,
+    // Whether the channel is waiting for its source to catch up, see `Dmac::stall`.
+    stalled: bool,
+}
+
+impl ChannelRegisters {
+    fn scratchpad_address(&self) -> PhysicalAddress {
+        PhysicalAddress::scratchpad(self.scratchpad_memory_address)
+    }
+
+    // SADR wraps around the 16 KiB scratchpad.
+    fn advance_scratchpad_address(&mut self) {
+        self.scratchpad_memory_address = (self.scratchpad_memory_address + 16).bits(0..=13);
+    }
 }
 
 impl Dmac {
-    pub fn write<T: Bytes>(&mut self, address: u32, value: T) {
-        match std::mem::size_of::<T>() {
-            4 => self.write32(address, u32::from_bytes(value.to_bytes().as_ref())),
-            8 => self.write32(address, u32::from_bytes(&value.to_bytes().as_ref()[0..4])),
-            _ => panic!(
-                "Invalid write size {} to {:08x}",
-                std::mem::size_of::<T>(),
-                address
-            ),
+    // Whether a channel is transferring.
+    pub fn busy(&self) -> bool {
+        !self.active_channels.is_empty()
+    }
+
+    // INT1, wired to COP0 Cause.IP3.
+    pub fn interrupt_pending(&self) -> bool {
+        self.status.interrupt_pending()
+    }
+
+    pub fn step(bus: &mut Bus) {
+        if !bus.dmac.control.enabled() || bus.dmac.hold_state_enabled {
+            return;
+        }
+        // TODO arbitration
+        for channel in bus.dmac.active_channels {
+            match channel {
+                Channel::Vif0 => todo!(),
+                Channel::Vif1 => todo!(),
+                Channel::Gif => Self::step_source(bus, channel),
+                Channel::FromIpu => todo!(),
+                Channel::ToIpu => todo!(),
+                Channel::Sif0 => todo!(),
+                Channel::Sif1 => todo!(),
+                Channel::Sif2 => todo!(),
+                Channel::FromSpr => Self::step_from_scratchpad(bus),
+                Channel::ToSpr => Self::step_source(bus, channel),
+            }
+        }
+    }
+
+    // Transfers from memory to the peripheral of the channel until the peripheral is full or the
+    // transfer is done. The registers are kept up to date after every quad word so that they can
+    // be read back mid-transfer.
+    fn step_source(bus: &mut Bus, channel: Channel) {
+        loop {
+            let registers = &bus.dmac.channels[channel];
+            if registers.quad_word_count == 0 && !registers.process_next_tag {
+                Self::finish(bus, channel);
+                return;
+            }
+            if !Self::destination_ready(bus, channel) {
+                return;
+            }
+            if registers.quad_word_count == 0 {
+                if bus
+                    .dmac
+                    .memory_fifo_empty(channel, bus.dmac.tag_address(channel))
+                {
+                    bus.dmac
+                        .stall(channel, StatusRegister::set_mfifo_empty_interrupt_status);
+                    return;
+                }
+                Self::read_source_chain_tag(bus, channel);
+                continue;
+            }
+            let memory_address = bus.dmac.memory_address(channel);
+            if bus.dmac.in_memory_fifo(channel)
+                && bus.dmac.memory_fifo_empty(channel, memory_address)
+            {
+                bus.dmac
+                    .stall(channel, StatusRegister::set_mfifo_empty_interrupt_status);
+                return;
+            }
+            if bus.dmac.past_stall_address(channel, memory_address) {
+                bus.dmac
+                    .stall(channel, StatusRegister::set_dma_stall_interrupt_status);
+                return;
+            }
+            bus.dmac.channels[channel].stalled = false;
+            let registers = &bus.dmac.channels[channel];
+            let data = bus.read::<u128>(memory_address);
+            trace!(
+                target: "dmac",
+                "Transferred quad word 0x{:08x} from 0x{:08x} to {:?} (QWC={})",
+                data,
+                memory_address.0,
+                channel,
+                registers.quad_word_count
+            );
+            Self::push_to_destination(bus, channel, data);
+            bus.dmac.advance_memory_address(channel);
+            bus.dmac.channels[channel].quad_word_count -= 1;
+        }
+    }
+
+    // Transfers from the scratchpad at SADR to memory. In chain mode, the tags are read from the
+    // scratchpad ahead of their packets.
+    fn step_from_scratchpad(bus: &mut Bus) {
+        let channel = Channel::FromSpr;
+        loop {
+            let registers = &bus.dmac.channels[channel];
+            if registers.quad_word_count == 0 {
+                if !registers.process_next_tag {
+                    Self::finish(bus, channel);
+                    return;
+                }
+                Self::read_destination_chain_tag(bus);
+                continue;
+            }
+            let scratchpad_address = registers.scratchpad_address();
+            let memory_address = bus.dmac.memory_address(channel);
+            let data = bus.read::<u128>(scratchpad_address);
+            trace!(
+                target: "dmac",
+                "Transferred quad word 0x{:08x} from scratchpad 0x{:04x} to 0x{:08x} (QWC={})",
+                data,
+                registers.scratchpad_memory_address,
+                memory_address.0,
+                registers.quad_word_count
+            );
+            bus.write(memory_address, data);
+            bus.dmac.advance_memory_address(channel);
+            let registers = &mut bus.dmac.channels[channel];
+            registers.advance_scratchpad_address();
+            registers.quad_word_count -= 1;
+        }
+    }
+
+    fn destination_ready(bus: &Bus, channel: Channel) -> bool {
+        match channel {
+            Channel::Gif => !bus.gif.fifo.is_full(),
+            Channel::ToSpr => true,
+            _ => todo!(),
+        }
+    }
+
+    fn push_to_destination(bus: &mut Bus, channel: Channel, data: u128) {
+        match channel {
+            Channel::Gif => bus.gif.fifo.push_back(data),
+            Channel::ToSpr => {
+                let scratchpad_address = bus.dmac.channels[channel].scratchpad_address();
+                bus.write(scratchpad_address, data);
+                bus.dmac.channels[channel].advance_scratchpad_address();
+            }
+            _ => todo!(),
+        }
+    }
+
+    // Moves MADR past a transferred quad word. In interleave mode, SQWC quad words are skipped
+    // after every TQWC transferred ones.
+    fn advance_memory_address(&mut self, channel: Channel) {
+        let registers = &mut self.channels[channel];
+        registers.memory_address += 16;
+        if let ChannelMode::Interleave = registers.control.mode() {
+            registers.interleaved_quad_words += 1;
+            if registers.interleaved_quad_words == self.skip_quad_word.bits(16..24) {
+                registers.interleaved_quad_words = 0;
+                registers.memory_address += self.skip_quad_word.bits(0..8) * 16;
+            }
+        }
+        self.channels[channel].memory_address = self.memory_address(channel);
+        // The drain channel may read up to the last address written in normal mode or by a cnts
+        // packet.
+        let registers = &self.channels[channel];
+        if self.control.stall_control_source_channel() == Some(channel)
+            && match registers.control.mode() {
+                ChannelMode::Normal => true,
+                ChannelMode::Chain => matches!(
+                    registers.control.destination_tag().tag_id,
+                    DestinationTagId::CountStall
+                ),
+                ChannelMode::Interleave => false,
+            }
+        {
+            self.stall_address = registers.memory_address.0;
+        }
+    }
+
+    // Whether MADR of the channel points into the MFIFO ring buffer. fromSPR fills it, and the
+    // drain channel reads its tags from it, along with the packets following them.
+    fn in_memory_fifo(&self, channel: Channel) -> bool {
+        match self.control.memory_fifo_drain_channel() {
+            Some(_) if channel == Channel::FromSpr => true,
+            Some(drain_channel) if drain_channel == channel => {
+                self.channels[channel].control.tag().data_follows_tag()
+            }
+            _ => false,
+        }
+    }
+
+    // The ring buffer starts at RBOR, with RBSR masking the offsets into it.
+    fn ring_buffer_address(&self, address: PhysicalAddress) -> PhysicalAddress {
+        PhysicalAddress(self.ring_buffer_offset + (address.0 & self.ring_buffer_size))
+    }
+
+    // MADR, wrapped into the ring buffer in MFIFO mode.
+    fn memory_address(&self, channel: Channel) -> PhysicalAddress {
+        let memory_address = self.channels[channel].memory_address;
+        if self.in_memory_fifo(channel) {
+            self.ring_buffer_address(memory_address)
+        } else {
+            memory_address
+        }
+    }
+
+    // TADR, wrapped into the ring buffer for the MFIFO drain channel.
+    fn tag_address(&self, channel: Channel) -> PhysicalAddress {
+        let tag_address = self.channels[channel].tag_address;
+        if self.control.memory_fifo_drain_channel() == Some(channel) {
+            self.ring_buffer_address(tag_address)
+        } else {
+            tag_address
+        }
+    }
+
+    // Whether the MFIFO drain channel has caught up with fromSPR at `address`.
+    fn memory_fifo_empty(&self, channel: Channel, address: PhysicalAddress) -> bool {
+        self.control.memory_fifo_drain_channel() == Some(channel)
+            && address == self.memory_address(Channel::FromSpr)
+    }
+
+    // Whether the stall control drain channel would read past STADR, which the source channel
+    // hasn't written yet. Only refs packets are stall controlled.
+    fn past_stall_address(&self, channel: Channel, address: PhysicalAddress) -> bool {
+        self.control.stall_control_drain_channel() == Some(channel)
+            && matches!(
+                self.channels[channel].control.tag().tag_id,
+                TagId::References
+            )
+            && address.0 + 16 > self.stall_address
+    }
+
+    // Leaves the channel active until its source catches up, raising the interrupt once when it
+    // starts waiting.
+    fn stall(&mut self, channel: Channel, set_interrupt_status: fn(&mut StatusRegister, bool)) {
+        let registers = &mut self.channels[channel];
+        if !registers.stalled {
+            registers.stalled = true;
+            set_interrupt_status(&mut self.status, true);
+            debug!(target: "dmac", "{:?} channel stalled", channel);
+        }
+    }
+
+    // Reads the tag at TADR, setting up the transfer of the packet it describes and the address
+    // of the next tag.
+    fn read_source_chain_tag(bus: &mut Bus, channel: Channel) {
+        let tag_address = bus.dmac.tag_address(channel);
+        let raw_tag = bus.read::<u128>(tag_address);
+        if bus.dmac.channels[channel].control.tag_transfer_enable() {
+            Self::push_to_destination(bus, channel, raw_tag);
+        }
+        let registers = &mut bus.dmac.channels[channel];
+        registers.control.set_dma_tag(raw_tag.bits(16..32) as u16);
+        let tag = SourceChainTag::from(raw_tag as u64);
+        trace!(
+            target: "dmac",
+            "{:?} source chain tag at 0x{:08x}: {:?} QWC={} ADDR=0x{:08x}",
+            channel,
+            tag_address.0,
+            tag.tag_id,
+            tag.quad_word_count,
+            tag.address.0
+        );
+        let quad_word_count = tag.quad_word_count as u32;
+        let following_data = tag_address + 16 + quad_word_count * 16;
+        registers.quad_word_count = quad_word_count;
+        match tag.tag_id {
+            TagId::ReferenceEnd => {
+                registers.memory_address = tag.address;
+                registers.tag_address += 16;
+                registers.process_next_tag = false;
+            }
+            TagId::Count => {
+                registers.memory_address = tag_address + 16;
+                registers.tag_address = following_data;
+            }
+            TagId::Next => {
+                registers.memory_address = tag_address + 16;
+                registers.tag_address = tag.address;
+            }
+            TagId::Reference | TagId::References => {
+                registers.memory_address = tag.address;
+                registers.tag_address += 16;
+            }
+            TagId::Call => {
+                registers.memory_address = tag_address + 16;
+                match registers.control.address_stack_pointer() {
+                    0 => registers.tag_address_save_0 = following_data,
+                    1 => registers.tag_address_save_1 = following_data,
+                    _ => {
+                        // ASR0 and ASR1 only hold two return addresses.
+                        warn!(
+                            target: "dmac",
+                            "{:?} call stack overflow at 0x{:08x}, ending the transfer",
+                            channel,
+                            tag_address.0
+                        );
+                        registers.quad_word_count = 0;
+                        registers.process_next_tag = false;
+                        return;
+                    }
+                }
+                registers
+                    .control
+                    .set_address_stack_pointer(registers.control.address_stack_pointer() + 1);
+                registers.tag_address = tag.address;
+            }
+            TagId::Return => {
+                registers.memory_address = tag_address + 16;
+                match registers.control.address_stack_pointer() {
+                    0 => registers.process_next_tag = false,
+                    address_stack_pointer => {
+                        registers.tag_address = if address_stack_pointer == 2 {
+                            registers.tag_address_save_1
+                        } else {
+                            registers.tag_address_save_0
+                        };
+                        registers
+                            .control
+                            .set_address_stack_pointer(address_stack_pointer - 1);
+                    }
+                }
+            }
+            TagId::End => {
+                registers.memory_address = tag_address + 16;
+                registers.process_next_tag = false;
+            }
+        }
+        if tag.interrupt_request && registers.control.tag_interrupt_enable() {
+            registers.process_next_tag = false;
+        }
+        registers.stalled = false;
+        bus.dmac.channels[channel].tag_address = bus.dmac.tag_address(channel);
+        bus.dmac.channels[channel].memory_address = bus.dmac.memory_address(channel);
+    }
+
+    // Reads the tag at SADR, which gives the destination of the packet following it.
+    fn read_destination_chain_tag(bus: &mut Bus) {
+        let channel = Channel::FromSpr;
+        let scratchpad_address = bus.dmac.channels[channel].scratchpad_address();
+        let raw_tag = bus.read::<u128>(scratchpad_address);
+        let registers = &mut bus.dmac.channels[channel];
+        registers.advance_scratchpad_address();
+        registers.control.set_dma_tag(raw_tag.bits(16..32) as u16);
+        let tag = DestinationChainTag::from(raw_tag as u64);
+        trace!(
+            target: "dmac",
+            "{:?} destination chain tag at scratchpad 0x{:04x}: {:?} QWC={} ADDR=0x{:08x}",
+            channel,
+            scratchpad_address.0.bits(0..14),
+            tag.tag_id,
+            tag.quad_word_count,
+            tag.address.0
+        );
+        registers.quad_word_count = tag.quad_word_count as u32;
+        registers.memory_address = tag.address;
+        if tag.ends_chain(&registers.control) {
+            registers.process_next_tag = false;
         }
     }
 
-    pub fn read<T: Bytes>(&self, address: u32) -> T {
-        match std::mem::size_of::<T>() {
-            4 => T::from_bytes(self.read32(address).to_bytes().as_ref()),
-            8 => T::from_bytes((self.read32(address) as u64).to_bytes().as_ref()),
-            _ => panic!(
-                "Invalid read size {} to {:08x}",
-                std::mem::size_of::<T>(),
-                address
-            ),
+    fn finish(bus: &mut Bus, channel: Channel) {
+        let registers = &mut bus.dmac.channels[channel];
+        registers.control.set_start(false);
+        bus.dmac.active_channels.remove(channel);
+        bus.dmac.status.set_interrupt_status(channel, true);
+        debug!(
+            target: "dmac",
+            "{:?} channel finished, control=0x{:08x}",
+            channel,
+            registers.control.raw
+        );
+    }
+}
+
+impl MmioDevice for Dmac {
+    fn name(&self) -> &'static str {
+        "DMAC"
+    }
+
+    fn read32(&self, address: u32) -> u32 {
+        // TODO: check which addresses can actually be read
+        let channel = match address {
+            0x1000_8000..0x1000_9000 => Channel::Vif0,
+            0x1000_9000..0x1000_A000 => Channel::Vif1,
+            0x1000_A000..0x1000_B000 => Channel::Gif,
+            0x1000_B000..0x1000_B400 => Channel::FromIpu,
+            0x1000_B400..0x1000_C000 => Channel::ToIpu,
+            0x1000_C000..0x1000_C400 => Channel::Sif0,
+            0x1000_C400..0x1000_C800 => Channel::Sif1,
+            0x1000_C800..0x1000_D000 => Channel::Sif2,
+            0x1000_D000..0x1000_D400 => Channel::FromSpr,
+            0x1000_D400..0x1000_E000 => Channel::ToSpr,
+            0x1000_E000 => return self.control.raw,
+            0x1000_E010 => return self.status.raw,
+            0x1000_E020 => return self.priority_control,
+            0x1000_E030 => return self.skip_quad_word,
+            0x1000_E040 => return self.ring_buffer_size,
+            0x1000_E050 => return self.ring_buffer_offset,
+            0x1000_E060 => return self.stall_address,
+            // D_ENABLER
+            0x1000_F520 => {
+                let mut result = 0;
+                result.set_bit(16, self.hold_state_enabled);
+                return result;
+            }
+            _ => panic!("Invalid DMAC read address: 0x{:08x}", address),
+        };
+        match address & 0xFF {
+            0x00 => self.channels[channel].control.raw,
+            0x10 => self.channels[channel].memory_address.0,
+            0x20 => self.channels[channel].quad_word_count,
+            0x30 => self.channels[channel].tag_address.0,
+            0x40 => self.channels[channel].tag_address_save_0.0,
+            0x50 => self.channels[channel].tag_address_save_1.0,
+            0x80 => self.channels[channel].scratchpad_memory_address,
+            _ => panic!("Invalid read from DMAC: 0x{:08x}", address),
         }
     }
 
-    pub fn write32(&mut self, address: u32, value: u32) {
+    fn write32(&mut self, address: u32, value: u32) {
         // TODO: check which addresses can actually be written
         let channel = match address {
             0x1000_8000..0x1000_9000 => Channel::Vif0,
@@ -128,8 +534,23 @@ impl Dmac {
         match address & 0xFF {
             0x00 => {
                 self.channels[channel].control.raw = value;
-                if self.channels[channel].control.start() {
-                    self.channels[channel].process_next_tag = true;
+                let registers = &mut self.channels[channel];
+                if registers.control.start() {
+                    // A chain started with a non-zero QWC first finishes the packet of the tag in
+                    // CHCR, which decides whether the chain goes on.
+                    registers.process_next_tag = match registers.control.mode() {
+                        ChannelMode::Normal | ChannelMode::Interleave => false,
+                        ChannelMode::Chain if registers.quad_word_count == 0 => true,
+                        ChannelMode::Chain => match channel {
+                            Channel::FromSpr => !registers
+                                .control
+                                .destination_tag()
+                                .ends_chain(&registers.control),
+                            _ => !registers.control.tag().ends_chain(&registers.control),
+                        },
+                    };
+                    registers.interleaved_quad_words = 0;
+                    registers.stalled = false;
                     self.active_channels.insert(channel);
                 }
             }
@@ -143,153 +564,12 @@ impl Dmac {
         }
     }
 
-    pub fn read32(&self, address: u32) -> u32 {
-        // TODO: check which addresses can actually be read
-        let channel = match address {
-            0x1000_8000..0x1000_9000 => Channel::Vif0,
-            0x1000_9000..0x1000_A000 => Channel::Vif1,
-            0x1000_A000..0x1000_B000 => Channel::Gif,
-            0x1000_B000..0x1000_B400 => Channel::FromIpu,
-            0x1000_B400..0x1000_C000 => Channel::ToIpu,
-            0x1000_C000..0x1000_C400 => Channel::Sif0,
-            0x1000_C400..0x1000_C800 => Channel::Sif1,
-            0x1000_C800..0x1000_D000 => Channel::Sif2,
-            0x1000_D000..0x1000_D400 => Channel::FromSpr,
-            0x1000_D400..0x1000_E000 => Channel::ToSpr,
-            0x1000_E000 => return self.control.raw,
-            0x1000_E010 => return self.status.raw,
-            0x1000_E020 => return self.priority_control,
-            0x1000_E030 => return self.skip_quad_word,
-            0x1000_E040 => return self.ring_buffer_size,
-            0x1000_E050 => return self.ring_buffer_offset,
-            0x1000_E060 => return self.stall_address,
-            // D_ENABLER
-            0x1000_F520 => {
-                let mut result = 0;
-                result.set_bit(16, self.hold_state_enabled);
-                return result;
-            }
-            _ => panic!("Invalid DMAC read address: 0x{:08x}", address),
-        };
-        match address & 0xFF {
-            0x00 => self.channels[channel].control.raw,
-            0x10 => self.channels[channel].memory_address.0,
-            0x20 => self.channels[channel].quad_word_count,
-            0x30 => self.channels[channel].tag_address.0,
-            0x40 => self.channels[channel].tag_address_save_0.0,
-            0x50 => self.channels[channel].tag_address_save_1.0,
-            0x80 => self.channels[channel].scratchpad_memory_address,
-            _ => panic!("Invalid read from DMAC: 0x{:08x}", address),
-        }
+    fn read64(&self, address: u32) -> u64 {
+        self.read32(address) as u64
     }
 
-    pub fn step(bus: &mut Bus) {
-        if bus.dmac.hold_state_enabled {
-            return;
-        }
-        // TODO arbitration
-        for channel in bus.dmac.active_channels {
-            let registers = &bus.dmac.channels[channel];
-            if registers.control.start() {
-                match channel {
-                    Channel::Vif0 => todo!(),
-                    Channel::Vif1 => todo!(),
-                    Channel::Gif => match registers.control.mode() {
-                        ChannelMode::Normal => {
-                            let mut memory_address = registers.memory_address;
-                            let mut quad_word_count = registers.quad_word_count;
-                            while quad_word_count > 0 && !bus.gif.fifo.is_full() {
-                                let data = bus.read::<u128>(memory_address);
-                                bus.gif.fifo.push_back(data);
-                                // println!(
-                                //     "Transferred quad word 0x{:08x} from 0x{:08x} to GIF FIFO (QWC={})",
-                                //     data,
-                                //     memory_address, quad_word_count
-                                // );
-                                memory_address.0 += 16;
-                                quad_word_count -= 1;
-                            }
-                            let registers = &mut bus.dmac.channels[channel];
-                            if quad_word_count == 0 {
-                                registers.control.set_start(false);
-                                bus.dmac.active_channels.remove(channel);
-                                // println!(
-                                //     "GIF channel finished, control=0x{:08x}",
-                                //     registers.control.raw
-                                // );
-                            }
-                            registers.memory_address = memory_address;
-                            registers.quad_word_count = quad_word_count;
-                        }
-                        ChannelMode::Chain => {
-                            let mut memory_address = registers.memory_address;
-                            let mut quad_word_count = registers.quad_word_count;
-                            while quad_word_count > 0 && !bus.gif.fifo.is_full() {
-                                let data = bus.read::<u128>(memory_address);
-                                bus.gif.fifo.push_back(data);
-                                // println!(
-                                //     "Transferred quad word 0x{:08x} from 0x{:08x} to GIF FIFO (QWC={})",
-                                //     data,
-                                //     memory_address, quad_word_count
-                                // );
-                                memory_address.0 += 16;
-                                quad_word_count -= 1;
-                            }
-                            if quad_word_count == 0 {
-                                if registers.process_next_tag {
-                                    assert!(!registers.control.tag_transfer_enable());
-                                    let source_chain_tag = bus.read::<u128>(registers.tag_address);
-                                    let registers = &mut bus.dmac.channels[channel];
-                                    registers
-                                        .control
-                                        .set_dma_tag(source_chain_tag.bits(16..32) as u16);
-                                    let source_chain_tag =
-                                        SourceChainTag::from(source_chain_tag as u64);
-                                    quad_word_count = source_chain_tag.quad_word_count as u32;
-                                    match source_chain_tag.tag_id {
-                                        TagId::ReferenceEnd => {
-                                            memory_address = source_chain_tag.address;
-                                            registers.tag_address += 16;
-                                            registers.process_next_tag = false;
-                                        }
-                                        TagId::Count => {
-                                            memory_address = registers.tag_address;
-                                            memory_address += 16;
-                                            registers.tag_address = source_chain_tag.address;
-                                        }
-                                        TagId::Next => todo!(),
-                                        TagId::Reference => todo!(),
-                                        TagId::References => todo!(),
-                                        TagId::Call => todo!(),
-                                        TagId::Return => todo!(),
-                                        TagId::End => todo!(),
-                                    }
-                                } else {
-                                    let registers = &mut bus.dmac.channels[channel];
-                                    registers.control.set_start(false);
-                                    bus.dmac.active_channels.remove(channel);
-                                    // println!(
-                                    //     "GIF channel finished, control=0x{:08x}",
-                                    //     registers.control.raw
-                                    // );
-                                }
-                            }
-                            let registers = &mut bus.dmac.channels[channel];
-                            registers.memory_address = memory_address;
-                            registers.quad_word_count = quad_word_count;
-                        }
-                        ChannelMode::Interleave => todo!(),
-                    },
-                    Channel::FromIpu => todo!(),
-                    Channel::ToIpu => todo!(),
-                    Channel::Sif0 => todo!(),
-                    Channel::Sif1 => todo!(),
-                    Channel::Sif2 => todo!(),
-                    Channel::FromSpr => todo!(),
-                    Channel::ToSpr => todo!(),
-                }
-            }
-        }
+    fn write64(&mut self, address: u32, value: u64) {
+        self.write32(address, value as u32)
     }
 }
 
@@ -299,14 +579,11 @@ struct ControlRegister {
 }
 
 impl ControlRegister {
+    // DMAE
     pub fn enabled(&self) -> bool {
         self.raw.bit(0)
     }
 
-    pub fn released(&self) -> bool {
-        self.raw.bit(1)
-    }
-
     pub fn memory_fifo_drain_channel(&self) -> Option<Channel> {
         match self.raw.bits(2..4) {
             0b00 => None,
@@ -321,8 +598,8 @@ impl ControlRegister {
         match self.raw.bits(4..6) {
             0b00 => None,
             0b01 => Some(Channel::Sif0),
-            0b10 => Some(Channel::Vif1),
-            0b11 => Some(Channel::Gif),
+            0b10 => Some(Channel::FromSpr),
+            0b11 => Some(Channel::FromIpu),
             _ => unreachable!(),
         }
     }
@@ -336,17 +613,6 @@ impl ControlRegister {
             _ => unreachable!(),
         }
     }
-
-    pub fn release_cycle(&self) -> usize {
-        match self.raw.bits(8..11) {
-            0b000 => 8,
-            0b001 => 16,
-            0b010 => 32,
-            0b011 => 64,
-            0b100 => 128,
-            _ => 256,
-        }
-    }
 }
 
 #[derive(Debug, Default, Copy, Clone)]
@@ -411,6 +677,13 @@ impl StatusRegister {
         self.raw.set_bit(30, value);
     }
 
+    pub fn interrupt_pending(self) -> bool {
+        Channel::all().any(|channel| self.interrupt_status(channel) && self.interrupt_mask(channel))
+            || (self.dma_stall_interrupt_status() && self.dma_stall_interrupt_mask())
+            || (self.mfifo_empty_interrupt_status() && self.mfifo_empty_interrupt_mask())
+            || self.buserr_interrupt_status()
+    }
+
     pub fn write(&mut self, value: Self) {
         for channel in Channel::all() {
             if value.interrupt_status(channel) {
@@ -444,11 +717,6 @@ struct ChannelControlRegister {
 }
 
 impl ChannelControlRegister {
-    pub fn direction(self) -> ChannelDirection {
-        ChannelDirection::from_u32(self.raw.bits(0..=0))
-            .unwrap_or_else(|| panic!("Invalid DMAC channel direction: {}", self.raw.bits(0..=0)))
-    }
-
     pub fn mode(self) -> ChannelMode {
         ChannelMode::from_u32(self.raw.bits(2..=3))
             .unwrap_or_else(|| panic!("Invalid DMAC channel mode: {}", self.raw.bits(2..=3)))
@@ -458,6 +726,10 @@ impl ChannelControlRegister {
         self.raw.bits(4..=5)
     }
 
+    pub fn set_address_stack_pointer(&mut self, value: u32) {
+        self.raw.set_bits(4..=5, value);
+    }
+
     pub fn tag_transfer_enable(self) -> bool {
         self.raw.bit(6)
     }
@@ -481,12 +753,15 @@ impl ChannelControlRegister {
     pub fn set_dma_tag(&mut self, value: u16) {
         self.raw.set_bits(16.., value as u32);
     }
-}
 
-#[derive(Debug, Copy, Clone, FromPrimitive)]
-enum ChannelDirection {
-    ToMemory = 0b0,
-    FromMemory = 0b1,
+    // The tag of the last packet, without its QWC and address.
+    pub fn tag(self) -> SourceChainTag {
+        SourceChainTag::from((self.dma_tag() as u64) << 16)
+    }
+
+    pub fn destination_tag(self) -> DestinationChainTag {
+        DestinationChainTag::from((self.dma_tag() as u64) << 16)
+    }
 }
 
 #[derive(Debug, Copy, Clone, FromPrimitive)]
@@ -497,19 +772,32 @@ enum ChannelMode {
 }
 
 struct SourceChainTag {
-    quad_word_count: u16,              // QWC
-    priority_control: PriorityControl, // PCE
-    tag_id: TagId,                     // ID
-    interrupt_request: bool,           // IRQ
-    address: PhysicalAddress,          // ADDR, SPR
+    quad_word_count: u16,     // QWC
+    tag_id: TagId,            // ID
+    interrupt_request: bool,  // IRQ
+    address: PhysicalAddress, // ADDR, SPR
+}
+
+impl SourceChainTag {
+    // Whether the packet is stored right after the tag rather than at its address.
+    fn data_follows_tag(&self) -> bool {
+        matches!(
+            self.tag_id,
+            TagId::Count | TagId::Next | TagId::Call | TagId::Return | TagId::End
+        )
+    }
+
+    // Whether the chain ends after the packet of the tag.
+    fn ends_chain(&self, control: &ChannelControlRegister) -> bool {
+        matches!(self.tag_id, TagId::ReferenceEnd | TagId::End)
+            || (self.interrupt_request && control.tag_interrupt_enable())
+    }
 }
 
 impl From<u64> for SourceChainTag {
     fn from(raw: u64) -> Self {
         Self {
             quad_word_count: raw.bits(0..=15) as u16,
-            priority_control: PriorityControl::from_u64(raw.bits(26..=27))
-                .unwrap_or_else(|| panic!("Invalid DMAC priority control: {}", raw.bits(26..=27))),
             tag_id: TagId::from_u64(raw.bits(28..=30))
                 .unwrap_or_else(|| panic!("Invalid DMAC tag ID: {}", raw.bits(28..=30))),
             interrupt_request: raw.bit(31),
@@ -518,14 +806,6 @@ impl From<u64> for SourceChainTag {
     }
 }
 
-#[derive(Debug, Copy, Clone, FromPrimitive)]
-enum PriorityControl {
-    Nothing = 0b00,
-    Reserved = 0b01,
-    Disabled = 0b10,
-    Enabled = 0b11,
-}
-
 #[derive(Debug, Copy, Clone, FromPrimitive)]
 enum TagId {
     ReferenceEnd = 0b000, // refe
@@ -535,5 +815,335 @@ enum TagId {
     References = 0b100,   // refs
     Call = 0b101,         // call
     Return = 0b110,       // ret
-    End = 0b111,          // ret
+    End = 0b111,          // end
+}
+
+struct DestinationChainTag {
+    quad_word_count: u16,     // QWC
+    tag_id: DestinationTagId, // ID
+    interrupt_request: bool,  // IRQ
+    address: PhysicalAddress, // ADDR, SPR
+}
+
+impl DestinationChainTag {
+    fn ends_chain(&self, control: &ChannelControlRegister) -> bool {
+        matches!(self.tag_id, DestinationTagId::End)
+            || (self.interrupt_request && control.tag_interrupt_enable())
+    }
+}
+
+impl From<u64> for DestinationChainTag {
+    fn from(raw: u64) -> Self {
+        Self {
+            quad_word_count: raw.bits(0..=15) as u16,
+            tag_id: DestinationTagId::from_u64(raw.bits(28..=30)).unwrap_or_else(|| {
+                panic!("Invalid DMAC destination tag ID: {}", raw.bits(28..=30))
+            }),
+            interrupt_request: raw.bit(31),
+            address: PhysicalAddress(raw.bits(32..64) as u32),
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, FromPrimitive)]
+enum DestinationTagId {
+    CountStall = 0b000, // cnts
+    Count = 0b001,      // cnt
+    End = 0b111,        // end
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const D_CTRL: u32 = 0x1000_E000;
+    const D_STAT: u32 = 0x1000_E010;
+    const D_RBSR: u32 = 0x1000_E040;
+    const D_RBOR: u32 = 0x1000_E050;
+    const D_SQWC: u32 = 0x1000_E030;
+    const D_STADR: u32 = 0x1000_E060;
+    const D2_CHCR: u32 = 0x1000_A000;
+    const D2_TADR: u32 = 0x1000_A030;
+    const D8_CHCR: u32 = 0x1000_D000;
+    const D8_MADR: u32 = 0x1000_D010;
+    const D8_QWC: u32 = 0x1000_D020;
+    const D8_SADR: u32 = 0x1000_D080;
+    const D9_CHCR: u32 = 0x1000_D400;
+    const D9_MADR: u32 = 0x1000_D410;
+    const D9_QWC: u32 = 0x1000_D420;
+    const D9_TADR: u32 = 0x1000_D430;
+    const D9_SADR: u32 = 0x1000_D480;
+    const CHAIN: u32 = 0b01 << 2;
+    const TAG_INTERRUPT_ENABLE: u32 = 1 << 7;
+    const INTERLEAVE: u32 = 0b10 << 2;
+    const START: u32 = 1 << 8;
+
+    const DMA_STALL_INTERRUPT: u32 = 13;
+    const MFIFO_EMPTY_INTERRUPT: u32 = 14;
+
+    // A bus with the DMAC enabled (D_CTRL.DMAE), as the BIOS leaves it.
+    fn enabled_bus() -> Bus {
+        let mut bus = Bus::new();
+        bus.dmac.write32(D_CTRL, 1);
+        bus
+    }
+
+    fn source_tag(tag_id: TagId, quad_word_count: u16, address: u32, interrupt: bool) -> u128 {
+        (address as u128) << 32
+            | (interrupt as u128) << 31
+            | (tag_id as u128) << 28
+            | quad_word_count as u128
+    }
+
+    // Writes a tag followed by its one quad word packet, the quad word being `data`.
+    fn write_packet(bus: &mut Bus, address: u32, tag: u128, data: u128) {
+        bus.write(PhysicalAddress(address), tag);
+        bus.write(PhysicalAddress(address + 16), data);
+    }
+
+    // Runs a toSPR chain starting at `tag_address` to completion, returning what it wrote to the
+    // start of the scratchpad.
+    fn run_to_scratchpad(bus: &mut Bus, tag_address: u32, control: u32) -> Vec<u128> {
+        bus.dmac.write32(D9_SADR, 0);
+        bus.dmac.write32(D9_TADR, tag_address);
+        bus.dmac.write32(D9_CHCR, control | CHAIN | START);
+        for _ in 0..16 {
+            if !bus.dmac.busy() {
+                break;
+            }
+            Dmac::step(bus);
+        }
+        assert!(!bus.dmac.busy());
+        assert!(bus
+            .dmac
+            .read32(D_STAT)
+            .bit(Channel::ToSpr.into_usize() as u32));
+        (0..8)
+            .map(|index| bus.read(PhysicalAddress::scratchpad(index * 16)))
+            .collect()
+    }
+
+    // Runs a normal fromSPR transfer of `data` from the start of the scratchpad to `address`.
+    fn run_from_scratchpad(bus: &mut Bus, address: u32, data: &[u128]) {
+        for (index, &quad_word) in data.iter().enumerate() {
+            bus.write(PhysicalAddress::scratchpad(index as u32 * 16), quad_word);
+        }
+        bus.dmac.write32(D8_SADR, 0);
+        bus.dmac.write32(D8_MADR, address);
+        bus.dmac.write32(D8_QWC, data.len() as u32);
+        bus.dmac.write32(D8_CHCR, START);
+        Dmac::step(bus);
+        assert!(!bus.dmac.active_channels.contains(Channel::FromSpr));
+    }
+
+    fn gif_fifo(bus: &Bus) -> Vec<u128> {
+        bus.gif.fifo.iter().copied().collect()
+    }
+
+    #[test]
+    fn source_chain_call_and_return() {
+        let mut bus = enabled_bus();
+        write_packet(
+            &mut bus,
+            0x1000,
+            source_tag(TagId::Call, 1, 0x2000, false),
+            1,
+        );
+        write_packet(&mut bus, 0x1020, source_tag(TagId::End, 1, 0, false), 5);
+        write_packet(
+            &mut bus,
+            0x2000,
+            source_tag(TagId::Call, 1, 0x3000, false),
+            2,
+        );
+        write_packet(&mut bus, 0x2020, source_tag(TagId::Return, 1, 0, false), 4);
+        write_packet(&mut bus, 0x3000, source_tag(TagId::Return, 1, 0, false), 3);
+        assert_eq!(
+            run_to_scratchpad(&mut bus, 0x1000, 0),
+            [1, 2, 3, 4, 5, 0, 0, 0]
+        );
+        assert_eq!(
+            bus.dmac.channels[Channel::ToSpr]
+                .control
+                .address_stack_pointer(),
+            0
+        );
+        assert_eq!(bus.dmac.read32(0x1000_D440), 0x1020); // ASR0
+        assert_eq!(bus.dmac.read32(0x1000_D450), 0x2020); // ASR1
+    }
+
+    #[test]
+    fn source_chain_call_stack_overflow() {
+        let mut bus = enabled_bus();
+        write_packet(
+            &mut bus,
+            0x1000,
+            source_tag(TagId::Call, 1, 0x2000, false),
+            1,
+        );
+        write_packet(
+            &mut bus,
+            0x2000,
+            source_tag(TagId::Call, 1, 0x3000, false),
+            2,
+        );
+        write_packet(
+            &mut bus,
+            0x3000,
+            source_tag(TagId::Call, 1, 0x4000, false),
+            3,
+        );
+        write_packet(&mut bus, 0x4000, source_tag(TagId::End, 1, 0, false), 4);
+        // The third call ends the transfer without its packet.
+        assert_eq!(
+            run_to_scratchpad(&mut bus, 0x1000, 0),
+            [1, 2, 0, 0, 0, 0, 0, 0]
+        );
+        assert_eq!(
+            bus.dmac.channels[Channel::ToSpr]
+                .control
+                .address_stack_pointer(),
+            2
+        );
+    }
+
+    #[test]
+    fn source_chain_tag_interrupt() {
+        let mut bus = enabled_bus();
+        write_packet(&mut bus, 0x1000, source_tag(TagId::Count, 1, 0, false), 1);
+        write_packet(&mut bus, 0x1020, source_tag(TagId::Count, 1, 0, true), 2);
+        write_packet(&mut bus, 0x1040, source_tag(TagId::End, 1, 0, false), 3);
+        // With TIE, the chain stops after the packet of the tag with IRQ.
+        assert_eq!(
+            run_to_scratchpad(&mut bus, 0x1000, TAG_INTERRUPT_ENABLE),
+            [1, 2, 0, 0, 0, 0, 0, 0]
+        );
+        assert_eq!(bus.dmac.read32(D9_TADR), 0x1040);
+        // Without it, IRQ is ignored.
+        for index in 0..8 {
+            bus.write(PhysicalAddress::scratchpad(index * 16), 0u128);
+        }
+        assert_eq!(
+            run_to_scratchpad(&mut bus, 0x1000, 0),
+            [1, 2, 3, 0, 0, 0, 0, 0]
+        );
+    }
+
+    #[test]
+    fn destination_chain_from_scratchpad() {
+        let mut bus = enabled_bus();
+        let destination_tag = |tag_id: DestinationTagId, quad_word_count: u16, address: u32| {
+            (address as u128) << 32 | (tag_id as u128) << 28 | quad_word_count as u128
+        };
+        let scratchpad = [
+            destination_tag(DestinationTagId::Count, 1, 0x3000),
+            1,
+            destination_tag(DestinationTagId::End, 2, 0x4000),
+            2,
+            3,
+        ];
+        for (index, quad_word) in scratchpad.into_iter().enumerate() {
+            bus.write(PhysicalAddress::scratchpad(index as u32 * 16), quad_word);
+        }
+        bus.dmac.write32(D8_SADR, 0);
+        bus.dmac.write32(D8_QWC, 0);
+        bus.dmac.write32(D8_CHCR, CHAIN | START);
+        Dmac::step(&mut bus);
+        assert!(!bus.dmac.busy());
+        assert_eq!(bus.read::<u128>(PhysicalAddress(0x3000)), 1);
+        assert_eq!(bus.read::<u128>(PhysicalAddress(0x4000)), 2);
+        assert_eq!(bus.read::<u128>(PhysicalAddress(0x4010)), 3);
+        assert_eq!(bus.dmac.read32(D8_SADR), 0x50);
+        assert_eq!(bus.dmac.read32(D8_MADR), 0x4020);
+    }
+
+    #[test]
+    fn interleave_to_scratchpad() {
+        let mut bus = enabled_bus();
+        // Transfer one quad word, skip one, and wrap SADR around the end of the scratchpad.
+        bus.dmac.write32(D_SQWC, 1 << 16 | 1);
+        for (index, quad_word) in [1u128, 9, 2, 9, 3].into_iter().enumerate() {
+            bus.write(PhysicalAddress(0x5000 + index as u32 * 16), quad_word);
+        }
+        bus.dmac.write32(D9_SADR, 0x3FF0);
+        bus.dmac.write32(D9_MADR, 0x5000);
+        bus.dmac.write32(D9_QWC, 3);
+        bus.dmac.write32(D9_CHCR, INTERLEAVE | START);
+        Dmac::step(&mut bus);
+        assert!(!bus.dmac.busy());
+        assert_eq!(bus.read::<u128>(PhysicalAddress::scratchpad(0x3FF0)), 1);
+        assert_eq!(bus.read::<u128>(PhysicalAddress::scratchpad(0)), 2);
+        assert_eq!(bus.read::<u128>(PhysicalAddress::scratchpad(0x10)), 3);
+        assert_eq!(bus.dmac.read32(D9_SADR), 0x20);
+        assert_eq!(bus.dmac.read32(D9_MADR), 0x5060);
+    }
+
+    #[test]
+    fn memory_fifo_wraps_around_ring_buffer() {
+        let mut bus = enabled_bus();
+        // A 1 KiB ring buffer at 0x10000 drained by GIF.
+        bus.dmac.write32(D_CTRL, 0b11 << 2 | 1);
+        bus.dmac.write32(D_RBOR, 0x1_0000);
+        bus.dmac.write32(D_RBSR, 0x3F0);
+        let packets = [
+            source_tag(TagId::Count, 1, 0, false),
+            1,
+            source_tag(TagId::Count, 1, 0, false),
+            2,
+        ];
+        run_from_scratchpad(&mut bus, 0x1_03E0, &packets);
+        assert_eq!(bus.read::<u128>(PhysicalAddress(0x1_03F0)), 1);
+        assert_eq!(bus.read::<u128>(PhysicalAddress(0x1_0010)), 2);
+        assert_eq!(bus.dmac.read32(D8_MADR), 0x1_0020);
+
+        bus.dmac.write32(D2_TADR, 0x1_03E0);
+        bus.dmac.write32(D2_CHCR, CHAIN | START);
+        Dmac::step(&mut bus);
+        // GIF follows the tags around the end of the ring buffer, then waits for more.
+        assert_eq!(gif_fifo(&bus), [1, 2]);
+        assert_eq!(bus.dmac.read32(D2_TADR), 0x1_0020);
+        assert!(bus.dmac.busy());
+        assert!(bus.dmac.read32(D_STAT).bit(MFIFO_EMPTY_INTERRUPT));
+
+        run_from_scratchpad(
+            &mut bus,
+            0x1_0020,
+            &[source_tag(TagId::End, 1, 0, false), 3],
+        );
+        Dmac::step(&mut bus);
+        assert_eq!(gif_fifo(&bus), [1, 2, 3]);
+        assert!(!bus.dmac.busy());
+    }
+
+    #[test]
+    fn stall_releases_once_source_catches_up() {
+        let mut bus = enabled_bus();
+        // fromSPR writes what GIF reads, and SIM is unmasked.
+        bus.dmac.write32(D_CTRL, 0b10 << 6 | 0b10 << 4 | 1);
+        bus.dmac.write32(D_STADR, 0x2000);
+        bus.dmac.write32(D_STAT, 1 << 29);
+        bus.write(
+            PhysicalAddress(0x1000),
+            source_tag(TagId::References, 4, 0x2000, false),
+        );
+        bus.write(PhysicalAddress(0x1010), source_tag(TagId::End, 0, 0, false));
+        bus.dmac.write32(D2_TADR, 0x1000);
+        bus.dmac.write32(D2_CHCR, CHAIN | START);
+        Dmac::step(&mut bus);
+        assert!(gif_fifo(&bus).is_empty());
+        assert!(bus.dmac.read32(D_STAT).bit(DMA_STALL_INTERRUPT));
+        assert!(bus.dmac.interrupt_pending());
+        bus.dmac.write32(D_STAT, 1 << DMA_STALL_INTERRUPT);
+
+        run_from_scratchpad(&mut bus, 0x2000, &[1, 2]);
+        assert_eq!(bus.dmac.read32(D_STADR), 0x2020);
+        Dmac::step(&mut bus);
+        assert_eq!(gif_fifo(&bus), [1, 2]);
+        assert!(bus.dmac.busy());
+
+        run_from_scratchpad(&mut bus, 0x2020, &[3, 4]);
+        Dmac::step(&mut bus);
+        assert_eq!(gif_fifo(&bus), [1, 2, 3, 4]);
+        assert!(!bus.dmac.busy());
+    }
 }
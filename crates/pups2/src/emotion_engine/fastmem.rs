@@ -0,0 +1,313 @@
+use std::{
+    ffi::c_void,
+    fs::File,
+    ops::Range,
+    os::fd::{AsRawFd, FromRawFd},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Once, OnceLock,
+    },
+};
+
+use enum_map::Enum;
+use mmap_rs::{MmapFlags, MmapMut, MmapNone, MmapOptions};
+
+use super::{
+    bus::{PhysicalAddress, BOOT_MEMORY_SIZE, MAIN_MEMORY_SIZE, SCRATCHPAD_SIZE},
+    core::{
+        mmu::{OFFSET_BITS, PAGE_SIZE},
+        Mode,
+    },
+};
+
+// A window spans the whole virtual address space of a mode.
+pub const WINDOW_BITS: u32 = 32;
+const WINDOW_SIZE: usize = 1 << WINDOW_BITS;
+const RESERVATION_SIZE: usize = Mode::LENGTH * WINDOW_SIZE;
+
+const MAIN_MEMORY_END: u32 = MAIN_MEMORY_SIZE as u32;
+const BOOT_MEMORY_START: u32 = 0x1FC0_0000;
+const BOOT_MEMORY_END: u32 = BOOT_MEMORY_START + BOOT_MEMORY_SIZE as u32;
+const SCRATCHPAD_START: u32 = 0x8000_0000;
+const SCRATCHPAD_END: u32 = SCRATCHPAD_START + SCRATCHPAD_SIZE as u32;
+
+// Where the backed regions live in the memory file.
+const BOOT_MEMORY_OFFSET: usize = MAIN_MEMORY_SIZE;
+const SCRATCHPAD_OFFSET: usize = BOOT_MEMORY_OFFSET + BOOT_MEMORY_SIZE;
+const MEMORY_SIZE: usize = SCRATCHPAD_OFFSET + SCRATCHPAD_SIZE;
+
+// The host accesses compiled code may make to a physical page.
+pub const READABLE: u32 = 1 << 0;
+pub const WRITABLE: u32 = 1 << 1;
+
+// Host reservations mirroring the virtual address space of each mode, so that compiled code can
+// reach memory with a single host access at `base + mode window + virtual address`. Main memory,
+// boot memory and the scratchpad live in a shared memory file, which the bus maps once and the
+// windows map wherever the MMU translates to them. Everything else, MMIO included, is left
+// inaccessible: accesses to it fault, and the fault handler lets compiled code redo them through
+// the bus.
+pub struct Fastmem {
+    registration: Box<Registration>,
+    memory: File,
+    _reservation: MmapNone,
+}
+
+pub struct FastmemRegions {
+    pub fastmem: Fastmem,
+    pub main_memory: MmapMut,
+    pub boot_memory: MmapMut,
+    pub scratchpad: MmapMut,
+}
+
+// What the fault handler needs to know about a reservation. Registered in `FASTMEMS` while the
+// reservation lives.
+struct Registration {
+    base: usize,
+    // The host page compiled code last faulted on, which the fault handler has covered with a
+    // scratch page so that the access completes. Compiled code checks it after every access and
+    // takes the slow path if it is set, which puts the page back.
+    faulted_page: AtomicUsize,
+}
+
+const MAX_FASTMEMS: usize = 64;
+static FASTMEMS: [AtomicUsize; MAX_FASTMEMS] = [const { AtomicUsize::new(0) }; MAX_FASTMEMS];
+static INSTALL_FAULT_HANDLER: Once = Once::new();
+static PREVIOUS_FAULT_HANDLER: OnceLock<libc::sigaction> = OnceLock::new();
+
+impl Fastmem {
+    pub fn reserve() -> FastmemRegions {
+        assert_eq!(MmapOptions::page_size(), PAGE_SIZE as usize);
+        INSTALL_FAULT_HANDLER.call_once(install_fault_handler);
+        let memory = unsafe { libc::memfd_create(c"pups2".as_ptr(), libc::MFD_CLOEXEC) };
+        assert!(
+            memory >= 0,
+            "Failed to create guest memory: {}",
+            std::io::Error::last_os_error()
+        );
+        let memory = unsafe { File::from_raw_fd(memory) };
+        memory
+            .set_len(MEMORY_SIZE as u64)
+            .expect("Failed to size guest memory");
+        let map = |offset: usize, size: usize| unsafe {
+            MmapOptions::new(size)
+                .expect("Failed to create guest memory options")
+                .with_flags(MmapFlags::SHARED)
+                .with_file(&memory, offset as u64)
+                .map_mut()
+                .expect("Failed to map guest memory")
+        };
+        let main_memory = map(0, MAIN_MEMORY_SIZE);
+        let boot_memory = map(BOOT_MEMORY_OFFSET, BOOT_MEMORY_SIZE);
+        let scratchpad = map(SCRATCHPAD_OFFSET, SCRATCHPAD_SIZE);
+        let reservation = MmapOptions::new(RESERVATION_SIZE)
+            .expect("Failed to create fastmem options")
+            .with_flags(MmapFlags::NO_RESERVE)
+            .map_none()
+            .expect("Failed to reserve fastmem");
+        let registration = Box::new(Registration {
+            base: reservation.start(),
+            faulted_page: AtomicUsize::new(0),
+        });
+        let registration_address = &*registration as *const Registration as usize;
+        FASTMEMS
+            .iter()
+            .find(|slot| {
+                slot.compare_exchange(0, registration_address, Ordering::AcqRel, Ordering::Relaxed)
+                    .is_ok()
+            })
+            .expect("Too many fastmem reservations");
+        FastmemRegions {
+            fastmem: Fastmem {
+                registration,
+                memory,
+                _reservation: reservation,
+            },
+            main_memory,
+            boot_memory,
+            scratchpad,
+        }
+    }
+
+    pub fn base(&self) -> *mut u8 {
+        self.registration.base as *mut u8
+    }
+
+    pub fn faulted_page(&self) -> *const AtomicUsize {
+        &self.registration.faulted_page
+    }
+
+    // The mode and page compiled code last faulted on, if it hasn't been put back yet.
+    pub fn take_faulted_page(&self) -> Option<(Mode, u32)> {
+        let host_page = self.registration.faulted_page.swap(0, Ordering::Relaxed);
+        (host_page != 0).then(|| {
+            let offset = host_page - self.registration.base;
+            (
+                Mode::from_usize(offset / WINDOW_SIZE),
+                ((offset % WINDOW_SIZE) >> OFFSET_BITS) as u32,
+            )
+        })
+    }
+
+    // Boot memory is read only from the fast path, as writes to it are redirected by the bus.
+    pub fn page_flags(frame: PhysicalAddress) -> u32 {
+        match frame.0 {
+            0..MAIN_MEMORY_END => READABLE | WRITABLE,
+            BOOT_MEMORY_START..BOOT_MEMORY_END => READABLE,
+            SCRATCHPAD_START..SCRATCHPAD_END => READABLE | WRITABLE,
+            _ => 0,
+        }
+    }
+
+    fn memory_offset(frame: PhysicalAddress) -> Option<usize> {
+        match frame.0 {
+            0..MAIN_MEMORY_END => Some(frame.0 as usize),
+            BOOT_MEMORY_START..BOOT_MEMORY_END => {
+                Some(BOOT_MEMORY_OFFSET + (frame.0 - BOOT_MEMORY_START) as usize)
+            }
+            SCRATCHPAD_START..SCRATCHPAD_END => {
+                Some(SCRATCHPAD_OFFSET + (frame.0 - SCRATCHPAD_START) as usize)
+            }
+            _ => None,
+        }
+    }
+
+    fn page_address(&self, mode: Mode, page: u32) -> usize {
+        self.registration.base + mode as usize * WINDOW_SIZE + ((page as usize) << OFFSET_BITS)
+    }
+
+    // Maps consecutive pages of a window onto consecutive frames starting at `frame`, allowing
+    // `access`. Frames without host memory behind them are never accessible.
+    pub fn map(&self, mode: Mode, pages: Range<u32>, frame: PhysicalAddress, access: u32) {
+        let address = self.page_address(mode, pages.start) as *mut c_void;
+        let size = pages.len() << OFFSET_BITS;
+        let protection = match access {
+            0 => libc::PROT_NONE,
+            READABLE => libc::PROT_READ,
+            _ => libc::PROT_READ | libc::PROT_WRITE,
+        };
+        let result = match Self::memory_offset(frame).filter(|_| access != 0) {
+            Some(offset) => unsafe {
+                libc::mmap(
+                    address,
+                    size,
+                    protection,
+                    libc::MAP_SHARED | libc::MAP_FIXED,
+                    self.memory.as_raw_fd(),
+                    offset as libc::off_t,
+                )
+            },
+            None => unsafe {
+                libc::mmap(
+                    address,
+                    size,
+                    libc::PROT_NONE,
+                    libc::MAP_PRIVATE | libc::MAP_ANONYMOUS | libc::MAP_NORESERVE | libc::MAP_FIXED,
+                    -1,
+                    0,
+                )
+            },
+        };
+        assert!(
+            result != libc::MAP_FAILED,
+            "Failed to map fastmem: {}",
+            std::io::Error::last_os_error()
+        );
+    }
+
+    // Makes a mapped page read only, e.g. once code has been compiled from it.
+    pub fn make_read_only(&self, mode: Mode, page: u32) {
+        let address = self.page_address(mode, page) as *mut c_void;
+        let result = unsafe { libc::mprotect(address, PAGE_SIZE as usize, libc::PROT_READ) };
+        assert!(
+            result == 0,
+            "Failed to protect fastmem: {}",
+            std::io::Error::last_os_error()
+        );
+    }
+}
+
+impl Drop for Fastmem {
+    fn drop(&mut self) {
+        let registration_address = &*self.registration as *const Registration as usize;
+        for slot in &FASTMEMS {
+            let _ =
+                slot.compare_exchange(registration_address, 0, Ordering::AcqRel, Ordering::Relaxed);
+        }
+    }
+}
+
+fn install_fault_handler() {
+    unsafe {
+        let mut previous: libc::sigaction = std::mem::zeroed();
+        assert_eq!(
+            libc::sigaction(libc::SIGSEGV, std::ptr::null(), &mut previous),
+            0
+        );
+        PREVIOUS_FAULT_HANDLER.set(previous).unwrap();
+        let mut action: libc::sigaction = std::mem::zeroed();
+        action.sa_sigaction = handle_fault as *const () as usize;
+        action.sa_flags = libc::SA_SIGINFO | libc::SA_ONSTACK;
+        libc::sigemptyset(&mut action.sa_mask);
+        assert_eq!(
+            libc::sigaction(libc::SIGSEGV, &action, std::ptr::null_mut()),
+            0
+        );
+    }
+}
+
+// Lets a faulting access to a window complete on a scratch page and records the page, see
+// `Registration::faulted_page`. Any other fault goes to the handler that was there before, e.g.
+// the one reporting stack overflows.
+extern "C" fn handle_fault(signal: libc::c_int, info: *mut libc::siginfo_t, context: *mut c_void) {
+    let address = unsafe { (*info).si_addr() } as usize;
+    for slot in &FASTMEMS {
+        let registration = slot.load(Ordering::Acquire) as *const Registration;
+        if registration.is_null() {
+            continue;
+        }
+        let registration = unsafe { &*registration };
+        if !(registration.base..registration.base + RESERVATION_SIZE).contains(&address) {
+            continue;
+        }
+        let host_page = address & !(PAGE_SIZE as usize - 1);
+        // A second fault before the first page is put back means something other than compiled
+        // code is touching the window.
+        if registration.faulted_page.load(Ordering::Relaxed) != 0 {
+            break;
+        }
+        let scratch_page = unsafe {
+            libc::mmap(
+                host_page as *mut c_void,
+                PAGE_SIZE as usize,
+                libc::PROT_READ | libc::PROT_WRITE,
+                libc::MAP_PRIVATE | libc::MAP_ANONYMOUS | libc::MAP_FIXED,
+                -1,
+                0,
+            )
+        };
+        if scratch_page == libc::MAP_FAILED {
+            break;
+        }
+        registration
+            .faulted_page
+            .store(host_page, Ordering::Relaxed);
+        return;
+    }
+    let previous = PREVIOUS_FAULT_HANDLER.get().unwrap();
+    unsafe {
+        if previous.sa_flags & libc::SA_SIGINFO != 0 {
+            let handler = std::mem::transmute::<
+                usize,
+                extern "C" fn(libc::c_int, *mut libc::siginfo_t, *mut c_void),
+            >(previous.sa_sigaction);
+            handler(signal, info, context);
+        } else if previous.sa_sigaction == libc::SIG_DFL || previous.sa_sigaction == libc::SIG_IGN {
+            // The access faults again once the handler returns, this time fatally.
+            libc::signal(signal, libc::SIG_DFL);
+        } else {
+            let handler =
+                std::mem::transmute::<usize, extern "C" fn(libc::c_int)>(previous.sa_sigaction);
+            handler(signal);
+        }
+    }
+}
@@ -1,9 +1,10 @@
+use log::{log_enabled, trace, Level};
 use num_derive::FromPrimitive;
 use num_traits::FromPrimitive;
 
-use crate::{bits::Bits, bytes::Bytes, fifo::Fifo};
+use crate::{bits::Bits, fifo::Fifo};
 
-use super::{bus::Bus, gs};
+use super::{bus::Bus, gs, mmio::MmioDevice};
 
 pub struct Gif {
     pub fifo: Fifo<u128>,
@@ -127,10 +128,6 @@ impl TransferStatus {
     pub fn set_register_counter(&mut self, value: u8) {
         self.raw.set_bits(16..=19, value)
     }
-
-    pub fn vu_address(self) -> u16 {
-        self.raw.bits(20..=29) as u16
-    }
 }
 
 impl Gif {
@@ -147,68 +144,30 @@ impl Gif {
         }
     }
 
-    pub fn write<T: Bytes>(&mut self, address: u32, value: T) {
-        match std::mem::size_of::<T>() {
-            4 => self.write32(address, u32::from_bytes(value.to_bytes().as_ref())),
-            _ => panic!("Invalid write size {}", std::mem::size_of::<T>()),
-        }
-    }
-
-    pub fn read<T: Bytes>(&self, address: u32) -> T {
-        match std::mem::size_of::<T>() {
-            4 => T::from_bytes(self.read32(address).to_bytes().as_ref()),
-            _ => panic!("Invalid read size {}", std::mem::size_of::<T>()),
-        }
-    }
-
-    pub fn write32(&mut self, address: u32, value: u32) {
-        match address {
-            0x1000_3000 => self.control = value,
-            0x1000_3010 => self.mode = value,
-            _ => panic!(
-                "Invalid GIF write of {} at address: 0x{:08x}",
-                value, address
-            ),
-        }
-    }
-
-    pub fn read32(&self, address: u32) -> u32 {
-        match address {
-            0x1000_3020 => self.status,
-            0x1000_3040 => self.tag.raw.bits(0..32) as u32,
-            0x1000_3050 => self.tag.raw.bits(32..64) as u32,
-            0x1000_3060 => self.tag.raw.bits(64..96) as u32,
-            0x1000_3070 => self.tag.raw.bits(96..128) as u32,
-            0x1000_3080 => self.transfer_status.raw,
-            0x1000_3090 => self.path3_transfer_status_counter,
-            0x1000_30a0 => self.path3_tag_value,
-            _ => panic!("Invalid GIF read at address: 0x{:08x}", address),
-        }
-    }
-
     pub fn step(bus: &mut Bus) {
         while let Some(data) = bus.gif.fifo.pop_front() {
-            // println!("FIFO data = {:08x}", data);
+            trace!(target: "gif", "FIFO data = {:08x}", data);
             let loop_counter = bus.gif.transfer_status.loop_counter();
             let mut register_counter = bus.gif.transfer_status.register_counter();
             if loop_counter == 0 && register_counter == 0 {
                 let tag = Tag { raw: data };
-                // println!("GIF tag: {:?}", tag);
-                // println!("format: {:?}", tag.data_format());
-                // println!("repeat count: {:?}", tag.repeat_count());
-                // println!("end of packet: {:?}", tag.end_of_packet());
-                // println!("register count: {:?}", tag.register_count());
+                if log_enabled!(target: "gif", Level::Trace) {
+                    trace!(
+                        target: "gif",
+                        "GIF tag: {:?}, format: {:?}, repeat count: {}, end of packet: {}, registers: {:?}",
+                        tag,
+                        tag.data_format(),
+                        tag.repeat_count(),
+                        tag.end_of_packet(),
+                        tag.registers().collect::<Vec<_>>()
+                    );
+                }
                 if tag.prim_field_enable() {
-                    // println!("GIF tag write to prim: {:?}", tag.prim_data());
+                    trace!(target: "gif", "GIF tag write to prim: {:?}", tag.prim_data());
                     bus.gs
                         .command_queue
                         .push_back((gs::Register::Primitive, tag.prim_data() as u64));
                 }
-                // print!("Registers:");
-                for register in tag.registers() {
-                    // print!(" {:?}", register);
-                }
-                // println!();
                 bus.gif.transfer_status.set_loop_counter(tag.repeat_count());
                 bus.gif.tag = tag;
                 continue;
@@ -280,11 +239,12 @@ impl Gif {
                             bus.gs
                                 .command_queue
                                 .push_back((register, data.bits(0..64) as u64));
-                            // println!(
-                            //     "GIF write address data: {:?}={:08x}",
-                            //     register,
-                            //     data.bits(0..64)
-                            // );
+                            trace!(
+                                target: "gif",
+                                "GIF write address data: {:?}={:08x}",
+                                register,
+                                data.bits(0..64)
+                            );
                         }
                         Register::Nop => todo!(),
                     }
@@ -292,7 +252,7 @@ impl Gif {
                     if register_counter == bus.gif.tag.register_count() {
                         register_counter = 0;
                         bus.gif.transfer_status.set_loop_counter(loop_counter - 1);
-                        // println!("Decrementing loop counter = {}", loop_counter - 1);
+                        trace!(target: "gif", "Decrementing loop counter = {}", loop_counter - 1);
                     }
                     bus.gif
                         .transfer_status
@@ -307,9 +267,40 @@ impl Gif {
                         .command_queue
                         .push_back((gs::Register::TransmissionData, data.bits(64..128) as u64));
                     bus.gif.transfer_status.set_loop_counter(loop_counter - 1);
-                    // println!("Decrementing loop counter = {}", loop_counter - 1);
+                    trace!(target: "gif", "Decrementing loop counter = {}", loop_counter - 1);
                 }
             }
         }
     }
 }
+
+impl MmioDevice for Gif {
+    fn name(&self) -> &'static str {
+        "GIF"
+    }
+
+    fn read32(&self, address: u32) -> u32 {
+        match address {
+            0x1000_3020 => self.status,
+            0x1000_3040 => self.tag.raw.bits(0..32) as u32,
+            0x1000_3050 => self.tag.raw.bits(32..64) as u32,
+            0x1000_3060 => self.tag.raw.bits(64..96) as u32,
+            0x1000_3070 => self.tag.raw.bits(96..128) as u32,
+            0x1000_3080 => self.transfer_status.raw,
+            0x1000_3090 => self.path3_transfer_status_counter,
+            0x1000_30a0 => self.path3_tag_value,
+            _ => panic!("Invalid GIF read at address: 0x{:08x}", address),
+        }
+    }
+
+    fn write32(&mut self, address: u32, value: u32) {
+        match address {
+            0x1000_3000 => self.control = value,
+            0x1000_3010 => self.mode = value,
+            _ => panic!(
+                "Invalid GIF write of {} at address: 0x{:08x}",
+                value, address
+            ),
+        }
+    }
+}
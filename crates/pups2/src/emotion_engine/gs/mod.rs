@@ -1,10 +1,14 @@
 use std::collections::VecDeque;
 
+use log::trace;
+
 use privileged_registers::PrivilegedRegisters;
 pub use registers::{Register, Registers};
 use rendering::Vertex;
 
-use crate::fifo::Fifo;
+use crate::{bits::Bits, fifo::Fifo};
+
+use super::{bus::Bus, intc::Interrupt};
 
 mod pixel_storage;
 mod privileged_registers;
@@ -20,6 +24,17 @@ pub struct Gs {
     registers: Registers,
     vertex_queue: Fifo<Vertex>,
     tmp_data: Vec<u8>,
+    // Set when an unmasked CSR event occurs, until the INTC is told.
+    interrupt_requested: bool,
+}
+
+// The event flags in CSR, whose masks in IMR are 8 bits higher.
+#[derive(Debug, Clone, Copy)]
+enum CsrEvent {
+    Signal = 0,
+    Finish = 1,
+    HSync = 2,
+    VSync = 3,
 }
 
 impl Gs {
@@ -31,13 +46,29 @@ impl Gs {
             registers: Registers::default(),
             vertex_queue: Fifo::with_capacity(2),
             tmp_data: Vec::new(),
+            interrupt_requested: false,
+        }
+    }
+
+    pub fn step(bus: &mut Bus) {
+        let gs = &mut bus.gs;
+        while let Some((register, data)) = gs.command_queue.pop_front() {
+            trace!(target: "gs", "Command: {:?}={:x?}", register, data);
+            gs.write_register(register, data);
+        }
+        if std::mem::take(&mut gs.interrupt_requested) {
+            bus.intc.request(Interrupt::Gs);
         }
     }
 
-    pub fn step(&mut self) {
-        while let Some((register, data)) = self.command_queue.pop_front() {
-            // println!("Command: {:?}={:x?}", register, data);
-            self.write_register(register, data);
+    // Sets an event flag in CSR, requesting an interrupt if it wasn't set and IMR doesn't mask it.
+    fn raise(&mut self, event: CsrEvent) {
+        let bit = event as usize;
+        let registers = &mut self.privileged_registers;
+        if !registers.status.bit(bit) && !registers.interrupt_mask.bit(bit + 8) {
+            trace!(target: "gs", "{event:?} interrupt");
+            self.interrupt_requested = true;
         }
+        registers.status.set_bit(bit, true);
     }
 }
@@ -1,9 +1,14 @@
+use log::debug;
 use num_derive::FromPrimitive;
 use num_traits::FromPrimitive;
 
 use crate::{bits::Bits, bytes::Bytes};
 
-use super::{registers::PixelStorageFormat, rendering::Rect, Gs};
+use super::{registers::PixelStorageFormat, rendering::Rect, CsrEvent, Gs};
+use crate::emotion_engine::mmio::MmioDevice;
+
+// SIGNAL, FINISH, HSINT, VSINT and EDWINT in CSR, which are cleared by writing 1.
+const CSR_EVENTS: u64 = 0b11111;
 
 #[derive(Debug, Default)]
 pub struct PrivilegedRegisters {
@@ -51,42 +56,11 @@ impl From<u64> for DisplayFrameBuffer {
 }
 
 impl Gs {
-    pub fn write_privileged<T: Bytes>(&mut self, address: u32, value: T) {
-        match std::mem::size_of::<T>() {
-            4 => {
-                let aligned_address = address & !0b111;
-                let offset_bytes = (address - aligned_address) as usize;
-                let existing = self.read_privileged64(aligned_address);
-                let value =
-                    (u32::from_bytes(value.to_bytes().as_ref()) as u64) << (offset_bytes * 8);
-                let value = existing & !u64::mask(offset_bytes * 8..offset_bytes * 8 + 32) | value;
-                self.write_privileged64(aligned_address, value);
-            }
-            8 => self.write_privileged64(address, u64::from_bytes(value.to_bytes().as_ref())),
-            _ => panic!("Invalid GS write size: {}", std::mem::size_of::<T>()),
-        }
-    }
-    pub fn read_privileged<T: Bytes>(&self, address: u32) -> T {
-        match std::mem::size_of::<T>() {
-            4 => {
-                let aligned_address = address & !0b111;
-                let result = self.read_privileged64(aligned_address);
-                let offset_bytes = (address - aligned_address) as usize;
-                T::from_bytes(&result.to_bytes()[offset_bytes..offset_bytes + 4])
-            }
-            8 => T::from_bytes(self.read_privileged64(address).to_bytes().as_ref()),
-            _ => panic!(
-                "Invalid privileged GS read size: {} to {address:08x}",
-                std::mem::size_of::<T>()
-            ),
-        }
-    }
-
     pub fn write_privileged64(&mut self, address: u32, value: u64) {
         match address {
             0x1200_0000 => {
                 self.privileged_registers.pcrtc_mode = PcrtcMode::from(value);
-                println!("Pmode = {:?}", self.privileged_registers.pcrtc_mode);
+                debug!(target: "gs", "Pmode = {:?}", self.privileged_registers.pcrtc_mode);
             }
             0x1200_0010 => self.privileged_registers.sync_mode1 = value,
             0x1200_0020 => self.privileged_registers.sync_mode2 = value,
@@ -96,38 +70,36 @@ impl Gs {
             0x1200_0060 => self.privileged_registers.syncv = value,
             0x1200_0070 => {
                 self.privileged_registers.display_frame_buffer1 = DisplayFrameBuffer::from(value);
-                println!(
+                debug!(
+                    target: "gs",
                     "Display frame buffer 1 = {:?}",
                     self.privileged_registers.display_frame_buffer1
                 )
             }
             0x1200_0080 => {
                 self.privileged_registers.display1 = Display::from(value);
-                println!("Display 1 = {:?}", self.privileged_registers.display1)
+                debug!(target: "gs", "Display 1 = {:?}", self.privileged_registers.display1)
             }
             0x1200_0090 => {
                 self.privileged_registers.display_frame_buffer2 = DisplayFrameBuffer::from(value);
-                println!(
+                debug!(
+                    target: "gs",
                     "Display frame buffer 2 = {:?}",
                     self.privileged_registers.display_frame_buffer2
                 )
             }
             0x1200_00A0 => {
                 self.privileged_registers.display2 = Display::from(value);
-                println!("Display 2 = {:?}", self.privileged_registers.display2)
+                debug!(target: "gs", "Display 2 = {:?}", self.privileged_registers.display2)
             }
             0x1200_00B0 => self.privileged_registers.write_buffer = value,
             0x1200_00C0 => self.privileged_registers.write_data = value,
             0x1200_00D0 => self.privileged_registers.write_start = value,
             0x1200_00E0 => self.privileged_registers.background_color = Rgb::from(value as u32),
             0x1200_1000 => {
-                let value = if value.bit(3) {
-                    // VSINT
-                    value & !u64::mask(3..=3)
-                } else {
-                    value
-                };
-                self.privileged_registers.status = value;
+                let status = self.privileged_registers.status;
+                self.privileged_registers.status =
+                    status & !value & CSR_EVENTS | value & !CSR_EVENTS;
             }
             0x1200_1010 => self.privileged_registers.interrupt_mask = value,
             0x1200_1040 => self.privileged_registers.bus_direction = value,
@@ -144,8 +116,47 @@ impl Gs {
         }
     }
 
+    pub fn hblank(&mut self) {
+        self.raise(CsrEvent::HSync);
+    }
+
     pub fn vblank(&mut self) {
-        self.privileged_registers.status.set_bit(3, true);
+        self.raise(CsrEvent::VSync);
+    }
+}
+
+impl MmioDevice for Gs {
+    fn name(&self) -> &'static str {
+        "GS"
+    }
+
+    // The registers are 64 bits wide, with each half accessible on its own.
+    fn read32(&self, address: u32) -> u32 {
+        let aligned_address = address & !0b111;
+        let result = self.read_privileged64(aligned_address);
+        let offset_bytes = (address - aligned_address) as usize;
+        u32::from_bytes(&result.to_bytes()[offset_bytes..offset_bytes + 4])
+    }
+
+    fn read64(&self, address: u32) -> u64 {
+        self.read_privileged64(address)
+    }
+
+    fn write32(&mut self, address: u32, value: u32) {
+        let aligned_address = address & !0b111;
+        let offset_bytes = (address - aligned_address) as usize;
+        let mut existing = self.read_privileged64(aligned_address);
+        if aligned_address == 0x1200_1000 {
+            // Writing the event flags back would clear them.
+            existing &= !CSR_EVENTS;
+        }
+        let value = (value as u64) << (offset_bytes * 8);
+        let value = existing & !u64::mask(offset_bytes * 8..offset_bytes * 8 + 32) | value;
+        self.write_privileged64(aligned_address, value);
+    }
+
+    fn write64(&mut self, address: u32, value: u64) {
+        self.write_privileged64(address, value)
     }
 }
 
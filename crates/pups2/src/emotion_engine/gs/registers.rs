@@ -1,9 +1,10 @@
+use log::debug;
 use num_derive::FromPrimitive;
 use num_traits::FromPrimitive;
 
 use crate::{bits::Bits, bytes::Bytes, fix::Fix};
 
-use super::Gs;
+use super::{CsrEvent, Gs};
 
 type Fix124 = Fix<u16, 4>;
 
@@ -163,17 +164,11 @@ impl Gs {
             Register::ColorClamp => self.registers.color_clamp = ColorClamp::from(data),
             Register::PixelTest1 => {
                 self.registers.contextual[0].pixel_test = PixelTest::from(data);
-                println!(
-                    "Pixel test 1: {:?}",
-                    self.registers.contextual[0].pixel_test
-                );
+                debug!(target: "gs", "Pixel test 1: {:?}", self.registers.contextual[0].pixel_test);
             }
             Register::PixelTest2 => {
                 self.registers.contextual[1].pixel_test = PixelTest::from(data);
-                println!(
-                    "Pixel test 2: {:?}",
-                    self.registers.contextual[1].pixel_test
-                );
+                debug!(target: "gs", "Pixel test 2: {:?}", self.registers.contextual[1].pixel_test);
             }
             Register::PixelAlphaBlending => todo!(),
             Register::FrameBufferAlpha1 => todo!(),
@@ -181,7 +176,8 @@ impl Gs {
             Register::FrameBuffer1 => {
                 self.registers.contextual[0].frame_buffer_settings =
                     FrameBufferSettings::from(data);
-                println!(
+                debug!(
+                    target: "gs",
                     "Frame buffer 1: {:x?}",
                     self.registers.contextual[0].frame_buffer_settings
                 );
@@ -189,21 +185,24 @@ impl Gs {
             Register::FrameBuffer2 => {
                 self.registers.contextual[1].frame_buffer_settings =
                     FrameBufferSettings::from(data);
-                println!(
+                debug!(
+                    target: "gs",
                     "Frame buffer 2: {:x?}",
                     self.registers.contextual[1].frame_buffer_settings
                 );
             }
             Register::ZBuffer1 => {
                 self.registers.contextual[0].z_buffer_settings = ZBufferSettings::from(data);
-                println!(
+                debug!(
+                    target: "gs",
                     "Z buffer 1: {:x?}",
                     self.registers.contextual[0].z_buffer_settings
                 );
             }
             Register::ZBuffer2 => {
                 self.registers.contextual[1].z_buffer_settings = ZBufferSettings::from(data);
-                println!(
+                debug!(
+                    target: "gs",
                     "Z buffer 2: {:x?}",
                     self.registers.contextual[1].z_buffer_settings
                 );
@@ -237,22 +236,27 @@ impl Gs {
                             self.registers.transmission_position.destination_y as u32;
                         let width = self.registers.transmission_size.width as u32;
                         let height = self.registers.transmission_size.height as u32;
-                        println!(
+                        debug!(
+                            target: "gs",
                             "Local transmission of {width}x{height} pixels from ({source_x}, {source_y}) to ({destination_x}, {destination_y})"
                         );
-                        println!(
+                        debug!(
+                            target: "gs",
                             "Source width: {}",
                             self.registers.bit_blit_buffer.source_width
                         );
-                        println!(
+                        debug!(
+                            target: "gs",
                             "Destination width: {}",
                             self.registers.bit_blit_buffer.destination_width
                         );
-                        println!(
+                        debug!(
+                            target: "gs",
                             "Source base pointer: {:x?}",
                             self.registers.bit_blit_buffer.source_base_pointer
                         );
-                        println!(
+                        debug!(
+                            target: "gs",
                             "Destination base pointer: {:x?}",
                             self.registers.bit_blit_buffer.destination_base_pointer
                         );
@@ -337,7 +341,10 @@ impl Gs {
                                 pixel += 1;
                                 self.registers.transmission_pixel = pixel;
                                 if pixel == pixels {
-                                    println!("Transmission of {pixels} pixels complete");
+                                    debug!(
+                                        target: "gs",
+                                        "Transmission of {pixels} pixels complete"
+                                    );
                                     self.registers.transmission_direction =
                                         TransmissionDirection::Deactivated;
                                     break;
@@ -351,9 +358,19 @@ impl Gs {
                 TransmissionDirection::LocalToLocal => panic!("Can't happen"),
                 TransmissionDirection::Deactivated => todo!(),
             },
-            Register::SignalSignal => todo!(),
-            Register::SignalFinish => todo!(),
-            Register::SignalLabel => todo!(),
+            // The upper half of the data masks which bits of the ID the lower half replaces.
+            Register::SignalSignal => {
+                let label_id = &mut self.privileged_registers.signal_label_id;
+                let mask = data.bits(32..64);
+                *label_id = *label_id & !mask | data & mask;
+                self.raise(CsrEvent::Signal);
+            }
+            Register::SignalFinish => self.raise(CsrEvent::Finish),
+            Register::SignalLabel => {
+                let label_id = &mut self.privileged_registers.signal_label_id;
+                let mask = data.bits(32..64) << 32;
+                *label_id = *label_id & !mask | data << 32 & mask;
+            }
         }
     }
 }
@@ -0,0 +1,74 @@
+use enum_map::Enum;
+
+use crate::bits::Bits;
+
+use super::mmio::MmioDevice;
+
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Intc {
+    status: u32, // INTC_STAT
+    mask: u32,   // INTC_MASK
+}
+
+#[derive(Debug, Enum, Copy, Clone)]
+pub enum Interrupt {
+    Gs,
+    Sbus,
+    VBlankStart,
+    VBlankEnd,
+    Vif0,
+    Vif1,
+    Vu0,
+    Vu1,
+    Ipu,
+    Timer0,
+    Timer1,
+    Timer2,
+    Timer3,
+    Sfifo,
+    Vu0Watchdog,
+}
+
+impl Intc {
+    pub fn request(&mut self, interrupt: Interrupt) {
+        self.status.set_bit(interrupt.into_usize(), true);
+    }
+
+    // INT0, wired to COP0 Cause.IP2.
+    pub fn interrupt_pending(&self) -> bool {
+        self.status & self.mask != 0
+    }
+}
+
+impl MmioDevice for Intc {
+    fn name(&self) -> &'static str {
+        "INTC"
+    }
+
+    fn read32(&self, address: u32) -> u32 {
+        match address {
+            0x1000_F000 => self.status,
+            0x1000_F010 => self.mask,
+            _ => panic!("Invalid INTC read address: 0x{:08x}", address),
+        }
+    }
+
+    fn write32(&mut self, address: u32, value: u32) {
+        let value = value.bits(0..Interrupt::LENGTH);
+        match address {
+            // Writing 1 to a status bit acknowledges the interrupt.
+            0x1000_F000 => self.status &= !value,
+            // Writing 1 to a mask bit toggles it.
+            0x1000_F010 => self.mask ^= value,
+            _ => panic!("Invalid INTC write address: 0x{:08x}", address),
+        }
+    }
+
+    fn read64(&self, address: u32) -> u64 {
+        self.read32(address) as u64
+    }
+
+    fn write64(&mut self, address: u32, value: u64) {
+        self.write32(address, value as u32)
+    }
+}
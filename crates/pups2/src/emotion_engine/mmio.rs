@@ -0,0 +1,287 @@
+use std::{ops::Range, str::FromStr};
+
+use log::{info, warn};
+
+use crate::bytes::Bytes;
+
+// A device with registers in the physical address space. Devices implement the access sizes their
+// registers support, the others panic.
+pub trait MmioDevice {
+    fn name(&self) -> &'static str;
+
+    fn read8(&self, address: u32) -> u8 {
+        unsupported(self.name(), "read", 8, address)
+    }
+
+    fn read16(&self, address: u32) -> u16 {
+        unsupported(self.name(), "read", 16, address)
+    }
+
+    fn read32(&self, address: u32) -> u32 {
+        unsupported(self.name(), "read", 32, address)
+    }
+
+    fn read64(&self, address: u32) -> u64 {
+        unsupported(self.name(), "read", 64, address)
+    }
+
+    fn read128(&self, address: u32) -> u128 {
+        unsupported(self.name(), "read", 128, address)
+    }
+
+    fn write8(&mut self, address: u32, _value: u8) {
+        unsupported(self.name(), "write", 8, address)
+    }
+
+    fn write16(&mut self, address: u32, _value: u16) {
+        unsupported(self.name(), "write", 16, address)
+    }
+
+    fn write32(&mut self, address: u32, _value: u32) {
+        unsupported(self.name(), "write", 32, address)
+    }
+
+    fn write64(&mut self, address: u32, _value: u64) {
+        unsupported(self.name(), "write", 64, address)
+    }
+
+    fn write128(&mut self, address: u32, _value: u128) {
+        unsupported(self.name(), "write", 128, address)
+    }
+}
+
+fn unsupported(name: &str, access: &str, bits: usize, address: u32) -> ! {
+    panic!(
+        "Invalid {}-bit {} {} at address: 0x{:08x}",
+        bits, name, access, address
+    )
+}
+
+// Calls the access hook of the size of `T`.
+pub fn read<T: Bytes>(device: &dyn MmioDevice, address: u32) -> T {
+    match std::mem::size_of::<T>() {
+        1 => T::from_bytes(&device.read8(address).to_bytes()),
+        2 => T::from_bytes(&device.read16(address).to_bytes()),
+        4 => T::from_bytes(&device.read32(address).to_bytes()),
+        8 => T::from_bytes(&device.read64(address).to_bytes()),
+        16 => T::from_bytes(&device.read128(address).to_bytes()),
+        size => panic!("Invalid read size {}", size),
+    }
+}
+
+pub fn write<T: Bytes>(device: &mut dyn MmioDevice, address: u32, value: T) {
+    let bytes = value.to_bytes();
+    let bytes = bytes.as_ref();
+    match bytes.len() {
+        1 => device.write8(address, u8::from_bytes(bytes)),
+        2 => device.write16(address, u16::from_bytes(bytes)),
+        4 => device.write32(address, u32::from_bytes(bytes)),
+        8 => device.write64(address, u64::from_bytes(bytes)),
+        16 => device.write128(address, u128::from_bytes(bytes)),
+        size => panic!("Invalid write size {}", size),
+    }
+}
+
+// What happens on accesses to addresses no device is mapped at.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum UnmappedPolicy {
+    #[default]
+    Panic,
+    // Reads return zero and writes are dropped, logging the access.
+    LogAndIgnore,
+    // Reads return all ones like an undriven bus and writes are dropped, silently.
+    OpenBus,
+}
+
+impl FromStr for UnmappedPolicy {
+    type Err = String;
+
+    fn from_str(string: &str) -> Result<Self, Self::Err> {
+        match string {
+            "panic" => Ok(UnmappedPolicy::Panic),
+            "ignore" => Ok(UnmappedPolicy::LogAndIgnore),
+            "open-bus" => Ok(UnmappedPolicy::OpenBus),
+            _ => Err(format!(
+                "expected panic, ignore or open-bus, got {}",
+                string
+            )),
+        }
+    }
+}
+
+// The devices owned by the bus, which the rest of the emulator accesses directly, and the ones
+// added through `Bus::add_device`, indexed in the order they were added.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Device {
+    Timer,
+    Gif,
+    Dmac,
+    Intc,
+    Rdram,
+    Gs,
+    Registered(usize),
+}
+
+const MMIO_START: u32 = 0x1000_0000;
+const MMIO_END: u32 = 0x2000_0000;
+const MMIO_PAGE_SIZE: u32 = 4 * 1024;
+
+// The I/O region is split into pages, each listing the devices mapped into it. Most pages belong
+// to a single device, but some are shared between a few registers of different devices.
+pub struct MmioMap {
+    pages: Box<[Vec<Mapping>]>,
+    pub unmapped_policy: UnmappedPolicy,
+}
+
+struct Mapping {
+    range: Range<u32>,
+    device: Device,
+}
+
+impl MmioMap {
+    pub fn new() -> Self {
+        MmioMap {
+            pages: (0..(MMIO_END - MMIO_START) / MMIO_PAGE_SIZE)
+                .map(|_| Vec::new())
+                .collect(),
+            unmapped_policy: UnmappedPolicy::default(),
+        }
+    }
+
+    pub fn map(&mut self, range: Range<u32>, device: Device) {
+        assert!(MMIO_START <= range.start && range.end <= MMIO_END);
+        assert!(
+            self.pages_of(range.clone())
+                .all(|page| self.pages[page]
+                    .iter()
+                    .all(|mapping| mapping.range.end <= range.start
+                        || range.end <= mapping.range.start)),
+            "Overlapping MMIO mapping at 0x{:08x}",
+            range.start
+        );
+        for page in self.pages_of(range.clone()) {
+            self.pages[page].push(Mapping {
+                range: range.clone(),
+                device,
+            });
+        }
+    }
+
+    fn pages_of(&self, range: Range<u32>) -> Range<usize> {
+        ((range.start - MMIO_START) / MMIO_PAGE_SIZE) as usize
+            ..(range.end - MMIO_START).div_ceil(MMIO_PAGE_SIZE) as usize
+    }
+
+    pub fn device(&self, address: u32) -> Option<Device> {
+        if !(MMIO_START..MMIO_END).contains(&address) {
+            return None;
+        }
+        self.pages[((address - MMIO_START) / MMIO_PAGE_SIZE) as usize]
+            .iter()
+            .find(|mapping| mapping.range.contains(&address))
+            .map(|mapping| mapping.device)
+    }
+}
+
+// Registers that are known to exist but not emulated yet. Reads return zero, writes are dropped.
+pub struct Unimplemented;
+
+impl Unimplemented {
+    fn read(&self, address: u32) -> u8 {
+        warn!(target: "bus.mmio", "Unhandled read at: 0x{:08x}", address);
+        0
+    }
+
+    fn write<T: std::fmt::LowerHex>(&self, address: u32, value: T) {
+        warn!(target: "bus.mmio", "Unhandled write: 0x{:08x}:=0x{:08x}", address, value);
+    }
+}
+
+impl MmioDevice for Unimplemented {
+    fn name(&self) -> &'static str {
+        "unimplemented"
+    }
+
+    fn read8(&self, address: u32) -> u8 {
+        self.read(address)
+    }
+
+    fn read16(&self, address: u32) -> u16 {
+        self.read(address).into()
+    }
+
+    fn read32(&self, address: u32) -> u32 {
+        self.read(address).into()
+    }
+
+    fn read64(&self, address: u32) -> u64 {
+        self.read(address).into()
+    }
+
+    fn read128(&self, address: u32) -> u128 {
+        self.read(address).into()
+    }
+
+    fn write8(&mut self, address: u32, value: u8) {
+        self.write(address, value)
+    }
+
+    fn write16(&mut self, address: u32, value: u16) {
+        self.write(address, value)
+    }
+
+    fn write32(&mut self, address: u32, value: u32) {
+        self.write(address, value)
+    }
+
+    fn write64(&mut self, address: u32, value: u64) {
+        self.write(address, value)
+    }
+
+    fn write128(&mut self, address: u32, value: u128) {
+        self.write(address, value)
+    }
+}
+
+// The debug output register the kernel's kputchar writes to, printed a line at a time.
+#[derive(Default)]
+pub struct Kputchar {
+    line: Vec<u8>,
+}
+
+impl Kputchar {
+    fn put(&mut self, byte: u8) {
+        if byte == b'\n' {
+            info!(target: "kernel", "STDOUT:{}", String::from_utf8_lossy(&self.line));
+            self.line.clear();
+        } else {
+            self.line.push(byte);
+        }
+    }
+}
+
+impl MmioDevice for Kputchar {
+    fn name(&self) -> &'static str {
+        "kputchar"
+    }
+
+    fn write8(&mut self, _address: u32, value: u8) {
+        self.put(value)
+    }
+
+    fn write16(&mut self, _address: u32, value: u16) {
+        self.put(value as u8)
+    }
+
+    fn write32(&mut self, _address: u32, value: u32) {
+        self.put(value as u8)
+    }
+
+    fn write64(&mut self, _address: u32, value: u64) {
+        self.put(value as u8)
+    }
+
+    fn write128(&mut self, _address: u32, value: u128) {
+        self.put(value as u8)
+    }
+}
@@ -1,8 +1,11 @@
 pub mod bus;
 pub mod core;
 pub mod dmac;
+pub mod fastmem;
 pub mod gif;
 pub mod gs;
+pub mod intc;
+pub mod mmio;
 pub mod rdram;
 pub mod scheduler;
 pub mod timer;
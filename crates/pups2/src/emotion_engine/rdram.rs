@@ -1,6 +1,8 @@
 use std::cell::RefCell;
 
-use crate::{bits::Bits, bytes::Bytes};
+use crate::bits::Bits;
+
+use super::mmio::MmioDevice;
 
 #[derive(Debug, Default, Clone)]
 pub struct Rdram {
@@ -9,22 +11,38 @@ pub struct Rdram {
     sdevid: RefCell<u32>,
 }
 
-impl Rdram {
-    pub fn write<T: Bytes>(&mut self, address: u32, value: T) {
-        match std::mem::size_of::<T>() {
-            4 => self.write32(address, u32::from_bytes(value.to_bytes().as_ref())),
-            _ => panic!("Invalid write size {}", std::mem::size_of::<T>()),
-        }
+impl Rdram {}
+
+impl MmioDevice for Rdram {
+    fn name(&self) -> &'static str {
+        "RDRAM"
     }
 
-    pub fn read<T: Bytes>(&self, address: u32) -> T {
-        match std::mem::size_of::<T>() {
-            4 => T::from_bytes(self.read32(address).to_bytes().as_ref()),
-            _ => panic!("Invalid read size {}", std::mem::size_of::<T>()),
+    fn read32(&self, address: u32) -> u32 {
+        match address {
+            0x1000_F430 => 0,
+            0x1000_F440 => {
+                let sop = self.mch_ricm.bits(6..10);
+                if sop != 0 {
+                    return 0;
+                }
+                let sa = self.mch_ricm.bits(16..24);
+                match sa {
+                    0x21 if *self.sdevid.borrow() < 2 => {
+                        *self.sdevid.borrow_mut() += 1;
+                        0x1F
+                    }
+                    0x23 => 0x0D0D,
+                    0x24 => 0x0090,
+                    0x40 => self.mch_ricm.bits(0..5),
+                    _ => 0,
+                }
+            }
+            _ => panic!("Invalid RDRAM read at address: 0x{:08x}", address),
         }
     }
 
-    pub fn write32(&mut self, address: u32, value: u32) {
+    fn write32(&mut self, address: u32, value: u32) {
         match address {
             0x1000_F430 => {
                 let sa = value.bits(16..24);
@@ -43,36 +61,4 @@ impl Rdram {
             ),
         }
     }
-
-    pub fn read32(&self, address: u32) -> u32 {
-        match address {
-            0x1000_F430 => 0,
-            0x1000_F440 => {
-                let sop = self.mch_ricm.bits(6..10);
-                if sop == 0 {
-                    let sa = self.mch_ricm.bits(16..24);
-                    match sa {
-                        0x21 => {
-                            if *self.sdevid.borrow() < 2 {
-                                *self.sdevid.borrow_mut() += 1;
-                                return 0x1F;
-                            }
-                        }
-                        0x23 => {
-                            return 0x0D0D;
-                        }
-                        0x24 => {
-                            return 0x0090;
-                        }
-                        0x40 => {
-                            return self.mch_ricm.bits(0..5);
-                        }
-                        _ => {}
-                    }
-                }
-                return 0;
-            }
-            _ => panic!("Invalid RDRAM read at address: 0x{:08x}", address),
-        }
-    }
 }
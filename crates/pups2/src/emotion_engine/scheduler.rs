@@ -8,9 +8,11 @@ pub struct Scheduler {
 #[derive(Eq, PartialEq, Debug, Clone, Copy)]
 pub enum Event {
     Run(u64),
+    HBlank,
     VBlankStart,
     GsVBlank,
     VBlankEnd,
+    Compare,
 }
 
 #[derive(Eq, PartialEq, Debug)]
@@ -24,9 +26,15 @@ impl Scheduler {
     const CYCLES_PER_FRAME: u64 = 4920115;
     const VBLANK_START_CYCLE: u64 = 4489019;
     const GS_VBLANK_DELAY: u64 = 65622;
+    const CYCLES_PER_HBLANK: u64 = 18743;
 
     pub fn new() -> Self {
         let mut pending = BinaryHeap::new();
+        pending.push(PendingEvent {
+            event: Event::HBlank,
+            period: Some(Scheduler::CYCLES_PER_HBLANK),
+            cycle: Self::CYCLES_PER_HBLANK,
+        });
         pending.push(PendingEvent {
             event: Event::VBlankStart,
             period: Some(Scheduler::CYCLES_PER_FRAME),
@@ -63,6 +71,24 @@ impl Scheduler {
         }
     }
 
+    pub fn cycles_until_next_event(&self) -> u64 {
+        self.pending
+            .peek()
+            .unwrap()
+            .cycle
+            .saturating_sub(self.cycle)
+    }
+
+    // Replaces any pending occurrence of a one-shot event.
+    pub fn schedule(&mut self, event: Event, cycles: u64) {
+        self.pending.retain(|pending| pending.event != event);
+        self.pending.push(PendingEvent {
+            event,
+            period: None,
+            cycle: self.cycle + cycles,
+        });
+    }
+
     pub fn tick(&mut self, cycles: u64) {
         self.cycle += cycles;
     }
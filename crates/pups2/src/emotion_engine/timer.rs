@@ -1,21 +1,34 @@
+use log::debug;
 use num_derive::FromPrimitive;
 use num_traits::FromPrimitive;
 
 use crate::{bits::Bits, bytes::Bytes};
 
+use super::{bus::Bus, intc::Interrupt, mmio::MmioDevice};
+
 pub struct Timer {
     timers: [TimerRegisters; 4],
-    bus_clock: u64,
+    // The level of the VBlank gate signal.
+    vblank: bool,
 }
 
 #[derive(Default, Clone)]
 struct TimerRegisters {
     mode: Mode,
+    count: u16,
     compare: u16,
     hold: u16,
-    start: u64,
+    // The bus clock cycles since the count last went up, for the divided clocks.
+    prescaler: u16,
 }
 
+const INTERRUPTS: [Interrupt; 4] = [
+    Interrupt::Timer0,
+    Interrupt::Timer1,
+    Interrupt::Timer2,
+    Interrupt::Timer3,
+];
+
 #[derive(Default, Clone, Copy)]
 struct Mode(u16);
 
@@ -76,6 +89,25 @@ impl Mode {
     pub fn set_overflow_flag(&mut self, value: bool) {
         self.0.set_bit(11, value);
     }
+
+    // Whether the gate holds the count while the VBlank signal is high. HBlank is a pulse without
+    // length here, so gating on its level never holds the count.
+    fn held(self, vblank: bool) -> bool {
+        self.gate()
+            && self.gate_selection() == GateSelection::VBlank
+            && self.gate_mode() == GateMode::SignalLow
+            && vblank
+    }
+
+    // The bus clock cycles per count, if the timer counts bus clock cycles.
+    fn divider(self) -> Option<u16> {
+        match self.selection() {
+            ClockSelection::BusClock => Some(1),
+            ClockSelection::BusClockDiv16 => Some(16),
+            ClockSelection::BusClockDiv256 => Some(256),
+            ClockSelection::HBlank => None,
+        }
+    }
 }
 
 #[derive(Debug, Clone, Copy, FromPrimitive)]
@@ -86,13 +118,13 @@ enum ClockSelection {
     HBlank = 0b11,
 }
 
-#[derive(Debug, Clone, Copy, FromPrimitive)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, FromPrimitive)]
 enum GateSelection {
     HBlank = 0b0,
     VBlank = 0b1,
 }
 
-#[derive(Debug, Clone, Copy, FromPrimitive)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, FromPrimitive)]
 enum GateMode {
     SignalLow = 0b00,
     ResetOnRisingEdge = 0b01,
@@ -109,25 +141,142 @@ impl Timer {
                 TimerRegisters::default(),
                 TimerRegisters::default(),
             ],
-            bus_clock: 0,
+            vblank: false,
+        }
+    }
+
+    // Runs the timers for a bus clock cycle.
+    pub fn step(bus: &mut Bus) {
+        for index in 0..bus.timer.timers.len() {
+            let timer = &mut bus.timer.timers[index];
+            if !timer.mode.count_up_enable() || timer.mode.held(bus.timer.vblank) {
+                continue;
+            }
+            let Some(divider) = timer.mode.divider() else {
+                continue;
+            };
+            timer.prescaler += 1;
+            if timer.prescaler == divider {
+                timer.prescaler = 0;
+                Self::count_up(bus, index);
+            }
+        }
+    }
+
+    // The bus clock cycles until a timer clocked by the bus clock requests an interrupt, if any
+    // will.
+    pub fn cycles_until_interrupt(&self) -> Option<u64> {
+        self.timers
+            .iter()
+            .filter(|timer| timer.mode.count_up_enable() && !timer.mode.held(self.vblank))
+            .filter_map(|timer| {
+                let divider = timer.mode.divider()? as u64;
+                let compare = (timer.mode.compare_interrupt_enable() && !timer.mode.equal_flag())
+                    .then(|| match timer.compare.wrapping_sub(timer.count) {
+                        0 => 0x1_0000,
+                        counts => counts as u64,
+                    });
+                let overflow = (timer.mode.overflow_interrupt_enable()
+                    && !timer.mode.overflow_flag())
+                .then(|| 0x1_0000 - timer.count as u64);
+                let counts = compare.into_iter().chain(overflow).min()?;
+                Some((counts - 1) * divider + divider - timer.prescaler as u64)
+            })
+            .min()
+    }
+
+    // Counts up the timers clocked by horizontal blanks, and pulses the HBlank gate of the others.
+    pub fn hblank(bus: &mut Bus) {
+        for index in 0..bus.timer.timers.len() {
+            let mode = bus.timer.timers[index].mode;
+            if mode.count_up_enable() && mode.divider().is_none() && !mode.held(bus.timer.vblank) {
+                Self::count_up(bus, index);
+            }
         }
+        bus.timer.gate_edge(GateSelection::HBlank, true);
+        bus.timer.gate_edge(GateSelection::HBlank, false);
+    }
+
+    pub fn set_vblank(&mut self, vblank: bool) {
+        self.vblank = vblank;
+        self.gate_edge(GateSelection::VBlank, vblank);
     }
 
-    pub fn write<T: Bytes>(&mut self, address: u32, value: T) {
-        match std::mem::size_of::<T>() {
-            4 => self.write16(address, u32::from_bytes(value.to_bytes().as_ref()) as u16),
-            _ => panic!("Invalid write size {}", std::mem::size_of::<T>()),
+    // Resets the counts of the timers gated on the edges of a signal. A timer clocked by HBlank
+    // can't be gated by it.
+    fn gate_edge(&mut self, selection: GateSelection, rising: bool) {
+        for timer in &mut self.timers {
+            let mode = timer.mode;
+            if !mode.count_up_enable()
+                || !mode.gate()
+                || mode.gate_selection() != selection
+                || (selection == GateSelection::HBlank && mode.divider().is_none())
+            {
+                continue;
+            }
+            let reset = match mode.gate_mode() {
+                GateMode::SignalLow => false,
+                GateMode::ResetOnRisingEdge => rising,
+                GateMode::ResetOnFallingEdge => !rising,
+                GateMode::ResetOnBothEdges => true,
+            };
+            if reset {
+                timer.count = 0;
+                timer.prescaler = 0;
+            }
         }
     }
 
-    pub fn read<T: Bytes>(&self, address: u32) -> T {
-        match std::mem::size_of::<T>() {
-            4 => T::from_bytes((self.read16(address) as u32).to_bytes().as_ref()),
-            _ => panic!("Invalid read size {}", std::mem::size_of::<T>()),
+    // Reaching the compare value or overflowing sets the enabled flags, and setting a flag
+    // requests the timer's interrupt.
+    fn count_up(bus: &mut Bus, index: usize) {
+        let timer = &mut bus.timer.timers[index];
+        let (count, overflow) = timer.count.overflowing_add(1);
+        timer.count = count;
+        let mut interrupt = false;
+        if count == timer.compare {
+            if timer.mode.compare_interrupt_enable() && !timer.mode.equal_flag() {
+                timer.mode.set_equal_flag(true);
+                interrupt = true;
+            }
+            if timer.mode.zero_return() {
+                timer.count = 0;
+            }
+        }
+        if overflow && timer.mode.overflow_interrupt_enable() && !timer.mode.overflow_flag() {
+            timer.mode.set_overflow_flag(true);
+            interrupt = true;
+        }
+        if interrupt {
+            debug!(target: "timer", "Timer {index} interrupt, mode: 0b{:012b}", timer.mode.0);
+            bus.intc.request(INTERRUPTS[index]);
+        }
+    }
+}
+
+impl MmioDevice for Timer {
+    fn name(&self) -> &'static str {
+        "TIMER"
+    }
+
+    fn read16(&self, address: u32) -> u16 {
+        let timer = match address {
+            0x1000_0000..0x1000_0800 => 0,
+            0x1000_0800..0x1000_1000 => 1,
+            0x1000_1000..0x1000_1800 => 2,
+            0x1000_1800..0x1000_2000 => 3,
+            _ => panic!("Invalid TIMER read at address: 0x{:08x}", address),
+        };
+        match address & 0xFF {
+            0x00 => self.timers[timer].count,
+            0x10 => self.timers[timer].mode.0,
+            0x20 => self.timers[timer].compare,
+            0x30 if timer == 0 || timer == 1 => self.timers[timer].hold,
+            _ => panic!("Invalid TIMER read at address: 0x{:08x}", address),
         }
     }
 
-    pub fn write16(&mut self, address: u32, value: u16) {
+    fn write16(&mut self, address: u32, value: u16) {
         let value = u16::from_bytes(value.to_bytes().as_ref());
         let timer = match address {
             0x1000_0000..0x1000_0800 => 0,
@@ -140,15 +289,20 @@ impl Timer {
             ),
         };
         match address & 0xFF {
-            0x00 => todo!(),
+            0x00 => {
+                self.timers[timer].count = value;
+                self.timers[timer].prescaler = 0;
+            }
             0x10 => {
+                // Writing 1 to a flag clears it.
+                let old_mode = self.timers[timer].mode;
                 let mut mode = Mode(value);
-                mode.set_equal_flag(false);
-                mode.set_overflow_flag(false);
+                mode.set_equal_flag(old_mode.equal_flag() && !mode.equal_flag());
+                mode.set_overflow_flag(old_mode.overflow_flag() && !mode.overflow_flag());
                 self.timers[timer].mode = mode;
-                println!("Timer {} mode: 0b{:04b}", timer, value);
+                debug!(target: "timer", "Timer {} mode: 0b{:04b}", timer, value);
             }
-            0x20 => todo!(),
+            0x20 => self.timers[timer].compare = value,
             0x30 if timer == 0 || timer == 1 => todo!(),
             _ => panic!(
                 "Invalid TIMER write of {} at address: 0x{:08x}",
@@ -157,24 +311,63 @@ impl Timer {
         }
     }
 
-    pub fn read16(&self, address: u32) -> u16 {
-        let timer = match address {
-            0x1000_0000..0x1000_0800 => 0,
-            0x1000_0800..0x1000_1000 => 1,
-            0x1000_1000..0x1000_1800 => 2,
-            0x1000_1800..0x1000_2000 => 3,
-            _ => panic!("Invalid TIMER read at address: 0x{:08x}", address),
-        };
-        match address & 0xFF {
-            0x00 => (self.bus_clock - self.timers[timer].start) as u16,
-            0x10 => self.timers[timer].mode.0,
-            0x20 => self.timers[timer].compare,
-            0x30 if timer == 0 || timer == 1 => self.timers[timer].hold,
-            _ => panic!("Invalid TIMER read at address: 0x{:08x}", address),
+    fn read32(&self, address: u32) -> u32 {
+        self.read16(address) as u32
+    }
+
+    fn write32(&mut self, address: u32, value: u32) {
+        self.write16(address, value as u16)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const T0_COUNT: u32 = 0x1000_0000;
+    const T0_MODE: u32 = 0x1000_0010;
+    const COUNT_UP_ENABLE: u16 = 1 << 7;
+    const GATE: u16 = 1 << 2;
+    const VBLANK_GATE: u16 = 1 << 3;
+
+    fn step(bus: &mut Bus, cycles: u32) {
+        for _ in 0..cycles {
+            Timer::step(bus);
         }
     }
 
-    pub fn step(&mut self) {
-        self.bus_clock += 1;
+    #[test]
+    fn vblank_gate_holds_count() {
+        let mut bus = Bus::new();
+        bus.timer
+            .write16(T0_MODE, COUNT_UP_ENABLE | GATE | VBLANK_GATE);
+        step(&mut bus, 3);
+        bus.timer.set_vblank(true);
+        step(&mut bus, 5);
+        assert_eq!(bus.timer.read16(T0_COUNT), 3);
+        assert_eq!(bus.timer.cycles_until_interrupt(), None);
+        bus.timer.set_vblank(false);
+        step(&mut bus, 2);
+        assert_eq!(bus.timer.read16(T0_COUNT), 5);
+    }
+
+    #[test]
+    fn gate_edges_reset_count() {
+        let mut bus = Bus::new();
+        // Reset when VBlank starts.
+        bus.timer
+            .write16(T0_MODE, COUNT_UP_ENABLE | GATE | VBLANK_GATE | 0b01 << 4);
+        step(&mut bus, 3);
+        bus.timer.set_vblank(true);
+        assert_eq!(bus.timer.read16(T0_COUNT), 0);
+        step(&mut bus, 2);
+        bus.timer.set_vblank(false);
+        assert_eq!(bus.timer.read16(T0_COUNT), 2);
+        // Reset on every HBlank.
+        bus.timer
+            .write16(T0_MODE, COUNT_UP_ENABLE | GATE | 0b11 << 4);
+        step(&mut bus, 3);
+        Timer::hblank(&mut bus);
+        assert_eq!(bus.timer.read16(T0_COUNT), 0);
     }
 }
@@ -0,0 +1,170 @@
+use std::{
+    fs::File,
+    io::{BufWriter, Write},
+    str::FromStr,
+    sync::Mutex,
+};
+
+use log::{LevelFilter, Log, Metadata, Record};
+
+// Diagnostics go through the `log` macros with one target per subsystem: ee.core, ee.jit,
+// bus.mmio, dmac, gif, gs, timer and kernel. Filters pick a level per target, a filter for
+// `ee` also covering `ee.core` and `ee.jit`.
+pub struct Filters {
+    default: LevelFilter,
+    // The most specific filter matching a target wins.
+    targets: Vec<(String, LevelFilter)>,
+}
+
+impl Filters {
+    fn level(&self, target: &str) -> LevelFilter {
+        self.targets
+            .iter()
+            .filter(|(prefix, _)| {
+                target
+                    .strip_prefix(prefix.as_str())
+                    .is_some_and(|rest| rest.is_empty() || rest.starts_with('.'))
+            })
+            .max_by_key(|(prefix, _)| prefix.len())
+            .map_or(self.default, |&(_, level)| level)
+    }
+
+    fn max_level(&self) -> LevelFilter {
+        self.targets
+            .iter()
+            .map(|&(_, level)| level)
+            .fold(self.default, std::cmp::max)
+    }
+}
+
+impl Default for Filters {
+    fn default() -> Self {
+        Filters {
+            default: LevelFilter::Info,
+            targets: Vec::new(),
+        }
+    }
+}
+
+// A comma-separated list of levels, optionally prefixed by a target, e.g.
+// `warn,ee.core=trace,gs=off`.
+impl FromStr for Filters {
+    type Err = String;
+
+    fn from_str(string: &str) -> Result<Self, Self::Err> {
+        let mut filters = Filters::default();
+        for filter in string.split(',').filter(|filter| !filter.is_empty()) {
+            let parse_level = |level: &str| {
+                LevelFilter::from_str(level).map_err(|_| format!("invalid log level {}", level))
+            };
+            match filter.split_once('=') {
+                Some((target, level)) => filters
+                    .targets
+                    .push((target.to_string(), parse_level(level)?)),
+                None => filters.default = parse_level(filter)?,
+            }
+        }
+        Ok(filters)
+    }
+}
+
+// How records are written to a trace file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TraceFormat {
+    // One line per record: the first letter of the level, the target and the message.
+    Compact,
+    // Per record: the level as a byte, the target prefixed by its length as a byte, and the
+    // message prefixed by its length as a little-endian u32.
+    Binary,
+}
+
+impl FromStr for TraceFormat {
+    type Err = String;
+
+    fn from_str(string: &str) -> Result<Self, Self::Err> {
+        match string {
+            "compact" => Ok(TraceFormat::Compact),
+            "binary" => Ok(TraceFormat::Binary),
+            _ => Err(format!("expected compact or binary, got {}", string)),
+        }
+    }
+}
+
+enum Output {
+    Stdout,
+    Trace(TraceFormat, BufWriter<File>),
+}
+
+pub struct Logger {
+    filters: Filters,
+    output: Mutex<Output>,
+}
+
+impl Logger {
+    // Installs the logger, writing to stdout or to a trace file.
+    pub fn install(filters: Filters, trace: Option<(File, TraceFormat)>) {
+        log::set_max_level(filters.max_level());
+        let output = match trace {
+            Some((file, format)) => Output::Trace(format, BufWriter::new(file)),
+            None => Output::Stdout,
+        };
+        let logger = Logger {
+            filters,
+            output: Mutex::new(output),
+        };
+        log::set_boxed_logger(Box::new(logger)).expect("Failed to install logger");
+        // Keep the records leading up to a panic, which are usually the interesting ones.
+        let default_hook = std::panic::take_hook();
+        std::panic::set_hook(Box::new(move |info| {
+            log::logger().flush();
+            default_hook(info)
+        }));
+    }
+}
+
+impl Log for Logger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= self.filters.level(metadata.target())
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+        let mut output = self.output.lock().unwrap();
+        match &mut *output {
+            Output::Stdout => println!(
+                "{:<5} {}: {}",
+                record.level(),
+                record.target(),
+                record.args()
+            ),
+            Output::Trace(TraceFormat::Compact, file) => writeln!(
+                file,
+                "{} {} {}",
+                &record.level().as_str()[..1],
+                record.target(),
+                record.args()
+            )
+            .expect("Failed to write trace"),
+            Output::Trace(TraceFormat::Binary, file) => {
+                let target = record.target().as_bytes();
+                let message = record.args().to_string();
+                file.write_all(&[record.level() as u8, target.len() as u8])
+                    .and_then(|()| file.write_all(target))
+                    .and_then(|()| file.write_all(&(message.len() as u32).to_le_bytes()))
+                    .and_then(|()| file.write_all(message.as_bytes()))
+                    .expect("Failed to write trace")
+            }
+        }
+    }
+
+    fn flush(&self) {
+        // The lock is still held if the panic happened while logging, losing the buffered records.
+        if let Ok(mut output) = self.output.try_lock() {
+            if let Output::Trace(_, file) = &mut *output {
+                file.flush().expect("Failed to write trace");
+            }
+        }
+    }
+}
@@ -5,18 +5,32 @@ mod enum_set;
 mod executable_memory_allocator;
 mod fifo;
 mod fix;
+mod logging;
 
 use argh::FromArgs;
 use bytes::Bytes;
 use elf::{endian::LittleEndian, ElfBytes};
 use emotion_engine::{
-    core::instruction_gen::Instruction,
+    bus::Bus,
+    core::{
+        instruction_gen::Instruction,
+        jit::JitConfig,
+        kernel::Kernel,
+        profile::{Profiler, ProfilerConfig},
+        Core,
+    },
     dmac::Dmac,
     gif::Gif,
-    scheduler::{self, Event},
+    gs::Gs,
+    intc::Interrupt,
+    mmio::UnmappedPolicy,
+    scheduler::{Event, Scheduler},
+    timer::Timer,
 };
+use log::{debug, info};
+use logging::{Filters, Logger, TraceFormat};
 use minifb::{Scale, ScaleMode, Window, WindowOptions};
-use std::time::Instant;
+use std::{fs::File, path::Path, time::Instant};
 
 #[derive(FromArgs)]
 #[argh(description = "Perpetually Unfinished PS2 emulator")]
@@ -25,6 +39,61 @@ struct Arguments {
     disassemble: bool,
     #[argh(option, short = 'b', description = "BIOS file")]
     bios: Option<String>,
+    #[argh(
+        switch,
+        description = "print how the JIT lowered each instruction on exit"
+    )]
+    jit_coverage: bool,
+    #[argh(
+        switch,
+        description = "run idle loops instead of skipping to the next event"
+    )]
+    no_idle_skip: bool,
+    #[argh(
+        switch,
+        description = "keep the hot registers in host registers across JIT blocks"
+    )]
+    jit_pinned_registers: bool,
+    #[argh(
+        switch,
+        description = "check every JIT block against the interpreter, panicking where they differ"
+    )]
+    lockstep: bool,
+    #[argh(
+        option,
+        description = "dump the guest instructions, IR and host code of every JIT block to a directory"
+    )]
+    jit_dump: Option<String>,
+    #[argh(
+        switch,
+        description = "list JIT blocks in /tmp/perf-<pid>.map for perf to symbolise"
+    )]
+    jit_perf_map: bool,
+    #[argh(
+        option,
+        description = "time every JIT block and print the given number of hottest ones on exit"
+    )]
+    jit_profile: Option<usize>,
+    #[argh(
+        option,
+        default = "UnmappedPolicy::Panic",
+        description = "what accesses to unmapped addresses do: panic, ignore or open-bus"
+    )]
+    unmapped: UnmappedPolicy,
+    #[argh(
+        option,
+        default = "Filters::default()",
+        description = "log levels, optionally per target, e.g. warn,ee.core=trace,gs=off"
+    )]
+    log: Filters,
+    #[argh(option, description = "write log records to a file instead of stdout")]
+    trace: Option<String>,
+    #[argh(
+        option,
+        default = "TraceFormat::Compact",
+        description = "how log records are written to the trace file: compact or binary"
+    )]
+    trace_format: TraceFormat,
     #[argh(positional, description = "ELF file")]
     file: String,
 }
@@ -60,36 +129,79 @@ fn disassemble(file: &str) -> std::io::Result<()> {
     Ok(())
 }
 
-fn execute(bios: &Option<String>, file: &str) -> std::io::Result<()> {
-    let mut core = emotion_engine::core::Core::new();
-    let mut bus = emotion_engine::bus::Bus::new();
-    if let Some(bios) = bios {
+// Runs the devices alongside the core for `cycles`, starting at `cycle`.
+fn step_devices(bus: &mut Bus, cycle: u64, cycles: u64) {
+    for i in 0..cycles {
+        if (cycle + i) % 2 == 0 {
+            Dmac::step(bus);
+            Gif::step(bus);
+            Gs::step(bus);
+            Timer::step(bus);
+        }
+    }
+}
+
+// Runs the devices on their own while the core spins in an idle loop, until the next event or
+// something the core may be polling for changes: a DMA transfer completing, or an interrupt
+// being requested, acknowledged or masked.
+fn skip_idle_loop(core: &mut Core, bus: &mut Bus, scheduler: &mut Scheduler) {
+    // A transfer can finish, stall or feed the GIF into raising an interrupt on any cycle, so
+    // while the DMAC is busy the devices are stepped a cycle at a time to stop right there.
+    if bus.dmac.busy() {
+        let dmac_interrupt_pending = bus.dmac.interrupt_pending();
+        let intc = bus.intc.clone();
+        while scheduler.cycles_until_next_event() > 0
+            && bus.dmac.busy()
+            && bus.dmac.interrupt_pending() == dmac_interrupt_pending
+            && bus.intc == intc
+        {
+            core.advance_time(1, bus);
+            step_devices(bus, scheduler.cycle, 1);
+            scheduler.tick(1);
+        }
+        return;
+    }
+    // Otherwise only the timers and the kernel's alarms request interrupts before the event. The
+    // devices step on even cycles, once per bus clock cycle.
+    let mut cycles = scheduler.cycles_until_next_event();
+    if let Some(bus_cycles) = bus.timer.cycles_until_interrupt() {
+        cycles = cycles.min(bus_cycles * 2 - 1 + scheduler.cycle % 2);
+    }
+    if let Some(alarm_cycles) = core
+        .kernel
+        .as_ref()
+        .and_then(Kernel::cycles_until_next_alarm)
+    {
+        cycles = cycles.min(alarm_cycles);
+    }
+    core.advance_time(cycles, bus);
+    step_devices(bus, scheduler.cycle, cycles);
+    scheduler.tick(cycles);
+}
+
+fn execute(
+    arguments: &Arguments,
+    jit_config: JitConfig,
+    profiler_config: ProfilerConfig,
+) -> std::io::Result<()> {
+    let mut core = Core::new();
+    core.jit.set_config(jit_config);
+    core.jit.profiler = Profiler::new(profiler_config)?;
+    let mut bus = Bus::new();
+    bus.mmio.unmapped_policy = arguments.unmapped;
+    if let Some(bios) = &arguments.bios {
         let bios_data = std::fs::read(bios)?;
         bus.boot_memory[0..bios_data.len()].copy_from_slice(&bios_data);
     } else {
-        let elf_data = std::fs::read(file)?;
-        let elf = ElfBytes::<LittleEndian>::minimal_parse(&elf_data).expect("Failed to parse ELF");
-        let entry_point = elf.ehdr.e_entry as u32;
+        let elf_data = std::fs::read(&arguments.file)?;
+        let entry_point = Kernel::load_elf(&mut bus, &elf_data).expect("Failed to parse ELF");
         core.state.program_counter = entry_point;
-        println!("Entry point: {:x?}", entry_point);
-        println!("Program header start: {:x?}", entry_point as u32);
-        for program_header in elf.segments().expect("Failed to get program headers") {
-            let physical_address = program_header.p_paddr;
-            let virtual_address = program_header.p_vaddr;
-            println!("Physical memory address: {:x?}", physical_address);
-            println!("Virtual memory address: {:x?}", virtual_address);
-            let data = elf
-                .segment_data(&program_header)
-                .expect("Failed to get segment data");
-            // state.tlb.mmap(
-            //     virtual_address as u32,
-            //     data.len() as u32,
-            //     physical_address as u32,
-            // );
-            bus.main_memory[physical_address as usize..physical_address as usize + data.len()]
-                .copy_from_slice(data);
-        }
-        core.mmu.mmap(0, 0x2000_0000, 0);
+        info!("Entry point: {:x?}", entry_point);
+        let host_directory = Path::new(&arguments.file).parent().unwrap_or(Path::new(""));
+        Kernel::install(&mut core, &mut bus, host_directory);
+    }
+    if arguments.lockstep {
+        core.enable_lockstep(&mut bus);
     }
     let mut window = Window::new(
         "pups2",
@@ -108,34 +220,47 @@ fn execute(bios: &Option<String>, file: &str) -> std::io::Result<()> {
     )
     .expect("Failed to create window");
     window.set_background_color(20, 20, 20);
-    let mut scheduler = scheduler::Scheduler::new();
+    let mut scheduler = Scheduler::new();
     let mut frame_start = Instant::now();
     loop {
         match scheduler.next_event() {
             Event::Run(cycles) => {
                 core.step(cycles, &mut bus);
-                for i in 0..cycles {
-                    if (scheduler.cycle + i) % 2 == 0 {
-                        Dmac::step(&mut bus);
-                        Gif::step(&mut bus);
-                        bus.gs.step();
-                        bus.timer.step();
-                    }
-                }
+                step_devices(&mut bus, scheduler.cycle, cycles);
                 scheduler.tick(cycles);
+                if core.jit.take_idle() {
+                    skip_idle_loop(&mut core, &mut bus, &mut scheduler);
+                }
+                if let Some(cycles) = core.state.control.take_compare_event() {
+                    scheduler.schedule(Event::Compare, cycles);
+                }
+            }
+            Event::Compare => {
+                core.state.control.compare_interrupt();
+                if let Some(cycles) = core.state.control.take_compare_event() {
+                    scheduler.schedule(Event::Compare, cycles);
+                }
+            }
+            Event::HBlank => {
+                bus.gs.hblank();
+                Timer::hblank(&mut bus);
             }
             Event::VBlankStart => {
-                println!("VBlank start");
+                debug!(target: "gs", "VBlank start");
+                bus.intc.request(Interrupt::VBlankStart);
+                bus.timer.set_vblank(true);
             }
             Event::GsVBlank => {
                 bus.gs.vblank();
-                println!("GS VBlank");
+                debug!(target: "gs", "GS VBlank");
             }
             Event::VBlankEnd => {
                 let frame_duration = frame_start.elapsed();
                 frame_start = Instant::now();
-                println!("VBlank end");
-                println!(
+                debug!(target: "gs", "VBlank end");
+                bus.intc.request(Interrupt::VBlankEnd);
+                bus.timer.set_vblank(false);
+                debug!(
                     "Frame duration: {} ms",
                     frame_duration.as_secs_f64() * 1000.0
                 );
@@ -166,14 +291,36 @@ fn execute(bios: &Option<String>, file: &str) -> std::io::Result<()> {
             }
         }
     }
+    if arguments.jit_coverage {
+        print!("{}", core.jit.coverage);
+    }
+    if let Some(hot_blocks) = arguments.jit_profile {
+        print!("{}", core.jit.profiler.hot_blocks(hot_blocks));
+    }
+    log::logger().flush();
     Ok(())
 }
 
 fn main() -> Result<(), std::io::Error> {
-    let args: Arguments = argh::from_env();
+    let mut args: Arguments = argh::from_env();
+    let trace = match &args.trace {
+        Some(path) => Some((File::create(path)?, args.trace_format)),
+        None => None,
+    };
+    Logger::install(std::mem::take(&mut args.log), trace);
     if args.disassemble {
         disassemble(&args.file)
     } else {
-        execute(&args.bios, &args.file)
+        let jit_config = JitConfig {
+            idle_loop_detection: !args.no_idle_skip,
+            register_pinning: args.jit_pinned_registers,
+            lockstep: false,
+        };
+        let profiler_config = ProfilerConfig {
+            dump_directory: args.jit_dump.as_ref().map(Into::into),
+            perf_map: args.jit_perf_map,
+            block_statistics: args.jit_profile.is_some(),
+        };
+        execute(&args, jit_config, profiler_config)
     }
 }